@@ -2,7 +2,9 @@ use std::collections::HashSet;
 use std::path::Path;
 use std::process::Command;
 
-use weave_core::entity_merge_with_registry;
+use weave_core::git::git_show_batch;
+use weave_core::pipeline::par_map;
+use weave_core::{entity_merge_in, file_budget, MergeContext, WeaveConfig};
 use sem_core::parser::plugins::create_default_registry;
 
 const SUPPORTED_EXTENSIONS: &[&str] = &[
@@ -20,6 +22,16 @@ struct Stats {
     differs_from_human: usize,
 }
 
+/// A file both parents of a merge commit changed.
+struct Sample {
+    commit: String,
+    file: String,
+    base: String,
+    ours: String,
+    theirs: String,
+    human: String,
+}
+
 #[derive(serde::Serialize)]
 struct CaseRecord {
     commit: String,
@@ -89,8 +101,8 @@ pub fn run(repo_path: &str, limit: usize, show_diff: bool, save_dir: Option<&str
         differs_from_human: 0,
     };
 
-    let mut cases: Vec<CaseRecord> = Vec::new();
-
+    // Collect the files both parents changed, with their four versions
+    let mut samples: Vec<Sample> = Vec::new();
     for (i, merge_commit) in merge_commits.iter().enumerate() {
         // Get the two parents
         let output = Command::new("git")
@@ -123,22 +135,27 @@ pub fn run(repo_path: &str, limit: usize, show_diff: bool, save_dir: Option<&str
         let files_p2 = changed_files(&repo, &base, p2)?;
 
         // Only care about files touched by BOTH branches
-        let both_touched: Vec<&String> = files_p1.intersection(&files_p2).collect();
-
+        let mut both_touched: Vec<&String> = files_p1
+            .intersection(&files_p2)
+            .filter(|file| {
+                let ext = Path::new(file).extension().and_then(|e| e.to_str()).unwrap_or("");
+                SUPPORTED_EXTENSIONS.contains(&ext)
+            })
+            .collect();
+        both_touched.sort();
+
+        // Get all four versions: base, ours (p1), theirs (p2), human (merge commit)
+        let specs: Vec<(&str, &str)> = both_touched
+            .iter()
+            .flat_map(|file| {
+                let file = file.as_str();
+                [base.as_str(), p1.as_str(), p2.as_str(), merge_commit.as_str()].map(move |rev| (rev, file))
+            })
+            .collect();
+        let mut blobs = git_show_batch(&repo, &specs)?.into_iter();
         for file in both_touched {
-            let ext = Path::new(file).extension().and_then(|e| e.to_str()).unwrap_or("");
-            if !SUPPORTED_EXTENSIONS.contains(&ext) {
-                continue;
-            }
-
-            // Get all four versions: base, ours (p1), theirs (p2), human (merge commit)
-            let (base_content, ours, theirs, human) = match (
-                git_show(&repo, &base, file),
-                git_show(&repo, p1, file),
-                git_show(&repo, p2, file),
-                git_show(&repo, merge_commit, file),
-            ) {
-                (Some(b), Some(o), Some(t), Some(h)) => (b, o, t, h),
+            let (base_content, ours, theirs, human) = match (blobs.next(), blobs.next(), blobs.next(), blobs.next()) {
+                (Some(Some(b)), Some(Some(o)), Some(Some(t)), Some(Some(h))) => (b, o, t, h),
                 _ => continue, // file added/deleted on one side
             };
 
@@ -152,68 +169,103 @@ pub fn run(repo_path: &str, limit: usize, show_diff: bool, save_dir: Option<&str
                 continue;
             }
 
-            stats.files_tested += 1;
-
-            let git_clean = diffy::merge(&base_content, &ours, &theirs).is_ok();
-            let weave_result = entity_merge_with_registry(&base_content, &ours, &theirs, file, &registry);
-            // Check both the conflicts vec AND the actual content for markers
-            let weave_clean = weave_result.is_clean()
-                && !weave_result.content.contains("<<<<<<<")
-                && !weave_result.content.contains(">>>>>>>");
-
-            match (weave_clean, git_clean) {
-                (true, true) => stats.both_clean += 1,
-                (false, false) => stats.both_conflict += 1,
-                (false, true) => {
-                    stats.regressions += 1;
-                    println!("  REGR   {}  {}", short(merge_commit), file);
+            samples.push(Sample {
+                commit: merge_commit.clone(),
+                file: file.clone(),
+                base: base_content,
+                ours,
+                theirs,
+                human,
+            });
+        }
+
+        if (i + 1) % 100 == 0 {
+            eprint!("\r  processed {}/{} merges...", i + 1, merge_commits.len());
+        }
+    }
+
+    // Merge them all in parallel, each with its own timeout. Entities go
+    // through the benchmarked repo's cache unless it turns it off, so
+    // reruns skip parsing
+    let config = WeaveConfig::default();
+    let entity_cache = WeaveConfig::load(&repo)?.entity_cache(&repo);
+    let outcomes = par_map(&samples, |sample| {
+        let git_clean = diffy::merge(&sample.base, &sample.ours, &sample.theirs).is_ok();
+        let context = MergeContext {
+            budget: file_budget(&config),
+            entity_cache: entity_cache.as_ref(),
+            ..Default::default()
+        };
+        let weave_result =
+            entity_merge_in(&sample.base, &sample.ours, &sample.theirs, &sample.file, &registry, &config, &context);
+        (git_clean, weave_result)
+    });
+
+    let mut cases: Vec<CaseRecord> = Vec::new();
+    for (sample, (git_clean, weave_result)) in samples.iter().zip(outcomes) {
+        let Sample {
+            commit: merge_commit,
+            file,
+            base: base_content,
+            ours,
+            theirs,
+            human,
+        } = sample;
+        stats.files_tested += 1;
+
+        // Check both the conflicts vec AND the actual content for markers
+        let weave_clean = weave_result.is_clean()
+            && !weave_result.content.contains("<<<<<<<")
+            && !weave_result.content.contains(">>>>>>>");
+
+        match (weave_clean, git_clean) {
+            (true, true) => stats.both_clean += 1,
+            (false, false) => stats.both_conflict += 1,
+            (false, true) => {
+                stats.regressions += 1;
+                println!("  REGR   {}  {}", short(merge_commit), file);
+                if let Some(dir) = save_dir {
+                    let case_dir = save_case(dir, merge_commit, file, base_content, ours, theirs, human, &weave_result.content)?;
+                    cases.push(CaseRecord {
+                        commit: merge_commit.clone(),
+                        file: file.clone(),
+                        category: "regression".to_string(),
+                        dir: case_dir,
+                    });
+                }
+            }
+            (true, false) => {
+                stats.weave_wins += 1;
+                if normalize(&weave_result.content) == normalize(human) {
+                    stats.matches_human += 1;
+                    println!("  MATCH  {}  {}", short(merge_commit), file);
                     if let Some(dir) = save_dir {
-                        let case_dir = save_case(dir, merge_commit, file, &base_content, &ours, &theirs, &human, &weave_result.content)?;
+                        let case_dir = save_case(dir, merge_commit, file, base_content, ours, theirs, human, &weave_result.content)?;
                         cases.push(CaseRecord {
                             commit: merge_commit.clone(),
                             file: file.clone(),
-                            category: "regression".to_string(),
+                            category: "match".to_string(),
                             dir: case_dir,
                         });
                     }
-                }
-                (true, false) => {
-                    stats.weave_wins += 1;
-                    if normalize(&weave_result.content) == normalize(&human) {
-                        stats.matches_human += 1;
-                        println!("  MATCH  {}  {}", short(merge_commit), file);
-                        if let Some(dir) = save_dir {
-                            let case_dir = save_case(dir, merge_commit, file, &base_content, &ours, &theirs, &human, &weave_result.content)?;
-                            cases.push(CaseRecord {
-                                commit: merge_commit.clone(),
-                                file: file.clone(),
-                                category: "match".to_string(),
-                                dir: case_dir,
-                            });
-                        }
-                    } else {
-                        stats.differs_from_human += 1;
-                        println!("  DIFF   {}  {}", short(merge_commit), file);
-                        if show_diff {
-                            print_inline_diff(&weave_result.content, &human);
-                        }
-                        if let Some(dir) = save_dir {
-                            let case_dir = save_case(dir, merge_commit, file, &base_content, &ours, &theirs, &human, &weave_result.content)?;
-                            cases.push(CaseRecord {
-                                commit: merge_commit.clone(),
-                                file: file.clone(),
-                                category: "diff".to_string(),
-                                dir: case_dir,
-                            });
-                        }
+                } else {
+                    stats.differs_from_human += 1;
+                    println!("  DIFF   {}  {}", short(merge_commit), file);
+                    if show_diff {
+                        print_inline_diff(&weave_result.content, human);
+                    }
+                    if let Some(dir) = save_dir {
+                        let case_dir = save_case(dir, merge_commit, file, base_content, ours, theirs, human, &weave_result.content)?;
+                        cases.push(CaseRecord {
+                            commit: merge_commit.clone(),
+                            file: file.clone(),
+                            category: "diff".to_string(),
+                            dir: case_dir,
+                        });
                     }
                 }
             }
         }
-
-        if (i + 1) % 100 == 0 {
-            eprint!("\r  processed {}/{} merges...", i + 1, merge_commits.len());
-        }
     }
 
     eprintln!();
@@ -290,19 +342,6 @@ fn changed_files(repo: &Path, base: &str, head: &str) -> Result<HashSet<String>,
         .collect())
 }
 
-fn git_show(repo: &Path, rev: &str, file: &str) -> Option<String> {
-    let output = Command::new("git")
-        .args(["show", &format!("{}:{}", rev, file)])
-        .current_dir(repo)
-        .output()
-        .ok()?;
-    if output.status.success() {
-        String::from_utf8(output.stdout).ok()
-    } else {
-        None
-    }
-}
-
 fn short(hash: &str) -> &str {
     &hash[..8.min(hash.len())]
 }
@@ -2,7 +2,10 @@ pub mod bench;
 pub mod bench_repo;
 pub mod claim;
 pub mod preview;
+pub mod provenance;
 pub mod release;
+pub mod rerere;
 pub mod setup;
 pub mod status;
 pub mod summary;
+pub mod validate;
@@ -1,17 +1,36 @@
+use std::path::Path;
+use std::process::Command;
+
 use colored::Colorize;
-use sem_core::parser::plugins::create_default_registry;
-use weave_core::entity_merge_with_registry;
 use weave_core::git;
+use weave_core::pipeline::{load_versions, merge_files, validate_merged, MergedFile};
+use weave_core::protect::protected_entities;
+use weave_core::verify::verify_merged;
+use weave_core::{shared_registry, MergeContext, WeaveConfig};
 
+/// Preview merging `branch` into HEAD. With `write`, the `[[verify]]` checks
+/// run, and the merged files are written to the working tree, conflicts
+/// included, without recording a merge.
 pub fn run(
     branch: &str,
     file_path: Option<&str>,
+    explain: bool,
+    verify: bool,
+    write: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let head = "HEAD";
     let merge_base = git::find_merge_base(head, branch)?;
 
     let files = if let Some(fp) = file_path {
         vec![fp.to_string()]
+    } else if verify || write {
+        // Every file either side changed, so the checks and the working
+        // tree see the whole merge
+        let mut files = git::diff_files(&merge_base, head)?;
+        files.extend(git::diff_files(&merge_base, branch)?);
+        files.sort();
+        files.dedup();
+        files
     } else {
         git::get_changed_files(&merge_base, head, branch)?
     };
@@ -21,26 +40,44 @@ pub fn run(
         return Ok(());
     }
 
-    let registry = create_default_registry();
+    let registry = shared_registry();
+    let repo_root = git::find_repo_root()?;
+    if write && !is_clean(&repo_root, &files)? {
+        return Err("uncommitted changes to files the merge writes: commit or stash them first".into());
+    }
+    let config = WeaveConfig::load(&repo_root)?;
+    let entity_cache = config.entity_cache(&repo_root);
+    let context = MergeContext {
+        repo_root: Some(&repo_root),
+        entity_cache: entity_cache.as_ref(),
+        trace: explain,
+        conflict_format: git::conflict_format(&repo_root, head, git::short_sha(&merge_base), branch),
+        ..Default::default()
+    };
     let mut total_conflicts = 0;
     let mut total_auto_resolved = 0;
 
-    for file in &files {
-        let base_content = git::git_show(&merge_base, file).unwrap_or_default();
-        let ours_content = git::git_show(head, file).unwrap_or_default();
-        let theirs_content = git::git_show(branch, file).unwrap_or_default();
+    let versions = load_versions(&repo_root, &merge_base, head, branch, &files)?;
+    let mut merged = merge_files(versions, registry, &config, &context, config.merge.timeout());
+    // Cross-entity checks, once every file is merged: changes on one side
+    // that break the other's
+    let warnings = validate_merged(&repo_root, &mut merged, registry, &config);
+    // `[[verify]]` checks over the merged tree, in a temporary worktree: a
+    // failure vetoes the auto-merge of the files the check covers
+    let verifications = if verify || write {
+        verify_merged(&repo_root, head, &mut merged, &config, &context.conflict_format)
+    } else {
+        Vec::new()
+    };
 
-        if ours_content == theirs_content || base_content == ours_content || base_content == theirs_content {
+    for merged_file in &merged {
+        // Changed on one side only: nothing to merge, but validated above
+        let Some(result) = merged_file.result.as_ref() else {
             continue;
-        }
-
-        let result = entity_merge_with_registry(
-            &base_content,
-            &ours_content,
-            &theirs_content,
-            file,
-            &registry,
-        );
+        };
+        let file = &merged_file.versions.file_path;
+        let ours_content = merged_file.versions.ours.as_str();
+        let theirs_content = merged_file.versions.theirs.as_str();
 
         let status = if result.is_clean() {
             total_auto_resolved += 1;
@@ -54,14 +91,51 @@ pub fn run(
         println!("    {}", result.stats);
 
         for conflict in &result.conflicts {
+            let (ours, theirs) = conflict.side_complexities(file);
             println!(
-                "    {} {} `{}`: {}",
+                "    {} {} `{}`: {} {}",
                 "✗".red(),
                 conflict.entity_type,
                 conflict.entity_name,
-                conflict.kind
+                conflict.kind,
+                format!("({}, ours {}, theirs {})", conflict.complexity, ours, theirs).dimmed()
             );
         }
+
+        for entity in protected_entities(&[ours_content, theirs_content], file, registry, &config) {
+            println!("    {} {} `{}` is protected", "⚑".blue(), entity.entity_type, entity.name);
+        }
+
+        for warning in &result.warnings {
+            println!("    {} {}", "⚠".yellow(), warning);
+        }
+
+        if let Some(generated) = &result.generated {
+            println!("    {} {}", "⚙".cyan(), generated);
+        }
+
+        if let Some(trace) = &result.trace {
+            for step in &trace.steps {
+                println!("    {} {}", "·".dimmed(), step.to_string().dimmed());
+            }
+        }
+    }
+
+    // A failure is reported with the warnings of the files it vetoed
+    let passed: Vec<_> = verifications.iter().filter(|v| v.success).collect();
+    if !passed.is_empty() {
+        println!();
+        for verification in passed {
+            println!("  {} {} ({} file(s))", "✓".green(), verification, verification.files.len());
+        }
+    }
+
+    if !warnings.is_empty() {
+        println!();
+        println!("  {}", "semantic validation".bold());
+        for warning in &warnings {
+            println!("    {} [{}] {}", "⚠".yellow(), warning.file_path, warning);
+        }
     }
 
     println!();
@@ -80,5 +154,47 @@ pub fn run(
         );
     }
 
+    if write {
+        write_merged(&repo_root, &merged)?;
+        println!("{} Wrote the merge to the working tree", "✓".green().bold());
+    }
+
+    if config.validate.fail_on_warnings && !warnings.is_empty() {
+        return Err(format!("{} semantic validation warning(s)", warnings.len()).into());
+    }
+
+    Ok(())
+}
+
+/// Whether `files` have no uncommitted changes in the working tree or the
+/// index.
+fn is_clean(repo_root: &Path, files: &[String]) -> Result<bool, Box<dyn std::error::Error>> {
+    let output = Command::new("git")
+        .args(["status", "--porcelain", "--"])
+        .args(files)
+        .current_dir(repo_root)
+        .output()?;
+    if !output.status.success() {
+        return Err("failed to check the working tree".into());
+    }
+    Ok(output.stdout.is_empty())
+}
+
+/// Write each merged file to the working tree, removing those the merge
+/// deletes.
+fn write_merged(repo_root: &Path, merged: &[MergedFile]) -> Result<(), Box<dyn std::error::Error>> {
+    for merged_file in merged {
+        let path = repo_root.join(&merged_file.versions.file_path);
+        match merged_file.content() {
+            Some(content) => {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(&path, content)?;
+            }
+            None if path.exists() => std::fs::remove_file(&path)?,
+            None => {}
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,73 @@
+use colored::{ColoredString, Colorize};
+use sem_core::parser::plugins::create_default_registry;
+use weave_core::git;
+use weave_core::provenance::LineOrigin;
+use weave_core::{entity_merge_in, MergeContext, WeaveConfig};
+
+pub fn run(branch: &str, file_path: &str, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let head = "HEAD";
+    let merge_base = git::find_merge_base(head, branch)?;
+
+    let base = git::git_show(&merge_base, file_path).unwrap_or_default();
+    let ours = git::git_show(head, file_path).unwrap_or_default();
+    let theirs = git::git_show(branch, file_path).unwrap_or_default();
+
+    let registry = create_default_registry();
+    let repo_root = git::find_repo_root()?;
+    let config = WeaveConfig::load(&repo_root)?;
+    let context = MergeContext {
+        repo_root: Some(&repo_root),
+        provenance: true,
+        ..Default::default()
+    };
+    let result = entity_merge_in(&base, &ours, &theirs, file_path, &registry, &config, &context);
+    let provenance = result.provenance.unwrap_or_default();
+
+    if json {
+        let output = serde_json::json!({
+            "file": file_path,
+            "clean": result.conflicts.is_empty(),
+            "summary": {
+                "base": provenance.count(LineOrigin::Base),
+                "ours": provenance.count(LineOrigin::Ours),
+                "theirs": provenance.count(LineOrigin::Theirs),
+                "both": provenance.count(LineOrigin::Both),
+                "weave": provenance.count(LineOrigin::Weave),
+                "conflict": provenance.count(LineOrigin::Conflict),
+            },
+            "ranges": provenance.ranges(),
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    let width = provenance.lines.len().to_string().len();
+    for (i, (line, origin)) in result.content.lines().zip(&provenance.lines).enumerate() {
+        println!("{:>width$} {:<8} │ {}", i + 1, colored_tag(*origin), line, width = width);
+    }
+
+    println!();
+    println!(
+        "{} base, {} ours, {} theirs, {} both, {} weave, {} conflict",
+        provenance.count(LineOrigin::Base),
+        provenance.count(LineOrigin::Ours).to_string().blue(),
+        provenance.count(LineOrigin::Theirs).to_string().magenta(),
+        provenance.count(LineOrigin::Both).to_string().cyan(),
+        provenance.count(LineOrigin::Weave).to_string().yellow(),
+        provenance.count(LineOrigin::Conflict).to_string().red(),
+    );
+
+    Ok(())
+}
+
+fn colored_tag(origin: LineOrigin) -> ColoredString {
+    let tag = origin.tag();
+    match origin {
+        LineOrigin::Base => tag.dimmed(),
+        LineOrigin::Ours => tag.blue(),
+        LineOrigin::Theirs => tag.magenta(),
+        LineOrigin::Both => tag.cyan(),
+        LineOrigin::Weave => tag.yellow().bold(),
+        LineOrigin::Conflict => tag.red().bold(),
+    }
+}
@@ -0,0 +1,94 @@
+use colored::Colorize;
+use weave_core::git;
+use weave_core::rerere::RerereCache;
+
+/// Characters of a resolution id shown in listings.
+const SHORT_ID: usize = 12;
+
+pub fn list() -> Result<(), Box<dyn std::error::Error>> {
+    let cache = RerereCache::open(&git::find_repo_root()?);
+    let resolutions = cache.list()?;
+    let pending = cache.pending_files()?;
+
+    if resolutions.is_empty() && pending.is_empty() {
+        println!("No recorded resolutions.");
+        return Ok(());
+    }
+
+    for r in &resolutions {
+        println!(
+            "{}  {} `{}` in {}",
+            short_id(&r.id).yellow(),
+            r.entity_type,
+            r.entity_name,
+            r.file_path
+        );
+    }
+    for file in &pending {
+        println!("{}  {} (awaiting commit)", "pending     ".dimmed(), file);
+    }
+
+    Ok(())
+}
+
+pub fn show(id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let cache = RerereCache::open(&git::find_repo_root()?);
+    let r = cache.find(id)?;
+
+    println!("{} {}", "resolution".yellow().bold(), r.id.yellow());
+    println!("{} `{}` in {}", r.entity_type, r.entity_name, r.file_path);
+    print_side("base", r.base.as_deref());
+    print_side("ours", r.ours.as_deref());
+    print_side("theirs", r.theirs.as_deref());
+    print_side("resolved", Some(&r.resolution));
+
+    Ok(())
+}
+
+pub fn forget(id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let cache = RerereCache::open(&git::find_repo_root()?);
+    let r = cache.forget(id)?;
+    println!(
+        "{} Forgot resolution of {} `{}` ({})",
+        "✓".green().bold(),
+        r.entity_type,
+        r.entity_name,
+        short_id(&r.id)
+    );
+    Ok(())
+}
+
+/// Record the resolutions of pending files from their committed content.
+/// Run by the post-commit hook installed by `weave setup`.
+pub fn record() -> Result<(), Box<dyn std::error::Error>> {
+    let cache = RerereCache::open(&git::find_repo_root()?);
+    for file in cache.pending_files()? {
+        let Ok(committed) = git::git_show("HEAD", &file) else {
+            // Deleted while resolving: nothing to learn from
+            cache.discard(&file)?;
+            continue;
+        };
+        for r in &cache.record(&file, &committed)? {
+            println!(
+                "weave: recorded resolution of {} `{}` in {}",
+                r.entity_type, r.entity_name, r.file_path
+            );
+        }
+    }
+    Ok(())
+}
+
+fn short_id(id: &str) -> &str {
+    id.get(..SHORT_ID).unwrap_or(id)
+}
+
+fn print_side(label: &str, content: Option<&str>) {
+    println!("\n{}", format!("--- {}", label).cyan());
+    match content {
+        Some(content) => print!("{}", content),
+        None => println!("{}", "(absent)".dimmed()),
+    }
+    if content.is_some_and(|c| !c.is_empty() && !c.ends_with('\n')) {
+        println!();
+    }
+}
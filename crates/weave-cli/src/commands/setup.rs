@@ -10,6 +10,9 @@ const SUPPORTED_EXTENSIONS: &[&str] = &[
     "*.rb", "*.cs", "*.php",
     "*.f90", "*.f95", "*.f03", "*.f08",
     "*.json", "*.yaml", "*.yml", "*.toml", "*.md",
+    "*.ipynb", "*.vue", "*.svelte", "*.css", "*.scss", "*.less",
+    "*.proto", "*.graphql", "*.gql", "*.sql",
+    "*.xml", "*.html", "*.htm", "*.svg", "*.csproj",
 ];
 
 pub fn run(driver_path: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
@@ -40,7 +43,9 @@ pub fn run(driver_path: Option<&str>) -> Result<(), Box<dyn std::error::Error>>
     }
 
     // Configure git merge driver
-    let driver_cmd = format!("{} %O %A %B %L %P", driver);
+    // %S %X %Y: the conflict marker labels (git 2.44+; older versions pass
+    // them through as is, and the driver ignores them)
+    let driver_cmd = format!("{} %O %A %B %L %P %S %X %Y", driver);
     let status = Command::new("git")
         .args(["config", "merge.weave.name", "Entity-level semantic merge"])
         .status()?;
@@ -70,7 +75,8 @@ pub fn run(driver_path: Option<&str>) -> Result<(), Box<dyn std::error::Error>>
     };
 
     let mut added = 0;
-    for ext in SUPPORTED_EXTENSIONS {
+    let build_files = weave_core::buildfile::gitattributes_patterns();
+    for ext in SUPPORTED_EXTENSIONS.iter().copied().chain(build_files) {
         let pattern = format!("{} merge=weave", ext);
         if !existing.contains(&pattern) {
             if !existing.is_empty() && !existing.ends_with('\n') {
@@ -96,6 +102,16 @@ pub fn run(driver_path: Option<&str>) -> Result<(), Box<dyn std::error::Error>>
         );
     }
 
+    // Post-commit hook: record how conflicts were resolved, for replay
+    install_hook("post-commit", "rerere record", "record conflict resolutions", false)?;
+    // Pre-merge-commit and pre-commit hooks: cross-entity validation once
+    // every file is merged, before the merge is recorded (cross-entity checks
+    // only with `[validate] on_merge`, plus any `[[verify]]`). git runs the
+    // first for a merge that merged cleanly, the second for `git commit`
+    // concluding a conflicted one; a failure stops the commit.
+    install_hook("pre-merge-commit", "validate --merge --hook", "validate merges", true)?;
+    install_hook("pre-commit", "validate --merge --hook", "validate merges", true)?;
+
     println!(
         "\n{} Weave is ready. Merge conflicts will now be resolved at the entity level.",
         "Done!".green().bold()
@@ -104,6 +120,52 @@ pub fn run(driver_path: Option<&str>) -> Result<(), Box<dyn std::error::Error>>
     Ok(())
 }
 
+/// Append `weave <command>` to the hook named `hook`, unless it already
+/// runs it. A `blocking` command failing fails the hook; otherwise its exit
+/// status is ignored.
+fn install_hook(hook: &str, command: &str, purpose: &str, blocking: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", &format!("hooks/{}", hook)])
+        .output()?;
+    if !output.status.success() {
+        return Err("Failed to locate git hooks directory".into());
+    }
+    let hook_path = std::path::PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+
+    let weave = std::env::current_exe()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| "weave".to_string());
+    let on_failure = if blocking { "exit 1" } else { "true" };
+    let line = format!("\"{}\" {} || {}\n", weave, command, on_failure);
+
+    let mut script = if hook_path.exists() {
+        fs::read_to_string(&hook_path)?
+    } else {
+        "#!/bin/sh\n".to_string()
+    };
+    if script.contains(command) {
+        println!("{} {} hook already configured", "✓".green().bold(), hook);
+        return Ok(());
+    }
+    if !script.ends_with('\n') {
+        script.push('\n');
+    }
+    script.push_str(&line);
+
+    if let Some(dir) = hook_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&hook_path, script)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755))?;
+    }
+
+    println!("{} Installed {} hook to {}", "✓".green().bold(), hook, purpose);
+    Ok(())
+}
+
 fn which_driver() -> Result<String, Box<dyn std::error::Error>> {
     // Check if weave-driver is next to current executable
     if let Ok(exe) = std::env::current_exe() {
@@ -1,6 +1,7 @@
 use colored::Colorize;
 use sem_core::parser::plugins::create_default_registry;
 use weave_core::git::find_repo_root;
+use weave_core::WeaveConfig;
 use weave_crdt::{get_agent_status, get_entities_for_file, sync_from_files, EntityStateDoc};
 
 pub fn run(
@@ -33,8 +34,15 @@ pub fn run(
 
     if let Some(file_path) = file {
         let registry = create_default_registry();
+        let cache = WeaveConfig::load(&repo_root)?.entity_cache(&repo_root);
         // Sync from file first
-        let _ = sync_from_files(&mut state, &repo_root, &[file_path.to_string()], &registry);
+        let _ = sync_from_files(
+            &mut state,
+            &repo_root,
+            &[file_path.to_string()],
+            &registry,
+            cache.as_ref(),
+        );
 
         let entities = get_entities_for_file(&state, file_path)?;
         if entities.is_empty() {
@@ -0,0 +1,165 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use colored::Colorize;
+use weave_core::git;
+use weave_core::pipeline::load_versions;
+use weave_core::verify::verify_tree;
+use weave_core::{shared_registry, supported_files, validate_versions, WeaveConfig};
+
+/// Validate a merge once every file is merged, in one pass over the
+/// repository: the cross-entity checks see the versions of every file either
+/// side changed, against the merged tree, and the `[[verify]]` checks covering
+/// a file both sides changed run over the merged tree, in a temporary
+/// worktree.
+///
+/// Validates the merge in progress (`MERGE_HEAD`), or else the merge commit
+/// at HEAD. The files a failed `[[verify]]` check covers, and with
+/// `[validate] fail_on_warnings` the files warned about, are marked
+/// conflicted in the index of a merge in progress, so the merge can't be
+/// committed until they're reviewed and `git add`ed; a committed merge makes
+/// the command fail instead. As the pre-merge-commit or pre-commit hook
+/// (`hook`), only a merge in progress is validated, before it's committed,
+/// and the cross-entity checks only run with `[validate] on_merge`.
+pub fn run(hook: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let repo_root = git::find_repo_root()?;
+    let config = WeaveConfig::load(&repo_root)?;
+    let cross_entity = !hook || config.validate.on_merge;
+    if !cross_entity && config.verify.is_empty() {
+        return Ok(());
+    }
+
+    let (ours, theirs, in_progress) = if rev_exists(&repo_root, "MERGE_HEAD") {
+        ("HEAD", "MERGE_HEAD", true)
+    } else if hook {
+        // Not committing a merge
+        return Ok(());
+    } else if rev_exists(&repo_root, "HEAD^2") {
+        ("HEAD^1", "HEAD^2", false)
+    } else {
+        return Err("no merge in progress, and HEAD is not a merge commit".into());
+    };
+    let merge_base = git::find_merge_base(ours, theirs)?;
+    let ours_files = git::diff_files(&merge_base, ours)?;
+    let theirs_files = git::diff_files(&merge_base, theirs)?;
+    let mut files: Vec<String> = ours_files.iter().chain(&theirs_files).cloned().collect();
+    files.sort();
+    files.dedup();
+
+    let mut flagged: Vec<String> = Vec::new();
+    let mut failures = 0;
+
+    if cross_entity {
+        let registry = shared_registry();
+        let versions = load_versions(&repo_root, &merge_base, ours, theirs, &files)?;
+        let warnings = validate_versions(&repo_root, &supported_files(&repo_root, registry), &versions, registry);
+        for warning in &warnings {
+            println!("{} [{}] {}", "⚠".yellow(), warning.file_path, warning);
+        }
+        if warnings.is_empty() {
+            println!("{} No cross-entity warnings in the merge", "✓".green().bold());
+        } else if config.validate.fail_on_warnings {
+            flagged.extend(warnings.iter().map(|w| w.file_path.clone()));
+            failures += warnings.len();
+        }
+    }
+
+    // The merged tree: HEAD, with the working tree's merge results of a
+    // merge in progress, except for its conflicted files
+    let unmerged = if in_progress { unmerged_files(&repo_root)? } else { Vec::new() };
+    let checked: Vec<String> = ours_files
+        .iter()
+        .filter(|f| theirs_files.contains(f) && !unmerged.contains(f))
+        .cloned()
+        .collect();
+    let tree: Vec<(String, Option<String>)> = if in_progress {
+        files
+            .iter()
+            .filter(|f| !unmerged.contains(f))
+            .map(|f| (f.clone(), git::read_file(&repo_root, f).ok()))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    for verification in verify_tree(&repo_root, "HEAD", &tree, &checked, &config) {
+        if verification.success {
+            println!("{} {}", "✓".green(), verification);
+        } else {
+            println!("{} {}", "✗".red(), verification);
+            flagged.extend(verification.files);
+            failures += 1;
+        }
+    }
+
+    if failures == 0 {
+        return Ok(());
+    }
+    if in_progress {
+        flagged.sort();
+        flagged.dedup();
+        for file in &flagged {
+            mark_conflicted(&repo_root, &merge_base, file)?;
+            println!("{} {} marked conflicted", "✗".red(), file);
+        }
+    }
+    Err(format!("{} merge validation failure(s)", failures).into())
+}
+
+/// Files with unmerged entries in the index.
+fn unmerged_files(repo_root: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let output = Command::new("git")
+        .args(["diff", "--name-only", "--diff-filter=U"])
+        .current_dir(repo_root)
+        .output()?;
+    if !output.status.success() {
+        return Err("failed to list unmerged files".into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).lines().map(str::to_string).collect())
+}
+
+fn rev_exists(repo_root: &Path, rev: &str) -> bool {
+    Command::new("git")
+        .args(["rev-parse", "--quiet", "--verify", rev])
+        .current_dir(repo_root)
+        .stderr(Stdio::null())
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
+/// Put the base, ours and theirs versions of `file` back in the index as an
+/// unmerged entry, keeping the merged content in the working tree.
+fn mark_conflicted(repo_root: &Path, merge_base: &str, file: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut info = String::new();
+    for (stage, rev) in [(1, merge_base), (2, "HEAD"), (3, "MERGE_HEAD")] {
+        let output = Command::new("git")
+            .args(["ls-tree", rev, "--", file])
+            .current_dir(repo_root)
+            .output()?;
+        // `<mode> blob <oid>\t<path>`, nothing when the file is missing there
+        let listing = String::from_utf8_lossy(&output.stdout);
+        if let Some((meta, _)) = listing.split_once('\t') {
+            if let [mode, _, oid] = meta.split_whitespace().collect::<Vec<_>>()[..] {
+                info.push_str(&format!("{} {} {}\t{}\n", mode, oid, stage, file));
+            }
+        }
+    }
+
+    let status = Command::new("git")
+        .args(["update-index", "--force-remove", "--", file])
+        .current_dir(repo_root)
+        .status()?;
+    if !status.success() {
+        return Err(format!("failed to unstage {}", file).into());
+    }
+    let mut child = Command::new("git")
+        .args(["update-index", "--index-info"])
+        .current_dir(repo_root)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().ok_or("failed to open git update-index")?.write_all(info.as_bytes())?;
+    if !child.wait()?.success() {
+        return Err(format!("failed to mark {} conflicted", file).into());
+    }
+    Ok(())
+}
@@ -24,6 +24,25 @@ enum Commands {
         /// Optional: preview a specific file only
         #[arg(long)]
         file: Option<String>,
+        /// Explain how each file and entity was merged
+        #[arg(long)]
+        explain: bool,
+        /// Run the `[[verify]]` checks over the merged tree, in a temporary worktree
+        #[arg(long)]
+        verify: bool,
+        /// Verify, then write the merged files to the working tree (conflicts included)
+        #[arg(long)]
+        write: bool,
+    },
+    /// Show where each line of a merged file would come from
+    Provenance {
+        /// The branch to merge into HEAD
+        branch: String,
+        /// The file to annotate
+        file: String,
+        /// Output line ranges as JSON
+        #[arg(long)]
+        json: bool,
     },
     /// Show entity and agent state from CRDT
     Status {
@@ -76,6 +95,39 @@ enum Commands {
         /// Entity name to release
         entity_name: String,
     },
+    /// Cross-entity validation and `[[verify]]` checks of a merge once every
+    /// file is merged: the merge in progress, or the merge commit at HEAD
+    Validate {
+        /// Validate a merge (the only mode for now)
+        #[arg(long, required = true)]
+        merge: bool,
+        /// Run as a pre-merge-commit or pre-commit hook: only a merge in progress, cross-entity checks only with `[validate] on_merge`
+        #[arg(long)]
+        hook: bool,
+    },
+    /// Manage recorded entity conflict resolutions
+    Rerere {
+        #[command(subcommand)]
+        command: RerereCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum RerereCommand {
+    /// List recorded resolutions and files awaiting commit
+    List,
+    /// Show the conflict and resolution recorded under an id
+    Show {
+        /// Resolution id (or a unique prefix)
+        id: String,
+    },
+    /// Delete a recorded resolution
+    Forget {
+        /// Resolution id (or a unique prefix)
+        id: String,
+    },
+    /// Record resolutions of conflicted files from HEAD (run by the post-commit hook)
+    Record,
 }
 
 fn main() {
@@ -85,8 +137,11 @@ fn main() {
         Commands::Setup { ref driver } => {
             commands::setup::run(driver.as_deref())
         }
-        Commands::Preview { ref branch, ref file } => {
-            commands::preview::run(branch, file.as_deref())
+        Commands::Preview { ref branch, ref file, explain, verify, write } => {
+            commands::preview::run(branch, file.as_deref(), explain, verify, write)
+        }
+        Commands::Provenance { ref branch, ref file, json } => {
+            commands::provenance::run(branch, file, json)
         }
         Commands::Status { ref file, ref agent } => {
             commands::status::run(file.as_deref(), agent.as_deref())
@@ -106,6 +161,13 @@ fn main() {
             ref file_path,
             ref entity_name,
         } => commands::release::run(agent_id, file_path, entity_name),
+        Commands::Validate { merge: _, hook } => commands::validate::run(hook),
+        Commands::Rerere { ref command } => match command {
+            RerereCommand::List => commands::rerere::list(),
+            RerereCommand::Show { id } => commands::rerere::show(id),
+            RerereCommand::Forget { id } => commands::rerere::forget(id),
+            RerereCommand::Record => commands::rerere::record(),
+        },
     };
 
     if let Err(e) = result {
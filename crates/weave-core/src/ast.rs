@@ -0,0 +1,460 @@
+//! Tree-sitter parsing for the languages sem-core extracts entities from.
+//!
+//! sem-core parses whole files to find entities but doesn't hand out its
+//! trees, so entity snippets are parsed again here, with the grammars of
+//! sem-core's code plugin: both always agree on what parses.
+
+use std::ops::Range;
+
+use sem_core::parser::plugins::code::languages::get_language_config;
+use tree_sitter::{Language, Node, Parser, Tree};
+
+/// sem-core's grammar for a file, by extension.
+pub(crate) fn language_for(file_path: &str) -> Option<Language> {
+    let (_, ext) = file_path.rsplit_once('.')?;
+    let config = get_language_config(&format!(".{}", ext.to_ascii_lowercase()))?;
+    (config.get_language)()
+}
+
+pub(crate) fn parse(language: &Language, source: &str) -> Option<Tree> {
+    let mut parser = Parser::new();
+    parser.set_language(language).ok()?;
+    parser.parse(source, None)
+}
+
+/// The 1-based line of the first ERROR or MISSING node, if the source
+/// doesn't parse cleanly.
+pub(crate) fn first_syntax_error(language: &Language, source: &str) -> Option<usize> {
+    let tree = parse(language, source)?;
+    let root = tree.root_node();
+    if !root.has_error() {
+        return None;
+    }
+    find_error(root).map(|node| node.start_position().row + 1)
+}
+
+fn find_error(node: Node) -> Option<Node> {
+    if node.is_error() || node.is_missing() {
+        return Some(node);
+    }
+    if !node.has_error() {
+        return None;
+    }
+    (0..node.child_count())
+        .filter_map(|i| node.child(i))
+        .find_map(find_error)
+}
+
+/// An entity's source split by role, each as a sequence of tokens or texts
+/// so layout doesn't matter. String literals are single tokens: a changed
+/// query or message changes behavior.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct EntityParts {
+    /// Code tokens of the declaration: name, parameters, return type,
+    /// and the members of declarative bodies (struct fields, enum variants).
+    pub signature: Vec<String>,
+    /// Code tokens of the body or initializer.
+    pub body: Vec<String>,
+    /// Comment texts, docstrings included, with each line trimmed.
+    pub comments: Vec<String>,
+}
+
+/// Split an entity snippet into its parts. `None` if it doesn't parse
+/// cleanly on its own, e.g. a method outside its class in Java.
+pub(crate) fn entity_parts(language: &Language, source: &str) -> Option<EntityParts> {
+    let tree = parse(language, source)?;
+    let root = tree.root_node();
+    if root.has_error() {
+        return None;
+    }
+    let (body, declarative) = match find_field(root, "body") {
+        Some(body) => {
+            let owner = body.parent().map(|p| p.kind()).unwrap_or_default();
+            let declarative = ["struct", "interface", "enum", "record", "protocol"]
+                .iter()
+                .any(|k| owner.contains(k));
+            (Some(body.byte_range()), declarative)
+        }
+        None => (find_field(root, "value").or_else(|| find_field(root, "right")).map(|v| v.byte_range()), false),
+    };
+    let mut parts = EntityParts::default();
+    collect(root, source.as_bytes(), body.filter(|_| !declarative), &mut parts);
+    Some(parts)
+}
+
+/// The first node, in source order, holding the given field.
+fn find_field<'tree>(node: Node<'tree>, field: &str) -> Option<Node<'tree>> {
+    if let Some(found) = node.child_by_field_name(field) {
+        return Some(found);
+    }
+    (0..node.child_count())
+        .filter_map(|i| node.child(i))
+        .find_map(|child| find_field(child, field))
+}
+
+fn collect(node: Node, source: &[u8], body: Option<Range<usize>>, parts: &mut EntityParts) {
+    let kind = node.kind();
+    let text = || node.utf8_text(source).unwrap_or_default().to_string();
+    if kind.contains("comment") {
+        let normalized: Vec<&str> = node
+            .utf8_text(source)
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect();
+        parts.comments.push(normalized.join("\n"));
+        return;
+    }
+    // A string statement on its own is a docstring
+    if is_string(node) && node.parent().is_some_and(|p| p.kind() == "expression_statement" && p.named_child_count() == 1) {
+        parts.comments.push(text());
+        return;
+    }
+    if node.child_count() == 0 || is_string(node) {
+        let token = text();
+        if token.is_empty() {
+            return;
+        }
+        if body.as_ref().is_some_and(|b| b.contains(&node.start_byte())) {
+            parts.body.push(token);
+        } else {
+            parts.signature.push(token);
+        }
+        return;
+    }
+    for child in (0..node.child_count()).filter_map(|i| node.child(i)) {
+        collect(child, source, body.clone(), parts);
+    }
+}
+
+fn is_string(node: Node) -> bool {
+    let kind = node.kind();
+    node.is_named() && (kind.contains("string") || kind == "heredoc_body") && !kind.contains("content")
+}
+
+/// A function or method declaration and its parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FunctionSignature {
+    pub name: String,
+    /// 1-based line of the declaration.
+    pub line: usize,
+    /// The parameter list as written, whitespace collapsed.
+    pub text: String,
+    pub parameters: Vec<Parameter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Parameter {
+    pub name: String,
+    pub kind: ParameterKind,
+    pub optional: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ParameterKind {
+    Positional,
+    /// Only passed by name, e.g. after `*` in Python or `b:` in Ruby.
+    KeywordOnly,
+    /// `*args`, `...rest`, `String... args`.
+    Variadic,
+    /// `**kwargs`.
+    VariadicKeyword,
+}
+
+/// A call of a function or method by name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CallSite {
+    /// The called name, without receiver or path.
+    pub callee: String,
+    /// 1-based line of the call.
+    pub line: usize,
+    /// The call as written, whitespace collapsed and shortened.
+    pub text: String,
+    pub positional: usize,
+    pub keywords: Vec<String>,
+    /// Spreads arguments (`*args`, `...xs`), so they can't be counted.
+    pub spread: bool,
+}
+
+impl FunctionSignature {
+    /// Whether a call passes arguments this signature accepts. Calls that
+    /// spread their arguments are always accepted.
+    pub fn accepts(&self, call: &CallSite) -> bool {
+        if call.spread {
+            return true;
+        }
+        let params = &self.parameters;
+        let has = |kind| params.iter().any(|p| p.kind == kind);
+        let positional: Vec<&Parameter> = params.iter().filter(|p| p.kind == ParameterKind::Positional).collect();
+        if call.positional > positional.len() && !has(ParameterKind::Variadic) {
+            return false;
+        }
+        let passed = |p: &Parameter| call.keywords.contains(&p.name);
+        let missing = positional.iter().skip(call.positional).any(|p| !p.optional && !passed(p))
+            || params
+                .iter()
+                .any(|p| p.kind == ParameterKind::KeywordOnly && !p.optional && !passed(p));
+        let unknown = !has(ParameterKind::VariadicKeyword)
+            && call.keywords.iter().any(|k| {
+                !params
+                    .iter()
+                    .any(|p| &p.name == k && matches!(p.kind, ParameterKind::Positional | ParameterKind::KeywordOnly))
+            });
+        !missing && !unknown
+    }
+}
+
+/// Every named function or method declared in a file, nested ones included.
+pub(crate) fn function_signatures(language: &Language, source: &str) -> Vec<FunctionSignature> {
+    let Some(tree) = parse(language, source) else {
+        return vec![];
+    };
+    let source = source.as_bytes();
+    let mut found = Vec::new();
+    walk(tree.root_node(), &mut |node| {
+        let Some(list) = node.child_by_field_name("parameters") else {
+            return;
+        };
+        let Some(name) = declared_name(node, source) else {
+            return;
+        };
+        found.push(FunctionSignature {
+            name,
+            line: node.start_position().row + 1,
+            text: collapse(node_text(list, source)),
+            parameters: parameters(list, source),
+        });
+    });
+    found
+}
+
+/// Every call by name in a file.
+pub(crate) fn call_sites(language: &Language, source: &str) -> Vec<CallSite> {
+    let Some(tree) = parse(language, source) else {
+        return vec![];
+    };
+    let source = source.as_bytes();
+    let mut found = Vec::new();
+    walk(tree.root_node(), &mut |node| {
+        if !(node.kind().contains("call") || node.kind().contains("invocation")) {
+            return;
+        }
+        let Some(arguments) = node.child_by_field_name("arguments") else {
+            return;
+        };
+        let Some(callee) = ["function", "method", "name"]
+            .iter()
+            .find_map(|field| node.child_by_field_name(field))
+        else {
+            return;
+        };
+        let mut call = CallSite {
+            callee: last_segment(node_text(callee, source)).to_string(),
+            line: node.start_position().row + 1,
+            text: shorten(collapse(node_text(node, source))),
+            positional: 0,
+            keywords: Vec::new(),
+            spread: false,
+        };
+        for arg in (0..arguments.child_count()).filter_map(|i| arguments.child(i)) {
+            let kind = arg.kind();
+            if kind == "..." || kind.contains("splat") || kind.contains("spread") {
+                call.spread = true;
+            } else if !arg.is_named() || kind.contains("comment") || kind.contains("block") {
+                continue;
+            } else if let Some(name) = keyword_name(arg, source) {
+                call.keywords.push(name);
+            } else {
+                call.positional += 1;
+            }
+        }
+        found.push(call);
+    });
+    found
+}
+
+fn walk<'tree>(node: Node<'tree>, visit: &mut impl FnMut(Node<'tree>)) {
+    visit(node);
+    for child in (0..node.child_count()).filter_map(|i| node.child(i)) {
+        walk(child, visit);
+    }
+}
+
+fn node_text<'a>(node: Node, source: &'a [u8]) -> &'a str {
+    node.utf8_text(source).unwrap_or_default()
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn shorten(text: String) -> String {
+    const MAX: usize = 60;
+    match text.char_indices().nth(MAX) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+/// `process` from `self.process`, `utils::process` or `$obj->process`.
+fn last_segment(path: &str) -> &str {
+    path.rsplit(['.', ':', '>', '\\']).next().unwrap_or(path).trim().trim_start_matches('$')
+}
+
+fn declared_name(node: Node, source: &[u8]) -> Option<String> {
+    if let Some(name) = node.child_by_field_name("name") {
+        return Some(node_text(name, source).to_string());
+    }
+    // C and C++ function declarators name the function in `declarator`
+    if let Some(declarator) = node.child_by_field_name("declarator") {
+        return Some(last_segment(node_text(declarator, source)).to_string());
+    }
+    // `const process = (a, b) => ...`
+    let parent = node.parent()?;
+    if parent.kind() == "variable_declarator" {
+        return parent.child_by_field_name("name").map(|n| node_text(n, source).to_string());
+    }
+    None
+}
+
+fn parameters(list: Node, source: &[u8]) -> Vec<Parameter> {
+    let mut found = Vec::new();
+    let mut keyword_only = false;
+    for (i, node) in (0..list.child_count()).filter_map(|i| list.child(i)).enumerate() {
+        let kind = node.kind();
+        let text = node_text(node, source);
+        // C's variadic `...` is a bare token
+        if kind == "..." {
+            found.push(Parameter {
+                name: String::new(),
+                kind: ParameterKind::Variadic,
+                optional: true,
+            });
+            continue;
+        }
+        if !node.is_named()
+            || kind.contains("comment")
+            || kind.contains("attribute")
+            || matches!(kind, "self_parameter" | "receiver_parameter" | "block_parameter" | "positional_separator")
+            || (i <= 1 && matches!(text, "self" | "cls"))
+            || text == "void"
+        {
+            continue;
+        }
+        if kind == "keyword_separator" {
+            keyword_only = true;
+            continue;
+        }
+        let kind = if has_descendant(node, &|k| k.contains("dictionary_splat") || k.contains("hash_splat")) {
+            ParameterKind::VariadicKeyword
+        } else if text.starts_with("params ")
+            || has_descendant(node, &|k| {
+                k == "..." || k.contains("splat") || k.contains("rest_pattern") || k.contains("variadic") || k.contains("spread")
+            })
+        {
+            // Python's `*args` ends the positional parameters
+            keyword_only |= has_descendant(node, &|k| k == "list_splat_pattern");
+            ParameterKind::Variadic
+        } else if keyword_only || kind == "keyword_parameter" {
+            ParameterKind::KeywordOnly
+        } else {
+            ParameterKind::Positional
+        };
+        let optional = node.kind().contains("default")
+            || node.kind().contains("optional")
+            || node.kind() == "assignment_pattern"
+            || node.child_by_field_name("value").is_some()
+            || node.child_by_field_name("default_value").is_some()
+            || (0..node.child_count())
+                .filter_map(|i| node.child(i))
+                .any(|c| c.kind() == "=" || c.kind() == "equals_value_clause");
+        // Go declares several parameters at once: `a, b int`
+        let mut cursor = node.walk();
+        let names: Vec<String> = node
+            .children_by_field_name("name", &mut cursor)
+            .map(|n| node_text(n, source).trim_start_matches('$').to_string())
+            .collect();
+        if names.len() > 1 {
+            found.extend(names.into_iter().map(|name| Parameter { name, kind, optional }));
+        } else {
+            let name = names.into_iter().next().unwrap_or_else(|| parameter_name(node, source));
+            found.push(Parameter { name, kind, optional });
+        }
+    }
+    found
+}
+
+fn parameter_name(node: Node, source: &[u8]) -> String {
+    ["pattern", "declarator"]
+        .iter()
+        .find_map(|field| node.child_by_field_name(field))
+        .or_else(|| find_kind(node, "identifier"))
+        .map(|n| last_segment(node_text(n, source)).to_string())
+        .unwrap_or_else(|| node_text(node, source).to_string())
+}
+
+/// The name of a keyword argument: `flag=True`, `flag: true`, `flag: 1`.
+fn keyword_name(arg: Node, source: &[u8]) -> Option<String> {
+    let name = match arg.kind() {
+        "keyword_argument" | "argument" => arg.child_by_field_name("name")?,
+        "pair" => arg.child_by_field_name("key")?,
+        _ => return None,
+    };
+    Some(node_text(name, source).trim_end_matches(':').to_string())
+}
+
+fn has_descendant(node: Node, predicate: &impl Fn(&str) -> bool) -> bool {
+    predicate(node.kind())
+        || (0..node.child_count())
+            .filter_map(|i| node.child(i))
+            .any(|child| has_descendant(child, predicate))
+}
+
+fn find_kind<'tree>(node: Node<'tree>, kind: &str) -> Option<Node<'tree>> {
+    if node.kind() == kind {
+        return Some(node);
+    }
+    (0..node.child_count())
+        .filter_map(|i| node.child(i))
+        .find_map(|child| find_kind(child, kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_syntax_error() {
+        let rust = language_for("src/lib.rs").unwrap();
+        assert_eq!(first_syntax_error(&rust, "fn a() {\n    1\n}\n"), None);
+        // Duplicated closing brace
+        assert_eq!(first_syntax_error(&rust, "fn a() {\n    1\n}\n}\n"), Some(4));
+        // Unclosed block
+        assert!(first_syntax_error(&rust, "fn a() {\n    1\n").is_some());
+    }
+
+    #[test]
+    fn test_language_for() {
+        assert!(language_for("app/models.py").is_some());
+        assert!(language_for("web/App.TSX").is_some());
+        assert!(language_for("README").is_none());
+        assert!(language_for("notes.txt").is_none());
+    }
+
+    #[test]
+    fn test_signature_accepts_calls() {
+        let python = language_for("app/jobs.py").unwrap();
+        let source = "def process(self, item, retries=3, *, dry_run):\n    pass\n\nprocess(job, dry_run=True)\nprocess(job)\nprocess(job, 1, 2, dry_run=False)\nprocess(job, dry=True)\nprocess(*args)\n";
+        let signatures = function_signatures(&python, source);
+        assert_eq!(signatures.len(), 1);
+        assert_eq!(signatures[0].name, "process");
+        assert_eq!(signatures[0].parameters.len(), 3);
+
+        let calls = call_sites(&python, source);
+        let accepted: Vec<bool> = calls.iter().map(|c| signatures[0].accepts(c)).collect();
+        assert_eq!(accepted, vec![true, false, false, false, true]);
+        assert_eq!(calls[1].line, 5);
+        assert_eq!(calls[1].text, "process(job)");
+    }
+}
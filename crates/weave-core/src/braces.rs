@@ -0,0 +1,392 @@
+//! Parse and merge brace-structured files: stylesheets, protobuf, GraphQL.
+//!
+//! A file is a [`Block`] of items. Each item is either a statement
+//! (`color: red;`, `string name = 1;`) or a header followed by a nested
+//! `{ ... }` block. Items are keyed by a per-format function, so the same
+//! rule/message/type can be matched across the three versions and merged
+//! member by member.
+//!
+//! Comments and whitespace before an item travel with it, so a file
+//! round-trips byte for byte when nothing changes.
+
+use std::collections::HashMap;
+
+use crate::conflict::{classify_conflict, EntityConflict, MergeStats, Rendered};
+use crate::keyed::{merge_keyed, pick_side, KeyedMerge};
+
+/// The lexical rules of one brace-structured format.
+pub(crate) struct Syntax {
+    /// Line comment prefixes (`//`, `#`). `/* */` comments are always recognized.
+    pub line_comments: &'static [&'static str],
+    /// Whether a newline ends a statement, as between GraphQL fields.
+    /// A line starting with `|`, `&`, `=`, `@`, `{`, `)` or `implements`
+    /// continues the previous one.
+    pub newline_terminates: bool,
+    /// Whether string literals before an item are descriptions that travel
+    /// with it, as in GraphQL.
+    pub descriptions: bool,
+    /// Key for matching an item across versions, as `kind:name`.
+    pub key: fn(&Item) -> String,
+    /// Entity type reported in conflicts for a key kind.
+    pub entity_type: fn(&str) -> &str,
+}
+
+/// A sequence of items: the whole file, or the inside of a `{ ... }` block.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Block {
+    pub items: Vec<(String, Item)>,
+    /// Whitespace and comments after the last item.
+    pub tail: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Item {
+    /// Whitespace and comments before the item.
+    pub leading: String,
+    /// Header including `{`, or a whole statement.
+    pub header: String,
+    pub body: Option<Box<Block>>,
+    /// `}` for blocks, empty otherwise.
+    pub footer: String,
+    /// Set when the header holds the markers of this conflict of the merge.
+    pub conflict: Option<usize>,
+}
+
+impl Block {
+    pub fn render(&self) -> String {
+        let mut out = Rendered::default();
+        self.render_into(&mut out);
+        out.content
+    }
+
+    /// Render a merged block, along with the conflict block each of its
+    /// `conflicts` conflicts is rendered as.
+    pub fn render_merged(&self, conflicts: usize) -> (String, Vec<Option<usize>>) {
+        let mut out = Rendered::default();
+        self.render_into(&mut out);
+        out.finish(conflicts)
+    }
+
+    fn render_into(&self, out: &mut Rendered) {
+        for (_, item) in &self.items {
+            out.push_str(&item.leading);
+            if let Some(conflict) = item.conflict {
+                out.push_conflict(conflict, &item.header);
+                continue;
+            }
+            out.push_str(&item.header);
+            if let Some(body) = &item.body {
+                body.render_into(out);
+            }
+            out.push_str(&item.footer);
+        }
+        out.push_str(&self.tail);
+    }
+}
+
+impl Item {
+
+    /// The item without its leading whitespace and comments.
+    pub fn text(&self) -> String {
+        let body = self.body.as_ref().map(|b| b.render()).unwrap_or_default();
+        format!("{}{}{}", self.header, body, self.footer)
+    }
+
+    /// The header with `{`/`;` and surrounding whitespace removed, and inner
+    /// whitespace collapsed.
+    pub fn normalized_header(&self) -> String {
+        let header = self.header.trim_end_matches(['{', ';']).trim();
+        header.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// Parse a file. Returns None on unbalanced braces.
+pub(crate) fn parse(content: &str, syntax: &Syntax) -> Option<Block> {
+    let mut parser = Parser {
+        src: content,
+        pos: 0,
+        syntax,
+    };
+    let block = parser.block(false)?;
+    (parser.pos == content.len()).then_some(block)
+}
+
+/// Merge two blocks against their base. Conflicting items are rendered as
+/// conflict markers in place and reported in `conflicts`.
+pub(crate) fn merge_block(
+    base: &Block,
+    ours: &Block,
+    theirs: &Block,
+    syntax: &Syntax,
+    stats: &mut MergeStats,
+    conflicts: &mut Vec<EntityConflict>,
+) -> Block {
+    let merged = merge_keyed(&base.items, &ours.items, &theirs.items, stats, |_, b, o, t| {
+        merge_item(b, o, t, syntax, conflicts)
+    });
+
+    let mut items = Vec::new();
+    for entry in merged {
+        match entry {
+            KeyedMerge::Clean(item) => items.push(item),
+            KeyedMerge::Conflict(c) => {
+                if let Some(item) = c.rendered {
+                    // Nested conflicts were already rendered inside the block
+                    items.push(item);
+                    continue;
+                }
+                let (kind, name) = split_key(&c.key);
+                let base_text = c.base.as_ref().map(Item::text);
+                let ours_text = c.ours.as_ref().map(Item::text);
+                let theirs_text = c.theirs.as_ref().map(Item::text);
+                let conflict = EntityConflict {
+                    entity_name: name.to_string(),
+                    entity_type: (syntax.entity_type)(kind).to_string(),
+                    kind: c.kind,
+                    complexity: classify_conflict(base_text.as_deref(), ours_text.as_deref(), theirs_text.as_deref()),
+                    ours_content: ours_text,
+                    theirs_content: theirs_text,
+                    base_content: base_text,
+                };
+
+                // Markers must start at column 0: drop the indentation before the item
+                let mut leading = c
+                    .ours
+                    .as_ref()
+                    .or(c.theirs.as_ref())
+                    .map(|i| i.leading.trim_end_matches([' ', '\t']).to_string())
+                    .unwrap_or_default();
+                if !leading.is_empty() && !leading.ends_with('\n') {
+                    leading.push('\n');
+                }
+                items.push(Item {
+                    leading,
+                    header: conflict.to_conflict_markers(),
+                    body: None,
+                    footer: String::new(),
+                    conflict: Some(conflicts.len()),
+                });
+                conflicts.push(conflict);
+            }
+        }
+    }
+
+    let tail = pick_side(Some(&base.tail), Some(&ours.tail), Some(&theirs.tail))
+        .flatten()
+        .unwrap_or(&ours.tail)
+        .clone();
+
+    // Keys only matter for matching; the merged block is just rendered
+    Block {
+        items: items.into_iter().map(|item| (String::new(), item)).collect(),
+        tail,
+    }
+}
+
+/// Merge an item changed on both sides. Blocks recurse into their children;
+/// statements changed differently conflict.
+fn merge_item(
+    base: Option<&Item>,
+    ours: &Item,
+    theirs: &Item,
+    syntax: &Syntax,
+    conflicts: &mut Vec<EntityConflict>,
+) -> Result<Item, Option<Item>> {
+    let (Some(ours_body), Some(theirs_body)) = (&ours.body, &theirs.body) else {
+        return Err(None);
+    };
+    let header = pick_side(base.map(|b| &b.header), Some(&ours.header), Some(&theirs.header))
+        .flatten()
+        .ok_or(None)?
+        .clone();
+    let leading = pick_side(base.map(|b| &b.leading), Some(&ours.leading), Some(&theirs.leading))
+        .flatten()
+        .unwrap_or(&ours.leading)
+        .clone();
+
+    let empty = Block::default();
+    let base_body = base.and_then(|b| b.body.as_deref()).unwrap_or(&empty);
+    let before = conflicts.len();
+    // Nested items don't count as top-level entities in the stats
+    let body = merge_block(base_body, ours_body, theirs_body, syntax, &mut MergeStats::default(), conflicts);
+
+    let item = Item {
+        leading,
+        header,
+        body: Some(Box::new(body)),
+        footer: ours.footer.clone(),
+        conflict: None,
+    };
+    if conflicts.len() == before {
+        Ok(item)
+    } else {
+        Err(Some(item))
+    }
+}
+
+/// Split a `kind:name#n` key into (kind, name).
+fn split_key(key: &str) -> (&str, &str) {
+    let (kind, rest) = key.split_once(':').unwrap_or(("", key));
+    let name = rest.rsplit_once('#').map(|(n, _)| n).unwrap_or(rest);
+    (kind, name)
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    syntax: &'a Syntax,
+}
+
+impl Parser<'_> {
+    /// Parse items until `}` (nested) or end of input (top level).
+    /// Leaves `pos` on the closing `}`.
+    fn block(&mut self, nested: bool) -> Option<Block> {
+        let mut items: Vec<(String, Item)> = Vec::new();
+        let mut seen: HashMap<String, usize> = HashMap::new();
+
+        loop {
+            let leading_start = self.pos;
+            self.skip_trivia();
+            let bytes = self.src.as_bytes();
+
+            if self.pos >= self.src.len() || bytes[self.pos] == b'}' {
+                let at_end = self.pos >= self.src.len();
+                if at_end == nested {
+                    // Unclosed block, or a stray `}` at the top level
+                    return None;
+                }
+                let tail = self.src[leading_start..self.pos].to_string();
+                return Some(Block { items, tail });
+            }
+
+            let leading = self.src[leading_start..self.pos].to_string();
+            let start = self.pos;
+            let end = self.scan_header()?;
+
+            let item = match bytes.get(end) {
+                Some(b'{') => {
+                    self.pos = end + 1;
+                    let body = self.block(true)?;
+                    self.pos += 1; // the closing `}`
+                    Item {
+                        leading,
+                        header: self.src[start..=end].to_string(),
+                        body: Some(Box::new(body)),
+                        footer: "}".to_string(),
+                        conflict: None,
+                    }
+                }
+                Some(b';') => {
+                    self.pos = end + 1;
+                    Item {
+                        leading,
+                        header: self.src[start..=end].to_string(),
+                        body: None,
+                        footer: String::new(),
+                        conflict: None,
+                    }
+                }
+                // Newline-terminated statement, or the last one without `;`
+                // before `}` or end of input
+                _ => {
+                    let header = self.src[start..end].trim_end();
+                    self.pos = start + header.len();
+                    Item {
+                        leading,
+                        header: header.to_string(),
+                        body: None,
+                        footer: String::new(),
+                        conflict: None,
+                    }
+                }
+            };
+
+            let key = (self.syntax.key)(&item);
+            let count = seen.entry(key.clone()).or_insert(0);
+            *count += 1;
+            items.push((format!("{}#{}", key, count), item));
+        }
+    }
+
+    /// Skip whitespace, comments and (if enabled) description strings.
+    fn skip_trivia(&mut self) {
+        loop {
+            let rest = &self.src[self.pos..];
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.starts_with("/*") {
+                self.pos += trimmed.find("*/").map(|i| i + 2).unwrap_or(trimmed.len());
+            } else if self.syntax.line_comments.iter().any(|p| trimmed.starts_with(p)) {
+                self.pos += trimmed.find('\n').map(|i| i + 1).unwrap_or(trimmed.len());
+            } else if self.syntax.descriptions && trimmed.starts_with("\"\"\"") {
+                self.pos += trimmed[3..].find("\"\"\"").map(|i| i + 6).unwrap_or(trimmed.len());
+            } else if self.syntax.descriptions && trimmed.starts_with('"') {
+                self.pos += string_end(trimmed.as_bytes(), 0);
+            } else {
+                return;
+            }
+        }
+    }
+
+    /// Find the `{`, `;` or `}` (or terminating newline) that ends the item
+    /// starting at `pos`, skipping strings, parentheses, comments and
+    /// `#{...}` interpolation.
+    fn scan_header(&self) -> Option<usize> {
+        let bytes = self.src.as_bytes();
+        let mut i = self.pos;
+        let mut parens = 0usize;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'"' | b'\'' => {
+                    i = string_end(bytes, i);
+                    continue;
+                }
+                b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                    i += self.src[i..].find("*/")? + 1;
+                }
+                b'#' if bytes.get(i + 1) == Some(&b'{') => {
+                    i += self.src[i..].find('}')?;
+                }
+                _ if parens == 0 && self.syntax.line_comments.iter().any(|p| self.src[i..].starts_with(p)) => {
+                    // Trailing comment: resume at the newline
+                    i += self.src[i..].find('\n').unwrap_or(bytes.len() - i);
+                    continue;
+                }
+                b'(' => parens += 1,
+                b')' => parens = parens.saturating_sub(1),
+                b'{' | b';' | b'}' if parens == 0 => return Some(i),
+                b'\n' if parens == 0 && self.syntax.newline_terminates && !self.continues(i + 1) => {
+                    return Some(i);
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        Some(bytes.len())
+    }
+
+    /// Whether the line starting at `at` continues the statement before it.
+    fn continues(&self, at: usize) -> bool {
+        let next = self.src[at..].trim_start();
+        next.starts_with(['|', '&', '=', '@', '{', ')'])
+            || next.starts_with("implements")
+            || self.syntax.line_comments.iter().any(|p| next.starts_with(p)) && {
+                // A comment line: look past it
+                let skip = self.src.len() - next.len();
+                next.find('\n').is_some_and(|nl| self.continues(skip + nl + 1))
+            }
+    }
+}
+
+/// Index just past the string literal starting at `start`.
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() && bytes[i] != quote {
+        if bytes[i] == b'\\' {
+            i += 1;
+        }
+        i += 1;
+    }
+    (i + 1).min(bytes.len())
+}
@@ -0,0 +1,94 @@
+//! Time budget for a merge, with cooperative cancellation.
+//!
+//! The entity merge checks its budget between its expensive phases: after
+//! parsing, after entity matching, before each diffy call and each member of
+//! an inner merge, and after every format merger. Once the budget is
+//! exhausted the file falls back to `git merge-file`, which always
+//! terminates, and nothing keeps running in the background.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long the entity merge of a file may take before falling back, unless
+/// `[merge] timeout_ms` says otherwise (see
+/// [`MergeConfig`](crate::config::MergeConfig)).
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A deadline and a cancellation flag shared by every clone, so one caller
+/// can stop merges running on other threads.
+#[derive(Debug, Clone, Default)]
+pub struct MergeBudget {
+    deadline: Option<Instant>,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Why a budget ran out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exhausted {
+    TimedOut,
+    Cancelled,
+}
+
+impl MergeBudget {
+    /// No deadline; only [`cancel`](Self::cancel) stops the merge.
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// A deadline `timeout` from now.
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            deadline: Some(Instant::now() + timeout),
+            ..Self::default()
+        }
+    }
+
+    /// The same cancellation flag with a deadline `timeout` from now, e.g. a
+    /// per-file budget under a budget for the whole merge.
+    pub fn child(&self, timeout: Duration) -> Self {
+        let deadline = Instant::now() + timeout;
+        Self {
+            deadline: Some(self.deadline.map_or(deadline, |d| d.min(deadline))),
+            cancelled: Arc::clone(&self.cancelled),
+        }
+    }
+
+    /// Stop every merge using this budget or a clone of it.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the merge should stop, and why.
+    pub fn exhausted(&self) -> Option<Exhausted> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            Some(Exhausted::Cancelled)
+        } else if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            Some(Exhausted::TimedOut)
+        } else {
+            None
+        }
+    }
+
+    /// [`exhausted`](Self::exhausted) as a `Result`, to stop with `?`.
+    pub fn check(&self) -> Result<(), Exhausted> {
+        self.exhausted().map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadline_and_shared_cancellation() {
+        assert_eq!(MergeBudget::unlimited().exhausted(), None);
+        assert_eq!(MergeBudget::with_timeout(Duration::ZERO).exhausted(), Some(Exhausted::TimedOut));
+
+        let budget = MergeBudget::with_timeout(Duration::from_secs(60));
+        let child = budget.child(Duration::from_secs(600));
+        assert_eq!(child.exhausted(), None);
+        budget.cancel();
+        assert_eq!(child.exhausted(), Some(Exhausted::Cancelled));
+    }
+}
@@ -0,0 +1,575 @@
+//! Entity-level merge for build files: Makefiles, justfiles and Dockerfiles.
+//!
+//! - `Makefile` / `*.mk`: targets (with their recipe) and variables
+//! - `justfile`: recipes, variables, aliases and settings
+//! - `Dockerfile` / `Containerfile`: build stages, from one `FROM` to the next,
+//!   keyed by their `AS name`, or by base image when unnamed
+//!
+//! Two branches adding different targets, recipes or stages merge cleanly.
+//! An entity changed on both sides is merged line by line on its own.
+//! Comments and blank lines before an entity travel with it.
+
+use std::collections::HashMap;
+
+use crate::conflict::{classify_conflict, BlockCounter, EntityConflict, MergeStats};
+use crate::keyed::{merge_keyed, pick_side, KeyedMerge};
+use crate::merge::{line_level_fallback, MergeContext, MergeResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BuildFileKind {
+    Make,
+    Just,
+    Docker,
+}
+
+/// The file names each kind is recognized by, as `.gitattributes` patterns:
+/// an exact name, `prefix*` or `*suffix`, matched case-insensitively.
+const PATTERNS: &[(&str, BuildFileKind)] = &[
+    ("Makefile", BuildFileKind::Make),
+    ("GNUmakefile", BuildFileKind::Make),
+    ("*.mk", BuildFileKind::Make),
+    ("justfile", BuildFileKind::Just),
+    (".justfile", BuildFileKind::Just),
+    ("*.just", BuildFileKind::Just),
+    ("Dockerfile", BuildFileKind::Docker),
+    ("Dockerfile.*", BuildFileKind::Docker),
+    ("*.dockerfile", BuildFileKind::Docker),
+    ("Containerfile", BuildFileKind::Docker),
+    ("Containerfile.*", BuildFileKind::Docker),
+];
+
+fn detect(file_path: &str) -> Option<BuildFileKind> {
+    let name = file_path.rsplit(['/', '\\']).next().unwrap_or(file_path).to_lowercase();
+    PATTERNS.iter().find_map(|&(pattern, kind)| {
+        let pattern = pattern.to_lowercase();
+        let matches = match (pattern.strip_prefix('*'), pattern.strip_suffix('*')) {
+            (Some(suffix), _) => name.ends_with(suffix),
+            (_, Some(prefix)) => name.starts_with(prefix),
+            _ => name == pattern,
+        };
+        matches.then_some(kind)
+    })
+}
+
+/// The `.gitattributes` patterns of every build file [`is_build_file`]
+/// recognizes.
+pub fn gitattributes_patterns() -> impl Iterator<Item = &'static str> {
+    PATTERNS.iter().map(|&(pattern, _)| pattern)
+}
+
+/// Check if a file is a Makefile, justfile or Dockerfile.
+pub fn is_build_file(file_path: &str) -> bool {
+    detect(file_path).is_some()
+}
+
+/// One entity: a target, recipe, stage, variable or other statement.
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    /// Blank lines and comments before the entity.
+    leading: String,
+    /// The entity's lines, including the final newline.
+    text: String,
+}
+
+/// A build file split into keyed entries.
+#[derive(Debug, Default)]
+struct BuildFile {
+    entries: Vec<(String, Entry)>,
+    /// Blank lines and comments after the last entry.
+    tail: String,
+    seen: HashMap<String, usize>,
+}
+
+impl BuildFile {
+    fn push(&mut self, key: String, leading: String, text: String) {
+        let count = self.seen.entry(key.clone()).or_insert(0);
+        *count += 1;
+        self.entries.push((format!("{}#{}", key, count), Entry { leading, text }));
+    }
+
+    fn last_text(&mut self) -> Option<&mut String> {
+        self.entries.last_mut().map(|(_, e)| &mut e.text)
+    }
+}
+
+/// Merge three versions of a build file entity by entity.
+///
+/// Returns None if any version can't be split (e.g. an `ifeq` without `endif`).
+pub fn merge_build_file(base: &str, ours: &str, theirs: &str, file_path: &str, context: &MergeContext) -> Option<MergeResult> {
+    let kind = detect(file_path)?;
+    let split = |content: &str| match kind {
+        BuildFileKind::Make => split_makefile(content),
+        BuildFileKind::Just => Some(split_justfile(content)),
+        BuildFileKind::Docker => Some(split_dockerfile(content)),
+    };
+    let base_file = split(base)?;
+    let ours_file = split(ours)?;
+    let theirs_file = split(theirs)?;
+
+    let mut stats = MergeStats::default();
+    let merged = merge_keyed(
+        &base_file.entries,
+        &ours_file.entries,
+        &theirs_file.entries,
+        &mut stats,
+        |_, b, o, t| {
+            let base_entry = b.ok_or(None)?;
+            let result = line_level_fallback(&base_entry.text, &o.text, &t.text, file_path, context);
+            if !result.is_clean() {
+                return Err(None);
+            }
+            let leading = pick_side(Some(&base_entry.leading), Some(&o.leading), Some(&t.leading))
+                .flatten()
+                .unwrap_or(&o.leading)
+                .clone();
+            Ok(Entry {
+                leading,
+                text: result.content,
+            })
+        },
+    );
+
+    let mut content = String::new();
+    let mut conflicts = Vec::new();
+    let mut conflict_blocks = Vec::new();
+    let mut counter = BlockCounter::default();
+    for entry in merged {
+        match entry {
+            KeyedMerge::Clean(e) => {
+                content.push_str(&e.leading);
+                content.push_str(&e.text);
+            }
+            KeyedMerge::Conflict(c) => {
+                let (entity_type, name) = describe_key(&c.key, kind);
+                let base_text = c.base.as_ref().map(|e| e.text.clone());
+                let ours_text = c.ours.as_ref().map(|e| e.text.clone());
+                let theirs_text = c.theirs.as_ref().map(|e| e.text.clone());
+                let conflict = EntityConflict {
+                    entity_name: name.to_string(),
+                    entity_type: entity_type.to_string(),
+                    kind: c.kind,
+                    complexity: classify_conflict(base_text.as_deref(), ours_text.as_deref(), theirs_text.as_deref()),
+                    ours_content: ours_text,
+                    theirs_content: theirs_text,
+                    base_content: base_text,
+                };
+                let leading = c.ours.as_ref().or(c.theirs.as_ref()).map(|e| e.leading.as_str()).unwrap_or("");
+                content.push_str(leading);
+                conflict_blocks.push(Some(counter.next(&content)));
+                content.push_str(&conflict.to_conflict_markers());
+                conflicts.push(conflict);
+            }
+        }
+    }
+
+    let tail = pick_side(Some(&base_file.tail), Some(&ours_file.tail), Some(&theirs_file.tail))
+        .flatten()
+        .unwrap_or(&ours_file.tail);
+    content.push_str(tail);
+
+    Some(MergeResult {
+        content,
+        conflicts,
+        conflict_blocks,
+        stats,
+        ..Default::default()
+    })
+}
+
+/// Split a key into (entity type, name) for conflict reports.
+fn describe_key(key: &str, kind: BuildFileKind) -> (&'static str, &str) {
+    let (prefix, rest) = key.split_once(':').unwrap_or(("stmt", key));
+    let name = rest.rsplit_once('#').map(|(n, _)| n).unwrap_or(rest);
+    let entity_type = match (prefix, kind) {
+        ("target", _) => "target",
+        ("recipe", _) => "recipe",
+        ("stage" | "from", _) => "stage",
+        ("var", BuildFileKind::Docker) => "arg",
+        ("var", _) => "variable",
+        ("alias", _) => "alias",
+        ("setting", _) => "setting",
+        ("cond", _) => "conditional",
+        _ => "statement",
+    };
+    (entity_type, name)
+}
+
+/// Split into lines, joining backslash continuations. Each line keeps its newline.
+fn logical_lines(content: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+    while pos < content.len() {
+        let end = content[pos..].find('\n').map(|i| pos + i + 1).unwrap_or(content.len());
+        let line = content[pos..end].trim_end_matches(['\n', '\r']);
+        pos = end;
+        if !line.ends_with('\\') || pos >= content.len() {
+            lines.push(&content[start..end]);
+            start = end;
+        }
+    }
+    lines
+}
+
+fn is_trivia(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.is_empty() || trimmed.starts_with('#')
+}
+
+fn normalize(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Makefile: targets with their tab-indented recipes, variables, conditionals.
+fn split_makefile(content: &str) -> Option<BuildFile> {
+    let mut file = BuildFile::default();
+    let mut pending = String::new();
+    let mut in_rule = false;
+    let mut lines = logical_lines(content).into_iter();
+
+    while let Some(line) = lines.next() {
+        if line.starts_with('\t') && in_rule {
+            // Blank lines and comments between recipe lines stay in the recipe
+            let text = file.last_text()?;
+            text.push_str(&std::mem::take(&mut pending));
+            text.push_str(line);
+            continue;
+        }
+        if is_trivia(line) {
+            pending.push_str(line);
+            continue;
+        }
+
+        let normalized = normalize(line);
+        let first = normalized.split(' ').next().unwrap_or("");
+        let mut text = line.to_string();
+        in_rule = false;
+
+        let key = match first {
+            "ifeq" | "ifneq" | "ifdef" | "ifndef" => {
+                take_until(&mut lines, &mut text, &["ifeq", "ifneq", "ifdef", "ifndef"], "endif")?;
+                format!("cond:{}", normalized)
+            }
+            "define" => {
+                take_until(&mut lines, &mut text, &["define"], "endef")?;
+                let name = normalized.split(' ').nth(1).unwrap_or("");
+                format!("var:{}", name)
+            }
+            _ => match make_line_key(&normalized) {
+                Some((key, rule)) => {
+                    in_rule = rule;
+                    key
+                }
+                None => format!("stmt:{}", normalized),
+            },
+        };
+        file.push(key, std::mem::take(&mut pending), text);
+    }
+
+    file.tail = pending;
+    Some(file)
+}
+
+/// Consume lines up to and including the `close` directive matching the
+/// block opened by the line already in `text`.
+fn take_until<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    text: &mut String,
+    open: &[&str],
+    close: &str,
+) -> Option<()> {
+    let mut depth = 1;
+    for line in lines.by_ref() {
+        text.push_str(line);
+        let first = line.split_whitespace().next().unwrap_or("");
+        if open.contains(&first) {
+            depth += 1;
+        } else if first == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(());
+            }
+        }
+    }
+    None
+}
+
+/// Key of a Makefile line, and whether it starts a rule (so tab-indented
+/// lines after it are its recipe).
+fn make_line_key(normalized: &str) -> Option<(String, bool)> {
+    let unprefixed = normalized
+        .strip_prefix("export ")
+        .or_else(|| normalized.strip_prefix("override "))
+        .unwrap_or(normalized);
+
+    let name_end = unprefixed
+        .find(|c: char| c.is_whitespace() || matches!(c, ':' | '=' | '+' | '?' | '!'))
+        .unwrap_or(unprefixed.len());
+    let name = &unprefixed[..name_end];
+    let op = unprefixed[name_end..].trim_start();
+
+    if op.starts_with("+=") {
+        // Appends from both sides are all kept
+        return Some((format!("stmt:{}", normalized), false));
+    }
+    if !name.is_empty()
+        && (op.starts_with('=')
+            || op.starts_with(":=")
+            || op.starts_with("::=")
+            || op.starts_with("?=")
+            || op.starts_with("!="))
+    {
+        return Some((format!("var:{}", name), false));
+    }
+
+    let (targets, _) = normalized.split_once(':')?;
+    let targets = targets.trim();
+    if targets.is_empty() {
+        return None;
+    }
+    // `.PHONY: build` and friends may appear many times; keep each one
+    if targets.starts_with('.') && targets.chars().skip(1).all(|c| c.is_ascii_uppercase() || c == '_') {
+        return Some((format!("stmt:{}", normalized), true));
+    }
+    Some((format!("target:{}", targets), true))
+}
+
+/// justfile: recipes with their indented bodies, variables, aliases, settings.
+/// Attribute lines (`[private]`) travel with the recipe below them.
+fn split_justfile(content: &str) -> BuildFile {
+    let mut file = BuildFile::default();
+    let mut pending = String::new();
+    let mut in_recipe = false;
+
+    for line in logical_lines(content) {
+        let indented = line.starts_with([' ', '\t']) && !line.trim().is_empty();
+        if indented && in_recipe {
+            if let Some(text) = file.last_text() {
+                text.push_str(&std::mem::take(&mut pending));
+                text.push_str(line);
+                continue;
+            }
+        }
+        let trimmed = line.trim();
+        if is_trivia(line) || (trimmed.starts_with('[') && trimmed.ends_with(']')) {
+            pending.push_str(line);
+            continue;
+        }
+
+        let normalized = normalize(line);
+        in_recipe = false;
+        let mut words = normalized.split(' ');
+        let first = words.next().unwrap_or("");
+        let key = match first {
+            "alias" => format!("alias:{}", words.next().unwrap_or("")),
+            "set" => format!("setting:{}", words.next().unwrap_or("")),
+            "import" | "mod" => format!("stmt:{}", normalized),
+            _ => {
+                let unprefixed = normalized.strip_prefix("export ").unwrap_or(&normalized);
+                let unprefixed = unprefixed.trim_start_matches('@');
+                let name_end = unprefixed
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                    .unwrap_or(unprefixed.len());
+                let name = &unprefixed[..name_end];
+                let rest = unprefixed[name_end..].trim_start();
+                if name.is_empty() {
+                    format!("stmt:{}", normalized)
+                } else if rest.starts_with(":=") {
+                    format!("var:{}", name)
+                } else if rest.contains(':') {
+                    in_recipe = true;
+                    format!("recipe:{}", name)
+                } else {
+                    format!("stmt:{}", normalized)
+                }
+            }
+        };
+        file.push(key, std::mem::take(&mut pending), line.to_string());
+    }
+
+    file.tail = pending;
+    file
+}
+
+/// Dockerfile: everything before the first `FROM`, then one entry per stage.
+/// Global `ARG`s before the first stage are keyed by name.
+fn split_dockerfile(content: &str) -> BuildFile {
+    let mut file = BuildFile::default();
+    let mut pending = String::new();
+    let mut in_stage = false;
+
+    for line in logical_lines(content) {
+        if is_trivia(line) {
+            pending.push_str(line);
+            continue;
+        }
+
+        let normalized = normalize(line);
+        let mut words = normalized.split(' ');
+        let instruction = words.next().unwrap_or("").to_uppercase();
+
+        if instruction == "FROM" {
+            in_stage = true;
+            let words: Vec<&str> = words.collect();
+            let name = words
+                .iter()
+                .position(|w| w.eq_ignore_ascii_case("AS"))
+                .and_then(|i| words.get(i + 1));
+            // An unnamed stage is keyed by its base image; the ordinal only
+            // counts stages on the same image, so inserting one elsewhere
+            // doesn't shift the others
+            let key = match name {
+                Some(name) => format!("stage:{}", name.to_lowercase()),
+                None => {
+                    let image = words.iter().find(|w| !w.starts_with("--")).unwrap_or(&"");
+                    format!("from:{}", image.to_lowercase())
+                }
+            };
+            file.push(key, std::mem::take(&mut pending), line.to_string());
+        } else if let Some(text) = file.last_text().filter(|_| in_stage) {
+            // Comments inside a stage stay with the instruction below them
+            text.push_str(&std::mem::take(&mut pending));
+            text.push_str(line);
+        } else if instruction == "ARG" {
+            let name = words.next().unwrap_or("").split('=').next().unwrap_or("");
+            file.push(format!("var:{}", name), std::mem::take(&mut pending), line.to_string());
+        } else {
+            file.push(format!("stmt:{}", normalized), std::mem::take(&mut pending), line.to_string());
+        }
+    }
+
+    file.tail = pending;
+    file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(file: &BuildFile) -> Vec<&str> {
+        file.entries.iter().map(|(k, _)| k.as_str()).collect()
+    }
+
+    fn render(file: &BuildFile) -> String {
+        let mut out: String = file.entries.iter().map(|(_, e)| format!("{}{}", e.leading, e.text)).collect();
+        out.push_str(&file.tail);
+        out
+    }
+
+    const MAKEFILE: &str = "CC ?= gcc\nCFLAGS += -Wall\n\n.PHONY: build test\n\n# Build everything\nbuild: main.o\n\t$(CC) -o app main.o \\\n\t  $(LDFLAGS)\n\ntest: build\n\t./app --test\n\nifeq ($(OS),Windows_NT)\nEXE = .exe\nendif\n";
+
+    #[test]
+    fn test_split_makefile() {
+        let file = split_makefile(MAKEFILE).unwrap();
+        assert_eq!(render(&file), MAKEFILE);
+        assert_eq!(
+            keys(&file),
+            vec![
+                "var:CC#1",
+                "stmt:CFLAGS += -Wall#1",
+                "stmt:.PHONY: build test#1",
+                "target:build#1",
+                "target:test#1",
+                "cond:ifeq ($(OS),Windows_NT)#1"
+            ]
+        );
+        assert!(file.entries[3].1.leading.contains("# Build everything"));
+    }
+
+    #[test]
+    fn test_makefile_both_add_targets() {
+        let base = "build:\n\tcargo build\n";
+        let ours = "build:\n\tcargo build\n\nlint:\n\tcargo clippy\n";
+        let theirs = "build:\n\tcargo build\n\nfmt:\n\tcargo fmt\n";
+        let result = merge_build_file(base, ours, theirs, "Makefile", &MergeContext::default()).unwrap();
+        assert!(result.is_clean());
+        assert_eq!(result.content, "build:\n\tcargo build\n\nlint:\n\tcargo clippy\n\nfmt:\n\tcargo fmt\n");
+        assert_eq!(result.stats.entities_added_ours, 1);
+        assert_eq!(result.stats.entities_added_theirs, 1);
+    }
+
+    #[test]
+    fn test_makefile_same_recipe_line_conflicts() {
+        let base = "build:\n\tcargo build\n";
+        let ours = "build:\n\tcargo build --release\n";
+        let theirs = "build:\n\tcargo build --locked\n";
+        let result = merge_build_file(base, ours, theirs, "Makefile", &MergeContext::default()).unwrap();
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].entity_type, "target");
+        assert_eq!(result.conflicts[0].entity_name, "build");
+    }
+
+    #[test]
+    fn test_justfile_both_add_recipes() {
+        let base = "set shell := [\"bash\", \"-c\"]\n\ndefault:\n    just --list\n";
+        let ours = "set shell := [\"bash\", \"-c\"]\n\ndefault:\n    just --list\n\n[private]\nlint:\n    cargo clippy\n";
+        let theirs = "set shell := [\"bash\", \"-c\"]\n\ndefault:\n    just --list\n\ntest filter='':\n    cargo test {{filter}}\n";
+        let file = split_justfile(ours);
+        assert_eq!(keys(&file), vec!["setting:shell#1", "recipe:default#1", "recipe:lint#1"]);
+        let result = merge_build_file(base, ours, theirs, "justfile", &MergeContext::default()).unwrap();
+        assert!(result.is_clean());
+        assert!(result.content.contains("[private]\nlint:\n    cargo clippy\n\ntest filter='':\n"));
+    }
+
+    #[test]
+    fn test_dockerfile_both_add_stages() {
+        let base = "# syntax=docker/dockerfile:1\nARG RUST=1.80\n\nFROM rust:${RUST} AS build\nRUN cargo build --release\n\nFROM debian:bookworm-slim\nCOPY --from=build /app /app\n";
+        let ours = base.replace("\nFROM debian", "\nFROM build AS test\nRUN cargo test\n\nFROM debian");
+        let theirs = base.replace("\nFROM debian", "\nFROM node:20 AS web\nRUN npm ci && npm run build\n\nFROM debian");
+        let file = split_dockerfile(&ours);
+        assert_eq!(render(&file), ours);
+        assert_eq!(keys(&file), vec!["var:RUST#1", "stage:build#1", "stage:test#1", "from:debian:bookworm-slim#1"]);
+
+        let result = merge_build_file(base, &ours, &theirs, "Dockerfile", &MergeContext::default()).unwrap();
+        assert!(result.is_clean());
+        let test = result.content.find("AS test").unwrap();
+        let web = result.content.find("AS web").unwrap();
+        let runtime = result.content.find("FROM debian").unwrap();
+        assert!(test < web && web < runtime);
+    }
+
+    #[test]
+    fn test_dockerfile_stage_changed_on_both_sides_merges_lines() {
+        let base = "FROM rust AS build\nWORKDIR /src\nCOPY . .\nRUN cargo build\n";
+        let ours = "FROM rust:1.80 AS build\nWORKDIR /src\nCOPY . .\nRUN cargo build\n";
+        let theirs = "FROM rust AS build\nWORKDIR /src\nCOPY . .\nRUN cargo build --release\n";
+        let result = merge_build_file(base, ours, theirs, "docker/Dockerfile", &MergeContext::default()).unwrap();
+        assert!(result.is_clean());
+        assert_eq!(result.content, "FROM rust:1.80 AS build\nWORKDIR /src\nCOPY . .\nRUN cargo build --release\n");
+    }
+
+    #[test]
+    fn test_dockerfile_unnamed_stage_inserted_before_others() {
+        let base = "FROM rust:1.80
+RUN cargo build
+
+FROM debian:bookworm-slim
+COPY app /app
+";
+        let ours = format!("FROM --platform=linux/amd64 alpine:3
+RUN apk add curl
+
+{}", base);
+        let theirs = base.replace("COPY app /app", "COPY app /usr/bin/app");
+        assert_eq!(
+            keys(&split_dockerfile(&ours)),
+            vec!["from:alpine:3#1", "from:rust:1.80#1", "from:debian:bookworm-slim#1"]
+        );
+
+        let result = merge_build_file(base, &ours, &theirs, "Dockerfile", &MergeContext::default()).unwrap();
+        assert!(result.is_clean());
+        assert_eq!(result.content, ours.replace("COPY app /app", "COPY app /usr/bin/app"));
+    }
+
+    #[test]
+    fn test_every_pattern_is_detected() {
+        for pattern in gitattributes_patterns() {
+            let name = pattern.replace('*', "x");
+            assert!(is_build_file(&format!("ci/{}", name)), "{}", pattern);
+        }
+        assert!(is_build_file("GNUmakefile"));
+        assert!(is_build_file("Containerfile.dev"));
+        assert!(is_build_file("tasks.just"));
+        assert!(!is_build_file("Makefile.toml"));
+    }
+}
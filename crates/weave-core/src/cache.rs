@@ -0,0 +1,254 @@
+//! On-disk cache of extracted entities, shared by every process working on
+//! a repository.
+//!
+//! During a merge git runs the driver once per file, and each run would
+//! parse base, ours and theirs from scratch; `weave bench-repo` and the MCP
+//! tools parse the same blobs over and over too. Extraction results are
+//! stored under `.weave/cache/`, one JSON file per sem-core version, plugin,
+//! file path and content hash (entity ids embed the path, so it is part of
+//! the key). Entries are written to a temporary file and renamed into
+//! place, so concurrent drivers never read a partial one. `[cache]
+//! max_size_mb` bounds it (64 MB by default; 0 turns it off).
+//!
+//! The size of the cache is tracked in a counter file next to the entries,
+//! bumped on every store. Concurrent stores may lose an update, so it's
+//! only an estimate: once it crosses the limit, the entries are listed, the
+//! least recently used are evicted down to three quarters of the limit and
+//! the counter is reset to what is left.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use sem_core::model::entity::SemanticEntity;
+use sha2::{Digest, Sha256};
+
+/// Location of the cache relative to the repo root.
+pub const CACHE_DIR: &str = ".weave/cache";
+
+/// Estimated size of the entries, in bytes.
+const SIZE_FILE: &str = "size";
+
+/// The sem-core the entities are extracted with.
+const SEM_CORE_VERSION: &str = sem_core::VERSION;
+
+/// The entity cache of one repository.
+#[derive(Debug, Clone)]
+pub struct EntityCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl EntityCache {
+    /// Open the cache of the repo at `repo_root`, holding at most
+    /// `max_bytes`. Nothing is created until something is stored.
+    pub fn open(repo_root: &Path, max_bytes: u64) -> Self {
+        Self {
+            dir: repo_root.join(CACHE_DIR),
+            max_bytes,
+        }
+    }
+
+    /// The entities of `content`, from the cache or, on a miss, from
+    /// `extract`, whose result is then stored. A cache that can't be read
+    /// or written only costs the extraction.
+    pub fn entities(
+        &self,
+        plugin_id: &str,
+        file_path: &str,
+        content: &str,
+        extract: impl FnOnce() -> Vec<SemanticEntity>,
+    ) -> Vec<SemanticEntity> {
+        let key = cache_key(plugin_id, file_path, content);
+        if let Some(entities) = self.get(&key) {
+            return entities;
+        }
+        let entities = extract();
+        let _ = self.put(&key, &entities);
+        entities
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    fn get(&self, key: &str) -> Option<Vec<SemanticEntity>> {
+        let path = self.entry_path(key);
+        let content = fs::read(&path).ok()?;
+        match serde_json::from_slice(&content) {
+            Ok(entities) => {
+                // Recently used: evicted last
+                if let Ok(file) = fs::File::options().write(true).open(&path) {
+                    let _ = file.set_modified(SystemTime::now());
+                }
+                Some(entities)
+            }
+            Err(_) => {
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    fn put(&self, key: &str, entities: &[SemanticEntity]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let ignore = self.dir.join(".gitignore");
+        if !ignore.exists() {
+            fs::write(ignore, "*\n")?;
+        }
+        let content = serde_json::to_vec(entities)?;
+        self.write(&self.entry_path(key), &content)?;
+
+        let size_path = self.dir.join(SIZE_FILE);
+        let size = fs::read_to_string(&size_path)
+            .ok()
+            .and_then(|size| size.trim().parse::<u64>().ok())
+            .unwrap_or(0)
+            + content.len() as u64;
+        let size = if size > self.max_bytes {
+            self.evict(self.max_bytes - self.max_bytes / 4)?
+        } else {
+            size
+        };
+        self.write(&size_path, size.to_string().as_bytes())
+    }
+
+    fn write(&self, path: &Path, content: &[u8]) -> io::Result<()> {
+        let mut file = tempfile::NamedTempFile::new_in(&self.dir)?;
+        file.write_all(content)?;
+        file.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    /// Remove the least recently used entries until at most `target` bytes
+    /// are left, and return the size of what is.
+    fn evict(&self, target: u64) -> io::Result<u64> {
+        let mut entries = Vec::new();
+        let mut total = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if entry.path().extension() != Some(OsStr::new("json")) {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            total += metadata.len();
+            entries.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+        entries.sort();
+        for (_, len, path) in entries {
+            if total <= target {
+                break;
+            }
+            // Another process may have evicted it already
+            let _ = fs::remove_file(path);
+            total -= len;
+        }
+        Ok(total)
+    }
+}
+
+/// [`EntityCache::entities`] through `cache` when there is one, a plain
+/// `extract()` otherwise.
+pub fn cached_entities(
+    cache: Option<&EntityCache>,
+    plugin_id: &str,
+    file_path: &str,
+    content: &str,
+    extract: impl FnOnce() -> Vec<SemanticEntity>,
+) -> Vec<SemanticEntity> {
+    match cache {
+        Some(cache) => cache.entities(plugin_id, file_path, content, extract),
+        None => extract(),
+    }
+}
+
+/// A hash of everything extraction depends on: the parsers (sem-core and
+/// weave versions, plugin), the path and the content.
+fn cache_key(plugin_id: &str, file_path: &str, content: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in [SEM_CORE_VERSION, env!("CARGO_PKG_VERSION"), plugin_id, file_path] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update(content.as_bytes());
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_entities_are_cached_per_path_and_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = EntityCache::open(dir.path(), 1 << 20);
+        let plugin = crate::merge::shared_registry().get_plugin("util.ts").unwrap();
+        let content = "export function a() {\n    return 1;\n}\n";
+        let extract = || plugin.extract_entities(content, "util.ts");
+
+        let extracted = cache.entities(plugin.id(), "util.ts", content, extract);
+        let cached = cache.entities(plugin.id(), "util.ts", content, || panic!("extracted twice"));
+        assert_eq!(
+            cached.iter().map(|e| &e.id).collect::<Vec<_>>(),
+            extracted.iter().map(|e| &e.id).collect::<Vec<_>>()
+        );
+        assert!(dir.path().join(CACHE_DIR).join(".gitignore").exists());
+
+        let mut misses = 0;
+        cache.entities(plugin.id(), "other.ts", content, || {
+            misses += 1;
+            vec![]
+        });
+        cache.entities(plugin.id(), "util.ts", "export const b = 2;\n", || {
+            misses += 1;
+            vec![]
+        });
+        assert_eq!(misses, 2);
+    }
+
+    #[test]
+    fn test_least_recently_used_entries_are_evicted() {
+        let dir = tempfile::tempdir().unwrap();
+        // Room for four empty entries (`[]`), evicted down to three
+        let cache = EntityCache::open(dir.path(), 8);
+        let cached = |file: &str| {
+            let mut hit = true;
+            cache.entities("rust", file, "", || {
+                hit = false;
+                vec![]
+            });
+            std::thread::sleep(Duration::from_millis(20));
+            hit
+        };
+
+        for file in ["a.rs", "b.rs", "c.rs", "d.rs"] {
+            assert!(!cached(file));
+        }
+        assert!(cached("a.rs"));
+        // Evicts b and c, used before a
+        assert!(!cached("e.rs"));
+        assert!(cached("a.rs"));
+        assert!(cached("d.rs"));
+        assert!(!cached("b.rs"));
+        assert_eq!(fs::read_to_string(dir.path().join(CACHE_DIR).join(SIZE_FILE)).unwrap(), "8");
+    }
+
+    #[test]
+    fn test_eviction_waits_for_the_counter() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = EntityCache::open(dir.path(), 8);
+        cache.entities("rust", "a.rs", "", Vec::new);
+        // Entries the counter doesn't know about stay until it crosses the limit
+        let stray = dir.path().join(CACHE_DIR).join("stray.json");
+        fs::write(&stray, "[]".repeat(10)).unwrap();
+        cache.entities("rust", "b.rs", "", Vec::new);
+        assert!(stray.exists());
+        for file in ["c.rs", "d.rs", "e.rs"] {
+            cache.entities("rust", file, "", Vec::new);
+        }
+        assert!(!stray.exists());
+    }
+}
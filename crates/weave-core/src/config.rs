@@ -0,0 +1,463 @@
+//! Repository-level weave configuration.
+//!
+//! Read from `.weave/config.toml` at the repo root. Every option is opt-in:
+//! a missing file (or a missing section) yields the default behavior.
+//!
+//! ```toml
+//! [merge]
+//! timeout_ms = 10000
+//!
+//! [manifest]
+//! semver = true
+//!
+//! [notebook]
+//! outputs = "clear"
+//!
+//! [markup]
+//! id_attributes = ["id", "name", "artifactId"]
+//!
+//! [generated]
+//! policy = "regenerate"
+//! command = "make generate"
+//!
+//! [validate]
+//! duplicates = "conflict"
+//!
+//! [[policy]]
+//! complexity = ["text"]
+//! resolve = "longer"
+//!
+//! [[protected]]
+//! files = "src/auth/**"
+//! names = ["check_*"]
+//!
+//! [cache]
+//! max_size_mb = 256
+//!
+//! [rerere]
+//! replay = false
+//! ```
+
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::budget::DEFAULT_TIMEOUT;
+use crate::cache::EntityCache;
+use crate::conflict::ConflictComplexity;
+
+/// Location of the config file relative to the repo root.
+pub const CONFIG_PATH: &str = ".weave/config.toml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("failed to read {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("invalid weave config: {0}")]
+    Parse(#[from] toml::de::Error),
+}
+
+/// Top-level weave configuration.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct WeaveConfig {
+    pub merge: MergeConfig,
+    pub manifest: ManifestConfig,
+    pub notebook: NotebookConfig,
+    pub markup: MarkupConfig,
+    /// Generated files are merged like any other file unless this section
+    /// is present (see [`crate::generated`]).
+    pub generated: Option<GeneratedConfig>,
+    pub validate: ValidateConfig,
+    /// Auto-resolution policies, tried in order (`[[policy]]` tables).
+    #[serde(rename = "policy")]
+    pub policies: Vec<ResolutionPolicy>,
+    /// Entities never auto-merged when changed on both sides (`[[protected]]`
+    /// tables, see [`crate::protect`]).
+    pub protected: Vec<ProtectedPattern>,
+    /// Checks run on the merged tree, each covering some of the auto-merged
+    /// files (`[[verify]]` tables, see [`crate::verify`]).
+    pub verify: Vec<VerifyCommand>,
+    pub cache: CacheConfig,
+    pub rerere: RerereConfig,
+}
+
+/// Options for package manifests (Cargo.toml, package.json, pyproject.toml, go.mod).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ManifestConfig {
+    /// When both branches bump the same dependency to different versions,
+    /// pick the higher compatible semver requirement instead of conflicting.
+    pub semver: bool,
+}
+
+/// Options for Jupyter notebooks (`.ipynb`).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct NotebookConfig {
+    /// What to do with a cell's outputs and execution count when both
+    /// branches changed them, or when its source was merged from both sides.
+    pub outputs: OutputPolicy,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputPolicy {
+    /// Keep our outputs.
+    #[default]
+    Ours,
+    /// Keep their outputs.
+    Theirs,
+    /// Drop outputs and reset the execution count.
+    Clear,
+}
+
+/// Options for XML and HTML files.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MarkupConfig {
+    /// Attributes (or, failing that, child elements) that identify an
+    /// element among its siblings, tried in order. Elements with none of
+    /// them are matched by position.
+    pub id_attributes: Vec<String>,
+}
+
+impl Default for MarkupConfig {
+    fn default() -> Self {
+        Self {
+            id_attributes: ["id", "name", "android:name", "Include", "Update", "artifactId"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Options for generated files (see [`crate::generated`]).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GeneratedConfig {
+    /// How to resolve a generated file changed on both sides.
+    pub policy: GeneratedPolicy,
+    /// Command run from the repo root, through the platform's shell, to
+    /// regenerate a file under the `regenerate` policy. It rewrites a copy
+    /// of the file at `$WEAVE_GENERATED_FILE`; the file's path in the
+    /// repository is in `$WEAVE_GENERATED_PATH`.
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GeneratedPolicy {
+    /// Leave one conflict over the whole file, to regenerate by hand.
+    #[default]
+    Conflict,
+    /// Keep our version, dropping theirs.
+    Ours,
+    /// Keep their version, dropping ours.
+    Theirs,
+    /// Run the configured command on our version and keep its output; a
+    /// failure leaves the file conflicted.
+    Regenerate,
+}
+
+/// Post-merge checks on the merged output.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ValidateConfig {
+    /// What to do when the merge defines a name twice in the same scope.
+    pub duplicates: DuplicatePolicy,
+    /// Also run cross-entity validation before each merge is committed,
+    /// from the merge hooks (`weave validate --merge --hook`). `weave
+    /// preview` always runs it.
+    pub on_merge: bool,
+    /// Turn the files cross-entity validation warns about into conflicts,
+    /// rather than merging them cleanly.
+    pub fail_on_warnings: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicatePolicy {
+    /// Merge cleanly and warn.
+    #[default]
+    Warn,
+    /// Turn the two definitions into a conflict.
+    Conflict,
+}
+
+/// Options for the entity merge of each file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MergeConfig {
+    /// How long the entity merge of a file may take before the file is
+    /// merged line by line instead. 0 disables the timeout.
+    pub timeout_ms: u64,
+}
+
+impl MergeConfig {
+    /// The per-file timeout, `None` when disabled.
+    pub fn timeout(&self) -> Option<Duration> {
+        (self.timeout_ms > 0).then(|| Duration::from_millis(self.timeout_ms))
+    }
+}
+
+impl Default for MergeConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: DEFAULT_TIMEOUT.as_millis() as u64,
+        }
+    }
+}
+
+/// Options for the entity extraction cache in `.weave/cache`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Size the cache is kept under by evicting the least recently used
+    /// entries, 64 MB by default. 0 disables the cache.
+    pub max_size_mb: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self { max_size_mb: 64 }
+    }
+}
+
+/// Options for replaying recorded conflict resolutions (see [`crate::rerere`]).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RerereConfig {
+    /// Replace conflicts resolved before with their recorded resolution.
+    /// Resolutions are still recorded when off.
+    pub replay: bool,
+}
+
+impl Default for RerereConfig {
+    fn default() -> Self {
+        Self { replay: true }
+    }
+}
+
+/// Resolve conflicts of the given complexities without markers.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResolutionPolicy {
+    /// Complexities this policy applies to, e.g. `["text"]` for comment and
+    /// formatting conflicts.
+    pub complexity: Vec<ConflictComplexity>,
+    /// Which side wins.
+    pub resolve: PolicyResolution,
+    /// Only files matching this glob (`*.md`, `docs/**`). All files if unset.
+    #[serde(default)]
+    pub files: Option<String>,
+    /// Only these entity types (`function`, `method`...). All types if empty.
+    #[serde(default)]
+    pub entity_types: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyResolution {
+    Ours,
+    Theirs,
+    /// The side with more content (ignoring whitespace); ours on a tie.
+    Longer,
+}
+
+impl std::fmt::Display for PolicyResolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolicyResolution::Ours => write!(f, "ours"),
+            PolicyResolution::Theirs => write!(f, "theirs"),
+            PolicyResolution::Longer => write!(f, "longer"),
+        }
+    }
+}
+
+/// A check that must pass on the merged tree when it covers an auto-merged
+/// file: a built-in `template`, or a `files` glob and a `command` (either of
+/// which overrides the template's).
+#[derive(Debug, Clone, Deserialize)]
+pub struct VerifyCommand {
+    #[serde(default)]
+    pub template: Option<VerifyTemplate>,
+    /// Files the check covers (see [`glob_match`](crate::glob::glob_match)).
+    #[serde(default)]
+    pub files: Option<String>,
+    /// Command run from the root of the merged tree, through the platform's
+    /// shell. `{files}` is replaced with the auto-merged files it covers.
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+/// Built-in `[[verify]]` checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifyTemplate {
+    /// `cargo check` on Rust files.
+    Rust,
+    /// `tsc --noEmit` on TypeScript files.
+    TypeScript,
+    /// `compileall` on Python files.
+    Python,
+    /// `go build` on Go files.
+    Go,
+}
+
+/// Entities to protect from auto-merging. Every criterion set must match;
+/// an unset one matches anything.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProtectedPattern {
+    /// Only files matching this glob.
+    #[serde(default)]
+    pub files: Option<String>,
+    /// Only these entity types.
+    #[serde(default)]
+    pub entity_types: Vec<String>,
+    /// Entity name globs, e.g. `check_*`.
+    #[serde(default)]
+    pub names: Vec<String>,
+}
+
+impl WeaveConfig {
+    /// Load `.weave/config.toml` from the given repo root.
+    /// Returns the default config if the file does not exist.
+    pub fn load(repo_root: &Path) -> Result<Self, ConfigError> {
+        let path = repo_root.join(CONFIG_PATH);
+        if path.exists() {
+            let content = std::fs::read_to_string(&path).map_err(|source| ConfigError::Io {
+                path: path.display().to_string(),
+                source,
+            })?;
+            Self::from_toml(&content)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// The entity cache of the repo at `repo_root` as `[cache]` configures
+    /// it, `None` when it's disabled.
+    pub fn entity_cache(&self, repo_root: &Path) -> Option<EntityCache> {
+        (self.cache.max_size_mb > 0).then(|| EntityCache::open(repo_root, self.cache.max_size_mb * 1024 * 1024))
+    }
+
+    /// Parse a config from TOML source.
+    pub fn from_toml(content: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(content)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_config_is_default() {
+        let config = WeaveConfig::from_toml("").unwrap();
+        assert!(!config.manifest.semver);
+        assert!(config.rerere.replay);
+        assert!(!WeaveConfig::from_toml("[rerere]\nreplay = false\n").unwrap().rerere.replay);
+    }
+
+    #[test]
+    fn test_manifest_semver_opt_in() {
+        let config = WeaveConfig::from_toml("[manifest]\nsemver = true\n").unwrap();
+        assert!(config.manifest.semver);
+    }
+
+    #[test]
+    fn test_notebook_output_policy() {
+        assert_eq!(WeaveConfig::default().notebook.outputs, OutputPolicy::Ours);
+        let config = WeaveConfig::from_toml("[notebook]\noutputs = \"clear\"\n").unwrap();
+        assert_eq!(config.notebook.outputs, OutputPolicy::Clear);
+    }
+
+    #[test]
+    fn test_markup_id_attributes() {
+        assert!(WeaveConfig::default().markup.id_attributes.contains(&"artifactId".to_string()));
+        let config = WeaveConfig::from_toml("[markup]\nid_attributes = [\"key\"]\n").unwrap();
+        assert_eq!(config.markup.id_attributes, vec!["key"]);
+    }
+
+    #[test]
+    fn test_generated_policy() {
+        assert!(WeaveConfig::default().generated.is_none());
+        let config = WeaveConfig::from_toml("[generated]\n").unwrap();
+        assert_eq!(config.generated.unwrap().policy, GeneratedPolicy::Conflict);
+        let config =
+            WeaveConfig::from_toml("[generated]\npolicy = \"regenerate\"\ncommand = \"sqlc generate\"\n").unwrap();
+        let generated = config.generated.unwrap();
+        assert_eq!(generated.policy, GeneratedPolicy::Regenerate);
+        assert_eq!(generated.command.as_deref(), Some("sqlc generate"));
+    }
+
+    #[test]
+    fn test_validate_duplicates() {
+        assert_eq!(WeaveConfig::default().validate.duplicates, DuplicatePolicy::Warn);
+        assert!(!WeaveConfig::default().validate.fail_on_warnings);
+        let config = WeaveConfig::from_toml(
+            "[validate]\nduplicates = \"conflict\"\non_merge = true\nfail_on_warnings = true\n",
+        )
+        .unwrap();
+        assert_eq!(config.validate.duplicates, DuplicatePolicy::Conflict);
+        assert!(config.validate.on_merge);
+        assert!(config.validate.fail_on_warnings);
+    }
+
+    #[test]
+    fn test_resolution_policies() {
+        let config = WeaveConfig::from_toml(
+            "[[policy]]\ncomplexity = [\"text\", \"text_syntax\"]\nresolve = \"longer\"\nfiles = \"docs/**\"\n\n\
+             [[policy]]\ncomplexity = [\"syntax\"]\nresolve = \"ours\"\nentity_types = [\"function\"]\n",
+        )
+        .unwrap();
+        assert_eq!(config.policies.len(), 2);
+        assert_eq!(config.policies[0].complexity, vec![ConflictComplexity::Text, ConflictComplexity::TextSyntax]);
+        assert_eq!(config.policies[0].resolve, PolicyResolution::Longer);
+        assert_eq!(config.policies[1].entity_types, vec!["function"]);
+        assert!(config.policies[1].files.is_none());
+    }
+
+    #[test]
+    fn test_protected_patterns() {
+        let config = WeaveConfig::from_toml("[[protected]]\nnames = [\"MIGRATION_VERSION\"]\n").unwrap();
+        assert_eq!(config.protected.len(), 1);
+        assert!(config.protected[0].files.is_none());
+        assert_eq!(config.protected[0].names, vec!["MIGRATION_VERSION"]);
+    }
+
+    #[test]
+    fn test_missing_file_is_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = WeaveConfig::load(dir.path()).unwrap();
+        assert!(!config.manifest.semver);
+        assert!(config.rerere.replay);
+        assert!(config.entity_cache(dir.path()).is_some());
+    }
+
+    #[test]
+    fn test_cache_can_be_disabled() {
+        assert_eq!(WeaveConfig::default().cache.max_size_mb, 64);
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join(".weave")).unwrap();
+        std::fs::write(dir.path().join(CONFIG_PATH), "[cache]\nmax_size_mb = 0\n").unwrap();
+        assert!(WeaveConfig::load(dir.path()).unwrap().entity_cache(dir.path()).is_none());
+    }
+
+    #[test]
+    fn test_merge_timeout() {
+        assert_eq!(WeaveConfig::default().merge.timeout(), Some(DEFAULT_TIMEOUT));
+        let config = WeaveConfig::from_toml("[merge]\ntimeout_ms = 0\n").unwrap();
+        assert_eq!(config.merge.timeout(), None);
+    }
+
+    #[test]
+    fn test_invalid_config_is_error() {
+        assert!(WeaveConfig::from_toml("[manifest]\nsemver = \"yes\"\n").is_err());
+    }
+}
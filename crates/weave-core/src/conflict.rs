@@ -1,5 +1,9 @@
 use std::fmt;
 
+use serde::Deserialize;
+
+use crate::ast::EntityParts;
+
 /// The type of conflict between two branches' changes to an entity.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ConflictKind {
@@ -42,9 +46,12 @@ impl fmt::Display for ConflictKind {
 /// - Syntax: signature/type changes, may need type-checking
 /// - Functional: body logic changes, needs careful review
 /// - Composite variants indicate multiple dimensions of change.
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// Named in snake case in config (`text`, `text_syntax`...).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ConflictComplexity {
-    /// Only text/comment/string changes
+    /// Only comment or layout changes
     Text,
     /// Signature, type, or structural changes (no body changes)
     Syntax,
@@ -102,38 +109,107 @@ impl fmt::Display for ConflictComplexity {
 }
 
 /// Classify conflict complexity by analyzing what changed between versions.
+///
+/// Line-based: for content without a grammar (SQL statements, markdown
+/// sections, build file blocks...). Code entities go through
+/// [`classify_conflict_in`].
 pub fn classify_conflict(base: Option<&str>, ours: Option<&str>, theirs: Option<&str>) -> ConflictComplexity {
+    let base = base.unwrap_or("");
+    let ours_diff = classify_change(base, ours.unwrap_or(""));
+    let theirs_diff = classify_change(base, theirs.unwrap_or(""));
+    ours_diff.union(&theirs_diff).complexity()
+}
+
+/// Classify conflict complexity on the syntax tree of the file's language,
+/// falling back to [`classify_conflict`] without a grammar.
+pub fn classify_conflict_in(
+    file_path: &str,
+    base: Option<&str>,
+    ours: Option<&str>,
+    theirs: Option<&str>,
+) -> ConflictComplexity {
+    let (ours_diff, theirs_diff) = classify_side_changes(file_path, base, ours, theirs);
+    ours_diff.union(&theirs_diff).complexity()
+}
+
+/// Classify what each side changed on its own, as `(ours, theirs)`.
+pub fn classify_sides(
+    file_path: &str,
+    base: Option<&str>,
+    ours: Option<&str>,
+    theirs: Option<&str>,
+) -> (ConflictComplexity, ConflictComplexity) {
+    let (ours_diff, theirs_diff) = classify_side_changes(file_path, base, ours, theirs);
+    (ours_diff.complexity(), theirs_diff.complexity())
+}
+
+fn classify_side_changes(
+    file_path: &str,
+    base: Option<&str>,
+    ours: Option<&str>,
+    theirs: Option<&str>,
+) -> (ChangeDimensions, ChangeDimensions) {
     let base = base.unwrap_or("");
     let ours = ours.unwrap_or("");
     let theirs = theirs.unwrap_or("");
-
-    // Compare ours and theirs changes vs base
-    let ours_diff = classify_change(base, ours);
-    let theirs_diff = classify_change(base, theirs);
-
-    // Merge the dimensions
-    let has_text = ours_diff.text || theirs_diff.text;
-    let has_syntax = ours_diff.syntax || theirs_diff.syntax;
-    let has_functional = ours_diff.functional || theirs_diff.functional;
-
-    match (has_text, has_syntax, has_functional) {
-        (true, false, false) => ConflictComplexity::Text,
-        (false, true, false) => ConflictComplexity::Syntax,
-        (false, false, true) => ConflictComplexity::Functional,
-        (true, true, false) => ConflictComplexity::TextSyntax,
-        (true, false, true) => ConflictComplexity::TextFunctional,
-        (false, true, true) => ConflictComplexity::SyntaxFunctional,
-        (true, true, true) => ConflictComplexity::TextSyntaxFunctional,
-        (false, false, false) => ConflictComplexity::Unknown,
-    }
+    let Some(language) = crate::ast::language_for(file_path) else {
+        return (classify_change(base, ours), classify_change(base, theirs));
+    };
+    let base_parts = crate::ast::entity_parts(&language, base);
+    let classify = |modified: &str| {
+        if base == modified {
+            return ChangeDimensions::default();
+        }
+        match (&base_parts, crate::ast::entity_parts(&language, modified)) {
+            (Some(base_parts), Some(modified_parts)) => classify_parts_change(base_parts, &modified_parts),
+            _ => classify_change(base, modified),
+        }
+    };
+    (classify(ours), classify(theirs))
 }
 
+#[derive(Debug, Default, PartialEq, Eq)]
 struct ChangeDimensions {
     text: bool,
     syntax: bool,
     functional: bool,
 }
 
+impl ChangeDimensions {
+    fn union(&self, other: &ChangeDimensions) -> ChangeDimensions {
+        ChangeDimensions {
+            text: self.text || other.text,
+            syntax: self.syntax || other.syntax,
+            functional: self.functional || other.functional,
+        }
+    }
+
+    fn complexity(&self) -> ConflictComplexity {
+        match (self.text, self.syntax, self.functional) {
+            (true, false, false) => ConflictComplexity::Text,
+            (false, true, false) => ConflictComplexity::Syntax,
+            (false, false, true) => ConflictComplexity::Functional,
+            (true, true, false) => ConflictComplexity::TextSyntax,
+            (true, false, true) => ConflictComplexity::TextFunctional,
+            (false, true, true) => ConflictComplexity::SyntaxFunctional,
+            (true, true, true) => ConflictComplexity::TextSyntaxFunctional,
+            (false, false, false) => ConflictComplexity::Unknown,
+        }
+    }
+}
+
+/// Classify a change from the parts of both versions: comments and layout
+/// are text; the declaration is syntax; the body is functional. String
+/// literals count where they appear.
+fn classify_parts_change(base: &EntityParts, modified: &EntityParts) -> ChangeDimensions {
+    let syntax = base.signature != modified.signature;
+    let functional = base.body != modified.body;
+    let text = base.comments != modified.comments;
+    // Same tokens and comments: only the layout changed
+    let text = text || !(syntax || functional);
+    ChangeDimensions { text, syntax, functional }
+}
+
 fn classify_change(base: &str, modified: &str) -> ChangeDimensions {
     if base == modified {
         return ChangeDimensions {
@@ -215,6 +291,16 @@ pub struct EntityConflict {
 }
 
 impl EntityConflict {
+    /// What each side changed on its own, as `(ours, theirs)`.
+    pub fn side_complexities(&self, file_path: &str) -> (ConflictComplexity, ConflictComplexity) {
+        classify_sides(
+            file_path,
+            self.base_content.as_deref(),
+            self.ours_content.as_deref(),
+            self.theirs_content.as_deref(),
+        )
+    }
+
     /// Render this conflict as enhanced conflict markers.
     pub fn to_conflict_markers(&self) -> String {
         let confidence = match &self.complexity {
@@ -252,6 +338,83 @@ impl EntityConflict {
     }
 }
 
+/// Counts the conflict blocks written to merged output so far, so a merger
+/// can record which block each conflict is rendered as when it emits the
+/// markers (see [`MergeResult::conflict_blocks`](crate::merge::MergeResult::conflict_blocks)).
+#[derive(Debug, Default)]
+pub(crate) struct BlockCounter {
+    scanned: usize,
+    blocks: usize,
+}
+
+impl BlockCounter {
+    /// The index of the next block appended to `output`, which must only
+    /// have grown since the last call.
+    pub(crate) fn next(&mut self, output: &str) -> usize {
+        // Only whole lines: a partial one is counted once it's complete
+        let end = output.rfind('\n').map_or(0, |i| i + 1).max(self.scanned);
+        self.blocks += count_blocks(&output[self.scanned..end]);
+        self.scanned = end;
+        self.blocks
+    }
+}
+
+/// Output rendered from a merged tree, recording the block each conflict is
+/// rendered as when its markers are written.
+#[derive(Debug, Default)]
+pub(crate) struct Rendered {
+    pub(crate) content: String,
+    counter: BlockCounter,
+    /// Block of each conflict, by index in the merge's conflicts.
+    blocks: Vec<Option<usize>>,
+}
+
+impl Rendered {
+    pub(crate) fn push_str(&mut self, text: &str) {
+        self.content.push_str(text);
+    }
+
+    /// Write the markers of the merge's conflict `index`.
+    pub(crate) fn push_conflict(&mut self, index: usize, markers: &str) {
+        if self.blocks.len() <= index {
+            self.blocks.resize(index + 1, None);
+        }
+        self.blocks[index] = Some(self.counter.next(&self.content));
+        self.content.push_str(markers);
+    }
+
+    /// The content, and the block of each of the merge's `count` conflicts.
+    pub(crate) fn finish(mut self, count: usize) -> (String, Vec<Option<usize>>) {
+        self.blocks.resize(count, None);
+        (self.content, self.blocks)
+    }
+}
+
+/// Number of `<<<<<<<` conflict blocks in `text`.
+pub(crate) fn count_blocks(text: &str) -> usize {
+    text.lines().filter(|l| l.starts_with("<<<<<<<")).count()
+}
+
+/// Byte range of each conflict block in `content`, from its `<<<<<<<` line
+/// through the `>>>>>>>` line closing it; `None` if it isn't closed.
+pub(crate) fn block_spans(content: &str) -> Vec<Option<std::ops::Range<usize>>> {
+    let mut spans = Vec::new();
+    let mut open = None;
+    let mut pos = 0;
+    for line in content.split_inclusive('\n') {
+        if line.starts_with("<<<<<<<") {
+            spans.push(None);
+            open = Some((spans.len() - 1, pos));
+        } else if line.starts_with(">>>>>>>") {
+            if let Some((block, start)) = open.take() {
+                spans[block] = Some(start..pos + line.len());
+            }
+        }
+        pos += line.len();
+    }
+    spans
+}
+
 /// A parsed conflict extracted from weave-enhanced conflict markers.
 #[derive(Debug, Clone)]
 pub struct ParsedConflict {
@@ -401,6 +564,10 @@ pub struct MergeStats {
     pub resolved_via_diffy: usize,
     /// Entities resolved via inner entity merge (high confidence).
     pub resolved_via_inner_merge: usize,
+    /// Conflicts resolved by replaying a recorded resolution (see `rerere`).
+    pub resolved_via_rerere: usize,
+    /// Conflicts resolved by a configured policy (see `policy`).
+    pub resolved_via_policy: usize,
 }
 
 impl MergeStats {
@@ -587,6 +754,63 @@ mod tests {
         assert_eq!(parsed[1].entity_name, "Bar");
         assert_eq!(parsed[1].complexity, ConflictComplexity::SyntaxFunctional);
     }
+
+    #[test]
+    fn test_classify_ast_multiline_signature() {
+        // The changed parameter is on the second line: not a body change
+        let base = "fn foo(\n    a: u32,\n    b: u32,\n) -> u32 {\n    a + b\n}\n";
+        let ours = "fn foo(\n    a: u64,\n    b: u32,\n) -> u32 {\n    a + b\n}\n";
+        let theirs = "fn foo(\n    a: u32,\n    b: u32,\n) -> u32 {\n    a * b\n}\n";
+        assert_eq!(
+            classify_conflict_in("src/lib.rs", Some(base), Some(ours), Some(theirs)),
+            ConflictComplexity::SyntaxFunctional
+        );
+        assert_eq!(
+            classify_sides("src/lib.rs", Some(base), Some(ours), Some(theirs)),
+            (ConflictComplexity::Syntax, ConflictComplexity::Functional)
+        );
+    }
+
+    #[test]
+    fn test_classify_ast_block_comments_and_strings() {
+        let base = "function foo() {\n    /* retry\n       twice */\n    throw new Error(\"failed\");\n}\n";
+        let ours = "function foo() {\n    /* retry\n       three times */\n    throw new Error(\"failed\");\n}\n";
+        let theirs = "function foo() {\n    /* retry\n       twice */\n    throw new Error(\"request failed\");\n}\n";
+        // A changed literal changes behavior, unlike a comment
+        assert_eq!(
+            classify_sides("app.ts", Some(base), Some(ours), Some(theirs)),
+            (ConflictComplexity::Text, ConflictComplexity::Functional)
+        );
+
+        let base = "def load(mode=\"r\"):\n    \"\"\"Load it.\"\"\"\n    return open(PATH, mode)\n";
+        let ours = "def load(mode=\"rb\"):\n    \"\"\"Load it.\"\"\"\n    return open(PATH, mode)\n";
+        let theirs = "def load(mode=\"r\"):\n    \"\"\"Load it, as text.\"\"\"\n    return open(PATH, mode)\n";
+        assert_eq!(
+            classify_sides("load.py", Some(base), Some(ours), Some(theirs)),
+            (ConflictComplexity::Syntax, ConflictComplexity::Text)
+        );
+    }
+
+    #[test]
+    fn test_classify_ast_reformat_is_text() {
+        let base = "def foo(a, b):\n    return a + b\n";
+        let ours = "def foo(a,b):\n    return a+b\n";
+        let theirs = "def foo(a, b):\n    return a - b\n";
+        assert_eq!(
+            classify_sides("util.py", Some(base), Some(ours), Some(theirs)),
+            (ConflictComplexity::Text, ConflictComplexity::Functional)
+        );
+    }
+
+    #[test]
+    fn test_classify_without_grammar_uses_lines() {
+        let base = "// old comment\n    return 1;\n";
+        let ours = "// ours comment\n    return 1;\n";
+        assert_eq!(
+            classify_conflict_in("notes.txt", Some(base), Some(ours), Some(base)),
+            classify_conflict(Some(base), Some(ours), Some(base))
+        );
+    }
 }
 
 impl fmt::Display for MergeStats {
@@ -613,6 +837,12 @@ impl fmt::Display for MergeStats {
         if self.entities_conflicted > 0 {
             write!(f, ", CONFLICTS: {}", self.entities_conflicted)?;
         }
+        if self.resolved_via_rerere > 0 {
+            write!(f, ", rerere: {}", self.resolved_via_rerere)?;
+        }
+        if self.resolved_via_policy > 0 {
+            write!(f, ", policy: {}", self.resolved_via_policy)?;
+        }
         if self.semantic_warnings > 0 {
             write!(f, ", semantic-warnings: {}", self.semantic_warnings)?;
         }
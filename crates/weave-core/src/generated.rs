@@ -0,0 +1,302 @@
+//! Generated-file detection and resolution.
+//!
+//! A file is generated when its header says so: Go's
+//! `// Code generated ... DO NOT EDIT.` on one of its first two lines, or
+//! `@generated` in its leading comment block (Cargo, Buck, protoc plugins).
+//! Generated files are outputs of a tool, not source, and merging them line
+//! by line produces something no generator would write. With a
+//! `[generated]` section in the config, a generated file both branches
+//! changed is resolved by its [`GeneratedPolicy`] instead: one conflict over
+//! the whole file (the default), ours, theirs, or ours run through a
+//! regeneration command. Keeping a side always comes with a warning naming
+//! the side dropped.
+
+use std::path::Path;
+
+use crate::config::{GeneratedConfig, GeneratedPolicy};
+use crate::conflict::MergeStats;
+use crate::merge::MergeResult;
+use crate::protect::{conflict_whole_file, is_comment_line};
+use crate::shell::shell;
+use crate::validate::{SemanticWarning, WarningKind};
+
+/// How a generated file was resolved.
+#[derive(Debug, Clone)]
+pub struct GeneratedOutcome {
+    pub policy: GeneratedPolicy,
+    /// Set under the `regenerate` policy, once the command has run.
+    pub regeneration: Option<Regeneration>,
+}
+
+/// The result of running the regeneration command.
+#[derive(Debug, Clone)]
+pub struct Regeneration {
+    pub command: String,
+    pub success: bool,
+    /// Combined stdout and stderr, or the reason the command couldn't run.
+    pub output: String,
+}
+
+impl std::fmt::Display for GeneratedOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.policy, &self.regeneration) {
+            (GeneratedPolicy::Conflict, _) => write!(f, "generated file — changed on both sides, left conflicted"),
+            (GeneratedPolicy::Ours, _) => write!(f, "generated file — kept ours"),
+            (GeneratedPolicy::Theirs, _) => write!(f, "generated file — kept theirs"),
+            (GeneratedPolicy::Regenerate, None) => write!(f, "generated file — needs regeneration"),
+            (GeneratedPolicy::Regenerate, Some(r)) if r.success => {
+                write!(f, "generated file — regenerated with `{}`", r.command)
+            }
+            (GeneratedPolicy::Regenerate, Some(r)) => {
+                write!(f, "generated file — `{}` failed, left conflicted: {}", r.command, r.output.trim())
+            }
+        }
+    }
+}
+
+/// Check if content starts with a generated-file header.
+pub fn is_generated(content: &str) -> bool {
+    content.lines().take(2).any(is_go_header)
+        || content
+            .lines()
+            .map(str::trim_start)
+            .take_while(|line| line.is_empty() || is_comment_line(line))
+            .any(|line| line.contains("@generated"))
+}
+
+/// Go's convention: `^// Code generated .* DO NOT EDIT\.$`.
+fn is_go_header(line: &str) -> bool {
+    line.trim_end_matches('\r')
+        .strip_prefix("// Code generated ")
+        .is_some_and(|rest| rest.ends_with(" DO NOT EDIT."))
+}
+
+/// Resolve a generated file changed on both sides according to the policy.
+/// Returns None if none of the versions is generated.
+///
+/// Under the `regenerate` policy, the command runs from `repo_root` on a
+/// copy of ours (see [`regenerate`]); without a repository, or when it
+/// fails, the file is left conflicted.
+pub(crate) fn resolve_generated(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    config: &GeneratedConfig,
+    repo_root: Option<&Path>,
+) -> Option<MergeResult> {
+    if !(is_generated(ours) || is_generated(theirs) || is_generated(base)) {
+        return None;
+    }
+
+    let mut result = MergeResult::default();
+    let mut regeneration = None;
+    match config.policy {
+        GeneratedPolicy::Conflict => conflict_whole_file(&mut result, [base, ours, theirs]),
+        GeneratedPolicy::Ours | GeneratedPolicy::Theirs => {
+            let kept_ours = config.policy == GeneratedPolicy::Ours;
+            result.content = if kept_ours { ours } else { theirs }.to_string();
+            result.stats = MergeStats {
+                entities_ours_only: kept_ours as usize,
+                entities_theirs_only: !kept_ours as usize,
+                ..Default::default()
+            };
+            result.warnings.push(SemanticWarning {
+                entity_name: "(file)".to_string(),
+                entity_type: "file".to_string(),
+                file_path: file_path.to_string(),
+                kind: WarningKind::GeneratedSideDropped { kept_ours },
+                related: vec![],
+            });
+        }
+        GeneratedPolicy::Regenerate => {
+            let (outcome, content) = match repo_root {
+                Some(repo_root) => regenerate(ours, config, repo_root, file_path),
+                None => (
+                    Regeneration {
+                        command: config.command.clone().unwrap_or_default(),
+                        success: false,
+                        output: "not merging in a repository".to_string(),
+                    },
+                    None,
+                ),
+            };
+            match content {
+                Some(content) => {
+                    result.content = content;
+                    result.stats.entities_both_changed_merged = 1;
+                }
+                None => conflict_whole_file(&mut result, [base, ours, theirs]),
+            }
+            regeneration = Some(outcome);
+        }
+    }
+
+    result.generated = Some(GeneratedOutcome {
+        policy: config.policy,
+        regeneration,
+    });
+    Some(result)
+}
+
+/// Run the regeneration command on `content`, the version to regenerate.
+///
+/// The content is written to a temporary file whose path is passed in
+/// `WEAVE_GENERATED_FILE`, with the file's path in the repository in
+/// `WEAVE_GENERATED_PATH`; the command runs from `repo_root`, through the
+/// platform's shell, and is expected to rewrite the temporary file. Neither
+/// the working tree nor the merged file is touched. Returns the regenerated
+/// content on success.
+fn regenerate(
+    content: &str,
+    config: &GeneratedConfig,
+    repo_root: &Path,
+    file_path: &str,
+) -> (Regeneration, Option<String>) {
+    let Some(command) = config.command.clone() else {
+        let regeneration = Regeneration {
+            command: String::new(),
+            success: false,
+            output: "no [generated] command configured".to_string(),
+        };
+        return (regeneration, None);
+    };
+    let failed = |command: String, output: String| {
+        let regeneration = Regeneration {
+            command,
+            success: false,
+            output,
+        };
+        (regeneration, None)
+    };
+
+    let file = match tempfile::NamedTempFile::new().and_then(|file| {
+        std::fs::write(file.path(), content)?;
+        Ok(file)
+    }) {
+        Ok(file) => file,
+        Err(e) => return failed(command, format!("failed to write a temporary file: {}", e)),
+    };
+    let output = shell(&command)
+        .current_dir(repo_root)
+        .env("WEAVE_GENERATED_FILE", file.path())
+        .env("WEAVE_GENERATED_PATH", file_path)
+        .output();
+
+    match output {
+        Ok(output) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            if !output.status.success() {
+                return failed(command, format!("{} ({})", text.trim(), output.status));
+            }
+            match std::fs::read_to_string(file.path()) {
+                Ok(regenerated) => {
+                    let regeneration = Regeneration {
+                        command,
+                        success: true,
+                        output: text,
+                    };
+                    (regeneration, Some(regenerated))
+                }
+                Err(e) => failed(command, format!("failed to read the regenerated file: {}", e)),
+            }
+        }
+        Err(e) => failed(command, e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GO_HEADER: &str = "// Code generated by sqlc. DO NOT EDIT.\n\npackage db\n";
+
+    fn config(policy: GeneratedPolicy, command: Option<&str>) -> GeneratedConfig {
+        GeneratedConfig {
+            policy,
+            command: command.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_detects_headers() {
+        assert!(is_generated(GO_HEADER));
+        assert!(is_generated("// Copyright 2024\n// Code generated by protoc-gen-go. DO NOT EDIT.\n"));
+        assert!(is_generated("# This file is automatically @generated by Cargo.\nversion = 3\n"));
+        assert!(is_generated("/**\n * @generated by relay-compiler\n */\nexport {};\n"));
+        assert!(!is_generated("package db\n\nfunc main() {}\n"));
+    }
+
+    #[test]
+    fn test_mentions_are_not_headers() {
+        // Past the first two lines
+        assert!(!is_generated("package db\n\n// Code generated by sqlc. DO NOT EDIT.\n"));
+        // Not Go's exact form
+        assert!(!is_generated("// DO NOT EDIT this list by hand\nconst A = 1;\n"));
+        // In code, or a comment after it
+        assert!(!is_generated("const MARKER = \"@generated\";\n"));
+        assert!(!is_generated("fn main() {}\n// strip @generated headers\n"));
+    }
+
+    #[test]
+    fn test_policy_picks_side_with_a_warning() {
+        let ours = format!("{}// ours\n", GO_HEADER);
+        let theirs = format!("{}// theirs\n", GO_HEADER);
+
+        let result = resolve_generated(GO_HEADER, &ours, &theirs, "db.go", &config(GeneratedPolicy::Ours, None), None).unwrap();
+        assert_eq!(result.content, ours);
+        assert!(result.is_clean());
+        assert!(matches!(result.warnings[0].kind, WarningKind::GeneratedSideDropped { kept_ours: true }));
+
+        let config = config(GeneratedPolicy::Theirs, None);
+        let result = resolve_generated(GO_HEADER, &ours, &theirs, "db.go", &config, None).unwrap();
+        assert_eq!(result.content, theirs);
+        assert!(matches!(result.warnings[0].kind, WarningKind::GeneratedSideDropped { kept_ours: false }));
+
+        assert!(resolve_generated("a\n", "b\n", "c\n", "db.go", &config, None).is_none());
+    }
+
+    #[test]
+    fn test_default_policy_conflicts() {
+        let ours = format!("{}// ours\n", GO_HEADER);
+        let theirs = format!("{}// theirs\n", GO_HEADER);
+        let result = resolve_generated(GO_HEADER, &ours, &theirs, "db.go", &GeneratedConfig::default(), None).unwrap();
+        assert_eq!(result.conflicts.len(), 1);
+        assert!(result.content.contains("// ours") && result.content.contains("// theirs"));
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_regenerate_runs_command_on_ours() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(
+            GeneratedPolicy::Regenerate,
+            Some("sed 's/stale/fresh/' \"$WEAVE_GENERATED_FILE\" > out && mv out \"$WEAVE_GENERATED_FILE\""),
+        );
+        let ours = format!("{}// stale\n", GO_HEADER);
+        let theirs = format!("{}// other\n", GO_HEADER);
+        let result = resolve_generated(GO_HEADER, &ours, &theirs, "gen.go", &config, Some(dir.path())).unwrap();
+
+        let regeneration = result.generated.as_ref().unwrap().regeneration.as_ref().unwrap();
+        assert!(regeneration.success, "{}", regeneration.output);
+        assert!(result.is_clean());
+        assert_eq!(result.content, format!("{}// fresh\n", GO_HEADER));
+        // The working tree is left alone
+        assert!(!dir.path().join("gen.go").exists());
+    }
+
+    #[test]
+    fn test_regenerate_failure_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(GeneratedPolicy::Regenerate, Some("echo broken >&2; exit 3"));
+        let ours = format!("{}// a\n", GO_HEADER);
+        let theirs = format!("{}// b\n", GO_HEADER);
+        let result = resolve_generated(GO_HEADER, &ours, &theirs, "gen.go", &config, Some(dir.path())).unwrap();
+
+        let outcome = result.generated.as_ref().unwrap();
+        assert!(!outcome.regeneration.as_ref().unwrap().success);
+        assert!(outcome.to_string().contains("broken"));
+        assert_eq!(result.conflicts.len(), 1);
+    }
+}
@@ -1,20 +1,34 @@
+//! Git access through libgit2, in process: no `git` subprocess per lookup,
+//! and paths are looked up in trees rather than parsed out of `rev:path`
+//! specs, so paths containing `:` work.
+//!
+//! Where libgit2 fails — objects a partial clone hasn't fetched yet, which
+//! only the `git` CLI fetches on demand, or a repository format libgit2
+//! doesn't support — the lookup is retried with the `git` CLI.
+
+use std::collections::HashMap;
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
+
+use git2::{Delta, DiffFindOptions, ObjectType, Repository, Tree};
+
+use crate::xdiff::{ConflictFormat, ConflictStyle};
 
 /// Find the root of the git repository by walking up from the current directory.
 pub fn find_repo_root() -> Result<PathBuf, Box<dyn std::error::Error>> {
-    let output = Command::new("git")
-        .args(["rev-parse", "--show-toplevel"])
-        .output()?;
-    if !output.status.success() {
-        return Err("Not inside a git repository".into());
+    match Repository::discover(".") {
+        Ok(repo) => work_tree(&repo),
+        Err(_) => Ok(PathBuf::from(
+            git_cli(Path::new("."), &["rev-parse", "--show-toplevel"])
+                .map_err(|_| "Not inside a git repository")?
+                .trim(),
+        )),
     }
-    let root = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    Ok(PathBuf::from(root))
 }
 
-/// Find the root of the git repository that contains the given path.
-/// Uses `git -C <dir> rev-parse --show-toplevel` so it works regardless of CWD.
+/// Find the root of the git repository that contains the given path,
+/// regardless of the current directory.
 pub fn find_repo_root_from_path(path: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
     let dir = if path.is_dir() {
         path.to_path_buf()
@@ -24,39 +38,138 @@ pub fn find_repo_root_from_path(path: &Path) -> Result<PathBuf, Box<dyn std::err
             .unwrap_or(Path::new("."))
             .to_path_buf()
     };
-    let output = Command::new("git")
-        .args(["-C", &dir.to_string_lossy(), "rev-parse", "--show-toplevel"])
-        .output()?;
-    if !output.status.success() {
-        return Err(format!("Not inside a git repository: {}", dir.display()).into());
+    match Repository::discover(&dir) {
+        Ok(repo) => work_tree(&repo),
+        Err(_) => Ok(PathBuf::from(
+            git_cli(&dir, &["rev-parse", "--show-toplevel"])
+                .map_err(|_| format!("Not inside a git repository: {}", dir.display()))?
+                .trim(),
+        )),
     }
-    let root = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    Ok(PathBuf::from(root))
 }
 
 /// Find the merge base between two refs.
 pub fn find_merge_base(head: &str, branch: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let output = Command::new("git")
-        .args(["merge-base", head, branch])
-        .output()?;
-    if !output.status.success() {
-        return Err(format!(
+    let base = open().and_then(|repo| {
+        let ours = repo.revparse_single(head)?.peel_to_commit()?.id();
+        let theirs = repo.revparse_single(branch)?.peel_to_commit()?.id();
+        Ok(repo.merge_base(ours, theirs)?.to_string())
+    });
+    match base.or_else(|_| git_cli(Path::new("."), &["merge-base", head, branch])) {
+        Ok(oid) => Ok(oid.trim().to_string()),
+        Err(_) => Err(format!(
             "Failed to find merge base between '{}' and '{}'. Are both branches valid?",
             head, branch
         )
-        .into());
+        .into()),
     }
-    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
 }
 
 /// Show file content at a given revision.
 pub fn git_show(rev: &str, file: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let spec = format!("{}:{}", rev, file);
-    let output = Command::new("git").args(["show", &spec]).output()?;
-    if !output.status.success() {
-        return Err(format!("git show {} failed", spec).into());
+    git_show_batch(Path::new("."), &[(rev, file)])?
+        .pop()
+        .flatten()
+        .ok_or_else(|| format!("{} does not exist at {}", file, rev).into())
+}
+
+/// Show many files at given revisions from the repository at `repo`, in the
+/// order given. Each revision is resolved once. `None` for a file that
+/// doesn't exist at its revision.
+pub fn git_show_batch(repo: &Path, specs: &[(&str, &str)]) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+    if specs.is_empty() {
+        return Ok(vec![]);
     }
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    let in_process = || -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+        let repo = Repository::discover(repo)?;
+        let mut trees: HashMap<&str, Tree> = HashMap::new();
+        let mut contents = Vec::with_capacity(specs.len());
+        for &(rev, file) in specs {
+            if !trees.contains_key(rev) {
+                trees.insert(rev, tree_at(&repo, rev)?);
+            }
+            contents.push(blob_at(&repo, &trees[rev], file)?);
+        }
+        Ok(contents)
+    };
+    in_process().or_else(|_| cat_file_batch(repo, specs))
+}
+
+/// [`git_show_batch`] with one `git cat-file --batch` run.
+fn cat_file_batch(repo: &Path, specs: &[(&str, &str)]) -> Result<Vec<Option<String>>, Box<dyn std::error::Error>> {
+    // cat-file reports an unknown revision like a missing file
+    let mut revs: Vec<&str> = specs.iter().map(|&(rev, _)| rev).collect();
+    revs.sort_unstable();
+    revs.dedup();
+    for rev in revs {
+        git_cli(repo, &["rev-parse", "--verify", "--quiet", &format!("{}^{{tree}}", rev)])
+            .map_err(|_| format!("Unknown revision '{}'", rev))?;
+    }
+
+    let mut child = Command::new("git")
+        .args(["cat-file", "--batch"])
+        .current_dir(repo)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdin = child.stdin.take().ok_or("git cat-file: no stdin")?;
+    let input: String = specs.iter().map(|(rev, file)| format!("{}:{}\n", rev, file)).collect();
+    // Written from another thread so a full stdout pipe can't deadlock us
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    writer.join().map_err(|_| "git cat-file: writer panicked")??;
+
+    let mut rest = output.stdout.as_slice();
+    let mut contents = Vec::with_capacity(specs.len());
+    for (rev, file) in specs {
+        let newline = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| format!("git cat-file: truncated output at {}:{}", rev, file))?;
+        let header = String::from_utf8_lossy(&rest[..newline]).into_owned();
+        rest = &rest[newline + 1..];
+        // `<oid> <type> <size>`, or `<spec> missing`
+        let mut fields = header.rsplitn(3, ' ');
+        let (Some(size), Some(kind)) = (fields.next(), fields.next()) else {
+            contents.push(None);
+            continue;
+        };
+        let Ok(size) = size.parse::<usize>() else {
+            contents.push(None);
+            continue;
+        };
+        let blob = rest
+            .get(..size)
+            .ok_or_else(|| format!("git cat-file: truncated output at {}:{}", rev, file))?;
+        contents.push((kind == "blob").then(|| String::from_utf8_lossy(blob).into_owned()));
+        rest = rest.get(size + 1..).unwrap_or_default();
+    }
+    Ok(contents)
+}
+
+/// How conflicts are written in the repository at `repo`: its
+/// `merge.conflictStyle` (git's default style when unset or unknown), with
+/// the given marker labels.
+pub fn conflict_format(repo: &Path, ours: &str, base: &str, theirs: &str) -> ConflictFormat {
+    let style = Repository::discover(repo)
+        .and_then(|repo| repo.config()?.get_string("merge.conflictStyle"))
+        .ok()
+        .or_else(|| git_cli(repo, &["config", "merge.conflictStyle"]).ok())
+        .and_then(|value| ConflictStyle::from_git(value.trim()))
+        .unwrap_or_default();
+    ConflictFormat {
+        style,
+        ours: ours.to_string(),
+        base: base.to_string(),
+        theirs: theirs.to_string(),
+    }
+}
+
+/// The abbreviated form of `sha` git labels conflicts with: its first seven
+/// characters, or all of it when shorter.
+pub fn short_sha(sha: &str) -> &str {
+    sha.get(..7).unwrap_or(sha)
 }
 
 /// Get files changed in both branches relative to their merge base.
@@ -65,23 +178,8 @@ pub fn get_changed_files(
     head: &str,
     branch: &str,
 ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let ours_output = Command::new("git")
-        .args(["diff", "--name-only", merge_base, head])
-        .output()?;
-    let ours_files: std::collections::HashSet<String> =
-        String::from_utf8_lossy(&ours_output.stdout)
-            .lines()
-            .map(|s| s.to_string())
-            .collect();
-
-    let theirs_output = Command::new("git")
-        .args(["diff", "--name-only", merge_base, branch])
-        .output()?;
-    let theirs_files: std::collections::HashSet<String> =
-        String::from_utf8_lossy(&theirs_output.stdout)
-            .lines()
-            .map(|s| s.to_string())
-            .collect();
+    let ours_files: std::collections::HashSet<String> = diff_files(merge_base, head)?.into_iter().collect();
+    let theirs_files: std::collections::HashSet<String> = diff_files(merge_base, branch)?.into_iter().collect();
 
     let mut both: Vec<String> = ours_files.intersection(&theirs_files).cloned().collect();
     both.sort();
@@ -93,15 +191,12 @@ pub fn diff_files(
     base_ref: &str,
     target_ref: &str,
 ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let output = Command::new("git")
-        .args(["diff", "--name-only", base_ref, target_ref])
-        .output()?;
-    let files: Vec<String> = String::from_utf8_lossy(&output.stdout)
-        .lines()
-        .filter(|s| !s.is_empty())
-        .map(|s| s.to_string())
-        .collect();
-    Ok(files)
+    open()
+        .and_then(|repo| changed_paths(&repo, base_ref, target_ref))
+        .or_else(|_| {
+            let names = git_cli(Path::new("."), &["diff", "--name-only", "-M", "-z", base_ref, target_ref])?;
+            Ok(names.split('\0').filter(|name| !name.is_empty()).map(str::to_string).collect())
+        })
 }
 
 /// Read a file from the working tree relative to a root path.
@@ -109,3 +204,147 @@ pub fn read_file(root: &Path, file_path: &str) -> Result<String, Box<dyn std::er
     let full = root.join(file_path);
     Ok(std::fs::read_to_string(full)?)
 }
+
+fn open() -> Result<Repository, Box<dyn std::error::Error>> {
+    Repository::discover(".").map_err(|_| "Not inside a git repository".into())
+}
+
+/// The output of `git <args>` run in `dir`, for what libgit2 can't do.
+fn git_cli(dir: &Path, args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("git").args(args).current_dir(dir).stderr(Stdio::null()).output()?;
+    if !output.status.success() {
+        return Err(format!("git {} failed", args.join(" ")).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn work_tree(repo: &Repository) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let workdir = repo.workdir().ok_or("Not inside a git work tree (bare repository)")?;
+    // libgit2 keeps a trailing slash; `git rev-parse --show-toplevel` doesn't
+    Ok(workdir.components().collect())
+}
+
+fn tree_at<'r>(repo: &'r Repository, rev: &str) -> Result<Tree<'r>, Box<dyn std::error::Error>> {
+    repo.revparse_single(rev)
+        .and_then(|object| object.peel_to_tree())
+        .map_err(|e| format!("Unknown revision '{}': {}", rev, e.message()).into())
+}
+
+/// The content of `file` in `tree`, `None` if it's missing or not a file.
+fn blob_at(repo: &Repository, tree: &Tree, file: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let entry = match tree.get_path(Path::new(file)) {
+        Ok(entry) => entry,
+        Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if entry.kind() != Some(ObjectType::Blob) {
+        return Ok(None);
+    }
+    let blob = repo.find_blob(entry.id())?;
+    Ok(Some(String::from_utf8_lossy(blob.content()).into_owned()))
+}
+
+/// `git diff --name-only from to`: renames are detected and listed under
+/// their new path.
+fn changed_paths(repo: &Repository, from: &str, to: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let old = tree_at(repo, from)?;
+    let new = tree_at(repo, to)?;
+    let mut diff = repo.diff_tree_to_tree(Some(&old), Some(&new), None)?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+    Ok(diff
+        .deltas()
+        .filter_map(|delta| {
+            let file = if delta.status() == Delta::Deleted { delta.old_file() } else { delta.new_file() };
+            file.path().map(|p| p.to_string_lossy().into_owned())
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=t", "-c", "user.email=t@t", "-c", "commit.gpgsign=false"])
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?}", args);
+    }
+
+    #[test]
+    fn test_show_batch_and_changed_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        git(root, &["init", "-q", "-b", "main"]);
+        std::fs::create_dir(root.join("src")).unwrap();
+        std::fs::write(root.join("src/a:b.rs"), "fn a() {}\n").unwrap();
+        std::fs::write(root.join("old.txt"), "line 1\nline 2\nline 3\nline 4\n").unwrap();
+        git(root, &["add", "."]);
+        git(root, &["commit", "-q", "-m", "base"]);
+        std::fs::write(root.join("src/a:b.rs"), "fn a() { 1 }\n").unwrap();
+        git(root, &["mv", "old.txt", "new.txt"]);
+        git(root, &["commit", "-q", "-am", "change"]);
+
+        let contents = git_show_batch(
+            &root.join("src"),
+            &[("HEAD~1", "src/a:b.rs"), ("HEAD", "src/a:b.rs"), ("HEAD", "old.txt"), ("HEAD", "src")],
+        )
+        .unwrap();
+        assert_eq!(
+            contents,
+            vec![Some("fn a() {}\n".to_string()), Some("fn a() { 1 }\n".to_string()), None, None]
+        );
+
+        let repo = Repository::open(root).unwrap();
+        let mut changed = changed_paths(&repo, "HEAD~1", "HEAD").unwrap();
+        changed.sort();
+        assert_eq!(changed, vec!["new.txt", "src/a:b.rs"]);
+
+        assert_eq!(find_repo_root_from_path(&root.join("src/a:b.rs")).unwrap(), root.canonicalize().unwrap());
+    }
+
+    #[test]
+    fn test_objects_missing_from_a_partial_clone_are_fetched() {
+        let dir = tempfile::tempdir().unwrap();
+        let origin = dir.path().join("origin");
+        std::fs::create_dir(&origin).unwrap();
+        git(&origin, &["init", "-q", "-b", "main"]);
+        git(&origin, &["config", "uploadpack.allowFilter", "true"]);
+        std::fs::write(origin.join("a.rs"), "fn a() {}\n").unwrap();
+        git(&origin, &["add", "."]);
+        git(&origin, &["commit", "-q", "-m", "base"]);
+        std::fs::write(origin.join("a.rs"), "fn a() { 1 }\n").unwrap();
+        git(&origin, &["commit", "-q", "-am", "change"]);
+
+        // No blob is fetched until one is read
+        let url = format!("file://{}", origin.display());
+        git(dir.path(), &["clone", "-q", "--filter=blob:none", "--no-checkout", &url, "clone"]);
+        let clone = dir.path().join("clone");
+        let contents = git_show_batch(&clone, &[("HEAD~1", "a.rs"), ("HEAD", "a.rs"), ("HEAD", "b.rs")]).unwrap();
+        assert_eq!(contents, vec![Some("fn a() {}\n".to_string()), Some("fn a() { 1 }\n".to_string()), None]);
+        assert!(git_show_batch(&clone, &[("no-such-branch", "a.rs")]).is_err());
+    }
+
+    #[test]
+    fn test_conflict_format_follows_the_repo_config() {
+        let dir = tempfile::tempdir().unwrap();
+        git(dir.path(), &["init", "-q", "-b", "main"]);
+        git(dir.path(), &["config", "merge.conflictStyle", "unknown"]);
+        assert_eq!(conflict_format(dir.path(), "HEAD", "base", "main").style, ConflictStyle::Merge);
+        git(dir.path(), &["config", "merge.conflictStyle", "zdiff3"]);
+        let format = conflict_format(dir.path(), "HEAD", "base", "main");
+        assert_eq!(format.style, ConflictStyle::ZealousDiff3);
+        assert_eq!(format.theirs, "main");
+    }
+
+    #[test]
+    fn test_short_sha() {
+        assert_eq!(short_sha("1a2b3c4d5e6f"), "1a2b3c4");
+        assert_eq!(short_sha("1a2b"), "1a2b");
+        assert_eq!(short_sha(""), "");
+    }
+}
@@ -0,0 +1,45 @@
+//! Glob matching for the path and name patterns in `.weave/config.toml`.
+
+/// Match a path against a glob: `*` and `?` within a path component, `**`
+/// across components. A pattern without `/` matches the file name alone,
+/// as in `.gitattributes`.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    if !pattern.contains('/') {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        return glob_match_bytes(pattern.as_bytes(), name.as_bytes());
+    }
+    glob_match_bytes(pattern.as_bytes(), path.as_bytes())
+}
+
+fn glob_match_bytes(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            // `**/` also matches zero components
+            let rest_after_slash = rest.strip_prefix(b"/").unwrap_or(rest);
+            glob_match_bytes(rest_after_slash, text) || (0..=text.len()).any(|i| glob_match_bytes(rest, &text[i..]))
+        }
+        [b'*', rest @ ..] => {
+            let component = text.iter().position(|&b| b == b'/').unwrap_or(text.len());
+            (0..=component).any(|i| glob_match_bytes(rest, &text[i..]))
+        }
+        [b'?', rest @ ..] => matches!(text, [c, tail @ ..] if *c != b'/' && glob_match_bytes(rest, tail)),
+        [p, rest @ ..] => matches!(text, [c, tail @ ..] if c == p && glob_match_bytes(rest, tail)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.md", "docs/guide/intro.md"));
+        assert!(!glob_match("*.md", "docs/guide/intro.mdx"));
+        assert!(glob_match("docs/**", "docs/guide/intro.md"));
+        assert!(glob_match("src/**/*.rs", "src/lib.rs"));
+        assert!(glob_match("src/**/*.rs", "src/merge/inner.rs"));
+        assert!(!glob_match("src/*.rs", "src/merge/inner.rs"));
+        assert!(glob_match("src/?ib.rs", "src/lib.rs"));
+    }
+}
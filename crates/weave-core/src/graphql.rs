@@ -0,0 +1,203 @@
+//! Entity-level merge for GraphQL schemas and documents (`.graphql`, `.gql`).
+//!
+//! Type definitions (`type`, `input`, `interface`, `enum`, `union`,
+//! `scalar`), directive definitions and operations are the entities. Fields
+//! inside a type — and values inside an enum — merge as keyed members, so two
+//! branches adding different fields to `type Query` merge cleanly.
+//!
+//! Description strings and `#` comments travel with the item they precede.
+
+use crate::braces::{self, Item, Syntax};
+use crate::conflict::MergeStats;
+use crate::merge::MergeResult;
+
+/// Check if a file is a GraphQL schema or document.
+pub fn is_graphql(file_path: &str) -> bool {
+    let lower = file_path.to_lowercase();
+    lower.ends_with(".graphql") || lower.ends_with(".gql")
+}
+
+const GRAPHQL: Syntax = Syntax {
+    line_comments: &["#"],
+    newline_terminates: true,
+    descriptions: true,
+    key: item_key,
+    entity_type,
+};
+
+/// Merge three versions of a GraphQL file by definition and field.
+///
+/// Returns None if any version can't be parsed (unbalanced braces).
+pub fn merge_graphql(base: &str, ours: &str, theirs: &str) -> Option<MergeResult> {
+    let base_doc = braces::parse(base, &GRAPHQL)?;
+    let ours_doc = braces::parse(ours, &GRAPHQL)?;
+    let theirs_doc = braces::parse(theirs, &GRAPHQL)?;
+
+    let mut stats = MergeStats::default();
+    let mut conflicts = Vec::new();
+    let merged = braces::merge_block(&base_doc, &ours_doc, &theirs_doc, &GRAPHQL, &mut stats, &mut conflicts);
+
+    let (content, conflict_blocks) = merged.render_merged(conflicts.len());
+    Some(MergeResult {
+        content,
+        conflicts,
+        conflict_blocks,
+        stats,
+        ..Default::default()
+    })
+}
+
+const DEFINITIONS: &[&str] = &[
+    "type",
+    "input",
+    "interface",
+    "enum",
+    "union",
+    "scalar",
+    "directive",
+    "schema",
+    "query",
+    "mutation",
+    "subscription",
+    "fragment",
+];
+
+/// Entity type for conflict reports.
+fn entity_type(kind: &str) -> &str {
+    match kind {
+        "stmt" => "statement",
+        other => other,
+    }
+}
+
+/// Key for matching an item across versions.
+fn item_key(item: &Item) -> String {
+    let normalized = item.normalized_header();
+    let (extend, rest) = match normalized.strip_prefix("extend ") {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, normalized.as_str()),
+    };
+
+    let word = leading_name(rest);
+    let after = rest[word.len()..].trim_start();
+    // `type: String` is a field named `type`, not a type definition
+    if DEFINITIONS.contains(&word) && !after.starts_with([':', '(']) {
+        let name = leading_name(after.trim_start_matches('@'));
+        let name = if word == "directive" {
+            format!("@{}", name)
+        } else {
+            name.to_string()
+        };
+        let kind = if extend {
+            format!("extend {}", word)
+        } else {
+            word.to_string()
+        };
+        return format!("{}:{}", kind, name);
+    }
+
+    if word.is_empty() {
+        format!("stmt:{}", normalized)
+    } else {
+        // Fields, arguments, enum values, root operation types, selections
+        format!("field:{}", word)
+    }
+}
+
+/// The identifier at the start of `s`.
+fn leading_name(s: &str) -> &str {
+    let end = s
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(s.len());
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = r#"# Root schema
+schema {
+  query: Query
+}
+
+"""
+A registered user.
+"""
+type User @key(fields: "id") {
+  id: ID!
+  "Display name"
+  name: String
+}
+
+type Query {
+  user(id: ID!): User
+}
+
+enum Role {
+  ADMIN
+  MEMBER
+}
+
+union SearchResult = User
+
+directive @auth(requires: Role = ADMIN) on OBJECT | FIELD_DEFINITION
+"#;
+
+    #[test]
+    fn test_round_trip_and_keys() {
+        let doc = braces::parse(BASE, &GRAPHQL).unwrap();
+        assert_eq!(doc.render(), BASE);
+        let keys: Vec<&str> = doc.items.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "schema:#1",
+                "type:User#1",
+                "type:Query#1",
+                "enum:Role#1",
+                "union:SearchResult#1",
+                "directive:@auth#1"
+            ]
+        );
+        let user = doc.items[1].1.body.as_ref().unwrap();
+        let fields: Vec<&str> = user.items.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(fields, vec!["field:id#1", "field:name#1"]);
+    }
+
+    #[test]
+    fn test_both_add_fields_to_same_type() {
+        let ours = BASE.replace("  user(id: ID!): User\n", "  user(id: ID!): User\n  users(first: Int): [User!]!\n");
+        let theirs = BASE
+            .replace("  user(id: ID!): User\n", "  user(id: ID!): User\n  me: User\n")
+            .replace("  MEMBER\n", "  MEMBER\n  GUEST\n");
+        let result = merge_graphql(BASE, &ours, &theirs).unwrap();
+        assert!(result.is_clean());
+        assert!(result
+            .content
+            .contains("type Query {\n  user(id: ID!): User\n  users(first: Int): [User!]!\n  me: User\n}"));
+        assert!(result.content.contains("  MEMBER\n  GUEST\n}"));
+    }
+
+    #[test]
+    fn test_both_add_types() {
+        let ours = format!("{}\ntype Post {{\n  id: ID!\n}}\n", BASE);
+        let theirs = format!("{}\nscalar DateTime\n", BASE);
+        let result = merge_graphql(BASE, &ours, &theirs).unwrap();
+        assert!(result.is_clean());
+        assert!(result.content.contains("type Post {"));
+        assert!(result.content.ends_with("scalar DateTime\n"));
+        assert_eq!(result.stats.entities_added_ours, 1);
+        assert_eq!(result.stats.entities_added_theirs, 1);
+    }
+
+    #[test]
+    fn test_same_field_changed_differently_conflicts() {
+        let ours = BASE.replace("  name: String\n", "  name: String!\n");
+        let theirs = BASE.replace("  name: String\n", "  name: String @deprecated\n");
+        let result = merge_graphql(BASE, &ours, &theirs).unwrap();
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].entity_type, "field");
+        assert_eq!(result.conflicts[0].entity_name, "name");
+    }
+}
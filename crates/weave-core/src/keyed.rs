@@ -0,0 +1,293 @@
+//! 3-way merge of ordered sequences of keyed items.
+//!
+//! Formats that weave merges itself (notebook cells, stylesheet rules, ...)
+//! reduce each version to a list of `(key, item)` pairs. This module decides,
+//! per key, whether to take ours, theirs, drop the item, or ask the caller to
+//! merge both sides — and in which order the surviving items appear.
+//!
+//! Ordering: ours is the skeleton. Items only present in theirs are inserted
+//! after their nearest predecessor in theirs that survives the merge (and
+//! after anything ours added at the same spot).
+
+use std::collections::{HashMap, HashSet};
+
+use crate::conflict::{ConflictKind, MergeStats};
+
+/// The outcome for one key.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum KeyedMerge<T> {
+    Clean(T),
+    Conflict(KeyedConflict<T>),
+}
+
+/// A key that could not be merged cleanly.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct KeyedConflict<T> {
+    pub key: String,
+    pub kind: ConflictKind,
+    pub base: Option<T>,
+    pub ours: Option<T>,
+    pub theirs: Option<T>,
+    /// Item to emit in place of the conflict, if the caller rendered one
+    /// (e.g. a cell whose source carries conflict markers).
+    pub rendered: Option<T>,
+}
+
+/// Merge three keyed sequences.
+///
+/// `merge_both` is called when both sides changed (or both added) the same key
+/// differently. It returns the merged item, or `Err` with an optional rendered
+/// item if the change conflicts. Keys must be unique within each sequence.
+pub(crate) fn merge_keyed<T, F>(
+    base: &[(String, T)],
+    ours: &[(String, T)],
+    theirs: &[(String, T)],
+    stats: &mut MergeStats,
+    mut merge_both: F,
+) -> Vec<KeyedMerge<T>>
+where
+    T: Clone + PartialEq,
+    F: FnMut(&str, Option<&T>, &T, &T) -> Result<T, Option<T>>,
+{
+    let base_map: HashMap<&str, &T> = base.iter().map(|(k, v)| (k.as_str(), v)).collect();
+    let ours_map: HashMap<&str, &T> = ours.iter().map(|(k, v)| (k.as_str(), v)).collect();
+    let theirs_map: HashMap<&str, &T> = theirs.iter().map(|(k, v)| (k.as_str(), v)).collect();
+
+    let order = merged_order(ours, theirs, &base_map, &ours_map);
+
+    let mut merged = Vec::new();
+    for key in &order {
+        let key = key.as_str();
+        let b = base_map.get(key).copied();
+        let o = ours_map.get(key).copied();
+        let t = theirs_map.get(key).copied();
+
+        match (b, o, t) {
+            (Some(b), Some(o), Some(t)) => {
+                if o == t {
+                    if o == b {
+                        stats.entities_unchanged += 1;
+                    } else {
+                        stats.entities_both_changed_merged += 1;
+                    }
+                    merged.push(KeyedMerge::Clean(o.clone()));
+                } else if o == b {
+                    stats.entities_theirs_only += 1;
+                    merged.push(KeyedMerge::Clean(t.clone()));
+                } else if t == b {
+                    stats.entities_ours_only += 1;
+                    merged.push(KeyedMerge::Clean(o.clone()));
+                } else {
+                    merged.push(resolve_both(key, Some(b), o, t, stats, &mut merge_both));
+                }
+            }
+            (Some(b), Some(o), None) => {
+                if o == b {
+                    stats.entities_deleted += 1;
+                } else {
+                    stats.entities_conflicted += 1;
+                    merged.push(KeyedMerge::Conflict(KeyedConflict {
+                        key: key.to_string(),
+                        kind: ConflictKind::ModifyDelete { modified_in_ours: true },
+                        base: Some(b.clone()),
+                        ours: Some(o.clone()),
+                        theirs: None,
+                        rendered: None,
+                    }));
+                }
+            }
+            (Some(b), None, Some(t)) => {
+                if t == b {
+                    stats.entities_deleted += 1;
+                } else {
+                    stats.entities_conflicted += 1;
+                    merged.push(KeyedMerge::Conflict(KeyedConflict {
+                        key: key.to_string(),
+                        kind: ConflictKind::ModifyDelete { modified_in_ours: false },
+                        base: Some(b.clone()),
+                        ours: None,
+                        theirs: Some(t.clone()),
+                        rendered: None,
+                    }));
+                }
+            }
+            (None, Some(o), Some(t)) => {
+                if o == t {
+                    stats.entities_added_ours += 1;
+                    merged.push(KeyedMerge::Clean(o.clone()));
+                } else {
+                    merged.push(resolve_both(key, None, o, t, stats, &mut merge_both));
+                }
+            }
+            (None, Some(o), None) => {
+                stats.entities_added_ours += 1;
+                merged.push(KeyedMerge::Clean(o.clone()));
+            }
+            (None, None, Some(t)) => {
+                stats.entities_added_theirs += 1;
+                merged.push(KeyedMerge::Clean(t.clone()));
+            }
+            (Some(_), None, None) | (None, None, None) => {}
+        }
+    }
+
+    // Base keys that never made it into the order were deleted by one side
+    // (and left unchanged by the other) or by both
+    let ordered: HashSet<&str> = order.iter().map(String::as_str).collect();
+    stats.entities_deleted += base.iter().filter(|(k, _)| !ordered.contains(k.as_str())).count();
+
+    merged
+}
+
+fn resolve_both<T, F>(
+    key: &str,
+    base: Option<&T>,
+    ours: &T,
+    theirs: &T,
+    stats: &mut MergeStats,
+    merge_both: &mut F,
+) -> KeyedMerge<T>
+where
+    T: Clone,
+    F: FnMut(&str, Option<&T>, &T, &T) -> Result<T, Option<T>>,
+{
+    match merge_both(key, base, ours, theirs) {
+        Ok(item) => {
+            stats.entities_both_changed_merged += 1;
+            KeyedMerge::Clean(item)
+        }
+        Err(rendered) => {
+            stats.entities_conflicted += 1;
+            KeyedMerge::Conflict(KeyedConflict {
+                key: key.to_string(),
+                kind: if base.is_some() {
+                    ConflictKind::BothModified
+                } else {
+                    ConflictKind::BothAdded
+                },
+                base: base.cloned(),
+                ours: Some(ours.clone()),
+                theirs: Some(theirs.clone()),
+                rendered,
+            })
+        }
+    }
+}
+
+/// Trivial 3-way pick of a value. Returns None if both sides changed it differently.
+pub(crate) fn pick_side<V: PartialEq>(base: Option<V>, ours: Option<V>, theirs: Option<V>) -> Option<Option<V>> {
+    if ours == theirs || base == theirs {
+        Some(ours)
+    } else if base == ours {
+        Some(theirs)
+    } else {
+        None
+    }
+}
+
+/// Key order of the merged sequence: all of ours, plus keys that exist in
+/// theirs but not ours and weren't deleted by ours (added in theirs, or
+/// deleted in ours but modified in theirs).
+fn merged_order<T: PartialEq>(
+    ours: &[(String, T)],
+    theirs: &[(String, T)],
+    base_map: &HashMap<&str, &T>,
+    ours_map: &HashMap<&str, &T>,
+) -> Vec<String> {
+    let mut order: Vec<String> = ours.iter().map(|(k, _)| k.clone()).collect();
+    let mut placed: HashSet<String> = order.iter().cloned().collect();
+    let theirs_keys: HashSet<&str> = theirs.iter().map(|(k, _)| k.as_str()).collect();
+
+    let mut predecessor: Option<&str> = None;
+    for (key, item) in theirs {
+        if !ours_map.contains_key(key.as_str()) {
+            let keep = match base_map.get(key.as_str()) {
+                None => true,
+                Some(b) => *b != item,
+            };
+            if keep {
+                let mut at = predecessor
+                    .and_then(|p| order.iter().position(|k| k == p))
+                    .map(|i| i + 1)
+                    .unwrap_or(0);
+                // Both sides appending after the same item: ours first, then theirs
+                while at < order.len()
+                    && !base_map.contains_key(order[at].as_str())
+                    && !theirs_keys.contains(order[at].as_str())
+                {
+                    at += 1;
+                }
+                order.insert(at, key.clone());
+                placed.insert(key.clone());
+            }
+        }
+        if placed.contains(key) {
+            predecessor = Some(key.as_str());
+        }
+    }
+
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seq(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn clean_values(merged: &[KeyedMerge<String>]) -> Vec<&str> {
+        merged
+            .iter()
+            .filter_map(|m| match m {
+                KeyedMerge::Clean(v) => Some(v.as_str()),
+                KeyedMerge::Conflict(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_independent_changes_and_order() {
+        let base = seq(&[("a", "1"), ("b", "2"), ("c", "3")]);
+        let ours = seq(&[("a", "1x"), ("b", "2"), ("c", "3"), ("d", "4")]);
+        let theirs = seq(&[("a", "1"), ("n", "new"), ("b", "2"), ("c", "3y")]);
+        let mut stats = MergeStats::default();
+
+        let merged = merge_keyed(&base, &ours, &theirs, &mut stats, |_, _, _, _| Err(None));
+
+        assert_eq!(clean_values(&merged), vec!["1x", "new", "2", "3y", "4"]);
+        assert_eq!(stats.entities_conflicted, 0);
+        assert_eq!(stats.entities_added_theirs, 1);
+    }
+
+    #[test]
+    fn test_delete_and_modify_delete() {
+        let base = seq(&[("a", "1"), ("b", "2")]);
+        let ours = seq(&[("b", "2x")]);
+        let theirs = seq(&[("a", "1")]);
+        let mut stats = MergeStats::default();
+
+        let merged = merge_keyed(&base, &ours, &theirs, &mut stats, |_, _, _, _| Err(None));
+
+        assert_eq!(merged.len(), 1);
+        assert!(matches!(
+            &merged[0],
+            KeyedMerge::Conflict(KeyedConflict { kind: ConflictKind::ModifyDelete { modified_in_ours: true }, .. })
+        ));
+        assert_eq!(stats.entities_deleted, 1);
+    }
+
+    #[test]
+    fn test_both_modified_calls_merge_both() {
+        let base = seq(&[("a", "1")]);
+        let ours = seq(&[("a", "1o")]);
+        let theirs = seq(&[("a", "1t")]);
+        let mut stats = MergeStats::default();
+
+        let merged = merge_keyed(&base, &ours, &theirs, &mut stats, |_, _, o, t| Ok(format!("{}+{}", o, t)));
+
+        assert_eq!(clean_values(&merged), vec!["1o+1t"]);
+        assert_eq!(stats.entities_both_changed_merged, 1);
+    }
+}
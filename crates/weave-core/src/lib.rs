@@ -1,10 +1,44 @@
+mod ast;
+mod braces;
+pub mod budget;
+pub mod buildfile;
+pub mod cache;
+pub mod config;
 pub mod conflict;
+pub mod generated;
 pub mod git;
+pub mod glob;
+pub mod graphql;
+mod keyed;
+pub mod manifest;
+pub mod markdown;
+pub mod markup;
 pub mod merge;
+pub mod notebook;
+pub mod pipeline;
+pub mod policy;
+pub mod protect;
+pub mod protobuf;
+pub mod provenance;
 pub mod reconstruct;
 pub mod region;
+pub mod rerere;
+pub mod sfc;
+mod shell;
+pub mod sql;
+pub mod stylesheet;
+pub mod trace;
 pub mod validate;
+pub mod verify;
+pub mod xdiff;
 
+pub use config::WeaveConfig;
 pub use conflict::{parse_weave_conflicts, ParsedConflict};
-pub use merge::{entity_merge, entity_merge_with_registry, MergeResult};
-pub use validate::{validate_merge, ModifiedEntity, SemanticWarning};
+pub use merge::{
+    entity_merge, entity_merge_in, entity_merge_with_budget, entity_merge_with_config, entity_merge_with_registry,
+    entity_merge_with_registry_and_config, file_budget, shared_registry, MergeContext, MergeResult,
+};
+pub use validate::{
+    find_dangling_references, find_signature_mismatches, modified_entities, supported_files, validate_merge,
+    validate_versions, FileVersions, ModifiedEntity, SemanticWarning,
+};
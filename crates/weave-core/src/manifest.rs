@@ -0,0 +1,648 @@
+//! Semver-aware resolution of concurrent dependency bumps in package manifests.
+//!
+//! When both branches bump the same dependency to different versions, the
+//! line merge conflicts on that key. With `[manifest] semver = true`, weave
+//! picks the higher of the two requirements when they are compatible (same
+//! operator and same major version, or same minor for `0.x`) and rewrites
+//! the lower side to agree before merging. Each auto-picked bump is reported
+//! as a `SemanticWarning` so it stays visible.
+//!
+//! Supported manifests: `Cargo.toml`, `package.json`, `pyproject.toml`, `go.mod`.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::validate::{SemanticWarning, WarningKind};
+
+/// A dependency bumped differently by both branches, resolved to the higher version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionBump {
+    pub dependency: String,
+    pub ours: String,
+    pub theirs: String,
+    pub chosen: String,
+}
+
+impl VersionBump {
+    pub fn to_warning(&self, file_path: &str) -> SemanticWarning {
+        SemanticWarning {
+            entity_name: self.dependency.clone(),
+            entity_type: "dependency".to_string(),
+            file_path: file_path.to_string(),
+            kind: WarningKind::VersionBumpResolved {
+                ours: self.ours.clone(),
+                theirs: self.theirs.clone(),
+                chosen: self.chosen.clone(),
+            },
+            related: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ManifestKind {
+    Cargo,
+    PackageJson,
+    Pyproject,
+    GoMod,
+}
+
+fn manifest_kind(file_path: &str) -> Option<ManifestKind> {
+    let file_name = file_path.rsplit(['/', '\\']).next().unwrap_or(file_path);
+    match file_name {
+        "Cargo.toml" => Some(ManifestKind::Cargo),
+        "package.json" => Some(ManifestKind::PackageJson),
+        "pyproject.toml" => Some(ManifestKind::Pyproject),
+        "go.mod" => Some(ManifestKind::GoMod),
+        _ => None,
+    }
+}
+
+/// Check if a file is a package manifest with dependency versions weave understands.
+pub fn is_manifest(file_path: &str) -> bool {
+    manifest_kind(file_path).is_some()
+}
+
+/// Location of a dependency's version requirement: line index and byte range within the line.
+#[derive(Debug, Clone, Copy)]
+struct VersionSpan {
+    line: usize,
+    start: usize,
+    end: usize,
+}
+
+/// Reconcile dependencies bumped differently on both sides.
+///
+/// Returns the rewritten (ours, theirs) pair plus the bumps that were resolved,
+/// or None if there was nothing to reconcile.
+pub fn reconcile_version_bumps(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+) -> Option<(String, String, Vec<VersionBump>)> {
+    let kind = manifest_kind(file_path)?;
+
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let mut ours_lines: Vec<String> = ours.split_inclusive('\n').map(String::from).collect();
+    let mut theirs_lines: Vec<String> = theirs.split_inclusive('\n').map(String::from).collect();
+
+    let base_deps = extract_dependencies(&base_lines, kind);
+    let ours_deps = extract_dependencies(&ours_lines, kind);
+    let theirs_deps = extract_dependencies(&theirs_lines, kind);
+
+    // Rewrites per side: (line, start, end, replacement)
+    let mut ours_edits: Vec<(VersionSpan, String)> = Vec::new();
+    let mut theirs_edits: Vec<(VersionSpan, String)> = Vec::new();
+    let mut bumps = Vec::new();
+
+    let mut keys: Vec<&String> = base_deps.keys().collect();
+    keys.sort();
+    for key in keys {
+        let (Some(b), Some(o), Some(t)) = (base_deps.get(key), ours_deps.get(key), theirs_deps.get(key)) else {
+            continue;
+        };
+        let base_req = span_text(&base_lines, b);
+        let ours_req = span_text(&ours_lines, o).to_string();
+        let theirs_req = span_text(&theirs_lines, t).to_string();
+
+        // Only a bump on both sides to different versions is a conflict worth resolving
+        if ours_req == base_req || theirs_req == base_req || ours_req == theirs_req {
+            continue;
+        }
+
+        let Some(pick) = pick_higher_compatible(&ours_req, &theirs_req) else {
+            continue;
+        };
+        let chosen = match pick {
+            Ordering::Greater => {
+                theirs_edits.push((*t, ours_req.clone()));
+                ours_req.clone()
+            }
+            _ => {
+                ours_edits.push((*o, theirs_req.clone()));
+                theirs_req.clone()
+            }
+        };
+        bumps.push(VersionBump {
+            dependency: dependency_name(key).to_string(),
+            ours: ours_req,
+            theirs: theirs_req,
+            chosen,
+        });
+    }
+
+    if bumps.is_empty() {
+        return None;
+    }
+
+    apply_edits(&mut ours_lines, ours_edits);
+    apply_edits(&mut theirs_lines, theirs_edits);
+
+    Some((ours_lines.concat(), theirs_lines.concat(), bumps))
+}
+
+fn span_text<'a, S: AsRef<str>>(lines: &'a [S], span: &VersionSpan) -> &'a str {
+    &lines[span.line].as_ref()[span.start..span.end]
+}
+
+/// Keys are `section\0name`; the dependency name is the part after the separator.
+fn dependency_name(key: &str) -> &str {
+    key.rsplit('\0').next().unwrap_or(key)
+}
+
+/// Apply version replacements, right-to-left within a line so earlier spans stay valid.
+fn apply_edits(lines: &mut [String], mut edits: Vec<(VersionSpan, String)>) {
+    edits.sort_by_key(|(span, _)| std::cmp::Reverse((span.line, span.start)));
+    for (span, replacement) in edits {
+        lines[span.line].replace_range(span.start..span.end, &replacement);
+    }
+}
+
+/// Extract dependency version spans keyed by section + dependency name.
+fn extract_dependencies<S: AsRef<str>>(lines: &[S], kind: ManifestKind) -> HashMap<String, VersionSpan> {
+    match kind {
+        ManifestKind::Cargo => extract_toml_dependencies(lines),
+        ManifestKind::Pyproject => {
+            let mut deps = extract_toml_dependencies(lines);
+            deps.extend(extract_pep508_dependencies(lines));
+            deps
+        }
+        ManifestKind::PackageJson => extract_package_json_dependencies(lines),
+        ManifestKind::GoMod => extract_go_mod_dependencies(lines),
+    }
+}
+
+/// Cargo.toml and Poetry-style tables:
+/// `name = "1.2"`, `name = { version = "1.2", ... }`, or `[dependencies.name]` + `version = "1.2"`.
+fn extract_toml_dependencies<S: AsRef<str>>(lines: &[S]) -> HashMap<String, VersionSpan> {
+    let mut deps = HashMap::new();
+    let mut section = String::new();
+
+    for (idx, line) in lines.iter().enumerate() {
+        let line = line.as_ref();
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            section = trimmed.trim_matches(|c| c == '[' || c == ']').trim().to_string();
+            continue;
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let Some(eq) = line.find('=') else {
+            continue;
+        };
+        let key = line[..eq].trim().trim_matches('"');
+
+        if section.ends_with("dependencies") {
+            let value_start = eq + 1;
+            let value = line[value_start..].trim_start();
+            let offset = line.len() - value.len();
+            let span = if value.starts_with('"') {
+                quoted_span(line, offset)
+            } else if value.starts_with('{') {
+                inline_table_version_span(line, offset)
+            } else {
+                None
+            };
+            if let Some((start, end)) = span {
+                deps.insert(format!("{}\0{}", section, key), VersionSpan { line: idx, start, end });
+            }
+        } else if let Some((table, name)) = section.rsplit_once('.') {
+            // `[dependencies.serde]` style table
+            if table.ends_with("dependencies") && key == "version" {
+                if let Some((start, end)) = quoted_span(line, eq + 1) {
+                    deps.insert(format!("{}\0{}", table, name), VersionSpan { line: idx, start, end });
+                }
+            }
+        }
+    }
+
+    deps
+}
+
+/// pyproject `[project]` dependency arrays of PEP 508 strings: `"requests>=2.28"`.
+fn extract_pep508_dependencies<S: AsRef<str>>(lines: &[S]) -> HashMap<String, VersionSpan> {
+    let mut deps = HashMap::new();
+    let mut section = String::new();
+    let mut array: Option<String> = None;
+
+    for (idx, line) in lines.iter().enumerate() {
+        let line = line.as_ref();
+        let trimmed = line.trim();
+        if array.is_none() && trimmed.starts_with('[') {
+            section = trimmed.trim_matches(|c| c == '[' || c == ']').trim().to_string();
+            continue;
+        }
+        if section != "project" && section != "project.optional-dependencies" {
+            continue;
+        }
+
+        let mut scan_from = 0;
+        if array.is_none() {
+            let Some(eq) = line.find('=') else {
+                continue;
+            };
+            let key = line[..eq].trim();
+            let is_dep_array = section == "project.optional-dependencies"
+                || key == "dependencies";
+            if !is_dep_array || !line[eq + 1..].trim_start().starts_with('[') {
+                continue;
+            }
+            array = Some(key.to_string());
+            scan_from = eq + 1;
+        }
+        let array_name = array.clone().unwrap_or_default();
+
+        // Every quoted string on this line is one requirement
+        let mut pos = scan_from;
+        while let Some((start, end)) = quoted_span(line, pos) {
+            let requirement = &line[start..end];
+            if let Some((name, spec_start, spec_end)) = split_pep508(requirement) {
+                deps.insert(
+                    format!("{}.{}\0{}", section, array_name, name),
+                    VersionSpan { line: idx, start: start + spec_start, end: start + spec_end },
+                );
+            }
+            pos = end + 1;
+        }
+
+        if trimmed.ends_with(']') || trimmed.ends_with("],") {
+            array = None;
+        }
+    }
+
+    deps
+}
+
+/// Split a PEP 508 requirement into (name, spec range). Only single-clause
+/// specifiers without environment markers are considered.
+fn split_pep508(requirement: &str) -> Option<(&str, usize, usize)> {
+    let name_end = requirement
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.'))
+        .unwrap_or(requirement.len());
+    if name_end == 0 {
+        return None;
+    }
+    let name = &requirement[..name_end];
+    let mut spec_start = name_end;
+    // Skip extras: `requests[socks]>=2.0`
+    if requirement[spec_start..].starts_with('[') {
+        spec_start += requirement[spec_start..].find(']')? + 1;
+    }
+    let spec = &requirement[spec_start..];
+    let leading_ws = spec.len() - spec.trim_start().len();
+    let spec_start = spec_start + leading_ws;
+    let spec_end = requirement.len() - (requirement.len() - requirement.trim_end().len());
+    let spec = &requirement[spec_start..spec_end];
+    if spec.is_empty() || spec.contains([',', ';']) {
+        return None;
+    }
+    Some((name, spec_start, spec_end))
+}
+
+/// package.json: `"name": "^1.2.3"` lines inside a dependency object.
+fn extract_package_json_dependencies<S: AsRef<str>>(lines: &[S]) -> HashMap<String, VersionSpan> {
+    const DEP_OBJECTS: &[&str] = &[
+        "dependencies",
+        "devDependencies",
+        "peerDependencies",
+        "optionalDependencies",
+    ];
+
+    let mut deps = HashMap::new();
+    let mut section: Option<&str> = None;
+
+    for (idx, line) in lines.iter().enumerate() {
+        let line = line.as_ref();
+        let trimmed = line.trim();
+
+        if section.is_none() {
+            if trimmed.ends_with('{') {
+                section = DEP_OBJECTS
+                    .iter()
+                    .find(|name| trimmed.starts_with(&format!("\"{}\"", name)))
+                    .copied();
+            }
+            continue;
+        }
+        if trimmed.starts_with('}') {
+            section = None;
+            continue;
+        }
+
+        let Some((key_start, key_end)) = quoted_span(line, 0) else {
+            continue;
+        };
+        let Some(colon) = line[key_end..].find(':').map(|c| key_end + c) else {
+            continue;
+        };
+        if let Some((start, end)) = quoted_span(line, colon + 1) {
+            let name = &line[key_start..key_end];
+            deps.insert(
+                format!("{}\0{}", section.unwrap_or_default(), name),
+                VersionSpan { line: idx, start, end },
+            );
+        }
+    }
+
+    deps
+}
+
+/// go.mod: `require path v1.2.3` or lines inside a `require ( ... )` block.
+fn extract_go_mod_dependencies<S: AsRef<str>>(lines: &[S]) -> HashMap<String, VersionSpan> {
+    let mut deps = HashMap::new();
+    let mut in_require_block = false;
+
+    for (idx, line) in lines.iter().enumerate() {
+        let line = line.as_ref();
+        let trimmed = line.trim();
+
+        let entry_offset = if in_require_block {
+            if trimmed.starts_with(')') {
+                in_require_block = false;
+                continue;
+            }
+            0
+        } else if let Some(rest) = trimmed.strip_prefix("require") {
+            if rest.trim_start().starts_with('(') {
+                in_require_block = true;
+                continue;
+            }
+            line.find("require").map(|p| p + "require".len()).unwrap_or(0)
+        } else {
+            continue;
+        };
+
+        // Tokens: module path, version, optional `// indirect`
+        let entry = &line[entry_offset..];
+        let mut tokens = entry.split_whitespace();
+        let (Some(module), Some(version)) = (tokens.next(), tokens.next()) else {
+            continue;
+        };
+        if module.starts_with("//") {
+            continue;
+        }
+        let module_pos = entry_offset + entry.find(module).unwrap_or(0);
+        let version_pos = module_pos + module.len() + line[module_pos + module.len()..].find(version).unwrap_or(0);
+        deps.insert(
+            format!("require\0{}", module),
+            VersionSpan { line: idx, start: version_pos, end: version_pos + version.len() },
+        );
+    }
+
+    deps
+}
+
+/// Find the first `"..."` string at or after `from`, returning the inner byte range.
+fn quoted_span(line: &str, from: usize) -> Option<(usize, usize)> {
+    let open = from + line.get(from..)?.find('"')?;
+    let close = open + 1 + line[open + 1..].find('"')?;
+    Some((open + 1, close))
+}
+
+/// Find `version = "..."` inside an inline table starting at `from`.
+fn inline_table_version_span(line: &str, from: usize) -> Option<(usize, usize)> {
+    let mut search = from;
+    while let Some(pos) = line[search..].find("version") {
+        let key_start = search + pos;
+        let key_end = key_start + "version".len();
+        let before_ok = line[..key_start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| c == '{' || c == ',' || c.is_whitespace());
+        let after = line[key_end..].trim_start();
+        if before_ok && after.starts_with('=') {
+            let eq = line.len() - after.len();
+            return quoted_span(line, eq + 1);
+        }
+        search = key_end;
+    }
+    None
+}
+
+/// A parsed single-clause version requirement like `^1.2.3`, `~=1.4`, `v0.9.1`.
+#[derive(Debug, PartialEq, Eq)]
+struct Requirement<'a> {
+    op: &'a str,
+    version: (u64, u64, u64),
+    pre: &'a str,
+}
+
+fn parse_requirement(req: &str) -> Option<Requirement<'_>> {
+    let req = req.trim();
+    let op_len = req
+        .find(|c: char| !matches!(c, '^' | '~' | '=' | '>' | 'v' | ' '))
+        .unwrap_or(req.len());
+    let op = req[..op_len].trim();
+    let rest = &req[op_len..];
+
+    let numeric_len = rest
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(rest.len());
+    let numeric = rest[..numeric_len].trim_end_matches('.');
+    let pre = &rest[numeric_len..];
+    if numeric.is_empty() || !(pre.is_empty() || pre.starts_with('-') || pre.starts_with('+')) {
+        return None;
+    }
+
+    let mut parts = numeric.split('.').map(|p| p.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some(Requirement { op, version: (major, minor, patch), pre })
+}
+
+/// Compare two requirements and decide which one wins.
+///
+/// Returns `Greater` if ours is higher, `Less` if theirs is higher, or None
+/// if they aren't comparable (different operators, incompatible majors, or
+/// minors under `~`, ranges or equal versions spelled differently). A
+/// pre-release only competes with its own version: like semver ranges,
+/// `^1.1.0` never reaches `1.2.0-alpha`.
+fn pick_higher_compatible(ours: &str, theirs: &str) -> Option<Ordering> {
+    let o = parse_requirement(ours)?;
+    let t = parse_requirement(theirs)?;
+    if o.op != t.op {
+        return None;
+    }
+    let compatible = if o.op == "~" {
+        o.version.0 == t.version.0 && o.version.1 == t.version.1
+    } else {
+        o.version.0 == t.version.0 && (o.version.0 != 0 || o.version.1 == t.version.1)
+    };
+    let (o_pre, t_pre) = (pre_release(o.pre), pre_release(t.pre));
+    if !compatible || ((o_pre.is_some() || t_pre.is_some()) && o.version != t.version) {
+        return None;
+    }
+    let ordering = o.version.cmp(&t.version).then_with(|| match (o_pre, t_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(o_pre), Some(t_pre)) => compare_pre_releases(o_pre, t_pre),
+    });
+    match ordering {
+        Ordering::Equal => None,
+        ordering => Some(ordering),
+    }
+}
+
+/// The pre-release of a version suffix, `rc.1` from `-rc.1+build.5`.
+/// Build metadata doesn't count.
+fn pre_release(suffix: &str) -> Option<&str> {
+    let pre = suffix.split('+').next().unwrap_or_default().strip_prefix('-')?;
+    (!pre.is_empty()).then_some(pre)
+}
+
+/// Semver precedence of two pre-releases: identifier by identifier,
+/// numeric ones numerically and below alphanumeric ones, and a prefix
+/// before what extends it.
+fn compare_pre_releases(ours: &str, theirs: &str) -> Ordering {
+    let mut ours = ours.split('.');
+    let mut theirs = theirs.split('.');
+    loop {
+        let ordering = match (ours.next(), theirs.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(o), Some(t)) => match (o.parse::<u64>(), t.parse::<u64>()) {
+                (Ok(o), Ok(t)) => o.cmp(&t),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => o.cmp(t),
+            },
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_requirement() {
+        assert_eq!(
+            parse_requirement("^1.2.3"),
+            Some(Requirement { op: "^", version: (1, 2, 3), pre: "" })
+        );
+        assert_eq!(
+            parse_requirement("1.2"),
+            Some(Requirement { op: "", version: (1, 2, 0), pre: "" })
+        );
+        assert_eq!(
+            parse_requirement("v0.9.1-rc.1"),
+            Some(Requirement { op: "v", version: (0, 9, 1), pre: "-rc.1" })
+        );
+        assert_eq!(parse_requirement(">=1.0, <2.0"), None);
+        assert_eq!(parse_requirement("*"), None);
+    }
+
+    #[test]
+    fn test_pick_higher_compatible() {
+        assert_eq!(pick_higher_compatible("1.0.190", "1.0.195"), Some(Ordering::Less));
+        assert_eq!(pick_higher_compatible("^4.18.0", "^4.17.2"), Some(Ordering::Greater));
+        // Different major: breaking, leave the conflict
+        assert_eq!(pick_higher_compatible("^1.4.0", "^2.0.0"), None);
+        // 0.x: minor bump is breaking
+        assert_eq!(pick_higher_compatible("0.11", "0.12"), None);
+        // Operator mismatch
+        assert_eq!(pick_higher_compatible("~1.2.0", "^1.3.0"), None);
+        // Tilde: only patch bumps are compatible
+        assert_eq!(pick_higher_compatible("~1.2.1", "~1.2.4"), Some(Ordering::Less));
+        assert_eq!(pick_higher_compatible("~1.2", "~1.3"), None);
+    }
+
+    #[test]
+    fn test_pick_higher_compatible_pre_releases() {
+        // A release beats its pre-releases, which compare per semver
+        assert_eq!(pick_higher_compatible("1.2.0-rc.1", "1.2.0"), Some(Ordering::Less));
+        assert_eq!(pick_higher_compatible("^1.2.0-rc.2", "^1.2.0-rc.10"), Some(Ordering::Less));
+        assert_eq!(pick_higher_compatible("1.2.0-beta", "1.2.0-alpha.1"), Some(Ordering::Greater));
+        assert_eq!(pick_higher_compatible("1.2.0-alpha", "1.2.0-alpha.1"), Some(Ordering::Less));
+        // A pre-release of another version isn't an upgrade to pick
+        assert_eq!(pick_higher_compatible("1.2.0-alpha", "1.1.0"), None);
+        // Build metadata doesn't count
+        assert_eq!(pick_higher_compatible("1.2.0+build.1", "1.2.0+build.2"), None);
+    }
+
+    #[test]
+    fn test_cargo_toml_both_bump_same_dependency() {
+        let base = "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1.0.190\"\ntokio = { version = \"1.30\", features = [\"full\"] }\n";
+        let ours = "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1.0.195\"\ntokio = { version = \"1.32\", features = [\"full\"] }\n";
+        let theirs = "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1.0.193\"\ntokio = { version = \"1.35\", features = [\"full\"] }\n";
+
+        let (new_ours, new_theirs, bumps) = reconcile_version_bumps(base, ours, theirs, "Cargo.toml").unwrap();
+        assert_eq!(new_ours, new_theirs);
+        assert!(new_ours.contains("serde = \"1.0.195\""));
+        assert!(new_ours.contains("version = \"1.35\""));
+        assert_eq!(bumps.len(), 2);
+        assert_eq!(bumps[0].dependency, "serde");
+        assert_eq!(bumps[0].chosen, "1.0.195");
+        assert_eq!(bumps[1].dependency, "tokio");
+        assert_eq!(bumps[1].chosen, "1.35");
+    }
+
+    #[test]
+    fn test_cargo_toml_dependency_table() {
+        let base = "[dependencies.serde]\nversion = \"1.0.100\"\nfeatures = [\"derive\"]\n";
+        let ours = "[dependencies.serde]\nversion = \"1.0.150\"\nfeatures = [\"derive\"]\n";
+        let theirs = "[dependencies.serde]\nversion = \"1.0.120\"\nfeatures = [\"derive\"]\n";
+        let (_, new_theirs, bumps) = reconcile_version_bumps(base, ours, theirs, "Cargo.toml").unwrap();
+        assert_eq!(bumps[0].dependency, "serde");
+        assert!(new_theirs.contains("version = \"1.0.150\""));
+    }
+
+    #[test]
+    fn test_package_json_both_bump() {
+        let base = "{\n  \"name\": \"app\",\n  \"dependencies\": {\n    \"express\": \"^4.17.0\",\n    \"lodash\": \"^4.17.20\"\n  }\n}\n";
+        let ours = "{\n  \"name\": \"app\",\n  \"dependencies\": {\n    \"express\": \"^4.18.2\",\n    \"lodash\": \"^4.17.20\"\n  }\n}\n";
+        let theirs = "{\n  \"name\": \"app\",\n  \"dependencies\": {\n    \"express\": \"^4.17.3\",\n    \"lodash\": \"^4.17.20\"\n  }\n}\n";
+        let (new_ours, new_theirs, bumps) = reconcile_version_bumps(base, ours, theirs, "web/package.json").unwrap();
+        assert_eq!(new_ours, new_theirs);
+        assert_eq!(bumps.len(), 1);
+        assert_eq!(bumps[0].chosen, "^4.18.2");
+    }
+
+    #[test]
+    fn test_pyproject_pep508_both_bump() {
+        let base = "[project]\nname = \"app\"\ndependencies = [\n    \"requests>=2.28\",\n    \"click>=8.0\",\n]\n";
+        let ours = "[project]\nname = \"app\"\ndependencies = [\n    \"requests>=2.31\",\n    \"click>=8.0\",\n]\n";
+        let theirs = "[project]\nname = \"app\"\ndependencies = [\n    \"requests>=2.29\",\n    \"click>=8.0\",\n]\n";
+        let (new_ours, new_theirs, bumps) = reconcile_version_bumps(base, ours, theirs, "pyproject.toml").unwrap();
+        assert_eq!(new_ours, new_theirs);
+        assert_eq!(bumps[0].dependency, "requests");
+        assert_eq!(bumps[0].chosen, ">=2.31");
+    }
+
+    #[test]
+    fn test_go_mod_require_block() {
+        let base = "module example.com/app\n\nrequire (\n\tgithub.com/pkg/errors v0.9.1\n\tgolang.org/x/sync v0.3.0 // indirect\n)\n";
+        let ours = "module example.com/app\n\nrequire (\n\tgithub.com/pkg/errors v0.9.1\n\tgolang.org/x/sync v0.3.2 // indirect\n)\n";
+        let theirs = "module example.com/app\n\nrequire (\n\tgithub.com/pkg/errors v0.9.1\n\tgolang.org/x/sync v0.3.5 // indirect\n)\n";
+        let (new_ours, _, bumps) = reconcile_version_bumps(base, ours, theirs, "go.mod").unwrap();
+        assert!(new_ours.contains("golang.org/x/sync v0.3.5 // indirect"));
+        assert_eq!(bumps[0].dependency, "golang.org/x/sync");
+    }
+
+    #[test]
+    fn test_major_bump_left_alone() {
+        let base = "[dependencies]\nclap = \"3.2\"\n";
+        let ours = "[dependencies]\nclap = \"3.3\"\n";
+        let theirs = "[dependencies]\nclap = \"4.0\"\n";
+        assert!(reconcile_version_bumps(base, ours, theirs, "Cargo.toml").is_none());
+    }
+
+    #[test]
+    fn test_not_a_manifest() {
+        assert!(!is_manifest("config.toml"));
+        assert!(is_manifest("crates/core/Cargo.toml"));
+        assert!(reconcile_version_bumps("a", "b", "c", "src/lib.rs").is_none());
+    }
+}
@@ -0,0 +1,547 @@
+//! Fence- and list-aware merge for Markdown.
+//!
+//! Used when the regular merge of a Markdown file conflicts. The document is
+//! split into sections by ATX heading (keyed by heading path), and each
+//! section body into blocks:
+//!
+//! - fenced code blocks, merged with the entity merge for their language
+//! - list items, keyed by content so additions on both sides are unioned
+//!   (both branches adding bullets under `## Unreleased` no longer conflicts)
+//! - other text, merged line by line
+
+use std::collections::HashMap;
+
+use sem_core::parser::registry::ParserRegistry;
+
+use crate::config::WeaveConfig;
+use crate::conflict::{classify_conflict, count_blocks, BlockCounter, EntityConflict, MergeStats};
+use crate::keyed::{merge_keyed, pick_side, KeyedMerge};
+use crate::merge::{entity_merge_in, line_level_fallback, MergeContext, MergeResult};
+
+/// Check if a file is Markdown.
+pub fn is_markdown(file_path: &str) -> bool {
+    let lower = file_path.to_lowercase();
+    lower.ends_with(".md") || lower.ends_with(".markdown")
+}
+
+/// A heading and the body up to the next heading.
+#[derive(Debug, Clone, PartialEq)]
+struct Section {
+    heading: String,
+    body: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockKind {
+    Fence,
+    ListItem,
+    Text,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Block {
+    kind: BlockKind,
+    text: String,
+}
+
+/// Merge three versions of a Markdown document section by section.
+///
+/// Returns None if the document can't be split (e.g. an unclosed fence).
+pub fn merge_markdown(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    registry: &ParserRegistry,
+    config: &WeaveConfig,
+    context: &MergeContext,
+) -> Option<MergeResult> {
+    let base_sections = split_sections(base)?;
+    let ours_sections = split_sections(ours)?;
+    let theirs_sections = split_sections(theirs)?;
+
+    let mut stats = MergeStats::default();
+    // Section key → the body's merge, conflicted
+    let mut inner_results = HashMap::new();
+
+    let merged = merge_keyed(&base_sections, &ours_sections, &theirs_sections, &mut stats, |key, b, o, t| {
+        let heading = pick_side(b.map(|s| &s.heading), Some(&o.heading), Some(&t.heading))
+            .flatten()
+            .ok_or(None)?
+            .clone();
+        let base_body = b.map(|s| s.body.as_str()).unwrap_or("");
+        let mut result = merge_body(base_body, &o.body, &t.body, file_path, registry, config, context);
+        let section = Section {
+            heading,
+            body: std::mem::take(&mut result.content),
+        };
+        if result.conflicts.is_empty() {
+            Ok(section)
+        } else {
+            inner_results.insert(key.to_string(), result);
+            Err(Some(section))
+        }
+    });
+
+    let mut content = String::new();
+    let mut conflicts = Vec::new();
+    let mut conflict_blocks = Vec::new();
+    let mut counter = BlockCounter::default();
+    for item in merged {
+        match item {
+            KeyedMerge::Clean(section) => {
+                content.push_str(&section.heading);
+                content.push_str(&section.body);
+            }
+            KeyedMerge::Conflict(c) => {
+                if let Some(section) = c.rendered {
+                    content.push_str(&section.heading);
+                    let offset = counter.next(&content);
+                    if let Some(inner) = inner_results.remove(&c.key) {
+                        append_inner(&mut conflicts, &mut conflict_blocks, inner, offset);
+                    }
+                    content.push_str(&section.body);
+                    continue;
+                }
+                let render = |s: &Section| format!("{}{}", s.heading, s.body);
+                let base_text = c.base.as_ref().map(render);
+                let ours_text = c.ours.as_ref().map(render);
+                let theirs_text = c.theirs.as_ref().map(render);
+                let conflict = EntityConflict {
+                    entity_name: section_name(&c.key).to_string(),
+                    entity_type: "section".to_string(),
+                    kind: c.kind,
+                    complexity: classify_conflict(base_text.as_deref(), ours_text.as_deref(), theirs_text.as_deref()),
+                    ours_content: ours_text,
+                    theirs_content: theirs_text,
+                    base_content: base_text,
+                };
+                conflict_blocks.push(Some(counter.next(&content)));
+                content.push_str(&conflict.to_conflict_markers());
+                conflicts.push(conflict);
+            }
+        }
+    }
+
+    Some(MergeResult {
+        content,
+        conflicts,
+        conflict_blocks,
+        stats,
+        ..Default::default()
+    })
+}
+
+/// Append the conflicts of a merge rendered after `offset` blocks of the
+/// enclosing output.
+fn append_inner(conflicts: &mut Vec<EntityConflict>, blocks: &mut Vec<Option<usize>>, inner: MergeResult, offset: usize) {
+    blocks.extend((0..inner.conflicts.len()).map(|i| inner.conflict_block(i).map(|b| b + offset)));
+    conflicts.extend(inner.conflicts);
+}
+
+/// Merge a section body changed on both sides: a plain line merge first,
+/// then block-wise if that conflicts. Conflicts are rendered in the body.
+fn merge_body(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    registry: &ParserRegistry,
+    config: &WeaveConfig,
+    context: &MergeContext,
+) -> MergeResult {
+    let line_result = line_level_fallback(base, ours, theirs, file_path, context);
+    if line_result.is_clean() {
+        return line_result;
+    }
+
+    if let Some(merged) = merge_blocks(base, ours, theirs, file_path, registry, config, context) {
+        let block_markers = count_blocks(&merged.content);
+        let line_markers = count_blocks(&line_result.content);
+        if block_markers <= line_markers {
+            return merged;
+        }
+    }
+
+    line_result
+}
+
+/// Block-wise merge of a section body. Returns None when the bodies don't
+/// line up (different numbers of fences or text runs), since positional
+/// keys would then pair unrelated blocks.
+fn merge_blocks(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    registry: &ParserRegistry,
+    config: &WeaveConfig,
+    context: &MergeContext,
+) -> Option<MergeResult> {
+    let base_blocks = split_blocks(base)?;
+    let ours_blocks = split_blocks(ours)?;
+    let theirs_blocks = split_blocks(theirs)?;
+
+    let shape = |blocks: &[(String, Block)]| {
+        let fences = blocks.iter().filter(|(_, b)| b.kind == BlockKind::Fence).count();
+        let texts = blocks.iter().filter(|(_, b)| b.kind == BlockKind::Text).count();
+        (fences, texts)
+    };
+    if shape(&base_blocks) != shape(&ours_blocks) || shape(&base_blocks) != shape(&theirs_blocks) {
+        return None;
+    }
+
+    // Block key → the block's merge, conflicted
+    let mut inner_results = HashMap::new();
+    let merged = merge_keyed(&base_blocks, &ours_blocks, &theirs_blocks, &mut MergeStats::default(), |key, b, o, t| {
+        let base_text = b.map(|b| b.text.as_str()).unwrap_or("");
+        let mut result = match o.kind {
+            BlockKind::Fence => merge_fence(base_text, &o.text, &t.text, file_path, registry, config, context)?,
+            BlockKind::Text => line_level_fallback(base_text, &o.text, &t.text, file_path, context),
+            // List items are keyed by content: a key present on both sides is the same item
+            BlockKind::ListItem => return Err(None),
+        };
+        let block = Block {
+            kind: o.kind,
+            text: std::mem::take(&mut result.content),
+        };
+        if result.conflicts.is_empty() {
+            Ok(block)
+        } else {
+            inner_results.insert(key.to_string(), result);
+            Err(Some(block))
+        }
+    });
+
+    let mut content = String::new();
+    let mut conflicts = Vec::new();
+    let mut conflict_blocks = Vec::new();
+    let mut counter = BlockCounter::default();
+    for item in merged {
+        match item {
+            KeyedMerge::Clean(block) => content.push_str(&block.text),
+            KeyedMerge::Conflict(c) => match c.rendered {
+                Some(block) => {
+                    let offset = counter.next(&content);
+                    if let Some(inner) = inner_results.remove(&c.key) {
+                        append_inner(&mut conflicts, &mut conflict_blocks, inner, offset);
+                    }
+                    content.push_str(&block.text);
+                }
+                None => {
+                    let conflict = EntityConflict {
+                        entity_name: c.key.clone(),
+                        entity_type: "block".to_string(),
+                        kind: c.kind,
+                        complexity: classify_conflict(
+                            c.base.as_ref().map(|b| b.text.as_str()),
+                            c.ours.as_ref().map(|b| b.text.as_str()),
+                            c.theirs.as_ref().map(|b| b.text.as_str()),
+                        ),
+                        ours_content: c.ours.map(|b| b.text),
+                        theirs_content: c.theirs.map(|b| b.text),
+                        base_content: c.base.map(|b| b.text),
+                    };
+                    conflict_blocks.push(Some(counter.next(&content)));
+                    content.push_str(&conflict.to_conflict_markers());
+                    conflicts.push(conflict);
+                }
+            },
+        }
+    }
+
+    Some(MergeResult {
+        content,
+        conflicts,
+        conflict_blocks,
+        ..Default::default()
+    })
+}
+
+/// Merge the inside of a fenced code block with the entity merge for its language.
+/// Returns `Err(None)` if the opening lines conflict.
+fn merge_fence(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    registry: &ParserRegistry,
+    config: &WeaveConfig,
+    context: &MergeContext,
+) -> Result<MergeResult, Option<Block>> {
+    let (base_open, base_inner, base_close) = split_fence(base);
+    let (ours_open, ours_inner, ours_close) = split_fence(ours);
+    let (theirs_open, theirs_inner, theirs_close) = split_fence(theirs);
+
+    let open = pick_side(Some(base_open), Some(ours_open), Some(theirs_open)).flatten().ok_or(None)?;
+    let close = pick_side(Some(base_close), Some(ours_close), Some(theirs_close)).flatten().unwrap_or(ours_close);
+
+    let lang = open.trim_start().trim_start_matches(['`', '~']).split_whitespace().next().unwrap_or("");
+    let path = format!("{}.{}", file_path, fence_extension(lang));
+    let mut result = entity_merge_in(base_inner, ours_inner, theirs_inner, &path, registry, config, &context.part());
+    result.content = format!("{}{}{}", open, result.content, close);
+    Ok(result)
+}
+
+/// Split a fence block into (opening line, inner content, closing line).
+fn split_fence(text: &str) -> (&str, &str, &str) {
+    let open_end = text.find('\n').map(|i| i + 1).unwrap_or(text.len());
+    let body = &text[open_end..];
+    // The closing fence is the last non-empty line
+    let trimmed = body.trim_end_matches('\n');
+    let close_start = trimmed.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    (&text[..open_end], &body[..close_start], &body[close_start..])
+}
+
+/// File extension for a fence info string, used to pick a parser.
+fn fence_extension(lang: &str) -> &str {
+    match lang.to_ascii_lowercase().as_str() {
+        "rust" | "rs" => "rs",
+        "python" | "py" => "py",
+        "typescript" | "ts" => "ts",
+        "tsx" => "tsx",
+        "javascript" | "js" => "js",
+        "jsx" => "jsx",
+        "go" | "golang" => "go",
+        "java" => "java",
+        "c" => "c",
+        "cpp" | "c++" => "cpp",
+        "ruby" | "rb" => "rb",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "css" => "css",
+        "scss" => "scss",
+        _ => "txt",
+    }
+}
+
+/// Split a document into sections keyed by heading path. Text before the
+/// first heading is the "preamble". Returns None for an unclosed fence.
+fn split_sections(content: &str) -> Option<Vec<(String, Section)>> {
+    let mut sections: Vec<(String, Section)> = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut path: Vec<(usize, String)> = Vec::new();
+    let mut current_key = "preamble".to_string();
+    let mut current = Section { heading: String::new(), body: String::new() };
+    let mut fence: Option<(char, usize)> = None;
+
+    for line in content.split_inclusive('\n') {
+        if let Some((ch, len)) = fence {
+            if is_fence_close(line, ch, len) {
+                fence = None;
+            }
+            current.body.push_str(line);
+            continue;
+        }
+        if let Some(open) = fence_open(line) {
+            fence = Some(open);
+            current.body.push_str(line);
+            continue;
+        }
+        if let Some((level, title)) = atx_heading(line) {
+            if !current.heading.is_empty() || !current.body.is_empty() {
+                sections.push((current_key.clone(), current));
+            }
+            path.retain(|(l, _)| *l < level);
+            path.push((level, title));
+            let key = path.iter().map(|(_, t)| t.as_str()).collect::<Vec<_>>().join(" > ");
+            let count = seen.entry(key.clone()).or_insert(0);
+            *count += 1;
+            current_key = format!("{}#{}", key, count);
+            current = Section { heading: line.to_string(), body: String::new() };
+            continue;
+        }
+        current.body.push_str(line);
+    }
+    if fence.is_some() {
+        return None;
+    }
+    if !current.heading.is_empty() || !current.body.is_empty() {
+        sections.push((current_key, current));
+    }
+
+    Some(sections)
+}
+
+/// Split a section body into fences, list items and text runs.
+fn split_blocks(body: &str) -> Option<Vec<(String, Block)>> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut fence: Option<(char, usize)> = None;
+
+    for line in body.split_inclusive('\n') {
+        if let Some((ch, len)) = fence {
+            blocks.last_mut()?.text.push_str(line);
+            if is_fence_close(line, ch, len) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(open) = fence_open(line) {
+            fence = Some(open);
+            blocks.push(Block { kind: BlockKind::Fence, text: line.to_string() });
+            continue;
+        }
+        if is_list_item_start(line) {
+            blocks.push(Block { kind: BlockKind::ListItem, text: line.to_string() });
+            continue;
+        }
+        // Indented continuation of a list item
+        let continues_item = line.starts_with([' ', '\t']) && !line.trim().is_empty();
+        match blocks.last_mut() {
+            Some(last) if last.kind == BlockKind::ListItem && continues_item => last.text.push_str(line),
+            Some(last) if last.kind == BlockKind::Text => last.text.push_str(line),
+            _ => blocks.push(Block { kind: BlockKind::Text, text: line.to_string() }),
+        }
+    }
+    if fence.is_some() {
+        return None;
+    }
+
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut counters = (0usize, 0usize);
+    let keyed = blocks
+        .into_iter()
+        .map(|block| {
+            let key = match block.kind {
+                BlockKind::Fence => {
+                    counters.0 += 1;
+                    format!("fence#{}", counters.0)
+                }
+                BlockKind::Text => {
+                    counters.1 += 1;
+                    format!("text#{}", counters.1)
+                }
+                BlockKind::ListItem => {
+                    let item = format!("item:{}", block.text.trim());
+                    let count = seen.entry(item.clone()).or_insert(0);
+                    *count += 1;
+                    format!("{}#{}", item, count)
+                }
+            };
+            (key, block)
+        })
+        .collect();
+
+    Some(keyed)
+}
+
+/// `# Title` → (1, "Title"). Closing `#`s are dropped.
+fn atx_heading(line: &str) -> Option<(usize, String)> {
+    let trimmed = line.trim_end();
+    let indent = trimmed.len() - trimmed.trim_start().len();
+    if indent > 3 {
+        return None;
+    }
+    let rest = trimmed.trim_start();
+    let level = rest.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let title = &rest[level..];
+    if !title.is_empty() && !title.starts_with([' ', '\t']) {
+        return None;
+    }
+    let title = title.trim().trim_end_matches('#').trim();
+    Some((level, title.to_string()))
+}
+
+/// Opening fence: 3+ backticks or tildes, indented at most 3 spaces.
+fn fence_open(line: &str) -> Option<(char, usize)> {
+    let rest = line.trim_start_matches(' ');
+    if line.len() - rest.len() > 3 {
+        return None;
+    }
+    let ch = rest.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = rest.chars().take_while(|c| *c == ch).count();
+    (len >= 3).then_some((ch, len))
+}
+
+fn is_fence_close(line: &str, ch: char, len: usize) -> bool {
+    let rest = line.trim();
+    rest.len() >= len && rest.chars().all(|c| c == ch)
+}
+
+fn is_list_item_start(line: &str) -> bool {
+    let rest = line.trim_start_matches(' ');
+    if line.len() - rest.len() > 3 {
+        return false;
+    }
+    if let Some(after) = rest.strip_prefix(['-', '*', '+']) {
+        return after.starts_with([' ', '\t']);
+    }
+    let digits = rest.chars().take_while(char::is_ascii_digit).count();
+    digits > 0
+        && digits <= 9
+        && rest[digits..].starts_with(['.', ')'])
+        && rest[digits + 1..].starts_with([' ', '\t'])
+}
+
+/// Heading title from a section key (`Changelog > Unreleased#1` → `Unreleased`).
+fn section_name(key: &str) -> &str {
+    let without_count = key.rsplit_once('#').map(|(k, _)| k).unwrap_or(key);
+    without_count.rsplit(" > ").next().unwrap_or(without_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(base: &str, ours: &str, theirs: &str) -> MergeResult {
+        let registry = sem_core::parser::plugins::create_default_registry();
+        merge_markdown(base, ours, theirs, "CHANGELOG.md", &registry, &WeaveConfig::default(), &MergeContext::default()).unwrap()
+    }
+
+    #[test]
+    fn test_changelog_both_add_bullets() {
+        let base = "# Changelog\n\n## [Unreleased]\n\n- Existing entry\n\n## [1.0.0]\n\n- Initial release\n";
+        let ours = "# Changelog\n\n## [Unreleased]\n\n- Add feature A\n- Existing entry\n\n## [1.0.0]\n\n- Initial release\n";
+        let theirs = "# Changelog\n\n## [Unreleased]\n\n- Fix bug B\n- Existing entry\n\n## [1.0.0]\n\n- Initial release\n";
+
+        let result = merge(base, ours, theirs);
+        assert!(result.is_clean(), "conflicts: {:?}", result.conflicts);
+        assert_eq!(
+            result.content,
+            "# Changelog\n\n## [Unreleased]\n\n- Add feature A\n- Fix bug B\n- Existing entry\n\n## [1.0.0]\n\n- Initial release\n"
+        );
+    }
+
+    #[test]
+    fn test_text_changed_on_both_sides_still_conflicts() {
+        let base = "# Title\n\nSome text.\n";
+        let ours = "# Title\n\nOur text.\n";
+        let theirs = "# Title\n\nTheir text.\n";
+        let result = merge(base, ours, theirs);
+        assert!(!result.is_clean());
+    }
+
+    #[test]
+    fn test_split_sections_ignores_headings_in_fences() {
+        let doc = "intro\n# A\n```sh\n# not a heading\n```\n## B\ntext\n";
+        let sections = split_sections(doc).unwrap();
+        let keys: Vec<&str> = sections.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["preamble", "A#1", "A > B#1"]);
+        let rebuilt: String = sections.iter().map(|(_, s)| format!("{}{}", s.heading, s.body)).collect();
+        assert_eq!(rebuilt, doc);
+    }
+
+    #[test]
+    fn test_split_blocks() {
+        let body = "\nIntro line.\n- one\n  continued\n- two\n\n```rust\nfn a() {}\n```\n";
+        let blocks = split_blocks(body).unwrap();
+        let kinds: Vec<BlockKind> = blocks.iter().map(|(_, b)| b.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![BlockKind::Text, BlockKind::ListItem, BlockKind::ListItem, BlockKind::Text, BlockKind::Fence]
+        );
+        assert_eq!(blocks[1].1.text, "- one\n  continued\n");
+    }
+
+    #[test]
+    fn test_split_fence() {
+        let (open, inner, close) = split_fence("```rust\nfn a() {}\n```\n");
+        assert_eq!(open, "```rust\n");
+        assert_eq!(inner, "fn a() {}\n");
+        assert_eq!(close, "```\n");
+    }
+}
@@ -0,0 +1,719 @@
+//! Element-level merge for XML and HTML.
+//!
+//! Elements are matched across versions by tag plus an identifying attribute
+//! (`id`, `name`, `Include`, ... — see [`MarkupConfig`]), or an identifying
+//! child element such as Maven's `<artifactId>`. Elements with no identifier
+//! are matched by position among their siblings of the same tag.
+//!
+//! When both sides changed the same element, its attributes merge as a keyed
+//! set and its children merge recursively, so one branch adding a
+//! `<dependency>` while another bumps a `<version>` elsewhere in `pom.xml`
+//! merges cleanly.
+//!
+//! Whitespace between elements travels with the element after it, so a file
+//! round-trips byte for byte when nothing changes.
+
+use std::collections::HashMap;
+
+use crate::config::MarkupConfig;
+use crate::conflict::{classify_conflict, EntityConflict, MergeStats, Rendered};
+use crate::keyed::{merge_keyed, pick_side, KeyedMerge};
+use crate::merge::{line_level_fallback, MergeContext, MergeResult};
+
+/// Check if a file is XML or HTML weave can merge by element.
+pub fn is_markup(file_path: &str) -> bool {
+    let lower = file_path.to_lowercase();
+    [
+        ".xml", ".html", ".htm", ".xhtml", ".svg", ".csproj", ".fsproj", ".vbproj", ".props", ".targets",
+        ".xaml", ".resx", ".plist", ".xsd", ".xsl", ".xslt",
+    ]
+    .iter()
+    .any(|ext| lower.ends_with(ext))
+}
+
+fn is_html(file_path: &str) -> bool {
+    let lower = file_path.to_lowercase();
+    lower.ends_with(".html") || lower.ends_with(".htm")
+}
+
+/// HTML elements that never have an end tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr",
+];
+
+/// HTML elements whose content is raw text, not markup.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "textarea", "title"];
+
+/// Children of an element, or the top level of the document.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Block {
+    items: Vec<(String, Item)>,
+    /// Whitespace after the last item.
+    tail: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Item {
+    /// Whitespace before the item.
+    leading: String,
+    node: Node,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Element(Box<Element>),
+    /// Text, comments, CDATA, processing instructions and doctypes, verbatim.
+    Raw(String),
+    /// The markers of the merge's conflict `index`.
+    Conflict { markers: String, index: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Element {
+    /// `<tag`
+    open: String,
+    /// Attributes keyed by name, each with its leading whitespace.
+    attributes: Vec<(String, String)>,
+    /// `>` or `/>`, with any whitespace before it.
+    close: String,
+    /// None for self-closing and void elements.
+    children: Option<Block>,
+    /// `</tag>`, empty for self-closing and void elements.
+    end: String,
+}
+
+impl Block {
+    fn render(&self) -> String {
+        let mut out = Rendered::default();
+        self.render_into(&mut out);
+        out.content
+    }
+
+    /// Render a merged block, along with the conflict block each of its
+    /// `conflicts` conflicts is rendered as.
+    fn render_merged(&self, conflicts: usize) -> (String, Vec<Option<usize>>) {
+        let mut out = Rendered::default();
+        self.render_into(&mut out);
+        out.finish(conflicts)
+    }
+
+    fn render_into(&self, out: &mut Rendered) {
+        for (_, item) in &self.items {
+            out.push_str(&item.leading);
+            item.node.render_into(out);
+        }
+        out.push_str(&self.tail);
+    }
+}
+
+impl Node {
+    fn render(&self) -> String {
+        let mut out = Rendered::default();
+        self.render_into(&mut out);
+        out.content
+    }
+
+    fn render_into(&self, out: &mut Rendered) {
+        match self {
+            Node::Element(e) => {
+                out.push_str(&e.open);
+                for (_, attribute) in &e.attributes {
+                    out.push_str(attribute);
+                }
+                out.push_str(&e.close);
+                if let Some(children) = &e.children {
+                    children.render_into(out);
+                }
+                out.push_str(&e.end);
+            }
+            Node::Raw(text) => out.push_str(text),
+            Node::Conflict { markers, index } => out.push_conflict(*index, markers),
+        }
+    }
+}
+
+/// Merge three versions of an XML or HTML file element by element.
+///
+/// Returns None if any version isn't well-formed enough to parse (e.g.
+/// mismatched end tags or HTML's optional `</li>`), so the caller can fall
+/// back to the regular merge.
+pub fn merge_markup(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    config: &MarkupConfig,
+    context: &MergeContext,
+) -> Option<MergeResult> {
+    let ctx = Context {
+        html: is_html(file_path),
+        id_attributes: &config.id_attributes,
+        file_path,
+        context,
+    };
+    let base_doc = ctx.parse(base)?;
+    let ours_doc = ctx.parse(ours)?;
+    let theirs_doc = ctx.parse(theirs)?;
+
+    let mut stats = MergeStats::default();
+    let mut conflicts = Vec::new();
+    let merged = ctx.merge_block(&base_doc, &ours_doc, &theirs_doc, &mut stats, &mut conflicts);
+
+    let (content, conflict_blocks) = merged.render_merged(conflicts.len());
+    Some(MergeResult {
+        content,
+        conflicts,
+        conflict_blocks,
+        stats,
+        ..Default::default()
+    })
+}
+
+struct Context<'a> {
+    html: bool,
+    id_attributes: &'a [String],
+    file_path: &'a str,
+    context: &'a MergeContext<'a>,
+}
+
+impl Context<'_> {
+    /// Merge two blocks against their base. Conflicting items are rendered as
+    /// conflict markers in place and reported in `conflicts`.
+    ///
+    /// Unlike the other structured merges, stats count elements at every
+    /// depth: an XML document has a single root.
+    fn merge_block(
+        &self,
+        base: &Block,
+        ours: &Block,
+        theirs: &Block,
+        stats: &mut MergeStats,
+        conflicts: &mut Vec<EntityConflict>,
+    ) -> Block {
+        let mut nested = MergeStats::default();
+        let merged = merge_keyed(&base.items, &ours.items, &theirs.items, stats, |_, b, o, t| {
+            self.merge_item(b, o, t, &mut nested, conflicts)
+        });
+        stats.entities_unchanged += nested.entities_unchanged;
+        stats.entities_ours_only += nested.entities_ours_only;
+        stats.entities_theirs_only += nested.entities_theirs_only;
+        stats.entities_both_changed_merged += nested.entities_both_changed_merged;
+        stats.entities_conflicted += nested.entities_conflicted;
+        stats.entities_added_ours += nested.entities_added_ours;
+        stats.entities_added_theirs += nested.entities_added_theirs;
+        stats.entities_deleted += nested.entities_deleted;
+
+        let mut items = Vec::new();
+        for entry in merged {
+            match entry {
+                KeyedMerge::Clean(item) => items.push(item),
+                KeyedMerge::Conflict(c) => {
+                    if let Some(item) = c.rendered {
+                        // Nested conflicts were already rendered inside the element
+                        items.push(item);
+                        continue;
+                    }
+                    let (entity_type, name) = describe_key(&c.key);
+                    let base_text = c.base.as_ref().map(|i| i.node.render());
+                    let ours_text = c.ours.as_ref().map(|i| i.node.render());
+                    let theirs_text = c.theirs.as_ref().map(|i| i.node.render());
+                    let conflict = EntityConflict {
+                        entity_name: name.to_string(),
+                        entity_type: entity_type.to_string(),
+                        kind: c.kind,
+                        complexity: classify_conflict(
+                            base_text.as_deref(),
+                            ours_text.as_deref(),
+                            theirs_text.as_deref(),
+                        ),
+                        ours_content: ours_text,
+                        theirs_content: theirs_text,
+                        base_content: base_text,
+                    };
+
+                    // Markers must start at column 0: drop the indentation before the item
+                    let mut leading = c
+                        .ours
+                        .as_ref()
+                        .or(c.theirs.as_ref())
+                        .map(|i| i.leading.trim_end_matches([' ', '\t']).to_string())
+                        .unwrap_or_default();
+                    if !leading.ends_with('\n') {
+                        leading.push('\n');
+                    }
+                    items.push(Item {
+                        leading,
+                        node: Node::Conflict {
+                            markers: conflict.to_conflict_markers(),
+                            index: conflicts.len(),
+                        },
+                    });
+                    conflicts.push(conflict);
+                }
+            }
+        }
+
+        let tail = pick_side(Some(&base.tail), Some(&ours.tail), Some(&theirs.tail))
+            .flatten()
+            .unwrap_or(&ours.tail)
+            .clone();
+
+        // Keys only matter for matching; the merged block is just rendered
+        Block {
+            items: items.into_iter().map(|item| (String::new(), item)).collect(),
+            tail,
+        }
+    }
+
+    /// Merge an item changed on both sides: attributes as a keyed set,
+    /// children recursively, text line by line.
+    fn merge_item(
+        &self,
+        base: Option<&Item>,
+        ours: &Item,
+        theirs: &Item,
+        stats: &mut MergeStats,
+        conflicts: &mut Vec<EntityConflict>,
+    ) -> Result<Item, Option<Item>> {
+        let leading = pick_side(base.map(|b| &b.leading), Some(&ours.leading), Some(&theirs.leading))
+            .flatten()
+            .unwrap_or(&ours.leading)
+            .clone();
+
+        let (o, t) = match (&ours.node, &theirs.node) {
+            (Node::Element(o), Node::Element(t)) => (o, t),
+            (Node::Raw(o), Node::Raw(t)) => {
+                let Some(Node::Raw(b)) = base.map(|b| &b.node) else {
+                    return Err(None);
+                };
+                let result = line_level_fallback(b, o, t, self.file_path, self.context);
+                if !result.is_clean() {
+                    return Err(None);
+                }
+                return Ok(Item {
+                    leading,
+                    node: Node::Raw(result.content),
+                });
+            }
+            _ => return Err(None),
+        };
+        let b = match base.map(|b| &b.node) {
+            Some(Node::Element(b)) => Some(b.as_ref()),
+            _ => None,
+        };
+
+        let open = pick_side(b.map(|b| &b.open), Some(&o.open), Some(&t.open)).flatten().ok_or(None)?;
+        let close = pick_side(b.map(|b| &b.close), Some(&o.close), Some(&t.close)).flatten().ok_or(None)?;
+        let end = pick_side(b.map(|b| &b.end), Some(&o.end), Some(&t.end)).flatten().ok_or(None)?;
+
+        // Attributes: a keyed set, conflicting only on the same attribute
+        let empty = Vec::new();
+        let mut attributes = Vec::new();
+        let merged_attributes = merge_keyed(
+            b.map(|b| &b.attributes).unwrap_or(&empty),
+            &o.attributes,
+            &t.attributes,
+            &mut MergeStats::default(),
+            |_, _, _, _| Err(None),
+        );
+        for entry in merged_attributes {
+            match entry {
+                KeyedMerge::Clean(a) => attributes.push((String::new(), a)),
+                KeyedMerge::Conflict(_) => return Err(None),
+            }
+        }
+
+        let before = conflicts.len();
+        let children = match (&o.children, &t.children) {
+            (Some(oc), Some(tc)) => {
+                let empty = Block::default();
+                let bc = b.and_then(|b| b.children.as_ref()).unwrap_or(&empty);
+                Some(self.merge_block(bc, oc, tc, stats, conflicts))
+            }
+            (None, None) => None,
+            _ => return Err(None),
+        };
+
+        let item = Item {
+            leading,
+            node: Node::Element(Box::new(Element {
+                open: open.clone(),
+                attributes,
+                close: close.clone(),
+                children,
+                end: end.clone(),
+            })),
+        };
+        if conflicts.len() == before {
+            Ok(item)
+        } else {
+            Err(Some(item))
+        }
+    }
+
+    fn parse(&self, content: &str) -> Option<Block> {
+        let mut parser = Parser {
+            src: content,
+            pos: 0,
+            ctx: self,
+        };
+        let block = parser.block(None)?;
+        (parser.pos == content.len()).then_some(block)
+    }
+
+    fn normalize_name(&self, name: &str) -> String {
+        if self.html {
+            name.to_lowercase()
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Key for an element: tag plus the first identifier found.
+    fn element_key(&self, tag: &str, element: &Element) -> String {
+        for id in self.id_attributes {
+            let id = self.normalize_name(id);
+            if let Some((_, attr)) = element.attributes.iter().find(|(name, _)| *name == id) {
+                return format!("{}[{}={}]", tag, id, attribute_value(attr));
+            }
+        }
+        let children = element.children.iter().flat_map(|c| &c.items);
+        for id in self.id_attributes {
+            for (_, child) in children.clone() {
+                let Node::Element(e) = &child.node else { continue };
+                if self.normalize_name(&e.open[1..]) != self.normalize_name(id) {
+                    continue;
+                }
+                let text = e.children.as_ref().map(Block::render).unwrap_or_default();
+                let text = text.trim();
+                if !text.is_empty() && !text.contains('<') {
+                    return format!("{}[{}={}]", tag, id, text);
+                }
+            }
+        }
+        tag.to_string()
+    }
+}
+
+/// Split a key into (entity type, name) for conflict reports.
+fn describe_key(key: &str) -> (&str, &str) {
+    let key = key.rsplit_once('#').map(|(k, _)| k).unwrap_or(key);
+    match key.split_once(':') {
+        Some(("text", _)) => ("text", ""),
+        Some(("comment", _)) => ("comment", ""),
+        Some(("decl", _)) => ("declaration", ""),
+        _ => ("element", key),
+    }
+}
+
+/// The value of a rendered attribute (` id="main"` → `main`).
+fn attribute_value(attr: &str) -> &str {
+    attr.split_once('=')
+        .map(|(_, v)| v.trim().trim_matches(['"', '\'']))
+        .unwrap_or("")
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    ctx: &'a Context<'a>,
+}
+
+impl Parser<'_> {
+    /// Parse nodes until the end tag of `parent` (left unconsumed) or end of
+    /// input at the top level.
+    fn block(&mut self, parent: Option<&str>) -> Option<Block> {
+        let mut items: Vec<(String, Item)> = Vec::new();
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        let mut push = |key: String, item: Item, items: &mut Vec<(String, Item)>| {
+            let count = seen.entry(key.clone()).or_insert(0);
+            *count += 1;
+            items.push((format!("{}#{}", key, count), item));
+        };
+
+        loop {
+            let start = self.pos;
+            let lt = self.src[start..].find('<').map(|i| start + i).unwrap_or(self.src.len());
+            let text = &self.src[start..lt];
+            let leading_len = text.len() - text.trim_start().len();
+
+            if !text.trim().is_empty() {
+                // Text with content is an item of its own; only the whitespace
+                // before it is leading
+                let content = text.trim_end();
+                let item = Item {
+                    leading: text[..leading_len].to_string(),
+                    node: Node::Raw(content[leading_len..].to_string()),
+                };
+                push("text:".to_string(), item, &mut items);
+                self.pos = start + content.len();
+                continue;
+            }
+
+            self.pos = lt;
+            let rest = &self.src[lt..];
+            if rest.is_empty() || rest.starts_with("</") {
+                if rest.is_empty() != parent.is_none() {
+                    // Unclosed element, or a stray end tag at the top level
+                    return None;
+                }
+                if let Some(parent) = parent {
+                    let name_end = rest.find(|c: char| c.is_whitespace() || c == '>')?;
+                    if self.ctx.normalize_name(&rest[2..name_end]) != self.ctx.normalize_name(parent) {
+                        return None;
+                    }
+                }
+                return Some(Block {
+                    items,
+                    tail: text.to_string(),
+                });
+            }
+
+            let leading = text.to_string();
+            let (key, node) = if let Some(end) = self.markup_declaration(rest) {
+                self.pos = lt + end;
+                let raw = rest[..end].to_string();
+                let kind = if raw.starts_with("<!--") { "comment:" } else { "decl:" };
+                (kind.to_string(), Node::Raw(raw))
+            } else {
+                self.pos = lt;
+                let (tag, element) = self.element()?;
+                (self.ctx.element_key(&tag, &element), Node::Element(Box::new(element)))
+            };
+            push(key, Item { leading, node }, &mut items);
+        }
+    }
+
+    /// Length of a comment, CDATA section, processing instruction or
+    /// doctype at the start of `rest`.
+    fn markup_declaration(&self, rest: &str) -> Option<usize> {
+        let (terminator, from) = if rest.starts_with("<!--") {
+            ("-->", 4)
+        } else if rest.starts_with("<![CDATA[") {
+            ("]]>", 9)
+        } else if rest.starts_with("<?") {
+            ("?>", 2)
+        } else if rest.starts_with("<!") {
+            (">", 2)
+        } else {
+            return None;
+        };
+        rest[from..].find(terminator).map(|i| from + i + terminator.len())
+    }
+
+    /// Parse the element starting at `pos`, through its end tag.
+    fn element(&mut self) -> Option<(String, Element)> {
+        let bytes = self.src.as_bytes();
+        let start = self.pos;
+        let mut i = start + 1;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' && bytes[i] != b'/' {
+            i += 1;
+        }
+        let tag = self.ctx.normalize_name(&self.src[start + 1..i]);
+        if tag.is_empty() {
+            return None;
+        }
+        let open = self.src[start..i].to_string();
+
+        let mut attributes = Vec::new();
+        let close_start;
+        loop {
+            let attr_start = i;
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            match bytes.get(i) {
+                None => return None,
+                Some(b'>') | Some(b'/') => {
+                    close_start = attr_start;
+                    break;
+                }
+                _ => {}
+            }
+            let name_start = i;
+            while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !matches!(bytes[i], b'=' | b'>' | b'/') {
+                i += 1;
+            }
+            let name = self.ctx.normalize_name(&self.src[name_start..i]);
+            let mut j = i;
+            while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+                j += 1;
+            }
+            if bytes.get(j) == Some(&b'=') {
+                j += 1;
+                while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+                    j += 1;
+                }
+                match bytes.get(j) {
+                    Some(&quote @ (b'"' | b'\'')) => {
+                        j += 1 + self.src[j + 1..].find(quote as char)? + 1;
+                    }
+                    _ => {
+                        while j < bytes.len() && !bytes[j].is_ascii_whitespace() && bytes[j] != b'>' {
+                            j += 1;
+                        }
+                    }
+                }
+                i = j;
+            }
+            if name.is_empty() {
+                return None;
+            }
+            attributes.push((name, self.src[attr_start..i].to_string()));
+        }
+
+        let self_closing = bytes[i] == b'/';
+        let close_end = self.src[i..].find('>')? + i + 1;
+        let close = self.src[close_start..close_end].to_string();
+        self.pos = close_end;
+
+        let mut element = Element {
+            open,
+            attributes,
+            close,
+            children: None,
+            end: String::new(),
+        };
+        if self_closing || (self.ctx.html && VOID_ELEMENTS.contains(&tag.as_str())) {
+            return Some((tag, element));
+        }
+
+        let children = if self.ctx.html && RAW_TEXT_ELEMENTS.contains(&tag.as_str()) {
+            // Script and style bodies are one text item, merged line by line
+            let end_tag = format!("</{}", tag);
+            let body_len = self.src[self.pos..].to_ascii_lowercase().find(&end_tag)?;
+            let body = &self.src[self.pos..self.pos + body_len];
+            self.pos += body_len;
+            let mut block = Block::default();
+            if !body.is_empty() {
+                block.items.push((
+                    "raw:#1".to_string(),
+                    Item {
+                        leading: String::new(),
+                        node: Node::Raw(body.to_string()),
+                    },
+                ));
+            }
+            block
+        } else {
+            self.block(Some(&tag))?
+        };
+
+        let end_len = self.src[self.pos..].find('>')? + 1;
+        element.end = self.src[self.pos..self.pos + end_len].to_string();
+        self.pos += end_len;
+        element.children = Some(children);
+        Some((tag, element))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(base: &str, ours: &str, theirs: &str, path: &str) -> MergeResult {
+        merge_markup(base, ours, theirs, path, &MarkupConfig::default(), &MergeContext::default()).unwrap()
+    }
+
+    const POM: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<project>
+  <!-- Build settings -->
+  <version>1.0.0</version>
+  <dependencies>
+    <dependency>
+      <groupId>org.slf4j</groupId>
+      <artifactId>slf4j-api</artifactId>
+      <version>2.0.9</version>
+    </dependency>
+  </dependencies>
+</project>
+"#;
+
+    #[test]
+    fn test_round_trip_and_keys() {
+        let ctx = Context {
+            html: false,
+            id_attributes: &MarkupConfig::default().id_attributes,
+            file_path: "pom.xml",
+            context: &MergeContext::default(),
+        };
+        let doc = ctx.parse(POM).unwrap();
+        assert_eq!(doc.render(), POM);
+        let keys: Vec<&str> = doc.items.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["decl:#1", "project#1"]);
+        let Node::Element(project) = &doc.items[1].1.node else { panic!() };
+        let children = project.children.as_ref().unwrap();
+        let keys: Vec<&str> = children.items.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["comment:#1", "version#1", "dependencies#1"]);
+        let Node::Element(deps) = &children.items[2].1.node else { panic!() };
+        assert_eq!(deps.children.as_ref().unwrap().items[0].0, "dependency[artifactId=slf4j-api]#1");
+    }
+
+    #[test]
+    fn test_pom_both_add_dependencies() {
+        let dep = |name: &str| {
+            format!(
+                "    <dependency>\n      <groupId>org.example</groupId>\n      <artifactId>{}</artifactId>\n    </dependency>\n",
+                name
+            )
+        };
+        let ours = POM.replace("  </dependencies>", &format!("{}  </dependencies>", dep("alpha")));
+        let theirs = POM
+            .replace("  </dependencies>", &format!("{}  </dependencies>", dep("beta")))
+            .replace("<version>1.0.0</version>", "<version>1.1.0</version>");
+        let result = merge(POM, &ours, &theirs, "pom.xml");
+        assert!(result.is_clean());
+        assert!(result.content.contains("<version>1.1.0</version>"));
+        let alpha = result.content.find("<artifactId>alpha").unwrap();
+        let beta = result.content.find("<artifactId>beta").unwrap();
+        assert!(alpha < beta);
+        assert!(result.content.ends_with("    </dependency>\n  </dependencies>\n</project>\n"));
+    }
+
+    #[test]
+    fn test_attributes_merge_as_set() {
+        let base = "<PackageReference Include=\"Serde\" Version=\"1.0\" />\n";
+        let ours = "<PackageReference Include=\"Serde\" Version=\"1.1\" />\n";
+        let theirs = "<PackageReference Include=\"Serde\" Version=\"1.0\" PrivateAssets=\"all\" />\n";
+        let result = merge(base, ours, theirs, "app.csproj");
+        assert!(result.is_clean());
+        assert_eq!(
+            result.content,
+            "<PackageReference Include=\"Serde\" Version=\"1.1\" PrivateAssets=\"all\" />\n"
+        );
+    }
+
+    #[test]
+    fn test_same_attribute_changed_differently_conflicts() {
+        let base = "<root>\n  <item id=\"a\" value=\"1\"/>\n  <item id=\"b\"/>\n</root>\n";
+        let ours = "<root>\n  <item id=\"a\" value=\"2\"/>\n  <item id=\"b\"/>\n</root>\n";
+        let theirs = "<root>\n  <item id=\"a\" value=\"3\"/>\n  <item id=\"b\" extra=\"x\"/>\n</root>\n";
+        let result = merge(base, ours, theirs, "data.xml");
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].entity_name, "item[id=a]");
+        assert_eq!(result.conflict_blocks, vec![Some(0)]);
+        assert!(result.content.contains("\n<<<<<<< ours"));
+        assert!(result.content.contains("<item id=\"b\" extra=\"x\"/>"));
+    }
+
+    #[test]
+    fn test_html_elements_by_id_and_void_tags() {
+        let base = "<!DOCTYPE html>\n<html>\n<head>\n  <meta charset=\"utf-8\">\n  <script>\n    init();\n  </script>\n</head>\n<body>\n  <nav id=\"top\"></nav>\n  <main id=\"content\"><p>Hello</p></main>\n</body>\n</html>\n";
+        let ours = base.replace("<nav id=\"top\"></nav>", "<nav id=\"top\" class=\"dark\"></nav>");
+        let theirs = base
+            .replace("<p>Hello</p>", "<p>Hello</p><br><p>World</p>")
+            .replace("    init();\n", "    init();\n    track();\n");
+        let result = merge(base, &ours, &theirs, "index.html");
+        assert!(result.is_clean());
+        assert!(result.content.contains("<nav id=\"top\" class=\"dark\"></nav>"));
+        assert!(result.content.contains("<p>Hello</p><br><p>World</p>"));
+        assert!(result.content.contains("track();"));
+    }
+
+    #[test]
+    fn test_mismatched_end_tag_returns_none() {
+        let config = MarkupConfig::default();
+        assert!(merge_markup("<ul><li>a</ul>", "", "", "x.html", &config, &MergeContext::default()).is_none());
+        assert!(merge_markup("<a>", "<a></a>", "<a></a>", "x.xml", &config, &MergeContext::default()).is_none());
+    }
+}
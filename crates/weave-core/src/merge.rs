@@ -1,8 +1,6 @@
 use std::collections::{HashMap, HashSet};
-use std::io::Write;
-use std::process::Command;
-use std::sync::mpsc;
-use std::time::Duration;
+use std::path::Path;
+use std::sync::OnceLock;
 
 use sem_core::model::change::ChangeType;
 use sem_core::model::entity::SemanticEntity;
@@ -10,24 +8,122 @@ use sem_core::model::identity::match_entities;
 use sem_core::parser::plugins::create_default_registry;
 use sem_core::parser::registry::ParserRegistry;
 
-use crate::conflict::{classify_conflict, ConflictKind, EntityConflict, MergeStats};
+use crate::budget::{Exhausted, MergeBudget};
+use crate::buildfile::{is_build_file, merge_build_file};
+use crate::cache::{cached_entities, EntityCache};
+use crate::config::{DuplicatePolicy, WeaveConfig};
+use crate::conflict::{classify_conflict, classify_conflict_in, ConflictKind, EntityConflict, MergeStats};
+use crate::generated::{resolve_generated, GeneratedOutcome};
+use crate::graphql::{is_graphql, merge_graphql};
+use crate::manifest::reconcile_version_bumps;
+use crate::markdown::{is_markdown, merge_markdown};
+use crate::markup::{is_markup, merge_markup};
+use crate::notebook::{is_notebook, merge_notebook};
+use crate::provenance::{attribute, attribute_unordered, LineOrigin, Provenance, RecordedOrigins};
+use crate::protobuf::{is_protobuf, merge_protobuf};
+use crate::sfc::{is_single_file_component, merge_single_file_component};
+use crate::sql::{is_sql, merge_sql};
+use crate::stylesheet::{is_stylesheet, merge_stylesheet};
+use crate::trace::{EntityPath, FallbackReason, FileDecision, MergeTrace, Record};
 use crate::region::{extract_regions, EntityRegion, FileRegion};
-use crate::validate::SemanticWarning;
+use crate::policy::apply_policies;
+use crate::protect::enforce_protection;
+use crate::rerere::RerereCache;
+use crate::validate::{
+    find_duplicate_definitions, may_have_duplicate_definitions, DefinitionSide, DuplicateDefinition, SemanticWarning,
+};
 use crate::reconstruct::reconstruct;
+use crate::xdiff::{self, ConflictFormat, ConflictStyle};
 
 /// Result of a merge operation.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct MergeResult {
     pub content: String,
     pub conflicts: Vec<EntityConflict>,
+    /// For each of `conflicts`, the index of the conflict block in `content`
+    /// it was rendered as, recorded where the markers were emitted. Missing
+    /// or `None` when a conflict has no block of its own, like a line-level
+    /// conflict over the whole file or per-member markers.
+    pub conflict_blocks: Vec<Option<usize>>,
     pub warnings: Vec<SemanticWarning>,
     pub stats: MergeStats,
+    /// Set when the file was resolved as a generated file.
+    pub generated: Option<GeneratedOutcome>,
+    /// Why each decision was taken, when requested with [`WeaveConfig::trace`].
+    pub trace: Option<MergeTrace>,
+    /// Where each line of `content` came from, when requested with
+    /// [`WeaveConfig::provenance`].
+    pub provenance: Option<Provenance>,
 }
 
 impl MergeResult {
     pub fn is_clean(&self) -> bool {
         self.conflicts.is_empty()
     }
+
+    /// The conflict block `conflicts[index]` was rendered as.
+    pub fn conflict_block(&self, index: usize) -> Option<usize> {
+        self.conflict_blocks.get(index).copied().flatten()
+    }
+
+    /// Replace the conflict blocks of the given conflicts, as `(index into
+    /// conflicts, replacement)`, and drop those conflicts. Conflicts without
+    /// a block of their own are left as they are. Returns the indices of the
+    /// conflicts resolved, in order.
+    pub(crate) fn resolve_conflicts(&mut self, resolutions: Vec<(usize, String)>) -> Vec<usize> {
+        let spans = crate::conflict::block_spans(&self.content);
+        let mut by_block = std::collections::BTreeMap::new();
+        for (index, replacement) in resolutions {
+            let Some(block) = self.conflict_block(index) else {
+                continue;
+            };
+            if let Some(Some(span)) = spans.get(block) {
+                by_block.insert(block, (index, span.clone(), replacement));
+            }
+        }
+
+        // Replacements are attributed against the versions of their conflict
+        if let Some(provenance) = &mut self.provenance {
+            for (index, span, replacement) in by_block.values().rev() {
+                let start = self.content[..span.start].matches('\n').count();
+                let end = start + self.content[span.clone()].lines().count();
+                let conflict = &self.conflicts[*index];
+                let version = |content: &Option<String>| content.clone().unwrap_or_default();
+                let origins = attribute(
+                    &version(&conflict.base_content),
+                    &version(&conflict.ours_content),
+                    &version(&conflict.theirs_content),
+                    replacement,
+                );
+                provenance.splice(start..end, origins);
+            }
+        }
+
+        let mut content = String::with_capacity(self.content.len());
+        let mut pos = 0;
+        for (_, span, replacement) in by_block.values() {
+            content.push_str(&self.content[pos..span.start]);
+            content.push_str(replacement);
+            pos = span.end;
+        }
+        content.push_str(&self.content[pos..]);
+        self.content = content;
+
+        // Blocks after a replaced one move up
+        let replaced: Vec<usize> = by_block.keys().copied().collect();
+        let mut resolved: Vec<usize> = by_block.values().map(|(index, ..)| *index).collect();
+        resolved.sort_unstable();
+        let blocks = std::mem::take(&mut self.conflict_blocks);
+        let conflicts = std::mem::take(&mut self.conflicts);
+        for (index, conflict) in conflicts.into_iter().enumerate() {
+            if resolved.binary_search(&index).is_err() {
+                let block = blocks.get(index).copied().flatten();
+                self.conflict_blocks.push(block.map(|b| b - replaced.partition_point(|&r| r < b)));
+                self.conflicts.push(conflict);
+            }
+        }
+        resolved
+    }
 }
 
 /// The resolved content for a single entity after merging.
@@ -47,98 +143,454 @@ pub enum ResolvedEntity {
     Deleted,
 }
 
+/// The default parser registry, created once and shared by every merge
+/// that isn't given one.
+pub fn shared_registry() -> &'static ParserRegistry {
+    static REGISTRY: OnceLock<ParserRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(create_default_registry)
+}
+
 /// Perform entity-level 3-way merge.
 ///
 /// Falls back to line-level merge (via diffy) when:
 /// - No parser matches the file type
 /// - Parser returns 0 entities for non-empty content
 /// - File exceeds 1MB
+/// - The entity merge takes longer than `[merge] timeout_ms` (5s by default)
 pub fn entity_merge(
     base: &str,
     ours: &str,
     theirs: &str,
     file_path: &str,
 ) -> MergeResult {
-    let registry = create_default_registry();
-
-    // Timeout: if entity merge takes > 5 seconds, diffy is likely hitting
-    // pathological input. Fall back to git merge-file which always terminates.
-    let base_owned = base.to_string();
-    let ours_owned = ours.to_string();
-    let theirs_owned = theirs.to_string();
-    let path_owned = file_path.to_string();
-
-    let (tx, rx) = mpsc::channel();
-    std::thread::spawn(move || {
-        let reg = create_default_registry();
-        let result = entity_merge_with_registry(&base_owned, &ours_owned, &theirs_owned, &path_owned, &reg);
-        let _ = tx.send(result);
-    });
+    entity_merge_with_config(base, ours, theirs, file_path, &WeaveConfig::default())
+}
 
-    match rx.recv_timeout(Duration::from_secs(5)) {
-        Ok(result) => result,
-        Err(_) => {
-            // Timed out, fall back to git merge-file
-            let mut stats = MergeStats::default();
-            stats.used_fallback = true;
-            git_merge_file(base, ours, theirs, &mut stats)
+/// Like `entity_merge`, with repository configuration (`.weave/config.toml`) applied.
+pub fn entity_merge_with_config(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    config: &WeaveConfig,
+) -> MergeResult {
+    entity_merge_with_budget(base, ours, theirs, file_path, shared_registry(), config, &file_budget(config))
+}
+
+/// The budget of merging one file under `config`: its `[merge] timeout_ms`,
+/// or none.
+pub fn file_budget(config: &WeaveConfig) -> MergeBudget {
+    // Past the timeout, diffy is likely hitting pathological input: fall
+    // back to git merge-file, which always terminates.
+    config.merge.timeout().map_or_else(MergeBudget::unlimited, MergeBudget::with_timeout)
+}
+
+/// Like `entity_merge`, parsing with `registry`, and caching the extracted
+/// entities in `entity_cache` when given (see [`WeaveConfig::entity_cache`]).
+pub fn entity_merge_with_registry(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    registry: &ParserRegistry,
+    entity_cache: Option<&EntityCache>,
+) -> MergeResult {
+    let context = MergeContext {
+        entity_cache,
+        ..Default::default()
+    };
+    entity_merge_in(base, ours, theirs, file_path, registry, &WeaveConfig::default(), &context)
+}
+
+pub fn entity_merge_with_registry_and_config(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    registry: &ParserRegistry,
+    config: &WeaveConfig,
+) -> MergeResult {
+    entity_merge_with_budget(base, ours, theirs, file_path, registry, config, &MergeBudget::unlimited())
+}
+
+/// Like `entity_merge_with_registry_and_config`, stopping the entity merge
+/// once `budget` runs out (see [`crate::budget`]) and merging line by line
+/// with `git merge-file` instead.
+pub fn entity_merge_with_budget(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    registry: &ParserRegistry,
+    config: &WeaveConfig,
+    budget: &MergeBudget,
+) -> MergeResult {
+    let context = MergeContext {
+        budget: budget.clone(),
+        ..Default::default()
+    };
+    entity_merge_in(base, ours, theirs, file_path, registry, config, &context)
+}
+
+/// How a merge runs, as opposed to how the repository configures it
+/// ([`WeaveConfig`]): its budget, the repository and caches it may use, and
+/// what to record in the result.
+#[derive(Debug, Clone, Default)]
+pub struct MergeContext<'a> {
+    /// Unlimited by default.
+    pub budget: MergeBudget,
+    /// The repository merged in. Conflicts with a resolution recorded there
+    /// are replayed (see [`crate::rerere`]), unless `[rerere] replay = false`.
+    pub repo_root: Option<&'a Path>,
+    /// Where extracted entities are cached across runs (see [`crate::cache`]).
+    pub entity_cache: Option<&'a EntityCache>,
+    /// Record why each decision was taken in [`MergeResult::trace`].
+    pub trace: bool,
+    /// Attribute each merged line to its origin in [`MergeResult::provenance`].
+    pub provenance: bool,
+    /// How line-level conflicts are written: git's default style, labelled
+    /// `ours` and `theirs`, unless set (see [`crate::git::conflict_format`]).
+    pub conflict_format: ConflictFormat,
+}
+
+impl MergeContext<'_> {
+    /// The context a format merger merges a part of the file in (a cell, a
+    /// fenced block, a component's script): the same budget and conflict
+    /// format, without rerere, the cache, the trace or provenance, which
+    /// apply to the file as a whole.
+    pub(crate) fn part(&self) -> MergeContext<'static> {
+        MergeContext {
+            budget: self.budget.clone(),
+            conflict_format: self.conflict_format.clone(),
+            ..Default::default()
         }
     }
 }
 
-pub fn entity_merge_with_registry(
+/// Merge in `context`: the entry point the others delegate to.
+pub fn entity_merge_in(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    registry: &ParserRegistry,
+    config: &WeaveConfig,
+    context: &MergeContext,
+) -> MergeResult {
+    let mut trace = context.trace.then(MergeTrace::default);
+    let mut result = merge_file(base, ours, theirs, file_path, registry, config, context, trace.as_mut());
+    // Merges that don't record origins (line fallbacks, format mergers)
+    // worked on the whole file: attribute against the whole versions
+    if context.provenance && result.provenance.is_none() {
+        result.provenance = Some(Provenance {
+            lines: attribute(base, ours, theirs, &result.content),
+        });
+    }
+
+    // Conflicts resolved before (rebases, repeated merges): replay the recorded resolution
+    if !result.is_clean() && config.rerere.replay {
+        if let Some(repo_root) = context.repo_root {
+            let replayed = RerereCache::open(repo_root).replay(&mut result, file_path, config);
+            if replayed > 0 {
+                trace.as_mut().file(FileDecision::RerereReplayed(replayed));
+            }
+        }
+    }
+
+    // Whatever is left: resolve what the configured policies cover
+    if !result.is_clean() {
+        let resolved = apply_policies(&mut result, file_path, config);
+        if resolved > 0 {
+            trace.as_mut().file(FileDecision::PoliciesApplied(resolved));
+        }
+    }
+
+    // Last, so no resolver above merges a protected entity silently
+    let forced = enforce_protection(
+        &mut result,
+        [base, ours, theirs],
+        file_path,
+        registry,
+        config,
+        context.entity_cache,
+    );
+    if forced > 0 {
+        trace.as_mut().file(FileDecision::ProtectionEnforced(forced));
+    }
+
+    result.trace = trace;
+    result
+}
+
+/// The budget ran out: merge line by line with `git merge-file`.
+fn budget_fallback(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    exhausted: Exhausted,
+    format: &ConflictFormat,
+    mut trace: Option<&mut MergeTrace>,
+) -> MergeResult {
+    trace.file(FileDecision::Fallback(match exhausted {
+        Exhausted::TimedOut => FallbackReason::Timeout,
+        Exhausted::Cancelled => FallbackReason::Cancelled,
+    }));
+    let mut stats = MergeStats {
+        used_fallback: true,
+        ..Default::default()
+    };
+    git_merge_file(base, ours, theirs, &mut stats, format)
+}
+
+/// A format merger finished: keep its result unless the budget ran out
+/// while it ran or the result doesn't parse (see [`syntax_fallback`]).
+fn within_budget(
+    result: MergeResult,
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    context: &MergeContext,
+    trace: Option<&mut MergeTrace>,
+) -> MergeResult {
+    if let Some(exhausted) = context.budget.exhausted() {
+        return budget_fallback(base, ours, theirs, exhausted, &context.conflict_format, trace);
+    }
+    if result.is_clean() {
+        if let Some(fallback) =
+            syntax_fallback(&result.content, base, ours, theirs, file_path, &context.conflict_format, trace)
+        {
+            return fallback;
+        }
+    }
+    result
+}
+
+/// Post-merge syntax validation: output synthesized from both sides must
+/// parse as cleanly as each of them did. A stray brace or a duplicated
+/// closing block leaves ERROR or MISSING nodes even when most entities
+/// still parse. Returns the `git merge-file` result to ship instead, when
+/// `content` has a syntax error neither side had.
+fn syntax_fallback(
+    content: &str,
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    format: &ConflictFormat,
+    mut trace: Option<&mut MergeTrace>,
+) -> Option<MergeResult> {
+    let language = crate::ast::language_for(file_path)?;
+    let line = crate::ast::first_syntax_error(&language, content)?;
+    if crate::ast::first_syntax_error(&language, ours).is_some()
+        || crate::ast::first_syntax_error(&language, theirs).is_some()
+    {
+        return None;
+    }
+    trace.file(FileDecision::Fallback(FallbackReason::SyntaxError { line }));
+    let mut stats = MergeStats {
+        used_fallback: true,
+        ..Default::default()
+    };
+    let mut result = git_merge_file(base, ours, theirs, &mut stats, format);
+    let warning = |kind| crate::validate::SemanticWarning {
+        entity_name: "(file)".to_string(),
+        entity_type: "file".to_string(),
+        file_path: file_path.to_string(),
+        kind,
+        related: vec![],
+    };
+    result
+        .warnings
+        .push(warning(crate::validate::WarningKind::SyntaxErrorFallback { line }));
+    if result.is_clean() && crate::ast::first_syntax_error(&language, &result.content).is_some() {
+        result
+            .warnings
+            .push(warning(crate::validate::WarningKind::ParseFailedAfterMerge));
+    }
+    Some(result)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn merge_file(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    registry: &ParserRegistry,
+    config: &WeaveConfig,
+    context: &MergeContext,
+    mut trace: Option<&mut MergeTrace>,
+) -> MergeResult {
+    // Manifests: reconcile dependencies bumped on both sides before merging,
+    // so the line merge sees both sides agree on the picked version.
+    if config.manifest.semver {
+        if let Some((ours, theirs, bumps)) = reconcile_version_bumps(base, ours, theirs, file_path) {
+            trace.file(FileDecision::VersionBumpsReconciled(bumps.len()));
+            let mut result = merge_entities(base, &ours, &theirs, file_path, registry, config, context, trace);
+            result
+                .warnings
+                .extend(bumps.iter().map(|bump| bump.to_warning(file_path)));
+            return result;
+        }
+    }
+
+    let result = merge_entities(base, ours, theirs, file_path, registry, config, context, trace.as_deref_mut());
+
+    // Markdown: if the merge conflicted, retry with fenced code blocks merged
+    // per language and list items unioned; keep whichever has fewer markers.
+    if !result.is_clean() && is_markdown(file_path) {
+        if let Some(md_result) = merge_markdown(base, ours, theirs, file_path, registry, config, context) {
+            let md_markers = md_result.content.lines().filter(|l| l.starts_with("<<<<<<<")).count();
+            let markers = result.content.lines().filter(|l| l.starts_with("<<<<<<<")).count();
+            trace.file(FileDecision::MarkdownRetry { kept: md_markers < markers });
+            if md_markers < markers {
+                return within_budget(md_result, base, ours, theirs, file_path, context, trace);
+            }
+        }
+    }
+
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+fn merge_entities(
     base: &str,
     ours: &str,
     theirs: &str,
     file_path: &str,
     registry: &ParserRegistry,
+    config: &WeaveConfig,
+    context: &MergeContext,
+    mut trace: Option<&mut MergeTrace>,
 ) -> MergeResult {
     // Fast path: if ours == theirs, no merge needed
     if ours == theirs {
+        trace.file(FileDecision::Identical);
         return MergeResult {
             content: ours.to_string(),
-            conflicts: vec![],
-            warnings: vec![],
-            stats: MergeStats::default(),
+            ..Default::default()
         };
     }
 
     // Fast path: if base == ours, take theirs entirely
     if base == ours {
+        trace.file(FileDecision::OnlyTheirsChanged);
         return MergeResult {
             content: theirs.to_string(),
-            conflicts: vec![],
-            warnings: vec![],
             stats: MergeStats {
                 entities_theirs_only: 1,
                 ..Default::default()
             },
+            ..Default::default()
         };
     }
 
     // Fast path: if base == theirs, take ours entirely
     if base == theirs {
+        trace.file(FileDecision::OnlyOursChanged);
         return MergeResult {
             content: ours.to_string(),
-            conflicts: vec![],
-            warnings: vec![],
             stats: MergeStats {
                 entities_ours_only: 1,
                 ..Default::default()
             },
+            ..Default::default()
         };
     }
 
     // Binary file detection: if any version has null bytes, use git merge-file directly
     if is_binary(base) || is_binary(ours) || is_binary(theirs) {
+        trace.file(FileDecision::Fallback(FallbackReason::Binary));
         let mut stats = MergeStats::default();
         stats.used_fallback = true;
-        return git_merge_file(base, ours, theirs, &mut stats);
+        return git_merge_file(base, ours, theirs, &mut stats, &context.conflict_format);
+    }
+
+    // Generated files, when configured: never merge a generator's output line by line
+    let generated = config.generated.as_ref().and_then(|generated| {
+        resolve_generated(base, ours, theirs, file_path, generated, context.repo_root)
+    });
+    if let Some(result) = generated {
+        trace.file(FileDecision::FormatMerger("generated file"));
+        return result;
+    }
+
+    // Notebooks: merge cell by cell rather than as JSON lines.
+    // Before the size check — outputs with images easily exceed it.
+    if is_notebook(file_path) {
+        if let Some(result) = merge_notebook(base, ours, theirs, file_path, registry, &config.notebook, context) {
+            trace.file(FileDecision::FormatMerger("notebook"));
+            return within_budget(result, base, ours, theirs, file_path, context, trace);
+        }
+        trace.file(FileDecision::FormatUnparsed("notebook"));
     }
 
     // Large file fallback
     if base.len() > 1_000_000 || ours.len() > 1_000_000 || theirs.len() > 1_000_000 {
-        return line_level_fallback(base, ours, theirs, file_path);
+        trace.file(FileDecision::Fallback(FallbackReason::LargeFile));
+        return line_level_fallback(base, ours, theirs, file_path, context);
+    }
+
+    // Vue/Svelte: split into <script>/<style>/<template> and merge each with
+    // the right strategy, instead of the line fallback below.
+    if is_single_file_component(file_path) {
+        if let Some(result) = merge_single_file_component(base, ours, theirs, file_path, registry, config, context) {
+            trace.file(FileDecision::FormatMerger("component"));
+            return within_budget(result, base, ours, theirs, file_path, context, trace);
+        }
+        trace.file(FileDecision::FormatUnparsed("component"));
+    }
+
+    // Stylesheets: rules and declarations as entities
+    if is_stylesheet(file_path) {
+        if let Some(result) = merge_stylesheet(base, ours, theirs, file_path) {
+            trace.file(FileDecision::FormatMerger("stylesheet"));
+            return within_budget(result, base, ours, theirs, file_path, context, trace);
+        }
+        trace.file(FileDecision::FormatUnparsed("stylesheet"));
+    }
+
+    // Schemas: protobuf messages, GraphQL types and SQL tables as entities,
+    // with their fields/columns merged as keyed members
+    if is_protobuf(file_path) {
+        if let Some(result) = merge_protobuf(base, ours, theirs, file_path) {
+            trace.file(FileDecision::FormatMerger("protobuf"));
+            return within_budget(result, base, ours, theirs, file_path, context, trace);
+        }
+        trace.file(FileDecision::FormatUnparsed("protobuf"));
+    }
+    if is_graphql(file_path) {
+        if let Some(result) = merge_graphql(base, ours, theirs) {
+            trace.file(FileDecision::FormatMerger("GraphQL"));
+            return within_budget(result, base, ours, theirs, file_path, context, trace);
+        }
+        trace.file(FileDecision::FormatUnparsed("GraphQL"));
+    }
+    if is_sql(file_path) {
+        if let Some(result) = merge_sql(base, ours, theirs, file_path, context) {
+            trace.file(FileDecision::FormatMerger("SQL"));
+            return within_budget(result, base, ours, theirs, file_path, context, trace);
+        }
+        trace.file(FileDecision::FormatUnparsed("SQL"));
+    }
+
+    // XML/HTML: elements matched by tag and identifying attribute
+    if is_markup(file_path) {
+        if let Some(result) = merge_markup(base, ours, theirs, file_path, &config.markup, context) {
+            trace.file(FileDecision::FormatMerger("markup"));
+            return within_budget(result, base, ours, theirs, file_path, context, trace);
+        }
+        trace.file(FileDecision::FormatUnparsed("markup"));
+    }
+
+    // Makefile targets, justfile recipes and Dockerfile stages as entities
+    if is_build_file(file_path) {
+        if let Some(result) = merge_build_file(base, ours, theirs, file_path, context) {
+            trace.file(FileDecision::FormatMerger("build file"));
+            return within_budget(result, base, ours, theirs, file_path, context, trace);
+        }
+        trace.file(FileDecision::FormatUnparsed("build file"));
     }
 
     // If the file type isn't natively supported, the registry returns the fallback
@@ -148,33 +600,51 @@ pub fn entity_merge_with_registry(
     // merge entirely for fallback-plugin files and go straight to line-level merge.
     let plugin = match registry.get_plugin(file_path) {
         Some(p) if p.id() != "fallback" => p,
-        _ => return line_level_fallback(base, ours, theirs, file_path),
+        _ => {
+            trace.file(FileDecision::Fallback(FallbackReason::NoParser));
+            return line_level_fallback(base, ours, theirs, file_path, context);
+        }
     };
+    trace.file(FileDecision::EntityMerge {
+        plugin: plugin.id().to_string(),
+    });
 
     // Extract entities from all three versions. Keep unfiltered lists for inner merge
     // (child entities provide tree-sitter-based method decomposition for classes).
-    let base_all = plugin.extract_entities(base, file_path);
-    let ours_all = plugin.extract_entities(ours, file_path);
-    let theirs_all = plugin.extract_entities(theirs, file_path);
+    // Through the on-disk cache when the repo has one: the driver runs once per file
+    let extract = |content: &str| {
+        cached_entities(context.entity_cache, plugin.id(), file_path, content, || {
+            plugin.extract_entities(content, file_path)
+        })
+    };
+    let base_all = extract(base);
+    let ours_all = extract(ours);
+    let theirs_all = extract(theirs);
 
     // Filter out nested entities for top-level matching and region extraction
     let base_entities = filter_nested_entities(base_all.clone());
     let ours_entities = filter_nested_entities(ours_all.clone());
     let theirs_entities = filter_nested_entities(theirs_all.clone());
+    if let Some(exhausted) = context.budget.exhausted() {
+        return budget_fallback(base, ours, theirs, exhausted, &context.conflict_format, trace);
+    }
 
     // Fallback if parser returns nothing for non-empty content
     if base_entities.is_empty() && !base.trim().is_empty() {
-        return line_level_fallback(base, ours, theirs, file_path);
+        trace.file(FileDecision::Fallback(FallbackReason::NoEntities));
+        return line_level_fallback(base, ours, theirs, file_path, context);
     }
     // Allow empty entities if content is actually empty
     if ours_entities.is_empty() && !ours.trim().is_empty() && theirs_entities.is_empty() && !theirs.trim().is_empty() {
-        return line_level_fallback(base, ours, theirs, file_path);
+        trace.file(FileDecision::Fallback(FallbackReason::NoEntities));
+        return line_level_fallback(base, ours, theirs, file_path, context);
     }
 
     // Fallback if too many duplicate entity names. Entity matching is O(n*m) on
     // same-named entities which can hang on files with many `var app = ...` etc.
     if has_excessive_duplicates(&base_entities) || has_excessive_duplicates(&ours_entities) || has_excessive_duplicates(&theirs_entities) {
-        return line_level_fallback(base, ours, theirs, file_path);
+        trace.file(FileDecision::Fallback(FallbackReason::ExcessiveDuplicates));
+        return line_level_fallback(base, ours, theirs, file_path, context);
     }
 
     // Extract regions from all three
@@ -191,6 +661,9 @@ pub fn entity_merge_with_registry(
     // Match entities: base↔ours and base↔theirs
     let ours_changes = match_entities(&base_entities, &ours_entities, file_path, None, None, None);
     let theirs_changes = match_entities(&base_entities, &theirs_entities, file_path, None, None, None);
+    if let Some(exhausted) = context.budget.exhausted() {
+        return budget_fallback(base, ours, theirs, exhausted, &context.conflict_format, trace);
+    }
 
     // Build lookup maps
     let base_entity_map: HashMap<&str, &SemanticEntity> =
@@ -224,6 +697,28 @@ pub fn entity_merge_with_registry(
         .iter()
         .map(|(new, old)| (old.clone(), new.clone()))
         .collect();
+    let traced_renames = if trace.is_some() {
+        vec![
+            (true, &ours_rename_to_base, &ours_entity_map),
+            (false, &theirs_rename_to_base, &theirs_entity_map),
+        ]
+    } else {
+        vec![]
+    };
+    for (in_ours, renames, entity_map) in traced_renames {
+        let mut renames: Vec<(&String, &String)> = renames.iter().collect();
+        renames.sort();
+        for (new_id, old_id) in renames {
+            let name = |map: &HashMap<&str, &SemanticEntity>, id: &str| {
+                map.get(id).map(|e| e.name.clone()).unwrap_or_else(|| id.to_string())
+            };
+            trace.file(FileDecision::Renamed {
+                in_ours,
+                from: name(&base_entity_map, old_id),
+                to: name(entity_map, new_id),
+            });
+        }
+    }
 
     // Collect all entity IDs across all versions
     let mut all_entity_ids: Vec<String> = Vec::new();
@@ -266,6 +761,8 @@ pub fn entity_merge_with_registry(
     let mut stats = MergeStats::default();
     let mut conflicts: Vec<EntityConflict> = Vec::new();
     let mut resolved_entities: HashMap<String, ResolvedEntity> = HashMap::new();
+    // Entity id → index in `conflicts`, under every id reconstruct may emit it as
+    let mut conflict_ids: HashMap<String, usize> = HashMap::new();
 
     // Detect rename/rename conflicts: same base entity renamed differently in both branches.
     // These must be flagged before the entity resolution loop, which would otherwise silently
@@ -279,7 +776,24 @@ pub fn entity_merge_with_registry(
         }
     }
 
+    let mut resolver = EntityResolver {
+        file_path,
+        budget: &context.budget,
+        base_region_content: &base_region_content,
+        ours_region_content: &ours_region_content,
+        theirs_region_content: &theirs_region_content,
+        base_all: &base_all,
+        ours_all: &ours_all,
+        theirs_all: &theirs_all,
+        stats: &mut stats,
+        trace: trace.as_deref_mut(),
+        origins: context.provenance.then(HashMap::new),
+    };
+    let mut entity_origins: HashMap<String, Vec<LineOrigin>> = HashMap::new();
     for entity_id in &all_entity_ids {
+        if let Err(exhausted) = resolver.budget.check() {
+            return budget_fallback(base, ours, theirs, exhausted, &context.conflict_format, resolver.trace);
+        }
         // Handle rename/rename conflicts: both branches renamed this base entity differently
         if rename_conflict_ids.contains(entity_id) {
             let ours_new_id = &base_to_ours_rename[entity_id];
@@ -295,7 +809,8 @@ pub fn entity_merge_with_registry(
             let ours_rc = ours_entity.map(|e| ours_region_content.get(&e.id).cloned().unwrap_or_else(|| e.content.clone()));
             let theirs_rc = theirs_entity.map(|e| theirs_region_content.get(&e.id).cloned().unwrap_or_else(|| e.content.clone()));
 
-            stats.entities_conflicted += 1;
+            resolver.stats.entities_conflicted += 1;
+            resolver.trace.entity(base_name, base_entity.map(|e| e.entity_type.as_str()).unwrap_or_default(), EntityPath::RenameRename);
             let conflict = EntityConflict {
                 entity_name: base_name.to_string(),
                 entity_type: base_entity.map(|e| e.entity_type.clone()).unwrap_or_default(),
@@ -309,6 +824,8 @@ pub fn entity_merge_with_registry(
                 theirs_content: theirs_rc,
                 base_content: base_rc,
             };
+            conflict_ids.insert(entity_id.clone(), conflicts.len());
+            conflict_ids.insert(ours_new_id.clone(), conflicts.len());
             conflicts.push(conflict.clone());
             let resolution = ResolvedEntity::Conflict(conflict);
             resolved_entities.insert(entity_id.clone(), resolution.clone());
@@ -326,27 +843,44 @@ pub fn entity_merge_with_registry(
         let in_ours = ours_entity_map.get(ours_id).or_else(|| ours_entity_map.get(entity_id.as_str()));
         let in_theirs = theirs_entity_map.get(theirs_id).or_else(|| theirs_entity_map.get(entity_id.as_str()));
 
-        let ours_change = ours_change_map.get(entity_id);
-        let theirs_change = theirs_change_map.get(entity_id);
-
-        let resolution = resolve_entity(
-            entity_id,
-            in_base,
-            in_ours,
-            in_theirs,
-            ours_change,
-            theirs_change,
-            &base_region_content,
-            &ours_region_content,
-            &theirs_region_content,
-            &base_all,
-            &ours_all,
-            &theirs_all,
-            &mut stats,
-        );
+        let resolution = match resolve_entity(&mut resolver, in_base, in_ours, in_theirs) {
+            Ok(resolution) => resolution,
+            Err(exhausted) => return budget_fallback(base, ours, theirs, exhausted, &context.conflict_format, resolver.trace),
+        };
+
+        // What reconstruct emits for the entity, attributed against its versions
+        let emitted = match &resolution {
+            ResolvedEntity::Clean(region) => Some(&region.content),
+            ResolvedEntity::ScopedConflict { content, .. } => Some(content),
+            _ => None,
+        };
+        if let (Some(recorded), Some(emitted)) = (resolver.origins.as_mut(), emitted) {
+            let lines = in_ours.and_then(|e| recorded.remove(&e.id)).unwrap_or_else(|| {
+                let version = |entity: Option<&&SemanticEntity>, contents: &HashMap<String, String>| {
+                    entity.map_or(String::new(), |e| contents.get(&e.id).unwrap_or(&e.content).clone())
+                };
+                attribute(
+                    &version(in_base, &base_region_content),
+                    &version(in_ours, &ours_region_content),
+                    &version(in_theirs, &theirs_region_content),
+                    emitted,
+                )
+            });
+            let renamed = [base_to_ours_rename.get(entity_id.as_str()), base_to_theirs_rename.get(entity_id.as_str())];
+            for id in renamed.into_iter().flatten() {
+                entity_origins.insert(id.clone(), lines.clone());
+            }
+            entity_origins.insert(entity_id.clone(), lines);
+        }
 
         match &resolution {
-            ResolvedEntity::Conflict(ref c) => conflicts.push(c.clone()),
+            ResolvedEntity::Conflict(ref c) => {
+                let renamed = [base_to_ours_rename.get(entity_id.as_str()), base_to_theirs_rename.get(entity_id.as_str())];
+                for id in std::iter::once(entity_id).chain(renamed.into_iter().flatten()) {
+                    conflict_ids.insert(id.clone(), conflicts.len());
+                }
+                conflicts.push(c.clone())
+            }
             ResolvedEntity::ScopedConflict { conflict, .. } => conflicts.push(conflict.clone()),
             _ => {}
         }
@@ -362,26 +896,55 @@ pub fn entity_merge_with_registry(
     }
 
     // Merge interstitial regions
-    let merged_interstitials = merge_interstitials(&base_regions, &ours_regions, &theirs_regions);
+    let mut interstitial_origins = HashMap::new();
+    let (merged_interstitials, interstitials_both_changed) = match merge_interstitials(
+        &base_regions,
+        &ours_regions,
+        &theirs_regions,
+        &context.budget,
+        &context.conflict_format,
+        context.provenance.then_some(&mut interstitial_origins),
+        trace.as_deref_mut(),
+    ) {
+        Ok(merged) => merged,
+        Err(exhausted) => return budget_fallback(base, ours, theirs, exhausted, &context.conflict_format, trace),
+    };
 
     // Reconstruct the file
-    let content = reconstruct(
+    let recorded = RecordedOrigins {
+        entities: entity_origins,
+        interstitials: interstitial_origins,
+    };
+    let (emitted, blocks) = reconstruct(
         &ours_regions,
         &theirs_regions,
         &theirs_entities,
         &ours_entity_map,
         &resolved_entities,
         &merged_interstitials,
+        context.provenance.then_some(&recorded),
     );
+    let mut conflict_blocks = vec![None; conflicts.len()];
+    for (id, block) in blocks {
+        if let Some(&index) = conflict_ids.get(&id) {
+            conflict_blocks[index] = Some(block);
+        }
+    }
 
     // Post-merge cleanup: remove duplicate lines and normalize blank lines
-    let content = post_merge_cleanup(&content);
+    let (content, kept) = post_merge_cleanup_kept(&emitted.text);
+    let mut origins: Option<Vec<LineOrigin>> = emitted
+        .origins
+        .map(|origins| kept.iter().map(|&i| origins.get(i).copied().unwrap_or(LineOrigin::Weave)).collect());
 
     // Post-merge parse validation: verify the merged result still parses correctly
     // (MergeBot-inspired safety check — catch syntactically broken merges)
+    // The merged file is parsed at most once, and only when one of the
+    // checks below needs it
+    let mut merged_entities: Option<Vec<SemanticEntity>> = None;
     let mut warnings = vec![];
     if conflicts.is_empty() && stats.entities_both_changed_merged > 0 {
-        let merged_entities = plugin.extract_entities(&content, file_path);
+        let merged_entities = merged_entities.insert(plugin.extract_entities(&content, file_path));
         if merged_entities.is_empty() && !content.trim().is_empty() {
             warnings.push(crate::validate::SemanticWarning {
                 entity_name: "(file)".to_string(),
@@ -393,21 +956,71 @@ pub fn entity_merge_with_registry(
         }
     }
 
+    // Post-merge syntax validation, when something was synthesized from
+    // both sides: entities merged line by line, by member or by decorator,
+    // and interstitials changed on both sides. Anything else is a whole
+    // entity or region from one side, which parsed there.
+    let synthesized = stats.resolved_via_diffy + stats.resolved_via_inner_merge + interstitials_both_changed > 0;
+    if conflicts.is_empty() && synthesized {
+        if let Some(fallback) =
+            syntax_fallback(&content, base, ours, theirs, file_path, &context.conflict_format, trace.as_deref_mut())
+        {
+            return fallback;
+        }
+    }
+
+    // Duplicate definitions: both sides added the same name to one scope,
+    // which merges cleanly but doesn't compile. Only on clean output, as both
+    // versions inside a conflict block would count as duplicates.
+    let mut content = content;
+    let mut forced_markers = 0;
+    let duplicates = if conflicts.is_empty()
+        && !content.contains("<<<<<<<")
+        && may_have_duplicate_definitions(&ours_all, &theirs_all)
+    {
+        let merged_entities = merged_entities.get_or_insert_with(|| plugin.extract_entities(&content, file_path));
+        find_duplicate_definitions(merged_entities, &ours_all, &theirs_all)
+    } else {
+        vec![]
+    };
+    let duplicates = if config.validate.duplicates == DuplicatePolicy::Conflict && !duplicates.is_empty() {
+        let (with_conflicts, duplicate_conflicts, duplicate_blocks, remaining) =
+            duplicates_to_conflicts(&content, file_path, duplicates, origins.as_mut());
+        content = with_conflicts;
+        stats.entities_conflicted += duplicate_conflicts.len();
+        forced_markers = duplicate_conflicts.len();
+        conflicts.extend(duplicate_conflicts);
+        conflict_blocks.extend(duplicate_blocks);
+        remaining
+    } else {
+        duplicates
+    };
+    warnings.extend(duplicates.iter().map(|d| d.to_warning(file_path)));
+
     let entity_result = MergeResult {
         content,
         conflicts,
+        conflict_blocks,
         warnings,
         stats: stats.clone(),
+        provenance: origins.map(|lines| Provenance { lines }),
+        ..Default::default()
     };
 
     // Floor: never produce more conflict markers than git merge-file.
     // Entity merge can split one git conflict into multiple per-entity conflicts,
     // or interstitial merges can produce conflicts not tracked in the conflicts vec.
-    let entity_markers = entity_result.content.lines().filter(|l| l.starts_with("<<<<<<<")).count();
+    // Conflicts forced on duplicate definitions are deliberate (git would
+    // merge them silently), so only the others count against git's.
+    let entity_markers = entity_result.content.lines().filter(|l| l.starts_with("<<<<<<<")).count() - forced_markers;
     if entity_markers > 0 {
-        let git_result = git_merge_file(base, ours, theirs, &mut stats);
+        let git_result = git_merge_file(base, ours, theirs, &mut stats, &context.conflict_format);
         let git_markers = git_result.content.lines().filter(|l| l.starts_with("<<<<<<<")).count();
         if entity_markers > git_markers {
+            trace.file(FileDecision::Fallback(FallbackReason::MoreMarkersThanGit {
+                entity: entity_markers,
+                git: git_markers,
+            }));
             return git_result;
         }
     }
@@ -415,27 +1028,142 @@ pub fn entity_merge_with_registry(
     entity_result
 }
 
+/// Turn duplicate definitions into conflicts: the first definition is
+/// replaced by markers holding both, the second is dropped. Duplicates
+/// overlapping one already converted are returned as they are.
+///
+/// `content` must hold no conflict blocks yet; the conflicts come with the
+/// block each was rendered as. `origins`, the content's line origins, are
+/// updated to match.
+fn duplicates_to_conflicts(
+    content: &str,
+    file_path: &str,
+    duplicates: Vec<DuplicateDefinition>,
+    origins: Option<&mut Vec<LineOrigin>>,
+) -> (String, Vec<EntityConflict>, Vec<Option<usize>>, Vec<DuplicateDefinition>) {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut used = vec![false; lines.len() + 1];
+    // start line → (end line, replacement and the conflict it renders)
+    let mut replacements: HashMap<usize, (usize, Option<(String, usize)>)> = HashMap::new();
+    let mut conflicts = Vec::new();
+    let mut remaining = Vec::new();
+
+    for duplicate in duplicates {
+        let first = (duplicate.first.start_line, duplicate.first.end_line);
+        let second = (duplicate.second.start_line, duplicate.second.end_line);
+        let free = [first, second]
+            .iter()
+            .all(|&(start, end)| start >= 1 && start <= end && end <= lines.len() && !used[start..=end].contains(&true));
+        if !free {
+            remaining.push(duplicate);
+            continue;
+        }
+        for (start, end) in [first, second] {
+            used[start..=end].fill(true);
+        }
+
+        let text = |(start, end): (usize, usize)| lines[start - 1..end].join("\n") + "\n";
+        let second_is_ours =
+            duplicate.first.origin == DefinitionSide::Theirs || duplicate.second.origin == DefinitionSide::Ours;
+        let (ours_text, theirs_text) = if second_is_ours {
+            (text(second), text(first))
+        } else {
+            (text(first), text(second))
+        };
+        let conflict = EntityConflict {
+            entity_name: duplicate.first.name.clone(),
+            entity_type: duplicate.first.entity_type.clone(),
+            kind: ConflictKind::BothAdded,
+            complexity: classify_conflict_in(file_path, None, Some(&ours_text), Some(&theirs_text)),
+            ours_content: Some(ours_text),
+            theirs_content: Some(theirs_text),
+            base_content: None,
+        };
+        replacements.insert(first.0, (first.1, Some((conflict.to_conflict_markers(), conflicts.len()))));
+        replacements.insert(second.0, (second.1, None));
+        conflicts.push(conflict);
+    }
+
+    let mut out = String::with_capacity(content.len());
+    let mut out_origins = Vec::with_capacity(lines.len());
+    let mut blocks = vec![None; conflicts.len()];
+    let mut block = 0;
+    let mut line = 1;
+    while line <= lines.len() {
+        match replacements.get(&line) {
+            Some((end, replacement)) => {
+                if let Some((markers, index)) = replacement {
+                    out.push_str(markers);
+                    out_origins.extend(markers.lines().map(|_| LineOrigin::Conflict));
+                    blocks[*index] = Some(block);
+                    block += 1;
+                }
+                line = end + 1;
+            }
+            None => {
+                out.push_str(lines[line - 1]);
+                out.push('\n');
+                out_origins.push(origins.as_ref().and_then(|o| o.get(line - 1).copied()).unwrap_or(LineOrigin::Weave));
+                line += 1;
+            }
+        }
+    }
+    if !content.ends_with('\n') && out.ends_with('\n') {
+        out.pop();
+    }
+    if let Some(origins) = origins {
+        *origins = out_origins;
+    }
+    (out, conflicts, blocks, remaining)
+}
+
+/// What resolving an entity draws on besides the entity itself: the
+/// versions' region contents, their unfiltered entities (for the inner merge),
+/// and the merge's budget, stats, trace and recorded origins.
+struct EntityResolver<'a> {
+    file_path: &'a str,
+    budget: &'a MergeBudget,
+    base_region_content: &'a HashMap<String, String>,
+    ours_region_content: &'a HashMap<String, String>,
+    theirs_region_content: &'a HashMap<String, String>,
+    base_all: &'a [SemanticEntity],
+    ours_all: &'a [SemanticEntity],
+    theirs_all: &'a [SemanticEntity],
+    stats: &'a mut MergeStats,
+    trace: Option<&'a mut MergeTrace>,
+    /// Line origins of resolutions only the resolver can attribute, by
+    /// ours' entity id; `None` when provenance isn't tracked.
+    origins: Option<HashMap<String, Vec<LineOrigin>>>,
+}
+
+/// Resolve one entity. Fails if the budget runs out before a diffy merge or
+/// during the inner merge.
 fn resolve_entity(
-    _entity_id: &str,
+    resolver: &mut EntityResolver,
     in_base: Option<&&SemanticEntity>,
     in_ours: Option<&&SemanticEntity>,
     in_theirs: Option<&&SemanticEntity>,
-    _ours_change: Option<&ChangeType>,
-    _theirs_change: Option<&ChangeType>,
-    base_region_content: &HashMap<String, String>,
-    ours_region_content: &HashMap<String, String>,
-    theirs_region_content: &HashMap<String, String>,
-    base_all: &[SemanticEntity],
-    ours_all: &[SemanticEntity],
-    theirs_all: &[SemanticEntity],
-    stats: &mut MergeStats,
-) -> ResolvedEntity {
+) -> Result<ResolvedEntity, Exhausted> {
+    let EntityResolver {
+        file_path,
+        budget,
+        base_region_content,
+        ours_region_content,
+        theirs_region_content,
+        base_all,
+        ours_all,
+        theirs_all,
+        ref mut stats,
+        ref mut trace,
+        ref mut origins,
+    } = *resolver;
+
     // Helper: get region content (from file lines) for an entity, falling back to entity.content
     let region_content = |entity: &SemanticEntity, map: &HashMap<String, String>| -> String {
         map.get(&entity.id).cloned().unwrap_or_else(|| entity.content.clone())
     };
 
-    match (in_base, in_ours, in_theirs) {
+    Ok(match (in_base, in_ours, in_theirs) {
         // Entity exists in all three versions
         (Some(base), Some(ours), Some(theirs)) => {
             // Check modification status via structural hash AND region content.
@@ -454,16 +1182,19 @@ fn resolve_entity(
                 (false, false) => {
                     // Neither changed
                     stats.entities_unchanged += 1;
+                    trace.entity(&ours.name, &ours.entity_type, EntityPath::Unchanged);
                     ResolvedEntity::Clean(entity_to_region_with_content(ours, &region_content(ours, ours_region_content)))
                 }
                 (true, false) => {
                     // Only ours changed
                     stats.entities_ours_only += 1;
+                    trace.entity(&ours.name, &ours.entity_type, EntityPath::OursOnly);
                     ResolvedEntity::Clean(entity_to_region_with_content(ours, &region_content(ours, ours_region_content)))
                 }
                 (false, true) => {
                     // Only theirs changed
                     stats.entities_theirs_only += 1;
+                    trace.entity(&theirs.name, &theirs.entity_type, EntityPath::TheirsOnly);
                     ResolvedEntity::Clean(entity_to_region_with_content(theirs, &region_content(theirs, theirs_region_content)))
                 }
                 (true, true) => {
@@ -471,6 +1202,7 @@ fn resolve_entity(
                     if ours.content_hash == theirs.content_hash {
                         // Same change in both — take ours
                         stats.entities_both_changed_merged += 1;
+                        trace.entity(&ours.name, &ours.entity_type, EntityPath::SameChange);
                         ResolvedEntity::Clean(entity_to_region_with_content(ours, &region_content(ours, ours_region_content)))
                     } else {
                         // Try diffy 3-way merge on region content (preserves full syntax)
@@ -484,17 +1216,21 @@ fn resolve_entity(
                         // another makes semantic changes.
                         if is_whitespace_only_diff(&base_rc, &ours_rc) {
                             stats.entities_theirs_only += 1;
-                            return ResolvedEntity::Clean(entity_to_region_with_content(theirs, &theirs_rc));
+                            trace.entity(&theirs.name, &theirs.entity_type, EntityPath::OursReformatted);
+                            return Ok(ResolvedEntity::Clean(entity_to_region_with_content(theirs, &theirs_rc)));
                         }
                         if is_whitespace_only_diff(&base_rc, &theirs_rc) {
                             stats.entities_ours_only += 1;
-                            return ResolvedEntity::Clean(entity_to_region_with_content(ours, &ours_rc));
+                            trace.entity(&ours.name, &ours.entity_type, EntityPath::TheirsReformatted);
+                            return Ok(ResolvedEntity::Clean(entity_to_region_with_content(ours, &ours_rc)));
                         }
 
+                        budget.check()?;
                         match diffy_merge(&base_rc, &ours_rc, &theirs_rc) {
                             Some(merged) => {
                                 stats.entities_both_changed_merged += 1;
                                 stats.resolved_via_diffy += 1;
+                                trace.entity(&ours.name, &ours.entity_type, EntityPath::Diffy);
                                 ResolvedEntity::Clean(EntityRegion {
                                     entity_id: ours.id.clone(),
                                     entity_name: ours.name.clone(),
@@ -510,14 +1246,19 @@ fn resolve_entity(
                                 if let Some(merged) = try_decorator_aware_merge(&base_rc, &ours_rc, &theirs_rc) {
                                     stats.entities_both_changed_merged += 1;
                                     stats.resolved_via_diffy += 1;
-                                    return ResolvedEntity::Clean(EntityRegion {
+                                    trace.entity(&ours.name, &ours.entity_type, EntityPath::DecoratorMerge);
+                                    if let Some(origins) = origins {
+                                        let lines = attribute_unordered(&base_rc, &ours_rc, &theirs_rc, &merged);
+                                        origins.insert(ours.id.clone(), lines);
+                                    }
+                                    return Ok(ResolvedEntity::Clean(EntityRegion {
                                         entity_id: ours.id.clone(),
                                         entity_name: ours.name.clone(),
                                         entity_type: ours.entity_type.clone(),
                                         content: merged,
                                         start_line: ours.start_line,
                                         end_line: ours.end_line,
-                                    });
+                                    }));
                                 }
 
                                 // Strategy 2: inner entity merge for container types
@@ -533,19 +1274,24 @@ fn resolve_entity(
                                     let base_start = in_base.map(|b| b.start_line).unwrap_or(1);
                                     let ours_start = ours.start_line;
                                     let theirs_start = in_theirs.map(|t| t.start_line).unwrap_or(1);
-                                    if let Some(inner) = try_inner_entity_merge(
+                                    let inner = try_inner_entity_merge(
                                         &base_rc, &ours_rc, &theirs_rc,
                                         &base_children, &ours_children, &theirs_children,
                                         base_start, ours_start, theirs_start,
-                                    ) {
+                                        budget,
+                                    );
+                                    // The inner merge gives up when the budget runs out
+                                    budget.check()?;
+                                    if let Some(inner) = inner {
                                         if inner.has_conflicts {
                                             // Inner merge produced per-member conflicts:
                                             // content has scoped markers for just the conflicted
                                             // members; clean members are merged normally.
                                             stats.entities_conflicted += 1;
                                             stats.resolved_via_inner_merge += 1;
-                                            let complexity = classify_conflict(Some(&base_rc), Some(&ours_rc), Some(&theirs_rc));
-                                            return ResolvedEntity::ScopedConflict {
+                                            trace.entity(&ours.name, &ours.entity_type, EntityPath::InnerMergeConflict);
+                                            let complexity = classify_conflict_in(file_path, Some(&base_rc), Some(&ours_rc), Some(&theirs_rc));
+                                            return Ok(ResolvedEntity::ScopedConflict {
                                                 content: inner.content,
                                                 conflict: EntityConflict {
                                                     entity_name: ours.name.clone(),
@@ -556,23 +1302,25 @@ fn resolve_entity(
                                                     theirs_content: Some(theirs_rc),
                                                     base_content: Some(base_rc),
                                                 },
-                                            };
+                                            });
                                         } else {
                                             stats.entities_both_changed_merged += 1;
                                             stats.resolved_via_inner_merge += 1;
-                                            return ResolvedEntity::Clean(EntityRegion {
+                                            trace.entity(&ours.name, &ours.entity_type, EntityPath::InnerMerge);
+                                            return Ok(ResolvedEntity::Clean(EntityRegion {
                                                 entity_id: ours.id.clone(),
                                                 entity_name: ours.name.clone(),
                                                 entity_type: ours.entity_type.clone(),
                                                 content: inner.content,
                                                 start_line: ours.start_line,
                                                 end_line: ours.end_line,
-                                            });
+                                            }));
                                         }
                                     }
                                 }
                                 stats.entities_conflicted += 1;
-                                let complexity = classify_conflict(Some(&base_rc), Some(&ours_rc), Some(&theirs_rc));
+                                trace.entity(&ours.name, &ours.entity_type, EntityPath::Conflict);
+                                let complexity = classify_conflict_in(file_path, Some(&base_rc), Some(&ours_rc), Some(&theirs_rc));
                                 ResolvedEntity::Conflict(EntityConflict {
                                     entity_name: ours.name.clone(),
                                     entity_type: ours.entity_type.clone(),
@@ -595,9 +1343,10 @@ fn resolve_entity(
             if ours_modified {
                 // Modify/delete conflict
                 stats.entities_conflicted += 1;
+                trace.entity(&ours.name, &ours.entity_type, EntityPath::ModifyDelete);
                 let ours_rc = region_content(ours, ours_region_content);
                 let base_rc = region_content(_base, base_region_content);
-                let complexity = classify_conflict(Some(&base_rc), Some(&ours_rc), None);
+                let complexity = classify_conflict_in(file_path, Some(&base_rc), Some(&ours_rc), None);
                 ResolvedEntity::Conflict(EntityConflict {
                     entity_name: ours.name.clone(),
                     entity_type: ours.entity_type.clone(),
@@ -612,6 +1361,7 @@ fn resolve_entity(
             } else {
                 // Theirs deleted, ours unchanged → accept deletion
                 stats.entities_deleted += 1;
+                trace.entity(&ours.name, &ours.entity_type, EntityPath::Deleted);
                 ResolvedEntity::Deleted
             }
         }
@@ -622,9 +1372,10 @@ fn resolve_entity(
             if theirs_modified {
                 // Modify/delete conflict
                 stats.entities_conflicted += 1;
+                trace.entity(&theirs.name, &theirs.entity_type, EntityPath::ModifyDelete);
                 let theirs_rc = region_content(theirs, theirs_region_content);
                 let base_rc = region_content(_base, base_region_content);
-                let complexity = classify_conflict(Some(&base_rc), None, Some(&theirs_rc));
+                let complexity = classify_conflict_in(file_path, Some(&base_rc), None, Some(&theirs_rc));
                 ResolvedEntity::Conflict(EntityConflict {
                     entity_name: theirs.name.clone(),
                     entity_type: theirs.entity_type.clone(),
@@ -639,6 +1390,7 @@ fn resolve_entity(
             } else {
                 // Ours deleted, theirs unchanged → accept deletion
                 stats.entities_deleted += 1;
+                trace.entity(&theirs.name, &theirs.entity_type, EntityPath::Deleted);
                 ResolvedEntity::Deleted
             }
         }
@@ -646,12 +1398,14 @@ fn resolve_entity(
         // Entity only in ours (added by ours)
         (None, Some(ours), None) => {
             stats.entities_added_ours += 1;
+            trace.entity(&ours.name, &ours.entity_type, EntityPath::AddedOurs);
             ResolvedEntity::Clean(entity_to_region_with_content(ours, &region_content(ours, ours_region_content)))
         }
 
         // Entity only in theirs (added by theirs)
         (None, None, Some(theirs)) => {
             stats.entities_added_theirs += 1;
+            trace.entity(&theirs.name, &theirs.entity_type, EntityPath::AddedTheirs);
             ResolvedEntity::Clean(entity_to_region_with_content(theirs, &region_content(theirs, theirs_region_content)))
         }
 
@@ -660,13 +1414,15 @@ fn resolve_entity(
             if ours.content_hash == theirs.content_hash {
                 // Same content added by both → take ours
                 stats.entities_added_ours += 1;
+                trace.entity(&ours.name, &ours.entity_type, EntityPath::BothAddedSame);
                 ResolvedEntity::Clean(entity_to_region_with_content(ours, &region_content(ours, ours_region_content)))
             } else {
                 // Different content → conflict
                 stats.entities_conflicted += 1;
+                trace.entity(&ours.name, &ours.entity_type, EntityPath::BothAddedConflict);
                 let ours_rc = region_content(ours, ours_region_content);
                 let theirs_rc = region_content(theirs, theirs_region_content);
-                let complexity = classify_conflict(None, Some(&ours_rc), Some(&theirs_rc));
+                let complexity = classify_conflict_in(file_path, None, Some(&ours_rc), Some(&theirs_rc));
                 ResolvedEntity::Conflict(EntityConflict {
                     entity_name: ours.name.clone(),
                     entity_type: ours.entity_type.clone(),
@@ -680,14 +1436,15 @@ fn resolve_entity(
         }
 
         // Entity only in base (deleted by both)
-        (Some(_), None, None) => {
+        (Some(base), None, None) => {
             stats.entities_deleted += 1;
+            trace.entity(&base.name, &base.entity_type, EntityPath::DeletedBoth);
             ResolvedEntity::Deleted
         }
 
         // Should not happen
         (None, None, None) => ResolvedEntity::Deleted,
-    }
+    })
 }
 
 fn entity_to_region_with_content(entity: &SemanticEntity, content: &str) -> EntityRegion {
@@ -829,6 +1586,39 @@ fn try_decorator_aware_merge(base: &str, ours: &str, theirs: &str) -> Option<Str
     Some(result)
 }
 
+/// diffy's 3-way merge, its conflicts written in `format`: relabelled, or
+/// in the diff3 styles, which diffy doesn't write like git, redone by
+/// [`xdiff::merge3`].
+fn diffy_merge_in(base: &str, ours: &str, theirs: &str, format: &ConflictFormat) -> Result<String, String> {
+    match diffy::merge(base, ours, theirs) {
+        Ok(merged) => Ok(merged),
+        Err(_) if format.style != ConflictStyle::Merge => {
+            let merged = xdiff::merge3(base, ours, theirs, format);
+            if merged.conflicts == 0 {
+                Ok(merged.content)
+            } else {
+                Err(merged.content)
+            }
+        }
+        Err(conflicted) => Err(relabel_conflicts(&conflicted, format)),
+    }
+}
+
+/// diffy's `<<<<<<< ours` and `>>>>>>> theirs` markers, with `format`'s labels.
+fn relabel_conflicts(conflicted: &str, format: &ConflictFormat) -> String {
+    if format.ours == "ours" && format.theirs == "theirs" {
+        return conflicted.to_string();
+    }
+    conflicted
+        .split_inclusive('\n')
+        .map(|line| match line.trim_end_matches(['\r', '\n']) {
+            "<<<<<<< ours" => line.replacen("ours", &format.ours, 1),
+            ">>>>>>> theirs" => line.replacen("theirs", &format.theirs, 1),
+            _ => line.to_string(),
+        })
+        .collect()
+}
+
 /// Try 3-way merge on text using diffy. Returns None if there are conflicts.
 fn diffy_merge(base: &str, ours: &str, theirs: &str) -> Option<String> {
     let result = diffy::merge(base, ours, theirs);
@@ -842,11 +1632,18 @@ fn diffy_merge(base: &str, ours: &str, theirs: &str) -> Option<String> {
 /// Uses commutative (set-based) merge for import blocks — inspired by
 /// LastMerge/Mergiraf's "unordered children" concept.
 /// Falls back to line-level 3-way merge for non-import content.
+/// Fails if the budget runs out before a region both sides changed. The
+/// origins of each merged region's lines are recorded in `origins`.
+/// Returns the merged regions by key and how many both sides changed.
 fn merge_interstitials(
     base_regions: &[FileRegion],
     ours_regions: &[FileRegion],
     theirs_regions: &[FileRegion],
-) -> HashMap<String, String> {
+    budget: &MergeBudget,
+    format: &ConflictFormat,
+    mut origins: Option<&mut HashMap<String, Vec<LineOrigin>>>,
+    mut trace: Option<&mut MergeTrace>,
+) -> Result<(HashMap<String, String>, usize), Exhausted> {
     let base_map: HashMap<&str, &str> = base_regions
         .iter()
         .filter_map(|r| match r {
@@ -875,8 +1672,12 @@ fn merge_interstitials(
     all_keys.extend(base_map.keys());
     all_keys.extend(ours_map.keys());
     all_keys.extend(theirs_map.keys());
+    // Sorted so the trace is deterministic
+    let mut all_keys: Vec<&str> = all_keys.into_iter().collect();
+    all_keys.sort_unstable();
 
     let mut merged: HashMap<String, String> = HashMap::new();
+    let mut both_changed = 0;
 
     for key in all_keys {
         let base_content = base_map.get(key).copied().unwrap_or("");
@@ -884,36 +1685,47 @@ fn merge_interstitials(
         let theirs_content = theirs_map.get(key).copied().unwrap_or("");
 
         // If all same, no merge needed
-        if ours_content == theirs_content {
-            merged.insert(key.to_string(), ours_content.to_string());
+        let mut unordered = false;
+        let result = if ours_content == theirs_content {
+            ours_content.to_string()
         } else if base_content == ours_content {
-            merged.insert(key.to_string(), theirs_content.to_string());
+            theirs_content.to_string()
         } else if base_content == theirs_content {
-            merged.insert(key.to_string(), ours_content.to_string());
+            ours_content.to_string()
         } else {
+            budget.check()?;
+            both_changed += 1;
             // Both changed — check if this is an import-heavy region
             if is_import_region(base_content)
                 || is_import_region(ours_content)
                 || is_import_region(theirs_content)
             {
                 // Commutative merge: treat import lines as a set
-                let result = merge_imports_commutatively(base_content, ours_content, theirs_content);
-                merged.insert(key.to_string(), result);
+                trace.file(FileDecision::ImportUnion);
+                unordered = true;
+                merge_imports_commutatively(base_content, ours_content, theirs_content)
             } else {
                 // Regular line-level merge
-                match diffy::merge(base_content, ours_content, theirs_content) {
+                match diffy_merge_in(base_content, ours_content, theirs_content, format) {
                     Ok(m) => {
-                        merged.insert(key.to_string(), m);
+                        trace.file(FileDecision::InterstitialMerged { conflicted: false });
+                        m
                     }
                     Err(conflicted) => {
-                        merged.insert(key.to_string(), conflicted);
+                        trace.file(FileDecision::InterstitialMerged { conflicted: true });
+                        conflicted
                     }
                 }
             }
+        };
+        if let Some(origins) = origins.as_deref_mut() {
+            let attribute = if unordered { attribute_unordered } else { attribute };
+            origins.insert(key.to_string(), attribute(base_content, ours_content, theirs_content, &result));
         }
+        merged.insert(key.to_string(), result);
     }
 
-    merged
+    Ok((merged, both_changed))
 }
 
 /// Check if a region is predominantly import/use statements.
@@ -938,46 +1750,51 @@ fn is_import_region(content: &str) -> bool {
 /// 2. Missing blank lines between entities or declarations, and excessive
 ///    blank lines (3+ consecutive) collapsed to 2
 fn post_merge_cleanup(content: &str) -> String {
+    post_merge_cleanup_kept(content).0
+}
+
+/// [`post_merge_cleanup`], also returning the indices of the lines it kept.
+fn post_merge_cleanup_kept(content: &str) -> (String, Vec<usize>) {
     let lines: Vec<&str> = content.lines().collect();
-    let mut result: Vec<&str> = Vec::with_capacity(lines.len());
+    let mut result: Vec<usize> = Vec::with_capacity(lines.len());
 
     // Pass 1: Remove consecutive duplicate non-empty lines.
     // Uses exact comparison (including indentation) so that closing braces
     // at different nesting levels aren't falsely matched. Only catches true
     // duplicates like repeated typedefs or forward declarations.
-    for line in &lines {
+    for (i, line) in lines.iter().enumerate() {
         if line.trim().is_empty() {
-            result.push(line);
+            result.push(i);
             continue;
         }
-        if let Some(prev) = result.last() {
-            if !prev.trim().is_empty() && *prev == *line {
+        if let Some(&prev) = result.last() {
+            if !lines[prev].trim().is_empty() && lines[prev] == *line {
                 continue; // skip consecutive exact duplicate
             }
         }
-        result.push(line);
+        result.push(i);
     }
 
     // Pass 2: Collapse 3+ consecutive blank lines to 2 (one separator blank line).
-    let mut final_lines: Vec<&str> = Vec::with_capacity(result.len());
+    let mut kept: Vec<usize> = Vec::with_capacity(result.len());
     let mut consecutive_blanks = 0;
-    for line in &result {
-        if line.trim().is_empty() {
+    for i in result {
+        if lines[i].trim().is_empty() {
             consecutive_blanks += 1;
             if consecutive_blanks <= 2 {
-                final_lines.push(line);
+                kept.push(i);
             }
         } else {
             consecutive_blanks = 0;
-            final_lines.push(line);
+            kept.push(i);
         }
     }
 
-    let mut out = final_lines.join("\n");
+    let mut out = kept.iter().map(|&i| lines[i]).collect::<Vec<_>>().join("\n");
     if content.ends_with('\n') && !out.ends_with('\n') {
         out.push('\n');
     }
-    out
+    (out, kept)
 }
 
 /// Check if a line is a top-level import/use/require statement.
@@ -1071,18 +1888,27 @@ fn merge_imports_commutatively(base: &str, ours: &str, theirs: &str) -> String {
 /// where `{`, `}`, `;` are structural content rather than code separators.
 /// Expanding them destroys alignment and produces far more conflicts (confirmed
 /// on GitButler: YAML went from 68 git markers to 192 weave markers with Sesame).
-fn line_level_fallback(base: &str, ours: &str, theirs: &str, file_path: &str) -> MergeResult {
+///
+/// Once the budget has run out, diffy is skipped for `git merge-file` alone.
+pub(crate) fn line_level_fallback(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    context: &MergeContext,
+) -> MergeResult {
+    let (budget, format) = (&context.budget, &context.conflict_format);
     let mut stats = MergeStats::default();
     stats.used_fallback = true;
 
     // Skip Sesame preprocessing for data formats where {/}/; are content, not separators
     let skip = skip_sesame(file_path);
 
-    if skip {
+    if skip || budget.exhausted().is_some() {
         // Use git merge-file for data formats so we match git's output exactly.
         // diffy::merge uses a different diff algorithm that can produce more
         // conflict markers on structured data like lock files.
-        return git_merge_file(base, ours, theirs, &mut stats);
+        return git_merge_file(base, ours, theirs, &mut stats, format);
     }
 
     // Try Sesame expansion + diffy first, then compare against git merge-file.
@@ -1096,19 +1922,18 @@ fn line_level_fallback(base: &str, ours: &str, theirs: &str, file_path: &str) ->
             let content = collapse_separators(&merged, base);
             Some(MergeResult {
                 content: post_merge_cleanup(&content),
-                conflicts: vec![],
-                warnings: vec![],
                 stats: stats.clone(),
+                ..Default::default()
             })
         }
+        // Sesame expansion conflicted, try plain diffy
+        Err(_) if budget.exhausted().is_some() => None,
         Err(_) => {
-            // Sesame expansion conflicted, try plain diffy
-            match diffy::merge(base, ours, theirs) {
+            match diffy_merge_in(base, ours, theirs, format) {
                 Ok(merged) => Some(MergeResult {
                     content: merged,
-                    conflicts: vec![],
-                    warnings: vec![],
                     stats: stats.clone(),
+                    ..Default::default()
                 }),
                 Err(conflicted) => {
                     let _markers = conflicted.lines().filter(|l| l.starts_with("<<<<<<<")).count();
@@ -1125,8 +1950,8 @@ fn line_level_fallback(base: &str, ours: &str, theirs: &str, file_path: &str) ->
                             theirs_content: Some(theirs.to_string()),
                             base_content: Some(base.to_string()),
                         }],
-                        warnings: vec![],
                         stats: s,
+                        ..Default::default()
                     })
                 }
             }
@@ -1134,7 +1959,7 @@ fn line_level_fallback(base: &str, ours: &str, theirs: &str, file_path: &str) ->
     };
 
     // Get git merge-file result as our floor
-    let git_result = git_merge_file(base, ours, theirs, &mut stats);
+    let git_result = git_merge_file(base, ours, theirs, &mut stats, format);
 
     // Compare: use sesame result only if it has fewer or equal markers
     match sesame_result {
@@ -1152,105 +1977,41 @@ fn line_level_fallback(base: &str, ours: &str, theirs: &str, file_path: &str) ->
     }
 }
 
-/// Shell out to `git merge-file` for an exact match with git's line-level merge.
+/// Git's line-level merge, computed in process by [`xdiff::merge3`] with the
+/// same output as `git merge-file`.
 ///
 /// We use this instead of `diffy::merge` for data formats (lock files, JSON, YAML, TOML)
 /// where weave can't improve on git. `diffy` uses a different diff algorithm that can
 /// produce more conflict markers on structured data (e.g. 22 markers vs git's 19 on uv.lock).
-fn git_merge_file(base: &str, ours: &str, theirs: &str, stats: &mut MergeStats) -> MergeResult {
-    let dir = match tempfile::tempdir() {
-        Ok(d) => d,
-        Err(_) => return diffy_fallback(base, ours, theirs, stats),
-    };
-
-    let base_path = dir.path().join("base");
-    let ours_path = dir.path().join("ours");
-    let theirs_path = dir.path().join("theirs");
-
-    let write_ok = (|| -> std::io::Result<()> {
-        std::fs::File::create(&base_path)?.write_all(base.as_bytes())?;
-        std::fs::File::create(&ours_path)?.write_all(ours.as_bytes())?;
-        std::fs::File::create(&theirs_path)?.write_all(theirs.as_bytes())?;
-        Ok(())
-    })();
-
-    if write_ok.is_err() {
-        return diffy_fallback(base, ours, theirs, stats);
-    }
-
-    // git merge-file writes result to the first file (ours) in place
-    let output = Command::new("git")
-        .arg("merge-file")
-        .arg("-p") // print to stdout instead of modifying ours in place
-        .arg(&ours_path)
-        .arg(&base_path)
-        .arg(&theirs_path)
-        .output();
-
-    match output {
-        Ok(result) => {
-            let content = String::from_utf8_lossy(&result.stdout).into_owned();
-            if result.status.success() {
-                // Exit 0 = clean merge
-                MergeResult {
-                    content: post_merge_cleanup(&content),
-                    conflicts: vec![],
-                    warnings: vec![],
-                    stats: stats.clone(),
-                }
-            } else {
-                // Exit >0 = conflicts (exit code = number of conflicts)
-                stats.entities_conflicted = 1;
-                MergeResult {
-                    content,
-                    conflicts: vec![EntityConflict {
-                        entity_name: "(file)".to_string(),
-                        entity_type: "file".to_string(),
-                        kind: ConflictKind::BothModified,
-                        complexity: classify_conflict(Some(base), Some(ours), Some(theirs)),
-                        ours_content: Some(ours.to_string()),
-                        theirs_content: Some(theirs.to_string()),
-                        base_content: Some(base.to_string()),
-                    }],
-                    warnings: vec![],
-                    stats: stats.clone(),
-                }
-            }
-        }
-        // git not available, fall back to diffy
-        Err(_) => diffy_fallback(base, ours, theirs, stats),
+pub(crate) fn git_merge_file(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    stats: &mut MergeStats,
+    format: &ConflictFormat,
+) -> MergeResult {
+    let merged = xdiff::merge3(base, ours, theirs, format);
+    if merged.conflicts == 0 {
+        return MergeResult {
+            content: post_merge_cleanup(&merged.content),
+            stats: stats.clone(),
+            ..Default::default()
+        };
     }
-}
-
-/// Fallback to diffy::merge when git merge-file is unavailable.
-fn diffy_fallback(base: &str, ours: &str, theirs: &str, stats: &mut MergeStats) -> MergeResult {
-    match diffy::merge(base, ours, theirs) {
-        Ok(merged) => {
-            let content = post_merge_cleanup(&merged);
-            MergeResult {
-                content,
-                conflicts: vec![],
-                warnings: vec![],
-                stats: stats.clone(),
-            }
-        }
-        Err(conflicted) => {
-            stats.entities_conflicted = 1;
-            MergeResult {
-                content: conflicted,
-                conflicts: vec![EntityConflict {
-                    entity_name: "(file)".to_string(),
-                    entity_type: "file".to_string(),
-                    kind: ConflictKind::BothModified,
-                    complexity: classify_conflict(Some(base), Some(ours), Some(theirs)),
-                    ours_content: Some(ours.to_string()),
-                    theirs_content: Some(theirs.to_string()),
-                    base_content: Some(base.to_string()),
-                }],
-                warnings: vec![],
-                stats: stats.clone(),
-            }
-        }
+    stats.entities_conflicted = 1;
+    MergeResult {
+        content: merged.content,
+        conflicts: vec![EntityConflict {
+            entity_name: "(file)".to_string(),
+            entity_type: "file".to_string(),
+            kind: ConflictKind::BothModified,
+            complexity: classify_conflict(Some(base), Some(ours), Some(theirs)),
+            ours_content: Some(ours.to_string()),
+            theirs_content: Some(theirs.to_string()),
+            base_content: Some(base.to_string()),
+        }],
+        stats: stats.clone(),
+        ..Default::default()
     }
 }
 
@@ -1270,7 +2031,7 @@ fn has_excessive_duplicates(entities: &[SemanticEntity]) -> bool {
     counts.values().any(|&c| c >= 5)
 }
 
-fn filter_nested_entities(entities: Vec<SemanticEntity>) -> Vec<SemanticEntity> {
+pub(crate) fn filter_nested_entities(entities: Vec<SemanticEntity>) -> Vec<SemanticEntity> {
     if entities.len() <= 1 {
         return entities;
     }
@@ -1556,6 +2317,7 @@ fn children_to_chunks(
 ///
 /// Returns Some(result) if chunking succeeded, None if we can't parse the container.
 /// The result may contain per-member conflict markers (scoped conflicts).
+#[allow(clippy::too_many_arguments)]
 fn try_inner_entity_merge(
     base: &str,
     ours: &str,
@@ -1566,6 +2328,7 @@ fn try_inner_entity_merge(
     base_start_line: usize,
     ours_start_line: usize,
     theirs_start_line: usize,
+    budget: &MergeBudget,
 ) -> Option<InnerMergeResult> {
     // If sem-core produced child entities, use them directly instead of the
     // indentation heuristic. This gives tree-sitter-accurate method boundaries.
@@ -1627,6 +2390,9 @@ fn try_inner_entity_merge(
     let mut has_conflict = false;
 
     for name in &all_names {
+        if budget.exhausted().is_some() {
+            return None;
+        }
         let in_base = base_map.get(name.as_str());
         let in_ours = ours_map.get(name.as_str());
         let in_theirs = theirs_map.get(name.as_str());
@@ -2110,6 +2876,122 @@ fn collapse_separators(merged: &str, _base: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::trace::TraceStep;
+
+    #[test]
+    fn test_cancelled_budget_falls_back_to_git() {
+        let context = MergeContext {
+            trace: true,
+            ..Default::default()
+        };
+        context.budget.cancel();
+        let base = "fn a() {\n    1\n}\n\nfn b() {\n    2\n}\n";
+        let ours = "fn a() {\n    10\n}\n\nfn b() {\n    2\n}\n";
+        let theirs = "fn a() {\n    1\n}\n\nfn b() {\n    20\n}\n";
+
+        let config = WeaveConfig::default();
+        let result = entity_merge_in(base, ours, theirs, "src/lib.rs", shared_registry(), &config, &context);
+        assert!(result.stats.used_fallback);
+        assert!(result.trace.unwrap().steps.iter().any(|step| matches!(
+            step,
+            TraceStep::File(FileDecision::Fallback(FallbackReason::Cancelled))
+        )));
+    }
+
+    #[test]
+    fn test_line_conflicts_in_the_context_format() {
+        let context = MergeContext {
+            conflict_format: ConflictFormat {
+                style: ConflictStyle::Diff3,
+                ours: "HEAD".to_string(),
+                base: "1a2b3c4".to_string(),
+                theirs: "feature".to_string(),
+            },
+            ..Default::default()
+        };
+        let registry = shared_registry();
+        for file in ["notes.txt", "data.json"] {
+            let result = entity_merge_in("a\nb\nc\n", "a\nB\nc\n", "a\nb2\nc\n", file, registry, &WeaveConfig::default(), &context);
+            assert_eq!(result.content, "a\n<<<<<<< HEAD\nB\n||||||| 1a2b3c4\nb\n=======\nb2\n>>>>>>> feature\nc\n", "{}", file);
+        }
+    }
+
+    #[test]
+    fn test_expired_budget_stops_every_phase() {
+        let expired = MergeBudget::with_timeout(std::time::Duration::ZERO);
+        let unlimited = MergeBudget::unlimited();
+
+        // Line fallback: the Sesame-expanded merge is skipped for git merge-file alone
+        let (base, ours, theirs) = ("x { a; b; }\n", "x { c; b; }\n", "x { a; d; }\n");
+        let in_budget = |budget: &MergeBudget| MergeContext {
+            budget: budget.clone(),
+            ..Default::default()
+        };
+        assert!(line_level_fallback(base, ours, theirs, "a.c", &in_budget(&unlimited)).is_clean());
+        assert!(!line_level_fallback(base, ours, theirs, "a.c", &in_budget(&expired)).is_clean());
+
+        // Interstitials: no diffy merge of a region both sides changed
+        let header = |content: &str| {
+            vec![FileRegion::Interstitial(crate::region::InterstitialRegion {
+                position_key: "file_header".to_string(),
+                content: content.to_string(),
+            })]
+        };
+        let (base, ours, theirs) = (header("// one\n"), header("// two\n"), header("// three\n"));
+        let format = ConflictFormat::default();
+        assert!(merge_interstitials(&base, &ours, &theirs, &unlimited, &format, None, None).is_ok());
+        assert_eq!(merge_interstitials(&base, &ours, &theirs, &expired, &format, None, None), Err(Exhausted::TimedOut));
+
+        // Inner merge: gives up before the first member
+        let base = "class A {\n    fn a() {}\n\n    fn b() {}\n}\n";
+        let ours = "class A {\n    fn a() { 1 }\n\n    fn b() {}\n}\n";
+        let theirs = "class A {\n    fn a() {}\n\n    fn b() { 2 }\n}\n";
+        assert!(try_inner_entity_merge(base, ours, theirs, &[], &[], &[], 1, 1, 1, &unlimited).is_some());
+        assert!(try_inner_entity_merge(base, ours, theirs, &[], &[], &[], 1, 1, 1, &expired).is_none());
+
+        // Entity resolution: no diffy merge of an entity both sides changed
+        let base = "fn a() {\n    1\n}\n";
+        let ours = "fn a() {\n    // ours\n    1\n}\n";
+        let theirs = "fn a() {\n    1\n    // theirs\n}\n";
+        let plugin = shared_registry().get_plugin("lib.rs").unwrap();
+        let [base_all, ours_all, theirs_all] = [base, ours, theirs].map(|content| plugin.extract_entities(content, "lib.rs"));
+        let content = |content: &str, entities: &[SemanticEntity]| build_region_content_map(&extract_regions(content, entities));
+        let (base_rc, ours_rc, theirs_rc) = (content(base, &base_all), content(ours, &ours_all), content(theirs, &theirs_all));
+        let mut stats = MergeStats::default();
+        let mut resolver = EntityResolver {
+            file_path: "lib.rs",
+            budget: &expired,
+            base_region_content: &base_rc,
+            ours_region_content: &ours_rc,
+            theirs_region_content: &theirs_rc,
+            base_all: &base_all,
+            ours_all: &ours_all,
+            theirs_all: &theirs_all,
+            stats: &mut stats,
+            trace: None,
+            origins: None,
+        };
+        let (b, o, t) = (&base_all[0], &ours_all[0], &theirs_all[0]);
+        assert!(matches!(
+            resolve_entity(&mut resolver, Some(&b), Some(&o), Some(&t)),
+            Err(Exhausted::TimedOut)
+        ));
+
+        // Format mergers: their result is discarded for the line fallback
+        let context = MergeContext {
+            budget: expired,
+            trace: true,
+            ..Default::default()
+        };
+        let base = ".a {\n  color: red;\n}\n";
+        let ours = ".a {\n  color: blue;\n}\n";
+        let theirs = ".a {\n  color: red;\n}\n\n.b {\n  color: red;\n}\n";
+        let result = entity_merge_in(base, ours, theirs, "style.css", shared_registry(), &WeaveConfig::default(), &context);
+        assert!(result.stats.used_fallback);
+        let steps = result.trace.unwrap().steps;
+        assert!(steps.iter().any(|step| matches!(step, TraceStep::File(FileDecision::FormatMerger("stylesheet")))));
+        assert!(steps.iter().any(|step| matches!(step, TraceStep::File(FileDecision::Fallback(FallbackReason::Timeout)))));
+    }
 
     #[test]
     fn test_replace_at_word_boundaries() {
@@ -2247,13 +3129,47 @@ export function agentB() {
         );
     }
 
+    #[test]
+    fn test_syntax_error_falls_back_to_line_merge() {
+        // Each side parses, but ours turns the `if` into a plain block
+        // while theirs gives it an `else`
+        let base = "fn f(x: bool) {\n    if x {\n        a();\n    }\n    b();\n}\n\nfn g() {}\n";
+        let ours = "fn f(x: bool) {\n    {\n        a();\n    }\n    b();\n}\n\nfn g() {}\n";
+        let theirs = "fn f(x: bool) {\n    if x {\n        a();\n    } else {\n        c();\n    }\n    b();\n}\n\nfn g() {}\n";
+        let context = MergeContext {
+            trace: true,
+            ..Default::default()
+        };
+        let result = entity_merge_in(base, ours, theirs, "src/lib.rs", shared_registry(), &WeaveConfig::default(), &context);
+        assert!(result.stats.used_fallback);
+        let steps = result.trace.unwrap().steps;
+        assert!(steps
+            .iter()
+            .any(|step| matches!(step, TraceStep::File(FileDecision::Fallback(FallbackReason::SyntaxError { .. })))));
+        assert!(result
+            .warnings
+            .iter()
+            .any(|w| matches!(w.kind, crate::validate::WarningKind::SyntaxErrorFallback { .. })));
+        // Line by line, it doesn't parse either
+        assert!(result
+            .warnings
+            .iter()
+            .any(|w| matches!(w.kind, crate::validate::WarningKind::ParseFailedAfterMerge)));
+
+        // One side's entities as they are: nothing to check
+        let theirs = "fn f(x: bool) {\n    if x {\n        a();\n    }\n    b();\n}\n\nfn g() {\n    c();\n}\n";
+        let result = entity_merge_in(base, ours, theirs, "src/lib.rs", shared_registry(), &WeaveConfig::default(), &context);
+        assert!(result.is_clean());
+        assert!(!result.stats.used_fallback);
+    }
+
     #[test]
     fn test_line_level_fallback() {
         // Non-adjacent changes merge cleanly in 3-way merge
         let base = "a\nb\nc\nd\ne\n";
         let ours = "A\nb\nc\nd\ne\n";
         let theirs = "a\nb\nc\nd\nE\n";
-        let result = line_level_fallback(base, ours, theirs, "test.rs");
+        let result = line_level_fallback(base, ours, theirs, "test.rs", &MergeContext::default());
         assert!(result.is_clean());
         assert!(result.stats.used_fallback);
         assert_eq!(result.content, "A\nb\nc\nd\nE\n");
@@ -2265,7 +3181,7 @@ export function agentB() {
         let base = "a\nb\nc\n";
         let ours = "X\nb\nc\n";
         let theirs = "Y\nb\nc\n";
-        let result = line_level_fallback(base, ours, theirs, "test.rs");
+        let result = line_level_fallback(base, ours, theirs, "test.rs", &MergeContext::default());
         assert!(!result.is_clean());
         assert!(result.stats.used_fallback);
     }
@@ -2601,6 +3517,26 @@ export function agentB() {
         assert!(result.content.contains("def foo()"));
     }
 
+    #[test]
+    fn test_provenance_recorded_by_the_decorator_merge() {
+        let base = "def foo():\n    return 1\n\ndef bar():\n    return 2\n";
+        let ours = "@cache\ndef foo():\n    return 1\n\ndef bar():\n    return 2\n";
+        let theirs = "@deprecated\ndef foo():\n    return 1\n\ndef bar():\n    return 3\n";
+        let context = MergeContext {
+            provenance: true,
+            ..Default::default()
+        };
+        let result = entity_merge_in(base, ours, theirs, "test.py", shared_registry(), &WeaveConfig::default(), &context);
+        assert!(result.is_clean());
+        let provenance = result.provenance.expect("provenance requested");
+        assert_eq!(provenance.lines.len(), result.content.lines().count());
+        let origin = |text: &str| provenance.lines[result.content.lines().position(|l| l == text).unwrap()];
+        assert_eq!(origin("@cache"), LineOrigin::Ours);
+        assert_eq!(origin("@deprecated"), LineOrigin::Theirs);
+        assert_eq!(origin("def bar():"), LineOrigin::Base);
+        assert_eq!(origin("    return 3"), LineOrigin::Theirs);
+    }
+
     #[test]
     fn test_decorator_plus_body_change() {
         // One adds decorator, other modifies body — should merge both
@@ -2995,4 +3931,151 @@ fn subtract(a: i32, b: i32) -> i32 {
         assert!(result.content.contains("multiply"), "Should have multiply");
         assert!(result.content.contains("divide"), "Should have divide");
     }
+
+    #[test]
+    fn test_trace_records_entity_paths() {
+        let base = "function a() {\n    return 1;\n}\n\nfunction b() {\n    return 1;\n}\n";
+        let ours = "function a() {\n    return 2;\n}\n\nfunction b() {\n    return 1;\n}\n";
+        let theirs = "function a() {\n    return 1;\n}\n\nfunction b() {\n    return 3;\n}\n";
+        let context = MergeContext {
+            trace: true,
+            ..Default::default()
+        };
+        let registry = create_default_registry();
+        let result = entity_merge_in(base, ours, theirs, "test.ts", &registry, &WeaveConfig::default(), &context);
+        let trace = result.trace.expect("trace requested");
+
+        assert!(matches!(trace.steps[0], TraceStep::File(FileDecision::EntityMerge { .. })));
+        let paths: Vec<(&str, EntityPath)> = trace
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                TraceStep::Entity { name, path, .. } => Some((name.as_str(), *path)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(paths, vec![("a", EntityPath::OursOnly), ("b", EntityPath::TheirsOnly)]);
+        assert!(trace.to_string().contains("function `a`: changed in ours only"));
+    }
+
+    #[test]
+    fn test_duplicates_to_conflicts() {
+        use crate::validate::DefinitionSite;
+        let site = |name: &str, line: usize, origin: DefinitionSide| DefinitionSite {
+            name: name.to_string(),
+            entity_type: "function".to_string(),
+            start_line: line,
+            end_line: line,
+            content: String::new(),
+            origin,
+        };
+        let content = "fn a() {}\nfn format() { 2 }\nfn b() {}\nfn format() { 1 }\n";
+        let duplicates = vec![DuplicateDefinition {
+            first: site("format", 2, DefinitionSide::Theirs),
+            second: site("format", 4, DefinitionSide::Ours),
+        }];
+
+        let mut origins = vec![LineOrigin::Base, LineOrigin::Theirs, LineOrigin::Base, LineOrigin::Ours];
+        let (merged, conflicts, blocks, remaining) =
+            duplicates_to_conflicts(content, "lib.rs", duplicates, Some(&mut origins));
+        assert!(remaining.is_empty());
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(blocks, vec![Some(0)]);
+        assert_eq!(conflicts[0].kind, ConflictKind::BothAdded);
+        assert_eq!(conflicts[0].ours_content.as_deref(), Some("fn format() { 1 }\n"));
+        assert_eq!(
+            merged,
+            format!("fn a() {{}}\n{}fn b() {{}}\n", conflicts[0].to_conflict_markers())
+        );
+        assert_eq!(origins.len(), merged.lines().count());
+        assert_eq!(origins[0], LineOrigin::Base);
+        assert_eq!(origins[1], LineOrigin::Conflict);
+        assert_eq!(origins.last(), Some(&LineOrigin::Base));
+
+        // Blocks are numbered in file order, not in the order duplicates are found
+        let content = "fn parse() { 1 }\nfn format() { 2 }\nfn parse() { 2 }\nfn format() { 1 }\n";
+        let duplicates = vec![
+            DuplicateDefinition {
+                first: site("format", 2, DefinitionSide::Theirs),
+                second: site("format", 4, DefinitionSide::Ours),
+            },
+            DuplicateDefinition {
+                first: site("parse", 1, DefinitionSide::Ours),
+                second: site("parse", 3, DefinitionSide::Theirs),
+            },
+        ];
+        let (_, conflicts, blocks, _) = duplicates_to_conflicts(content, "lib.rs", duplicates, None);
+        assert_eq!(conflicts[1].entity_name, "parse");
+        assert_eq!(blocks, vec![Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn test_protected_entity_conflicts_despite_clean_merge() {
+        // Disjoint edits that diffy would combine
+        let base = "function checkAuth(user) {\n    const a = 1;\n    const b = 2;\n    const c = 3;\n    return a;\n}\n";
+        let ours = "function checkAuth(user) {\n    const a = 10;\n    const b = 2;\n    const c = 3;\n    return a;\n}\n";
+        let theirs = "function checkAuth(user) {\n    const a = 1;\n    const b = 2;\n    const c = 30;\n    return a;\n}\n";
+        let registry = create_default_registry();
+
+        let unprotected = entity_merge_with_registry(base, ours, theirs, "auth.ts", &registry, None);
+        assert!(unprotected.is_clean());
+
+        let config = WeaveConfig::from_toml("[[protected]]\nnames = [\"check*\"]\n").unwrap();
+        let result = entity_merge_with_registry_and_config(base, ours, theirs, "auth.ts", &registry, &config);
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].entity_name, "checkAuth");
+
+        let annotated = |s: &str| format!("// weave:protected\n{}", s);
+        let result = entity_merge_with_registry(&annotated(base), &annotated(ours), &annotated(theirs), "auth.ts", &registry, None);
+        assert!(!result.is_clean());
+
+        // Only the leading comment protects, not a mention in the body
+        let in_body = |s: &str| s.replacen("{\n", "{\n    // weave:protected\n", 1);
+        let result = entity_merge_with_registry(&in_body(base), &in_body(ours), &in_body(theirs), "auth.ts", &registry, None);
+        assert!(result.is_clean());
+    }
+
+    #[test]
+    fn test_trace_records_file_decisions() {
+        let registry = create_default_registry();
+        let config = WeaveConfig::default();
+        let context = MergeContext {
+            trace: true,
+            ..Default::default()
+        };
+
+        let result = entity_merge_in("a\n", "b\n", "b\n", "notes.txt", &registry, &config, &context);
+        assert!(matches!(result.trace.unwrap().steps[..], [TraceStep::File(FileDecision::Identical)]));
+
+        let base = ".a { color: red; }\n";
+        let ours = ".a { color: red; }\n.b { color: blue; }\n";
+        let theirs = ".c { color: green; }\n.a { color: red; }\n";
+        let result = entity_merge_in(base, ours, theirs, "app.css", &registry, &config, &context);
+        assert!(matches!(
+            result.trace.unwrap().steps[..],
+            [TraceStep::File(FileDecision::FormatMerger("stylesheet"))]
+        ));
+
+        // Not requested: no trace
+        let result = entity_merge_with_registry(base, ours, theirs, "app.css", &registry, None);
+        assert!(result.trace.is_none());
+    }
+
+    #[test]
+    fn test_merge_with_registry_caches_entities() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = EntityCache::open(dir.path(), 1 << 20);
+        let base = "fn a() {\n    1\n}\n\nfn b() {\n    2\n}\n";
+        let ours = base.replace("    1", "    10");
+        let theirs = base.replace("    2", "    20");
+
+        let result = entity_merge_with_registry(base, &ours, &theirs, "src/lib.rs", shared_registry(), Some(&cache));
+        assert!(result.is_clean());
+        // One entry per version
+        let entries = std::fs::read_dir(dir.path().join(crate::cache::CACHE_DIR))
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension().is_some_and(|e| e == "json"))
+            .count();
+        assert_eq!(entries, 3);
+    }
 }
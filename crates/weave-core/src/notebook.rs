@@ -0,0 +1,527 @@
+//! Cell-level merge for Jupyter notebooks (`.ipynb`).
+//!
+//! Notebooks are JSON, but a line merge of that JSON conflicts on every
+//! re-run: outputs and execution counts change constantly. Here each cell is
+//! an entity, keyed by its `id` (nbformat 4.5+) or, for older notebooks, by
+//! a hash of its type and source. Cell sources are merged with the regular
+//! entity merge for the kernel language; outputs and execution counts follow
+//! the `[notebook] outputs` policy.
+//!
+//! Conflicts are written inside the cell source, so the merged notebook
+//! stays valid JSON and can be opened in Jupyter to resolve them. Other
+//! fields (metadata, attachments...) changed differently on both sides have
+//! nowhere to hold markers: ours is kept and a warning names the field.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sem_core::parser::registry::ParserRegistry;
+
+use crate::config::{NotebookConfig, OutputPolicy, WeaveConfig};
+use crate::conflict::{classify_conflict, ConflictKind, EntityConflict, MergeStats};
+use crate::keyed::{merge_keyed, pick_side, KeyedMerge};
+use crate::merge::{entity_merge_in, MergeContext, MergeResult};
+use crate::validate::{SemanticWarning, WarningKind};
+
+/// Check if a file is a Jupyter notebook.
+pub fn is_notebook(file_path: &str) -> bool {
+    file_path.ends_with(".ipynb")
+}
+
+/// Merge three versions of a notebook cell by cell.
+///
+/// Returns None if any version isn't a notebook we can read, so the caller
+/// can fall back to the regular merge.
+pub fn merge_notebook(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    registry: &ParserRegistry,
+    config: &NotebookConfig,
+    context: &MergeContext,
+) -> Option<MergeResult> {
+    let base_nb: Value = serde_json::from_str(base).ok()?;
+    let ours_nb: Value = serde_json::from_str(ours).ok()?;
+    let theirs_nb: Value = serde_json::from_str(theirs).ok()?;
+
+    let base_cells = keyed_cells(&base_nb)?;
+    let ours_cells = keyed_cells(&ours_nb)?;
+    let theirs_cells = keyed_cells(&theirs_nb)?;
+
+    let source_path = format!("cell.{}", kernel_extension(&ours_nb));
+    let mut stats = MergeStats::default();
+    let mut source_conflicts: HashMap<String, EntityConflict> = HashMap::new();
+    let mut warnings = Vec::new();
+    let kept_ours = |entity_name: String, entity_type: &str, field: String| SemanticWarning {
+        entity_name,
+        entity_type: entity_type.to_string(),
+        file_path: file_path.to_string(),
+        kind: WarningKind::FieldKeptOurs { field },
+        related: vec![],
+    };
+
+    let merged = merge_keyed(&base_cells, &ours_cells, &theirs_cells, &mut stats, |key, b, o, t| {
+        let mut fields = Vec::new();
+        let merged = merge_cell(b, o, t, &source_path, registry, config, context, &mut fields);
+        warnings.extend(fields.into_iter().map(|field| kept_ours(cell_label(o), "cell", field)));
+        merged.map_err(|failed| {
+            if let Some(conflict) = failed.conflict {
+                source_conflicts.insert(key.to_string(), *conflict);
+            }
+            failed.rendered
+        })
+    });
+
+    let mut cells = Vec::new();
+    let mut conflicts = Vec::new();
+    for item in merged {
+        match item {
+            KeyedMerge::Clean(cell) => cells.push(cell),
+            KeyedMerge::Conflict(c) => {
+                let conflict = source_conflicts.remove(&c.key).unwrap_or_else(|| {
+                    let label_cell = c.ours.as_ref().or(c.theirs.as_ref()).or(c.base.as_ref());
+                    let base_src = c.base.as_ref().map(cell_source);
+                    let ours_src = c.ours.as_ref().map(cell_source);
+                    let theirs_src = c.theirs.as_ref().map(cell_source);
+                    EntityConflict {
+                        entity_name: label_cell.map(cell_label).unwrap_or_default(),
+                        entity_type: "cell".to_string(),
+                        kind: c.kind.clone(),
+                        complexity: classify_conflict(base_src.as_deref(), ours_src.as_deref(), theirs_src.as_deref()),
+                        ours_content: ours_src,
+                        theirs_content: theirs_src,
+                        base_content: base_src,
+                    }
+                });
+                conflicts.push(conflict);
+                // Keep whichever version survives so nothing is lost while resolving
+                if let Some(cell) = c.rendered.or(c.ours).or(c.theirs) {
+                    cells.push(cell);
+                }
+            }
+        }
+    }
+
+    // Notebook-level fields (metadata, nbformat, ...): per-key 3-way, ours on conflict
+    let (mut notebook, fields) = merge_objects(base_nb.as_object(), ours_nb.as_object()?, theirs_nb.as_object());
+    warnings.extend(fields.into_iter().map(|field| kept_ours("(notebook)".to_string(), "notebook", field)));
+    notebook.insert("cells".to_string(), Value::Array(cells));
+
+    let mut content = to_notebook_json(&Value::Object(notebook), &detect_indent(ours))?;
+    if ours.ends_with('\n') {
+        content.push('\n');
+    }
+
+    Some(MergeResult {
+        content,
+        conflicts,
+        warnings,
+        stats,
+        ..Default::default()
+    })
+}
+
+/// A cell that couldn't be merged cleanly.
+struct CellConflict {
+    /// The cell to emit, with conflict markers in its source.
+    rendered: Option<Value>,
+    /// The source conflict to report, if the source was what conflicted.
+    conflict: Option<Box<EntityConflict>>,
+}
+
+impl CellConflict {
+    fn unrendered() -> Self {
+        CellConflict { rendered: None, conflict: None }
+    }
+}
+
+/// Merge a cell changed on both sides. Fields other than the source and
+/// outputs that both sides changed differently keep ours and are added to
+/// `kept_ours`.
+#[allow(clippy::too_many_arguments)]
+fn merge_cell(
+    base: Option<&Value>,
+    ours: &Value,
+    theirs: &Value,
+    source_path: &str,
+    registry: &ParserRegistry,
+    config: &NotebookConfig,
+    context: &MergeContext,
+    kept_ours: &mut Vec<String>,
+) -> Result<Value, CellConflict> {
+    let field = |cell: Option<&Value>, name: &str| cell.and_then(|c| c.get(name)).cloned();
+
+    if pick_side(field(base, "cell_type"), field(Some(ours), "cell_type"), field(Some(theirs), "cell_type")).is_none() {
+        return Err(CellConflict::unrendered());
+    }
+
+    let (mut cell, fields) = merge_objects(base.and_then(Value::as_object), ours.as_object().ok_or_else(CellConflict::unrendered)?, theirs.as_object());
+    // The source and outputs are merged below
+    kept_ours.extend(fields.into_iter().filter(|f| !matches!(f.as_str(), "source" | "outputs" | "execution_count")));
+
+    // Source: merge with the kernel language (or markdown) entity merge
+    let base_src = base.map(cell_source).unwrap_or_default();
+    let ours_src = cell_source(ours);
+    let theirs_src = cell_source(theirs);
+    let mut source_merged_from_both = false;
+    let mut conflict = None;
+    let source = if ours_src == theirs_src || base_src == theirs_src {
+        ours_src.clone()
+    } else if base_src == ours_src {
+        theirs_src.clone()
+    } else {
+        source_merged_from_both = true;
+        let path = match ours.get("cell_type").and_then(Value::as_str) {
+            Some("markdown") => "cell.md",
+            Some("code") => source_path,
+            _ => "cell.txt",
+        };
+        let result = entity_merge_in(&base_src, &ours_src, &theirs_src, path, registry, &WeaveConfig::default(), &context.part());
+        if !result.is_clean() {
+            conflict = Some(Box::new(EntityConflict {
+                entity_name: cell_label(ours),
+                entity_type: "cell".to_string(),
+                kind: if base.is_some() {
+                    ConflictKind::BothModified
+                } else {
+                    ConflictKind::BothAdded
+                },
+                complexity: classify_conflict(base.map(|_| base_src.as_str()), Some(&ours_src), Some(&theirs_src)),
+                ours_content: Some(ours_src.clone()),
+                theirs_content: Some(theirs_src.clone()),
+                base_content: base.map(|_| base_src.clone()),
+            }));
+        }
+        result.content
+    };
+    cell.insert("source".to_string(), source_value(&source, ours.get("source")));
+
+    // Outputs and execution count move together
+    if ours.get("cell_type").and_then(Value::as_str) == Some("code") {
+        let outputs_of = |c: Option<&Value>| (field(c, "outputs"), field(c, "execution_count"));
+        let (base_out, ours_out, theirs_out) = (outputs_of(base), outputs_of(Some(ours)), outputs_of(Some(theirs)));
+        let picked = if source_merged_from_both {
+            None
+        } else {
+            pick_side(Some(base_out), Some(ours_out.clone()), Some(theirs_out.clone())).flatten()
+        };
+        let (outputs, execution_count) = match (picked, config.outputs) {
+            (Some(out), _) => out,
+            (None, OutputPolicy::Ours) => ours_out,
+            (None, OutputPolicy::Theirs) => theirs_out,
+            (None, OutputPolicy::Clear) => (Some(Value::Array(vec![])), Some(Value::Null)),
+        };
+        set_or_remove(&mut cell, "outputs", outputs);
+        set_or_remove(&mut cell, "execution_count", execution_count);
+    }
+
+    match conflict {
+        None => Ok(Value::Object(cell)),
+        Some(conflict) => Err(CellConflict {
+            rendered: Some(Value::Object(cell)),
+            conflict: Some(conflict),
+        }),
+    }
+}
+
+/// Per-key 3-way merge of two JSON objects. Keys changed differently on both
+/// sides keep ours and are returned alongside; "cells" is handled by the
+/// caller and skipped here.
+fn merge_objects(
+    base: Option<&Map<String, Value>>,
+    ours: &Map<String, Value>,
+    theirs: Option<&Map<String, Value>>,
+) -> (Map<String, Value>, Vec<String>) {
+    let mut merged = ours.clone();
+    let mut kept_ours = Vec::new();
+    let mut keys: Vec<&String> = ours.keys().collect();
+    if let Some(theirs) = theirs {
+        keys.extend(theirs.keys().filter(|k| !ours.contains_key(*k)));
+    }
+    if let Some(base) = base {
+        keys.extend(base.keys().filter(|k| !ours.contains_key(*k)));
+    }
+
+    for key in keys {
+        if key == "cells" {
+            continue;
+        }
+        let b = base.and_then(|m| m.get(key)).cloned();
+        let o = ours.get(key).cloned();
+        let t = theirs.and_then(|m| m.get(key)).cloned();
+        let value = pick_side(b, o.clone(), t).unwrap_or_else(|| {
+            kept_ours.push(key.clone());
+            o
+        });
+        set_or_remove(&mut merged, key, value);
+    }
+
+    (merged, kept_ours)
+}
+
+fn set_or_remove(map: &mut Map<String, Value>, key: &str, value: Option<Value>) {
+    match value {
+        Some(v) => {
+            map.insert(key.to_string(), v);
+        }
+        None => {
+            map.remove(key);
+        }
+    }
+}
+
+/// Cells paired with their merge key: the cell id, or a content hash for
+/// notebooks written before cell ids existed.
+fn keyed_cells(notebook: &Value) -> Option<Vec<(String, Value)>> {
+    let cells = notebook.get("cells")?.as_array()?;
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut keyed = Vec::with_capacity(cells.len());
+
+    for cell in cells {
+        let key = match cell.get("id").and_then(Value::as_str) {
+            Some(id) => format!("id:{}", id),
+            None => {
+                let mut hasher = DefaultHasher::new();
+                cell.get("cell_type").and_then(Value::as_str).hash(&mut hasher);
+                cell_source(cell).hash(&mut hasher);
+                format!("hash:{:016x}", hasher.finish())
+            }
+        };
+        // Identical cells without ids: disambiguate by occurrence
+        let count = seen.entry(key.clone()).or_insert(0);
+        *count += 1;
+        let key = if *count > 1 { format!("{}#{}", key, count) } else { key };
+        keyed.push((key, cell.clone()));
+    }
+
+    Some(keyed)
+}
+
+/// Cell source as a single string (nbformat allows a string or a list of lines).
+fn cell_source(cell: &Value) -> String {
+    match cell.get("source") {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(lines)) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+/// Encode a merged source in the same shape as the original.
+fn source_value(source: &str, original: Option<&Value>) -> Value {
+    match original {
+        Some(Value::String(_)) => Value::String(source.to_string()),
+        _ => Value::Array(
+            source
+                .split_inclusive('\n')
+                .map(|line| Value::String(line.to_string()))
+                .collect(),
+        ),
+    }
+}
+
+/// Human-readable cell name for conflict reports: its id, or its first line.
+fn cell_label(cell: &Value) -> String {
+    if let Some(id) = cell.get("id").and_then(Value::as_str) {
+        return id.to_string();
+    }
+    let source = cell_source(cell);
+    let first = source.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("");
+    let mut label: String = first.chars().take(40).collect();
+    if first.chars().count() > 40 {
+        label.push('…');
+    }
+    label
+}
+
+/// File extension for the notebook's kernel language, used to pick a parser.
+fn kernel_extension(notebook: &Value) -> &'static str {
+    let metadata = notebook.get("metadata");
+    let language = metadata
+        .and_then(|m| m.get("kernelspec"))
+        .and_then(|k| k.get("language"))
+        .or_else(|| metadata.and_then(|m| m.get("language_info")).and_then(|l| l.get("name")))
+        .and_then(Value::as_str)
+        .unwrap_or("python");
+
+    match language.to_ascii_lowercase().as_str() {
+        "python" | "python3" => "py",
+        "javascript" => "js",
+        "typescript" => "ts",
+        "rust" => "rs",
+        "go" => "go",
+        "java" => "java",
+        "c" => "c",
+        "c++" | "cpp" => "cpp",
+        "ruby" => "rb",
+        _ => "txt",
+    }
+}
+
+/// Indentation of the original file (nbformat writes one space).
+fn detect_indent(content: &str) -> String {
+    content
+        .lines()
+        .nth(1)
+        .map(|l| l.chars().take_while(|c| *c == ' ' || *c == '\t').collect::<String>())
+        .filter(|indent| !indent.is_empty())
+        .unwrap_or_else(|| " ".to_string())
+}
+
+fn to_notebook_json(notebook: &Value, indent: &str) -> Option<String> {
+    let mut buf = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buf, formatter);
+    notebook.serialize(&mut serializer).ok()?;
+    String::from_utf8(buf).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn code_cell(id: &str, source: &str, count: Option<u64>, output: Option<&str>) -> Value {
+        let outputs: Vec<Value> = output
+            .map(|text| vec![json!({"name": "stdout", "output_type": "stream", "text": [text]})])
+            .unwrap_or_default();
+        json!({
+            "cell_type": "code",
+            "execution_count": count,
+            "id": id,
+            "metadata": {},
+            "outputs": outputs,
+            "source": source.split_inclusive('\n').collect::<Vec<_>>(),
+        })
+    }
+
+    fn notebook(cells: Vec<Value>) -> String {
+        let nb = json!({
+            "cells": cells,
+            "metadata": {"kernelspec": {"display_name": "Python 3", "language": "python", "name": "python3"}},
+            "nbformat": 4,
+            "nbformat_minor": 5,
+        });
+        let mut s = to_notebook_json(&nb, " ").unwrap();
+        s.push('\n');
+        s
+    }
+
+    fn merge(base: &str, ours: &str, theirs: &str, outputs: OutputPolicy) -> MergeResult {
+        let registry = sem_core::parser::plugins::create_default_registry();
+        merge_notebook(base, ours, theirs, "nb.ipynb", &registry, &NotebookConfig { outputs }, &MergeContext::default()).unwrap()
+    }
+
+    #[test]
+    fn test_different_cells_edited() {
+        let base = notebook(vec![
+            code_cell("a", "import os\n", Some(1), None),
+            code_cell("b", "x = 1\nprint(x)", Some(2), Some("1\n")),
+        ]);
+        let ours = notebook(vec![
+            code_cell("a", "import os\nimport sys\n", Some(3), None),
+            code_cell("b", "x = 1\nprint(x)", Some(4), Some("1\n")),
+        ]);
+        let theirs = notebook(vec![
+            code_cell("a", "import os\n", Some(1), None),
+            code_cell("b", "x = 2\nprint(x)", Some(2), Some("2\n")),
+            code_cell("c", "y = 3", None, None),
+        ]);
+
+        let result = merge(&base, &ours, &theirs, OutputPolicy::Ours);
+        assert!(result.is_clean(), "conflicts: {:?}", result.conflicts);
+
+        let merged: Value = serde_json::from_str(&result.content).unwrap();
+        let cells = merged["cells"].as_array().unwrap();
+        assert_eq!(cells.len(), 3);
+        assert_eq!(cell_source(&cells[0]), "import os\nimport sys\n");
+        assert_eq!(cell_source(&cells[1]), "x = 2\nprint(x)");
+        assert_eq!(cells[2]["id"], "c");
+    }
+
+    #[test]
+    fn test_output_only_conflict_uses_policy() {
+        let base = notebook(vec![code_cell("a", "print(1)", Some(1), Some("1\n"))]);
+        let ours = notebook(vec![code_cell("a", "print(1)", Some(5), Some("1\n"))]);
+        let theirs = notebook(vec![code_cell("a", "print(1)", Some(9), Some("1\n"))]);
+
+        let result = merge(&base, &ours, &theirs, OutputPolicy::Clear);
+        assert!(result.is_clean());
+        let merged: Value = serde_json::from_str(&result.content).unwrap();
+        assert_eq!(merged["cells"][0]["execution_count"], Value::Null);
+        assert_eq!(merged["cells"][0]["outputs"], json!([]));
+
+        let result = merge(&base, &ours, &theirs, OutputPolicy::Ours);
+        let merged: Value = serde_json::from_str(&result.content).unwrap();
+        assert_eq!(merged["cells"][0]["execution_count"], 5);
+    }
+
+    #[test]
+    fn test_metadata_changed_on_both_sides_keeps_ours_with_warning() {
+        let with_metadata = |tags: &str, kernel: &str| {
+            let mut cell = code_cell("a", "print(1)", Some(1), None);
+            cell["metadata"] = json!({ "tags": [tags] });
+            let mut nb: Value = serde_json::from_str(&notebook(vec![cell])).unwrap();
+            nb["metadata"]["kernelspec"]["display_name"] = json!(kernel);
+            let mut s = to_notebook_json(&nb, " ").unwrap();
+            s.push('\n');
+            s
+        };
+        let base = with_metadata("slow", "Python 3");
+        let ours = with_metadata("fast", "Python 3.12");
+        let theirs = with_metadata("skip", "Python 3.11");
+
+        let result = merge(&base, &ours, &theirs, OutputPolicy::Ours);
+        let merged: Value = serde_json::from_str(&result.content).unwrap();
+        assert_eq!(merged["cells"][0]["metadata"]["tags"], json!(["fast"]));
+        assert_eq!(merged["metadata"]["kernelspec"]["display_name"], "Python 3.12");
+        let kept: Vec<(&str, &str)> = result
+            .warnings
+            .iter()
+            .map(|w| match &w.kind {
+                WarningKind::FieldKeptOurs { field } => (w.entity_type.as_str(), field.as_str()),
+                kind => panic!("unexpected warning {:?}", kind),
+            })
+            .collect();
+        assert_eq!(kept, vec![("cell", "metadata"), ("notebook", "metadata")]);
+    }
+
+    #[test]
+    fn test_cells_without_ids_matched_by_content() {
+        let strip = |mut cell: Value| {
+            cell.as_object_mut().unwrap().remove("id");
+            cell
+        };
+        let base = notebook(vec![strip(code_cell("", "a = 1", None, None)), strip(code_cell("", "b = 2", None, None))]);
+        let ours = notebook(vec![
+            strip(code_cell("", "a = 1", None, None)),
+            strip(code_cell("", "b = 2", None, None)),
+            strip(code_cell("", "c = 3", None, None)),
+        ]);
+        let theirs = notebook(vec![strip(code_cell("", "b = 2", None, None))]);
+
+        let result = merge(&base, &ours, &theirs, OutputPolicy::Ours);
+        assert!(result.is_clean());
+        let merged: Value = serde_json::from_str(&result.content).unwrap();
+        let sources: Vec<String> = merged["cells"].as_array().unwrap().iter().map(cell_source).collect();
+        assert_eq!(sources, vec!["b = 2", "c = 3"]);
+    }
+
+    #[test]
+    fn test_round_trip_formatting() {
+        let nb = notebook(vec![code_cell("a", "print(1)", Some(1), None)]);
+        let ours = notebook(vec![code_cell("a", "print(2)", Some(1), None)]);
+        let result = merge(&nb, &ours, &nb, OutputPolicy::Ours);
+        assert_eq!(result.content, ours);
+    }
+
+    #[test]
+    fn test_not_a_notebook() {
+        let registry = sem_core::parser::plugins::create_default_registry();
+        assert!(merge_notebook("{", "{}", "{}", "nb.ipynb", &registry, &NotebookConfig::default(), &MergeContext::default()).is_none());
+        assert!(merge_notebook("{}", "{}", "{}", "nb.ipynb", &registry, &NotebookConfig::default(), &MergeContext::default()).is_none());
+    }
+}
@@ -0,0 +1,243 @@
+//! Merging many files at once: the versions of every file are loaded in one
+//! pass over the repository, then merged concurrently on a pool of scoped
+//! worker threads. Results come back in the order the files were given,
+//! whatever order the workers finish in.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use sem_core::parser::registry::ParserRegistry;
+
+use crate::config::WeaveConfig;
+use crate::git::git_show_batch;
+use crate::merge::{entity_merge_in, MergeContext, MergeResult};
+use crate::protect::conflict_whole_file;
+use crate::validate::{supported_files, validate_versions, FileVersions, SemanticWarning};
+
+/// A file's versions and, when both sides changed it, its merge.
+#[derive(Debug)]
+pub struct MergedFile {
+    pub versions: FileVersions,
+    /// `None` when there was nothing to merge: the sides are identical, or
+    /// only one changed the file.
+    pub result: Option<MergeResult>,
+}
+
+impl MergedFile {
+    /// The file after the merge: the merge's content, conflicted or not, or
+    /// else the side that changed it. `None` when the merge deletes it.
+    pub fn content(&self) -> Option<&str> {
+        let versions = &self.versions;
+        let [base_exists, ours_exists, theirs_exists] = versions.exists;
+        match &self.result {
+            Some(result) => Some(&result.content),
+            None if versions.ours == versions.base && ours_exists == base_exists => {
+                theirs_exists.then_some(&versions.theirs)
+            }
+            None => ours_exists.then_some(&versions.ours),
+        }
+    }
+}
+
+/// Load the base, ours and theirs versions of `files` from `repo`. A file
+/// missing at a revision is empty there, and marked missing in `exists`.
+pub fn load_versions(
+    repo: &Path,
+    merge_base: &str,
+    ours: &str,
+    theirs: &str,
+    files: &[String],
+) -> Result<Vec<FileVersions>, Box<dyn std::error::Error>> {
+    let specs: Vec<(&str, &str)> = files
+        .iter()
+        .flat_map(|file| [(merge_base, file.as_str()), (ours, file.as_str()), (theirs, file.as_str())])
+        .collect();
+    let mut blobs = git_show_batch(repo, &specs)?.into_iter();
+    Ok(files
+        .iter()
+        .map(|file| {
+            let [base, ours, theirs] = [(); 3].map(|_| blobs.next().flatten());
+            FileVersions {
+                file_path: file.clone(),
+                exists: [base.is_some(), ours.is_some(), theirs.is_some()],
+                base: base.unwrap_or_default(),
+                ours: ours.unwrap_or_default(),
+                theirs: theirs.unwrap_or_default(),
+            }
+        })
+        .collect())
+}
+
+/// Whether both sides changed a file, differently.
+pub fn needs_merge(versions: &FileVersions) -> bool {
+    versions.ours != versions.theirs && versions.base != versions.ours && versions.base != versions.theirs
+}
+
+/// Merge every file both sides changed, concurrently, in `context`. Each
+/// file gets `timeout` (usually `config.merge.timeout()`), if any, under the
+/// context's budget; cancelling that budget stops them all.
+pub fn merge_files(
+    files: Vec<FileVersions>,
+    registry: &ParserRegistry,
+    config: &WeaveConfig,
+    context: &MergeContext,
+    timeout: Option<Duration>,
+) -> Vec<MergedFile> {
+    let results = par_map(&files, |versions| {
+        needs_merge(versions).then(|| {
+            let context = MergeContext {
+                budget: timeout.map_or_else(|| context.budget.clone(), |t| context.budget.child(t)),
+                ..context.clone()
+            };
+            entity_merge_in(
+                &versions.base,
+                &versions.ours,
+                &versions.theirs,
+                &versions.file_path,
+                registry,
+                config,
+                &context,
+            )
+        })
+    });
+    files
+        .into_iter()
+        .zip(results)
+        .map(|(versions, result)| MergedFile { versions, result })
+        .collect()
+}
+
+/// Cross-entity validation of a whole merge, once every file is merged: the
+/// versions of all of `merged` are checked together, against the files at
+/// `repo_root`. With `[validate] fail_on_warnings`, each file a warning is
+/// about that merged cleanly becomes a conflict, rather than merging
+/// silently.
+pub fn validate_merged(
+    repo_root: &Path,
+    merged: &mut [MergedFile],
+    registry: &ParserRegistry,
+    config: &WeaveConfig,
+) -> Vec<SemanticWarning> {
+    let versions: Vec<FileVersions> = merged.iter().map(|m| m.versions.clone()).collect();
+    let warnings = validate_versions(repo_root, &supported_files(repo_root, registry), &versions, registry);
+    if config.validate.fail_on_warnings {
+        for MergedFile { versions, result } in merged.iter_mut() {
+            let flagged = warnings.iter().any(|w| w.file_path == versions.file_path);
+            if let Some(result) = result.as_mut().filter(|r| flagged && r.is_clean()) {
+                conflict_whole_file(result, [&versions.base, &versions.ours, &versions.theirs]);
+            }
+        }
+    }
+    warnings
+}
+
+/// Map `f` over `items` on one scoped thread per core, keeping the order of
+/// `items`. A panic in `f` is resumed on the calling thread.
+pub fn par_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let workers = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(items.len());
+    if workers <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let (f, next) = (&f, &next);
+    let done: Vec<(usize, R)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(move || {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(i) else {
+                            return done;
+                        };
+                        done.push((i, f(item)));
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect()
+    });
+
+    let mut results: Vec<Option<R>> = std::iter::repeat_with(|| None).take(items.len()).collect();
+    for (i, result) in done {
+        results[i] = Some(result);
+    }
+    results.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_par_map_keeps_order() {
+        let items: Vec<usize> = (0..1000).collect();
+        assert_eq!(par_map(&items, |i| i * 2), (0..1000).map(|i| i * 2).collect::<Vec<_>>());
+        assert!(par_map(&[] as &[usize], |i| *i).is_empty());
+    }
+
+    #[test]
+    fn test_merge_files_skips_one_sided_changes() {
+        let versions = |path: &str, base: &str, ours: &str, theirs: &str| FileVersions {
+            file_path: path.to_string(),
+            base: base.to_string(),
+            ours: ours.to_string(),
+            theirs: theirs.to_string(),
+            exists: [true; 3],
+        };
+        let files = vec![
+            versions("a.txt", "1\n2\n3\n", "1\n2\n3\n", "1\n2\nthree\n"),
+            versions("b.txt", "1\n2\n3\n4\n5\n", "one\n2\n3\n4\n5\n", "1\n2\n3\n4\nfive\n"),
+        ];
+        let merged = merge_files(files, crate::merge::shared_registry(), &WeaveConfig::default(), &MergeContext::default(), None);
+
+        assert_eq!(merged.len(), 2);
+        assert!(merged[0].result.is_none());
+        assert_eq!(merged[1].versions.file_path, "b.txt");
+        let result = merged[1].result.as_ref().unwrap();
+        assert!(result.is_clean());
+        assert_eq!(result.content, "one\n2\n3\n4\nfive\n");
+    }
+
+    #[test]
+    fn test_failing_validation_conflicts_the_flagged_file() {
+        let utils_base = "export function formatDate(d: Date): string {\n    return d.toISOString();\n}\n\nexport function keep(): number {\n    return 1;\n}\n";
+        let utils_ours = "export function keep(): number {\n    return 1;\n}\n";
+        let view_base = "import { keep } from './utils';\n\nexport function render(): number {\n    return keep();\n}\n";
+        let view_ours = "import { keep } from './utils';\n\nexport function render(): number {\n    return keep() + 1;\n}\n";
+        let view_theirs = "import { formatDate, keep } from './utils';\n\nexport function render(): number {\n    return keep();\n}\n\nexport function stamp(): string {\n    return formatDate(new Date());\n}\n";
+        let files = vec![
+            FileVersions {
+                file_path: "src/utils.ts".to_string(),
+                base: utils_base.to_string(),
+                ours: utils_ours.to_string(),
+                theirs: utils_base.to_string(),
+                exists: [true; 3],
+            },
+            FileVersions {
+                file_path: "src/view.ts".to_string(),
+                base: view_base.to_string(),
+                ours: view_ours.to_string(),
+                theirs: view_theirs.to_string(),
+                exists: [true; 3],
+            },
+        ];
+        let registry = crate::merge::shared_registry();
+        let config = WeaveConfig::from_toml("[validate]\nfail_on_warnings = true\n").unwrap();
+        let mut merged = merge_files(files, registry, &config, &MergeContext::default(), None);
+        assert!(merged[1].result.as_ref().unwrap().is_clean());
+
+        let dir = tempfile::tempdir().unwrap();
+        let warnings = validate_merged(dir.path(), &mut merged, registry, &config);
+        assert!(warnings.iter().any(|w| w.file_path == "src/view.ts"));
+        assert!(merged[0].result.is_none());
+        assert!(!merged[1].result.as_ref().unwrap().is_clean());
+    }
+}
@@ -0,0 +1,227 @@
+//! Auto-resolution policies (`[[policy]]` in `.weave/config.toml`).
+//!
+//! A policy resolves conflicts of the given complexities by picking a side,
+//! e.g. the longer side of a comment-only (`text`) conflict. Policies are
+//! tried in order and the first one matching the file and entity type wins.
+//! Every policy-resolved entity is counted in
+//! [`MergeStats::resolved_via_policy`](crate::conflict::MergeStats::resolved_via_policy)
+//! and reported as a [`WarningKind::ResolvedByPolicy`] warning, so nothing
+//! is picked silently. Conflicts on protected entities (see
+//! [`crate::protect`]) are always left to the user.
+
+use crate::config::{PolicyResolution, ResolutionPolicy, WeaveConfig};
+use crate::conflict::EntityConflict;
+use crate::glob::glob_match;
+use crate::merge::MergeResult;
+use crate::protect::is_protected_conflict;
+use crate::validate::{SemanticWarning, WarningKind};
+
+/// Resolve every conflict a policy applies to. Returns how many were resolved.
+///
+/// Only conflicts rendered as a block of their own can be resolved, as
+/// recorded in [`MergeResult::conflict_blocks`].
+pub fn apply_policies(result: &mut MergeResult, file_path: &str, config: &WeaveConfig) -> usize {
+    if config.policies.is_empty() {
+        return 0;
+    }
+    let mut resolutions = Vec::new();
+    let mut warnings = Vec::new();
+    for (index, conflict) in result.conflicts.iter().enumerate() {
+        let Some(policy) = config.policies.iter().find(|p| applies(p, file_path, conflict)) else {
+            continue;
+        };
+        if result.conflict_block(index).is_none() || is_protected_conflict(conflict, file_path, config) {
+            continue;
+        }
+        let (side, took_ours) = pick(policy.resolve, conflict);
+        let mut replacement = side.unwrap_or_default().to_string();
+        if !replacement.is_empty() && !replacement.ends_with('\n') {
+            replacement.push('\n');
+        }
+        resolutions.push((index, replacement));
+        warnings.push(SemanticWarning {
+            entity_name: conflict.entity_name.clone(),
+            entity_type: conflict.entity_type.clone(),
+            file_path: file_path.to_string(),
+            kind: WarningKind::ResolvedByPolicy {
+                complexity: conflict.complexity.clone(),
+                took_ours,
+            },
+            related: vec![],
+        });
+    }
+
+    let indices: Vec<usize> = resolutions.iter().map(|(index, _)| *index).collect();
+    let resolved = result.resolve_conflicts(resolutions);
+    result.warnings.extend(
+        indices
+            .into_iter()
+            .zip(warnings)
+            .filter(|(index, _)| resolved.contains(index))
+            .map(|(_, warning)| warning),
+    );
+    result.stats.entities_conflicted = result.stats.entities_conflicted.saturating_sub(resolved.len());
+    result.stats.resolved_via_policy += resolved.len();
+    resolved.len()
+}
+
+fn applies(policy: &ResolutionPolicy, file_path: &str, conflict: &EntityConflict) -> bool {
+    policy.complexity.contains(&conflict.complexity)
+        && policy.files.as_deref().is_none_or(|glob| glob_match(glob, file_path))
+        && (policy.entity_types.is_empty() || policy.entity_types.contains(&conflict.entity_type))
+}
+
+/// The side a policy keeps, and whether it is ours.
+fn pick(resolution: PolicyResolution, conflict: &EntityConflict) -> (Option<&str>, bool) {
+    let ours = conflict.ours_content.as_deref();
+    let theirs = conflict.theirs_content.as_deref();
+    match resolution {
+        PolicyResolution::Ours => (ours, true),
+        PolicyResolution::Theirs => (theirs, false),
+        PolicyResolution::Longer => {
+            if significant_len(theirs) > significant_len(ours) {
+                (theirs, false)
+            } else {
+                (ours, true)
+            }
+        }
+    }
+}
+
+fn significant_len(content: Option<&str>) -> usize {
+    content.map_or(0, |c| c.chars().filter(|c| !c.is_whitespace()).count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conflict::{ConflictComplexity, ConflictKind, MergeStats};
+    use crate::provenance::{LineOrigin, Provenance};
+
+    fn conflict(name: &str, complexity: ConflictComplexity, ours: &str, theirs: &str) -> EntityConflict {
+        EntityConflict {
+            entity_name: name.to_string(),
+            entity_type: "function".to_string(),
+            kind: ConflictKind::BothModified,
+            complexity,
+            ours_content: Some(ours.to_string()),
+            theirs_content: Some(theirs.to_string()),
+            base_content: Some(format!("fn {}() {{}}\n", name)),
+        }
+    }
+
+    fn conflicted(conflicts: Vec<EntityConflict>) -> MergeResult {
+        let mut content = String::new();
+        for c in &conflicts {
+            content.push_str(&c.to_conflict_markers());
+            content.push('\n');
+        }
+        MergeResult {
+            content,
+            stats: MergeStats {
+                entities_conflicted: conflicts.len(),
+                ..Default::default()
+            },
+            conflict_blocks: (0..conflicts.len()).map(Some).collect(),
+            conflicts,
+            ..Default::default()
+        }
+    }
+
+    fn config(toml: &str) -> WeaveConfig {
+        WeaveConfig::from_toml(toml).unwrap()
+    }
+
+    #[test]
+    fn test_longer_side_wins_text_conflicts() {
+        let mut result = conflicted(vec![
+            conflict("a", ConflictComplexity::Text, "// short\nfn a() {}\n", "// much longer\nfn a() {}\n"),
+            conflict("b", ConflictComplexity::Functional, "fn b() { 1 }\n", "fn b() { 2 }\n"),
+        ]);
+        let config = config("[[policy]]\ncomplexity = [\"text\"]\nresolve = \"longer\"\n");
+
+        assert_eq!(apply_policies(&mut result, "src/lib.rs", &config), 1);
+        assert!(result.content.starts_with("// much longer\nfn a() {}\n\n<<<<<<<"));
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].entity_name, "b");
+        assert_eq!(result.stats.entities_conflicted, 1);
+        assert_eq!(result.stats.resolved_via_policy, 1);
+        assert!(matches!(
+            result.warnings[0].kind,
+            WarningKind::ResolvedByPolicy { took_ours: false, .. }
+        ));
+    }
+
+    #[test]
+    fn test_provenance_follows_the_resolution() {
+        let mut result = conflicted(vec![
+            conflict("a", ConflictComplexity::Text, "// short\nfn a() {}\n", "// much longer\nfn a() {}\n"),
+            conflict("b", ConflictComplexity::Functional, "fn b() { 1 }\n", "fn b() { 2 }\n"),
+        ]);
+        let lines = vec![LineOrigin::Conflict; result.content.lines().count()];
+        result.provenance = Some(Provenance { lines });
+        let config = config("[[policy]]\ncomplexity = [\"text\"]\nresolve = \"longer\"\n");
+
+        assert_eq!(apply_policies(&mut result, "src/lib.rs", &config), 1);
+        let lines = &result.provenance.as_ref().unwrap().lines;
+        assert_eq!(lines.len(), result.content.lines().count());
+        assert_eq!(lines[..3], [LineOrigin::Theirs, LineOrigin::Base, LineOrigin::Conflict]);
+        assert!(lines[3..].iter().all(|&origin| origin == LineOrigin::Conflict));
+    }
+
+    #[test]
+    fn test_policy_scoped_by_file_and_entity_type() {
+        let config = config(
+            "[[policy]]\ncomplexity = [\"syntax\"]\nresolve = \"ours\"\nfiles = \"src/**/*.rs\"\nentity_types = [\"function\"]\n",
+        );
+        let syntax = || vec![conflict("a", ConflictComplexity::Syntax, "fn a() {\n}\n", "fn a() {}\n")];
+
+        let mut result = conflicted(syntax());
+        assert_eq!(apply_policies(&mut result, "tests/it.rs", &config), 0);
+        assert_eq!(apply_policies(&mut result, "src/merge/inner.rs", &config), 1);
+        assert_eq!(result.content, "fn a() {\n}\n\n");
+
+        let mut result = conflicted(syntax());
+        result.conflicts[0].entity_type = "struct".to_string();
+        result.content = result.conflicts[0].to_conflict_markers();
+        assert_eq!(apply_policies(&mut result, "src/lib.rs", &config), 0);
+        assert!(!result.is_clean());
+    }
+
+    #[test]
+    fn test_resolves_the_block_recorded_for_the_conflict() {
+        let a = conflict("a", ConflictComplexity::Text, "// a\nfn a() {}\n", "// aa\nfn a() {}\n");
+        let b = conflict("b", ConflictComplexity::Functional, "fn b() { 1 }\n", "fn b() { 2 }\n");
+        // A line-level block with no conflict of its own comes first
+        let raw = "<<<<<<< ours\nx\n=======\ny\n>>>>>>> theirs\n";
+        let mut result = MergeResult {
+            content: format!("{}{}{}", raw, a.to_conflict_markers(), b.to_conflict_markers()),
+            conflict_blocks: vec![Some(1), Some(2)],
+            conflicts: vec![a, b.clone()],
+            ..Default::default()
+        };
+        let config = config("[[policy]]\ncomplexity = [\"text\"]\nresolve = \"longer\"\n");
+
+        assert_eq!(apply_policies(&mut result, "src/lib.rs", &config), 1);
+        assert_eq!(result.content, format!("{}// aa\nfn a() {{}}\n{}", raw, b.to_conflict_markers()));
+        assert_eq!(result.conflict_blocks, vec![Some(1)]);
+
+        // Without a block of its own, a conflict is left alone
+        let mut result = conflicted(vec![conflict("c", ConflictComplexity::Text, "// c\n", "// cc\n")]);
+        result.conflict_blocks = vec![None];
+        assert_eq!(apply_policies(&mut result, "src/lib.rs", &config), 0);
+    }
+
+    #[test]
+    fn test_protected_entities_are_never_resolved() {
+        let config = config(
+            "[[policy]]\ncomplexity = [\"text\"]\nresolve = \"ours\"\n\n[[protected]]\nnames = [\"check_*\"]\n",
+        );
+        let mut result = conflicted(vec![
+            conflict("check_token", ConflictComplexity::Text, "// a\nfn check_token() {}\n", "// b\nfn check_token() {}\n"),
+            conflict("sign", ConflictComplexity::Text, "// weave:protected\nfn sign() {}\n", "fn sign() {}\n"),
+        ]);
+        assert_eq!(apply_policies(&mut result, "src/auth.rs", &config), 0);
+        assert_eq!(result.conflicts.len(), 2);
+    }
+}
@@ -0,0 +1,343 @@
+//! Protected entities: never auto-merged when both branches change them.
+//!
+//! An entity is protected when it matches a `[[protected]]` pattern in
+//! `.weave/config.toml`, or when its leading comment carries a
+//! `weave:protected` annotation. Concurrent edits to a protected entity
+//! always conflict, whichever merger handled the file, and neither rerere
+//! nor policies resolve those conflicts.
+
+use std::collections::{BTreeSet, HashMap};
+
+use sem_core::model::entity::SemanticEntity;
+use sem_core::parser::registry::ParserRegistry;
+
+use crate::cache::{cached_entities, EntityCache};
+use crate::config::{ProtectedPattern, WeaveConfig};
+use crate::conflict::{block_spans, classify_conflict, classify_conflict_in, count_blocks, ConflictKind, EntityConflict};
+use crate::glob::glob_match;
+use crate::merge::{filter_nested_entities, MergeResult};
+use crate::provenance::LineOrigin;
+use crate::region::{extract_regions, EntityRegion, FileRegion};
+
+/// Marks an entity as protected from its source, e.g. `// weave:protected`.
+pub const PROTECTED_ANNOTATION: &str = "weave:protected";
+
+/// A protected entity found in a file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ProtectedEntity {
+    pub name: String,
+    pub entity_type: String,
+}
+
+impl ProtectedPattern {
+    /// Whether an entity matches every criterion this pattern sets.
+    pub fn matches(&self, file_path: &str, entity_type: &str, name: &str) -> bool {
+        self.files.as_deref().is_none_or(|glob| glob_match(glob, file_path))
+            && (self.entity_types.is_empty() || self.entity_types.iter().any(|t| t == entity_type))
+            && (self.names.is_empty() || self.names.iter().any(|glob| glob_match(glob, name)))
+    }
+}
+
+/// Whether an entity is protected, by config or by an annotation in the
+/// leading comment of any of its versions.
+pub fn is_protected(
+    patterns: &[ProtectedPattern],
+    file_path: &str,
+    entity_type: &str,
+    name: &str,
+    versions: &[Option<&str>],
+) -> bool {
+    patterns.iter().any(|p| p.matches(file_path, entity_type, name))
+        || versions.iter().flatten().any(|content| annotated(content))
+}
+
+/// Whether a conflict is on a protected entity, so no resolver may pick a
+/// side for it.
+pub(crate) fn is_protected_conflict(conflict: &EntityConflict, file_path: &str, config: &WeaveConfig) -> bool {
+    let versions = [
+        conflict.base_content.as_deref(),
+        conflict.ours_content.as_deref(),
+        conflict.theirs_content.as_deref(),
+    ];
+    is_protected(&config.protected, file_path, &conflict.entity_type, &conflict.entity_name, &versions)
+}
+
+/// Whether the comment lines an entity opens with carry the annotation. A
+/// mention in its body (a string, a comment further down) doesn't count.
+fn annotated(content: &str) -> bool {
+    content
+        .lines()
+        .map(str::trim_start)
+        .take_while(|line| line.is_empty() || is_comment_line(line))
+        .any(|line| line.contains(PROTECTED_ANNOTATION))
+}
+
+/// Whether the comment lines right above line `start` (1-based) carry the
+/// annotation. Regions only bundle doc comments, not a plain `//` line.
+fn annotated_above(lines: &[&str], start: usize) -> bool {
+    lines[..start.saturating_sub(1).min(lines.len())]
+        .iter()
+        .rev()
+        .map(|line| line.trim_start())
+        .take_while(|line| is_comment_line(line))
+        .any(|line| line.contains(PROTECTED_ANNOTATION))
+}
+
+/// The top-level entity regions of `content`, each with whether its
+/// leading comment carries the annotation.
+fn entity_regions(content: &str, entities: Vec<SemanticEntity>) -> Vec<(EntityRegion, bool)> {
+    let lines: Vec<&str> = content.lines().collect();
+    extract_regions(content, &filter_nested_entities(entities))
+        .into_iter()
+        .filter_map(|region| match region {
+            FileRegion::Entity(entity) => {
+                let annotated = annotated(&entity.content) || annotated_above(&lines, entity.start_line);
+                Some((entity, annotated))
+            }
+            FileRegion::Interstitial(_) => None,
+        })
+        .collect()
+}
+
+/// Whether a left-trimmed line is a comment, in any supported language.
+pub(crate) fn is_comment_line(line: &str) -> bool {
+    ["//", "/*", "*", "#", "--", "<!--", ";"].iter().any(|prefix| line.starts_with(prefix))
+}
+
+/// Conflict every protected entity both sides changed that `result` merged
+/// anyway. The merge runs this after every resolver (entity merge, format
+/// mergers, line fallbacks, generated files, rerere, policies), so none of
+/// them merges a protected entity silently. Returns how many entities it
+/// conflicted.
+///
+/// A protected entity that can't be found in the merged content conflicts
+/// the whole file.
+pub(crate) fn enforce_protection(
+    result: &mut MergeResult,
+    versions: [&str; 3],
+    file_path: &str,
+    registry: &ParserRegistry,
+    config: &WeaveConfig,
+    entity_cache: Option<&EntityCache>,
+) -> usize {
+    if config.protected.is_empty() && !versions.iter().any(|v| v.contains(PROTECTED_ANNOTATION)) {
+        return 0;
+    }
+    let Some(plugin) = registry.get_plugin(file_path).filter(|p| p.id() != "fallback") else {
+        return 0;
+    };
+    let regions = |content: &str| -> HashMap<String, (EntityRegion, bool)> {
+        let entities = cached_entities(entity_cache, plugin.id(), file_path, content, || {
+            plugin.extract_entities(content, file_path)
+        });
+        entity_regions(content, entities)
+            .into_iter()
+            .map(|(entity, annotated)| (entity.entity_id.clone(), (entity, annotated)))
+            .collect()
+    };
+
+    let [base, ours, theirs] = versions;
+    let (base_regions, theirs_regions) = (regions(base), regions(theirs));
+    let contested: Vec<(String, EntityConflict)> = regions(ours)
+        .into_values()
+        .filter_map(|(o, ours_annotated)| {
+            let (b, base_annotated) = base_regions.get(&o.entity_id)?;
+            let (t, theirs_annotated) = theirs_regions.get(&o.entity_id)?;
+            let both_changed = b.content != o.content && b.content != t.content && o.content != t.content;
+            let protected = ours_annotated
+                || *base_annotated
+                || *theirs_annotated
+                || is_protected(&config.protected, file_path, &o.entity_type, &o.entity_name, &[]);
+            if !both_changed || !protected {
+                return None;
+            }
+            let conflict = EntityConflict {
+                entity_name: o.entity_name,
+                entity_type: o.entity_type,
+                kind: ConflictKind::BothModified,
+                complexity: classify_conflict_in(file_path, Some(&b.content), Some(&o.content), Some(&t.content)),
+                ours_content: Some(o.content),
+                theirs_content: Some(t.content.clone()),
+                base_content: Some(b.content.clone()),
+            };
+            Some((o.entity_id, conflict))
+        })
+        .collect();
+    if contested.is_empty() {
+        return 0;
+    }
+
+    // Where each contested entity ended up, unless already in a conflict block
+    let merged = regions(&result.content);
+    let spans: Vec<_> = block_spans(&result.content).into_iter().flatten().collect();
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(result.content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let byte_range = |region: &EntityRegion| {
+        // From the first line of its bundled doc comment
+        let first_line = (region.end_line + 1).saturating_sub(region.content.lines().count().max(1));
+        let start = line_starts.get(first_line.saturating_sub(1)).copied().unwrap_or(result.content.len());
+        let end = line_starts.get(region.end_line).copied().unwrap_or(result.content.len());
+        start..end
+    };
+    let mut targets = Vec::new();
+    for (id, conflict) in contested {
+        match merged.get(&id) {
+            Some((region, _)) => {
+                let range = byte_range(region);
+                if !spans.iter().any(|span| span.start < range.end && range.start < span.end) {
+                    targets.push((range, conflict));
+                }
+            }
+            None => {
+                let reported = result.conflicts.iter().any(|c| c.entity_name == conflict.entity_name);
+                if !reported {
+                    conflict_whole_file(result, versions);
+                    return 1;
+                }
+            }
+        }
+    }
+
+    // Bottom up, so the ranges still to replace stay valid
+    targets.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+    let forced = targets.len();
+    for (range, conflict) in targets {
+        let block = count_blocks(&result.content[..range.start]);
+        for recorded in result.conflict_blocks.iter_mut().flatten() {
+            if *recorded >= block {
+                *recorded += 1;
+            }
+        }
+        let markers = conflict.to_conflict_markers();
+        if let Some(provenance) = &mut result.provenance {
+            let start = result.content[..range.start].matches('\n').count();
+            let end = start + result.content[range.clone()].lines().count();
+            provenance.splice(start..end, markers.lines().map(|_| LineOrigin::Conflict));
+        }
+        result.content.replace_range(range, &markers);
+        result.conflicts.push(conflict);
+        result.conflict_blocks.push(Some(block));
+        result.stats.entities_conflicted += 1;
+    }
+    forced
+}
+
+/// Replace `result` with a single conflict spanning the whole file.
+pub(crate) fn conflict_whole_file(result: &mut MergeResult, [base, ours, theirs]: [&str; 3]) {
+    let conflict = EntityConflict {
+        entity_name: "(file)".to_string(),
+        entity_type: "file".to_string(),
+        kind: ConflictKind::BothModified,
+        complexity: classify_conflict(Some(base), Some(ours), Some(theirs)),
+        ours_content: Some(ours.to_string()),
+        theirs_content: Some(theirs.to_string()),
+        base_content: Some(base.to_string()),
+    };
+    result.content = conflict.to_conflict_markers();
+    if let Some(provenance) = &mut result.provenance {
+        provenance.lines = vec![LineOrigin::Conflict; result.content.lines().count()];
+    }
+    result.conflicts = vec![conflict];
+    result.conflict_blocks = vec![Some(0)];
+    result.stats.entities_conflicted = 1;
+}
+
+/// The protected entities of the given versions of a file, deduplicated
+/// and sorted.
+pub fn protected_entities(
+    versions: &[&str],
+    file_path: &str,
+    registry: &ParserRegistry,
+    config: &WeaveConfig,
+) -> Vec<ProtectedEntity> {
+    let Some(plugin) = registry.get_plugin(file_path) else {
+        return vec![];
+    };
+    let mut found = BTreeSet::new();
+    for content in versions {
+        let entities = plugin.extract_entities(content, file_path);
+        for (entity, annotated) in entity_regions(content, entities) {
+            if annotated || is_protected(&config.protected, file_path, &entity.entity_type, &entity.entity_name, &[]) {
+                found.insert(ProtectedEntity {
+                    name: entity.entity_name,
+                    entity_type: entity.entity_type,
+                });
+            }
+        }
+    }
+    found.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(toml: &str) -> Vec<ProtectedPattern> {
+        WeaveConfig::from_toml(toml).unwrap().protected
+    }
+
+    #[test]
+    fn test_pattern_criteria_all_apply() {
+        let patterns = patterns(
+            "[[protected]]\nfiles = \"src/auth/**\"\nentity_types = [\"function\"]\nnames = [\"check_*\", \"verify\"]\n",
+        );
+        assert!(is_protected(&patterns, "src/auth/token.rs", "function", "check_scope", &[]));
+        assert!(is_protected(&patterns, "src/auth/token.rs", "function", "verify", &[]));
+        assert!(!is_protected(&patterns, "src/auth/token.rs", "function", "refresh", &[]));
+        assert!(!is_protected(&patterns, "src/auth/token.rs", "struct", "check_scope", &[]));
+        assert!(!is_protected(&patterns, "src/api.rs", "function", "check_scope", &[]));
+    }
+
+    #[test]
+    fn test_annotation_only_in_leading_comment() {
+        let leading = "/// Bumped by migrations.\n/// weave:protected\nconst SCHEMA_VERSION: u32 = 7;\n";
+        assert!(is_protected(&[], "src/db.rs", "constant", "SCHEMA_VERSION", &[Some(leading)]));
+        let in_body = "fn check() {\n    // weave:protected\n}\n";
+        assert!(!is_protected(&[], "src/auth.rs", "function", "check", &[Some(in_body)]));
+        let in_string = "const NOTE: &str = \"weave:protected\";\n";
+        assert!(!is_protected(&[], "src/lib.rs", "constant", "NOTE", &[Some(in_string)]));
+    }
+
+    #[test]
+    fn test_enforced_on_any_resolver_result() {
+        let base = "fn check() {\n    1\n}\n\nfn other() {\n    1\n}\n";
+        let ours = "fn check() {\n    // ours\n    1\n}\n\nfn other() {\n    2\n}\n";
+        let theirs = "fn check() {\n    1\n    // theirs\n}\n\nfn other() {\n    3\n}\n";
+        // As a line merge might leave it: `check` combined, `other` conflicted
+        let other = "<<<<<<< ours\nfn other() {\n    2\n}\n=======\nfn other() {\n    3\n}\n>>>>>>> theirs\n";
+        let mut result = MergeResult {
+            content: format!("fn check() {{\n    // ours\n    1\n    // theirs\n}}\n\n{}", other),
+            conflicts: vec![EntityConflict {
+                entity_name: "other".to_string(),
+                entity_type: "function".to_string(),
+                kind: ConflictKind::BothModified,
+                complexity: classify_conflict(Some("1"), Some("2"), Some("3")),
+                ours_content: None,
+                theirs_content: None,
+                base_content: None,
+            }],
+            conflict_blocks: vec![Some(0)],
+            ..Default::default()
+        };
+        let config = WeaveConfig::from_toml("[[protected]]\nnames = [\"check\"]\n").unwrap();
+        let registry = sem_core::parser::plugins::create_default_registry();
+
+        assert_eq!(enforce_protection(&mut result, [base, ours, theirs], "src/auth.rs", &registry, &config, None), 1);
+        assert_eq!(result.conflicts[1].entity_name, "check");
+        assert!(result.content.starts_with("<<<<<<< ours"));
+        assert!(result.content.ends_with(other));
+        // The forced conflict comes first, so the recorded block moves down
+        assert_eq!(result.conflict_blocks, vec![Some(1), Some(0)]);
+
+        // Once conflicted, it is left alone
+        assert_eq!(enforce_protection(&mut result, [base, ours, theirs], "src/auth.rs", &registry, &config, None), 0);
+    }
+
+    #[test]
+    fn test_annotation_in_any_version() {
+        let annotated = "// weave:protected\nconst SCHEMA_VERSION: u32 = 7;\n";
+        assert!(is_protected(&[], "src/db.rs", "constant", "SCHEMA_VERSION", &[None, Some(annotated)]));
+        assert!(!is_protected(&[], "src/db.rs", "constant", "SCHEMA_VERSION", &[Some("const X: u32 = 1;\n")]));
+    }
+}
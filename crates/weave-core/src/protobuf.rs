@@ -0,0 +1,250 @@
+//! Entity-level merge for Protocol Buffers schemas (`.proto`).
+//!
+//! Messages, enums, services and RPCs are the entities; fields inside a
+//! message (and values inside an enum) merge as keyed members, so two
+//! branches adding different fields to the same message merge cleanly.
+//!
+//! Two branches that each add a field with the same number merge without a
+//! textual conflict but break the wire format, so the merged schema is
+//! checked for field-number collisions that neither side had on its own.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::braces::{self, Block, Item, Syntax};
+use crate::conflict::MergeStats;
+use crate::merge::MergeResult;
+use crate::validate::{RelatedEntity, SemanticWarning, WarningKind};
+
+/// Check if a file is a protobuf schema.
+pub fn is_protobuf(file_path: &str) -> bool {
+    file_path.to_lowercase().ends_with(".proto")
+}
+
+const PROTO: Syntax = Syntax {
+    line_comments: &["//"],
+    newline_terminates: false,
+    descriptions: false,
+    key: item_key,
+    entity_type,
+};
+
+/// Merge three versions of a `.proto` file by message, enum, service and field.
+///
+/// Returns None if any version can't be parsed (unbalanced braces).
+pub fn merge_protobuf(base: &str, ours: &str, theirs: &str, file_path: &str) -> Option<MergeResult> {
+    let base_file = braces::parse(base, &PROTO)?;
+    let ours_file = braces::parse(ours, &PROTO)?;
+    let theirs_file = braces::parse(theirs, &PROTO)?;
+
+    let mut stats = MergeStats::default();
+    let mut conflicts = Vec::new();
+    let merged = braces::merge_block(&base_file, &ours_file, &theirs_file, &PROTO, &mut stats, &mut conflicts);
+    let (content, conflict_blocks) = merged.render_merged(conflicts.len());
+
+    let mut warnings = Vec::new();
+    if conflicts.is_empty() {
+        if let Some(merged_file) = braces::parse(&content, &PROTO) {
+            warnings = field_number_collisions(&ours_file, &theirs_file, &merged_file, file_path);
+        }
+    }
+
+    Some(MergeResult {
+        content,
+        conflicts,
+        conflict_blocks,
+        warnings,
+        stats,
+        ..Default::default()
+    })
+}
+
+/// Entity type for conflict reports.
+fn entity_type(kind: &str) -> &str {
+    match kind {
+        "stmt" => "statement",
+        other => other,
+    }
+}
+
+/// Key for matching an item across versions.
+fn item_key(item: &Item) -> String {
+    let normalized = item.normalized_header();
+    let mut words = normalized.split_whitespace();
+    let first = words.next().unwrap_or("");
+
+    match first {
+        "message" | "enum" | "service" | "oneof" | "rpc" => {
+            let name = words.next().unwrap_or("");
+            let name = name.split('(').next().unwrap_or(name);
+            format!("{}:{}", first, name)
+        }
+        "extend" => format!("extend:{}", words.collect::<Vec<_>>().join(" ")),
+        "syntax" | "edition" | "package" => format!("{}:", first),
+        "import" | "reserved" => format!("{}:{}", first, normalized),
+        "option" => {
+            let name = normalized["option".len()..].split('=').next().unwrap_or("").trim();
+            format!("option:{}", name)
+        }
+        _ => match field_name(&normalized) {
+            Some(name) => format!("field:{}", name),
+            None => format!("stmt:{}", normalized),
+        },
+    }
+}
+
+/// The name of a field (`repeated string tags = 4 [...]`) or enum value
+/// (`ACTIVE = 1`): the word before the `=`.
+fn field_name(normalized: &str) -> Option<&str> {
+    let (left, _) = normalized.split_once('=')?;
+    let name = left.split_whitespace().last()?;
+    name.chars().all(|c| c.is_alphanumeric() || c == '_').then_some(name)
+}
+
+/// The number of a field statement, if it is one.
+fn field_number(item: &Item) -> Option<(String, u64)> {
+    if item.body.is_some() {
+        return None;
+    }
+    let normalized = item.normalized_header();
+    let name = field_name(&normalized)?;
+    let first = normalized.split_whitespace().next()?;
+    if first == "option" {
+        return None;
+    }
+    let (_, right) = normalized.split_once('=')?;
+    let digits: String = right.trim_start().chars().take_while(|c| c.is_ascii_digit()).collect();
+    Some((name.to_string(), digits.parse().ok()?))
+}
+
+/// Field numbers per message path, with the fields using each.
+type NumberMap = BTreeMap<(String, u64), BTreeSet<String>>;
+
+fn collect_numbers(block: &Block, path: &str, message: Option<&str>, out: &mut NumberMap) {
+    for (key, item) in &block.items {
+        if let Some(body) = &item.body {
+            let (kind, rest) = key.split_once(':').unwrap_or(("", ""));
+            let name = rest.rsplit_once('#').map(|(n, _)| n).unwrap_or(rest);
+            let nested = if path.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}.{}", path, name)
+            };
+            match kind {
+                "message" => collect_numbers(body, &nested, Some(&nested), out),
+                // Oneof members share the number space of their message
+                "oneof" => collect_numbers(body, path, message, out),
+                _ => {}
+            }
+        } else if let (Some(message), Some((name, number))) = (message, field_number(item)) {
+            out.entry((message.to_string(), number)).or_default().insert(name);
+        }
+    }
+}
+
+/// Field numbers used by more than one field in the merged schema, where
+/// neither side had that same collision on its own.
+fn field_number_collisions(ours: &Block, theirs: &Block, merged: &Block, file_path: &str) -> Vec<SemanticWarning> {
+    let mut ours_numbers = NumberMap::new();
+    let mut theirs_numbers = NumberMap::new();
+    let mut merged_numbers = NumberMap::new();
+    collect_numbers(ours, "", None, &mut ours_numbers);
+    collect_numbers(theirs, "", None, &mut theirs_numbers);
+    collect_numbers(merged, "", None, &mut merged_numbers);
+
+    let mut warnings = Vec::new();
+    for ((message, number), fields) in &merged_numbers {
+        if fields.len() < 2 {
+            continue;
+        }
+        let key = (message.clone(), *number);
+        if ours_numbers.get(&key) == Some(fields) || theirs_numbers.get(&key) == Some(fields) {
+            continue;
+        }
+        let mut names = fields.iter().map(|f| format!("{}.{}", message, f));
+        let Some(first) = names.next() else { continue };
+        warnings.push(SemanticWarning {
+            entity_name: first,
+            entity_type: "field".to_string(),
+            file_path: file_path.to_string(),
+            kind: WarningKind::FieldNumberCollision { number: *number },
+            related: names
+                .map(|name| RelatedEntity {
+                    name,
+                    entity_type: "field".to_string(),
+                    file_path: file_path.to_string(),
+                })
+                .collect(),
+        });
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = r#"syntax = "proto3";
+
+package api.v1;
+
+// A user account.
+message User {
+  string id = 1;
+  string name = 2;
+}
+
+service Users {
+  rpc GetUser(GetUserRequest) returns (User);
+}
+"#;
+
+    #[test]
+    fn test_round_trip_and_keys() {
+        let file = braces::parse(BASE, &PROTO).unwrap();
+        assert_eq!(file.render(), BASE);
+        let keys: Vec<&str> = file.items.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["syntax:#1", "package:#1", "message:User#1", "service:Users#1"]);
+        let user = file.items[2].1.body.as_ref().unwrap();
+        let fields: Vec<&str> = user.items.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(fields, vec!["field:id#1", "field:name#1"]);
+    }
+
+    #[test]
+    fn test_both_add_fields_and_rpcs() {
+        let ours = BASE
+            .replace("  string name = 2;\n", "  string name = 2;\n  string email = 3;\n")
+            .replace("returns (User);\n", "returns (User);\n  rpc DeleteUser(DeleteUserRequest) returns (Empty);\n");
+        let theirs = BASE
+            .replace("  string id = 1;\n", "  string id = 1;\n  reserved 7;\n")
+            .replace("returns (User);\n", "returns (User);\n  rpc ListUsers(ListUsersRequest) returns (ListUsersResponse);\n");
+        let result = merge_protobuf(BASE, &ours, &theirs, "user.proto").unwrap();
+        assert!(result.is_clean());
+        assert!(result.warnings.is_empty());
+        assert!(result.content.contains("  string id = 1;\n  reserved 7;\n  string name = 2;\n  string email = 3;\n"));
+        assert!(result.content.contains("rpc DeleteUser"));
+        assert!(result.content.contains("rpc ListUsers"));
+    }
+
+    #[test]
+    fn test_field_number_collision_is_flagged() {
+        let ours = BASE.replace("  string name = 2;\n", "  string name = 2;\n  string email = 3;\n");
+        let theirs = BASE.replace("  string name = 2;\n", "  string name = 2;\n  int64 created_at = 3;\n");
+        let result = merge_protobuf(BASE, &ours, &theirs, "user.proto").unwrap();
+        assert!(result.is_clean());
+        assert_eq!(result.warnings.len(), 1);
+        assert!(matches!(result.warnings[0].kind, WarningKind::FieldNumberCollision { number: 3 }));
+        assert_eq!(result.warnings[0].entity_name, "User.created_at");
+        assert_eq!(result.warnings[0].related[0].name, "User.email");
+    }
+
+    #[test]
+    fn test_same_field_changed_differently_conflicts() {
+        let ours = BASE.replace("string name = 2;", "string display_name = 2;").replace("string id = 1;", "bytes id = 1;");
+        let theirs = BASE.replace("string id = 1;", "int64 id = 1;");
+        let result = merge_protobuf(BASE, &ours, &theirs, "user.proto").unwrap();
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].entity_type, "field");
+        assert_eq!(result.conflicts[0].entity_name, "id");
+        assert!(result.content.contains("<<<<<<< ours"));
+    }
+}
@@ -0,0 +1,283 @@
+//! Per-line provenance of a merged file.
+//!
+//! Origins are recorded where the merge emits each piece of output, against
+//! the versions that piece was merged from: an entity against its three
+//! versions, an interstitial against its three versions, and so on. Ordered
+//! merges (diffy, the inner merge) attribute a line to the versions a diff
+//! doesn't mark it as inserted in; set-based merges (import unions,
+//! decorators) to the versions containing it. Merges that work on the whole
+//! file (line fallbacks, format mergers) are attributed against the whole
+//! files. Lines none of the versions kept are the ones weave synthesized.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use serde::Serialize;
+
+/// Where a merged line came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineOrigin {
+    /// Unchanged on both sides.
+    Base,
+    /// Added or kept by ours.
+    Ours,
+    /// Added or kept by theirs.
+    Theirs,
+    /// The same line added by both sides.
+    Both,
+    /// In none of the inputs: synthesized by weave.
+    Weave,
+    /// Part of a conflict block, markers included.
+    Conflict,
+}
+
+impl LineOrigin {
+    /// Short tag for annotated views.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            LineOrigin::Base => "base",
+            LineOrigin::Ours => "ours",
+            LineOrigin::Theirs => "theirs",
+            LineOrigin::Both => "both",
+            LineOrigin::Weave => "weave",
+            LineOrigin::Conflict => "conflict",
+        }
+    }
+}
+
+/// The origin of every line of a merged file, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Provenance {
+    pub lines: Vec<LineOrigin>,
+}
+
+/// Consecutive lines with the same origin, 1-based and inclusive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProvenanceRange {
+    pub start: usize,
+    pub end: usize,
+    pub origin: LineOrigin,
+}
+
+impl Provenance {
+    /// Replace the origins of `lines` (0-based) with `origins`, after the
+    /// same lines of the content were replaced.
+    pub(crate) fn splice(&mut self, lines: Range<usize>, origins: impl IntoIterator<Item = LineOrigin>) {
+        let end = lines.end.min(self.lines.len());
+        let start = lines.start.min(end);
+        self.lines.splice(start..end, origins);
+    }
+
+    /// Runs of lines with the same origin.
+    pub fn ranges(&self) -> Vec<ProvenanceRange> {
+        let mut ranges: Vec<ProvenanceRange> = Vec::new();
+        for (i, &origin) in self.lines.iter().enumerate() {
+            match ranges.last_mut() {
+                Some(last) if last.origin == origin => last.end = i + 1,
+                _ => ranges.push(ProvenanceRange {
+                    start: i + 1,
+                    end: i + 1,
+                    origin,
+                }),
+            }
+        }
+        ranges
+    }
+
+    /// How many lines have the given origin.
+    pub fn count(&self, origin: LineOrigin) -> usize {
+        self.lines.iter().filter(|&&o| o == origin).count()
+    }
+}
+
+/// Attribute each line of `merged`, an ordered merge of `base`, `ours` and
+/// `theirs`: a line is kept from a version when the diff from it doesn't
+/// mark the line as inserted.
+pub(crate) fn attribute(base: &str, ours: &str, theirs: &str, merged: &str) -> Vec<LineOrigin> {
+    let count = merged.lines().count();
+    let not_in_base = inserted_lines(base, merged, count);
+    let not_in_ours = inserted_lines(ours, merged, count);
+    let not_in_theirs = inserted_lines(theirs, merged, count);
+    classify(merged, |i, _| (!not_in_base[i], !not_in_ours[i], !not_in_theirs[i]))
+}
+
+/// Attribute each line of `merged`, a set-based merge of `base`, `ours` and
+/// `theirs` (an import union, merged decorators): a line is kept from a
+/// version when the version has it, wherever.
+pub(crate) fn attribute_unordered(base: &str, ours: &str, theirs: &str, merged: &str) -> Vec<LineOrigin> {
+    let [base, ours, theirs] = [base, ours, theirs].map(|v| v.lines().collect::<HashSet<_>>());
+    classify(merged, |_, line| (base.contains(line), ours.contains(line), theirs.contains(line)))
+}
+
+/// The origin of each line of `merged` from which versions kept it; conflict
+/// blocks are [`LineOrigin::Conflict`] whatever they hold.
+fn classify(merged: &str, mut kept_in: impl FnMut(usize, &str) -> (bool, bool, bool)) -> Vec<LineOrigin> {
+    let mut in_conflict = false;
+    merged
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if line.starts_with("<<<<<<<") {
+                in_conflict = true;
+            }
+            if in_conflict {
+                if line.starts_with(">>>>>>>") {
+                    in_conflict = false;
+                }
+                return LineOrigin::Conflict;
+            }
+            match kept_in(i, line) {
+                (true, true, true) => LineOrigin::Base,
+                (false, true, true) => LineOrigin::Both,
+                (_, true, false) => LineOrigin::Ours,
+                (_, false, true) => LineOrigin::Theirs,
+                // Dropped by both sides (yet kept), or in none of them
+                (_, false, false) => LineOrigin::Weave,
+            }
+        })
+        .collect()
+}
+
+/// Origins recorded as the entity merge resolved each piece of the file, for
+/// [`crate::reconstruct::reconstruct`] to assemble.
+#[derive(Debug, Default)]
+pub struct RecordedOrigins {
+    /// By entity id, under every id the entity may be emitted as.
+    pub entities: HashMap<String, Vec<LineOrigin>>,
+    /// By interstitial position key.
+    pub interstitials: HashMap<String, Vec<LineOrigin>>,
+}
+
+/// Text being emitted, with the origin of each of its lines when tracked.
+#[derive(Debug, Default)]
+pub struct Emitted {
+    pub text: String,
+    pub origins: Option<Vec<LineOrigin>>,
+}
+
+impl Emitted {
+    /// Empty, tracking origins if `track`.
+    pub(crate) fn new(track: bool) -> Self {
+        Self {
+            text: String::new(),
+            origins: track.then(Vec::new),
+        }
+    }
+
+    /// Append `text`, whose lines have `origins` (called only when tracked;
+    /// missing entries are [`LineOrigin::Weave`]). A line `text` continues
+    /// keeps the origin it started with.
+    pub(crate) fn push(&mut self, text: &str, origins: impl FnOnce() -> Vec<LineOrigin>) {
+        if let Some(tracked) = &mut self.origins {
+            let origins = origins();
+            for (line, segment) in text.split_inclusive('\n').enumerate() {
+                if self.text.is_empty() || self.text.ends_with('\n') {
+                    tracked.push(origins.get(line).copied().unwrap_or(LineOrigin::Weave));
+                }
+                self.text.push_str(segment);
+            }
+        } else {
+            self.text.push_str(text);
+        }
+    }
+
+    /// Append `text`, every line of it from `origin`.
+    pub(crate) fn push_as(&mut self, text: &str, origin: LineOrigin) {
+        self.push(text, || vec![origin; text.lines().count().max(1)]);
+    }
+}
+
+/// For each of the `count` lines of `modified`, whether a diff from
+/// `original` marks it as inserted.
+fn inserted_lines(original: &str, modified: &str, count: usize) -> Vec<bool> {
+    let mut inserted = vec![false; count];
+    let patch = diffy::create_patch(original, modified);
+    for hunk in patch.hunks() {
+        // 1-based
+        let mut line = hunk.new_range().start();
+        for diff_line in hunk.lines() {
+            match diff_line {
+                diffy::Line::Context(_) => line += 1,
+                diffy::Line::Insert(_) => {
+                    if let Some(slot) = line.checked_sub(1).and_then(|i| inserted.get_mut(i)) {
+                        *slot = true;
+                    }
+                    line += 1;
+                }
+                diffy::Line::Delete(_) => {}
+            }
+        }
+    }
+    inserted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use LineOrigin::*;
+
+    #[test]
+    fn test_attributes_each_side() {
+        let base = "a\nb\nc\n";
+        let ours = "a\nb2\nc\n";
+        let theirs = "a\nb\nc\nd\n";
+        let merged = "a\nb2\nc\nd\n";
+        assert_eq!(attribute(base, ours, theirs, merged), vec![Base, Ours, Base, Theirs]);
+    }
+
+    #[test]
+    fn test_synthesized_and_conflict_lines() {
+        let base = "x\n";
+        let ours = "x\nsame\none\n";
+        let theirs = "x\nsame\ntwo\n";
+        let merged = "x\nsame\n<<<<<<< ours\none\n=======\ntwo\n>>>>>>> theirs\n// merged by weave\n";
+        assert_eq!(
+            attribute(base, ours, theirs, merged),
+            vec![Base, Both, Conflict, Conflict, Conflict, Conflict, Conflict, Weave]
+        );
+    }
+
+    #[test]
+    fn test_line_deleted_by_both_sides_is_not_base() {
+        let base = "a\nold\nb\n";
+        let ours = "a\nb\n";
+        let theirs = "a\nb\nc\n";
+        assert_eq!(attribute(base, ours, theirs, "a\nold\nb\nc\n"), vec![Base, Weave, Base, Theirs]);
+    }
+
+    #[test]
+    fn test_unordered_attribution_ignores_order() {
+        let base = "use a;\n";
+        let ours = "use b;\nuse a;\n";
+        let theirs = "use a;\nuse c;\n";
+        // A diff from ours would mark `use b;` as inserted after `use a;`
+        let merged = "use a;\nuse b;\nuse c;\n";
+        assert_eq!(attribute_unordered(base, ours, theirs, merged), vec![Base, Ours, Theirs]);
+    }
+
+    #[test]
+    fn test_emitted_continued_line_keeps_its_origin() {
+        let mut emitted = Emitted::new(true);
+        emitted.push_as("fn a() {}", Ours);
+        emitted.push_as(" // tail\n", Theirs);
+        emitted.push("x\ny\n", || vec![Base, Both]);
+        assert_eq!(emitted.text, "fn a() {} // tail\nx\ny\n");
+        assert_eq!(emitted.origins.unwrap(), vec![Ours, Base, Both]);
+    }
+
+    #[test]
+    fn test_ranges() {
+        let provenance = Provenance {
+            lines: vec![Base, Base, Ours, Theirs, Theirs],
+        };
+        assert_eq!(
+            provenance.ranges(),
+            vec![
+                ProvenanceRange { start: 1, end: 2, origin: Base },
+                ProvenanceRange { start: 3, end: 3, origin: Ours },
+                ProvenanceRange { start: 4, end: 5, origin: Theirs },
+            ]
+        );
+    }
+}
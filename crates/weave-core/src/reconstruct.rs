@@ -2,13 +2,19 @@ use std::collections::HashMap;
 
 use sem_core::model::entity::SemanticEntity;
 
+use crate::conflict::BlockCounter;
 use crate::merge::ResolvedEntity;
+use crate::provenance::{Emitted, LineOrigin, RecordedOrigins};
 use crate::region::FileRegion;
 
 /// Reconstruct a merged file from resolved entities and merged interstitials.
 ///
 /// Uses "ours" region ordering as the skeleton. Inserts theirs-only additions
 /// at their relative position (after the entity that precedes them in theirs).
+///
+/// Also returns the conflict block each conflicted entity was rendered as,
+/// by entity id. Given the origins recorded for the entities and
+/// interstitials, the output tracks the origin of each of its lines.
 pub fn reconstruct(
     ours_regions: &[FileRegion],
     theirs_regions: &[FileRegion],
@@ -16,8 +22,12 @@ pub fn reconstruct(
     ours_entity_map: &HashMap<&str, &SemanticEntity>,
     resolved_entities: &HashMap<String, ResolvedEntity>,
     merged_interstitials: &HashMap<String, String>,
-) -> String {
-    let mut output = String::new();
+    origins: Option<&RecordedOrigins>,
+) -> (Emitted, HashMap<String, usize>) {
+    let mut output = Emitted::new(origins.is_some());
+    let mut counter = BlockCounter::default();
+    let mut blocks = HashMap::new();
+    let entity_origins = |id: &str| origins.and_then(|o| o.entities.get(id)).cloned().unwrap_or_default();
 
     // Track which entity IDs we've emitted (from ours skeleton)
     let mut emitted_entities: std::collections::HashSet<String> = std::collections::HashSet::new();
@@ -44,9 +54,14 @@ pub fn reconstruct(
             FileRegion::Interstitial(interstitial) => {
                 // Use merged interstitial if available, otherwise ours
                 if let Some(merged) = merged_interstitials.get(&interstitial.position_key) {
-                    output.push_str(merged);
+                    output.push(merged, || {
+                        origins
+                            .and_then(|o| o.interstitials.get(&interstitial.position_key))
+                            .cloned()
+                            .unwrap_or_default()
+                    });
                 } else {
-                    output.push_str(&interstitial.content);
+                    output.push_as(&interstitial.content, LineOrigin::Ours);
                 }
             }
             FileRegion::Entity(entity_region) => {
@@ -57,18 +72,19 @@ pub fn reconstruct(
                 if let Some(resolved) = resolved_entities.get(&entity_region.entity_id) {
                     match resolved {
                         ResolvedEntity::Clean(region) => {
-                            output.push_str(&region.content);
+                            output.push(&region.content, || entity_origins(&entity_region.entity_id));
                             if !region.content.is_empty() && !region.content.ends_with('\n') {
-                                output.push('\n');
+                                output.push_as("\n", LineOrigin::Weave);
                             }
                         }
                         ResolvedEntity::Conflict(conflict) => {
-                            output.push_str(&conflict.to_conflict_markers());
+                            blocks.insert(entity_region.entity_id.clone(), counter.next(&output.text));
+                            output.push_as(&conflict.to_conflict_markers(), LineOrigin::Conflict);
                         }
                         ResolvedEntity::ScopedConflict { content, .. } => {
-                            output.push_str(content);
+                            output.push(content, || entity_origins(&entity_region.entity_id));
                             if !content.is_empty() && !content.ends_with('\n') {
-                                output.push('\n');
+                                output.push_as("\n", LineOrigin::Weave);
                             }
                         }
                         ResolvedEntity::Deleted => {
@@ -77,11 +93,11 @@ pub fn reconstruct(
                     }
                 } else {
                     // Entity not in resolved map — keep ours content
-                    output.push_str(&entity_region.content);
+                    output.push_as(&entity_region.content, LineOrigin::Ours);
                     if !entity_region.content.is_empty()
                         && !entity_region.content.ends_with('\n')
                     {
-                        output.push('\n');
+                        output.push_as("\n", LineOrigin::Weave);
                     }
                 }
 
@@ -93,23 +109,24 @@ pub fn reconstruct(
                         if let Some(resolved) = resolved_entities.get(&theirs_entity.id) {
                             match resolved {
                                 ResolvedEntity::Clean(region) => {
-                                    output.push('\n');
-                                    output.push_str(&region.content);
+                                    output.push_as("\n", LineOrigin::Theirs);
+                                    output.push(&region.content, || entity_origins(&theirs_entity.id));
                                     if !region.content.is_empty()
                                         && !region.content.ends_with('\n')
                                     {
-                                        output.push('\n');
+                                        output.push_as("\n", LineOrigin::Weave);
                                     }
                                 }
                                 ResolvedEntity::Conflict(conflict) => {
-                                    output.push('\n');
-                                    output.push_str(&conflict.to_conflict_markers());
+                                    output.push_as("\n", LineOrigin::Theirs);
+                                    blocks.insert(theirs_entity.id.clone(), counter.next(&output.text));
+                                    output.push_as(&conflict.to_conflict_markers(), LineOrigin::Conflict);
                                 }
                                 ResolvedEntity::ScopedConflict { content, .. } => {
-                                    output.push('\n');
-                                    output.push_str(content);
+                                    output.push_as("\n", LineOrigin::Theirs);
+                                    output.push(content, || entity_origins(&theirs_entity.id));
                                     if !content.is_empty() && !content.ends_with('\n') {
-                                        output.push('\n');
+                                        output.push_as("\n", LineOrigin::Weave);
                                     }
                                 }
                                 ResolvedEntity::Deleted => {}
@@ -128,7 +145,10 @@ pub fn reconstruct(
         for theirs_entity in insertions {
             if !emitted_entities.contains(&theirs_entity.id) {
                 if let Some(resolved) = resolved_entities.get(&theirs_entity.id) {
-                    emit_resolved(&mut output, resolved);
+                    let origins = || entity_origins(&theirs_entity.id);
+                    if let Some(block) = emit_resolved(&mut output, &mut counter, resolved, origins) {
+                        blocks.insert(theirs_entity.id.clone(), block);
+                    }
                 }
                 emitted_entities.insert(theirs_entity.id.clone());
             }
@@ -143,48 +163,49 @@ pub fn reconstruct(
         for theirs_entity in insertions {
             if !emitted_entities.contains(&theirs_entity.id) {
                 if let Some(resolved) = resolved_entities.get(&theirs_entity.id) {
-                    emit_resolved(&mut output, resolved);
+                    let origins = || entity_origins(&theirs_entity.id);
+                    if let Some(block) = emit_resolved(&mut output, &mut counter, resolved, origins) {
+                        blocks.insert(theirs_entity.id.clone(), block);
+                    }
                 }
                 emitted_entities.insert(theirs_entity.id.clone());
             }
         }
     }
 
-    output
+    (output, blocks)
 }
 
 /// Emit a resolved entity into the output (for theirs-only insertions).
-fn emit_resolved(output: &mut String, resolved: &ResolvedEntity) {
-    match resolved {
-        ResolvedEntity::Clean(region) => {
-            if !output.is_empty() && !output.ends_with('\n') {
-                output.push('\n');
-            }
-            output.push('\n');
-            output.push_str(&region.content);
-            if !region.content.is_empty() && !region.content.ends_with('\n') {
-                output.push('\n');
-            }
-        }
-        ResolvedEntity::Conflict(conflict) => {
-            if !output.is_empty() && !output.ends_with('\n') {
-                output.push('\n');
-            }
-            output.push('\n');
-            output.push_str(&conflict.to_conflict_markers());
-        }
-        ResolvedEntity::ScopedConflict { content, .. } => {
-            if !output.is_empty() && !output.ends_with('\n') {
-                output.push('\n');
-            }
-            output.push('\n');
-            output.push_str(content);
-            if !content.is_empty() && !content.ends_with('\n') {
-                output.push('\n');
-            }
-        }
-        ResolvedEntity::Deleted => {}
+/// Returns the block a conflict was rendered as.
+fn emit_resolved(
+    output: &mut Emitted,
+    counter: &mut BlockCounter,
+    resolved: &ResolvedEntity,
+    origins: impl FnOnce() -> Vec<LineOrigin>,
+) -> Option<usize> {
+    let content = match resolved {
+        ResolvedEntity::Clean(region) => region.content.as_str(),
+        ResolvedEntity::ScopedConflict { content, .. } => content.as_str(),
+        ResolvedEntity::Conflict(_) | ResolvedEntity::Deleted => "",
+    };
+    if matches!(resolved, ResolvedEntity::Deleted) {
+        return None;
+    }
+    if !output.text.is_empty() && !output.text.ends_with('\n') {
+        output.push_as("\n", LineOrigin::Weave);
     }
+    output.push_as("\n", LineOrigin::Theirs);
+    if let ResolvedEntity::Conflict(conflict) = resolved {
+        let block = counter.next(&output.text);
+        output.push_as(&conflict.to_conflict_markers(), LineOrigin::Conflict);
+        return Some(block);
+    }
+    output.push(content, origins);
+    if !content.is_empty() && !content.ends_with('\n') {
+        output.push_as("\n", LineOrigin::Weave);
+    }
+    None
 }
 
 /// Find the entity ID that precedes the given entity in a region list.
@@ -0,0 +1,459 @@
+//! Entity-level rerere: remember and replay conflict resolutions.
+//!
+//! Rebases and repeated merges of long-lived branches hit the same entity
+//! conflicts again and again. When the driver leaves conflicts in a file, it
+//! saves them as *pending*; once the file is committed, the post-commit hook
+//! (`weave rerere record`) finds what replaced each conflict block and stores
+//! it under `.weave/rerere/`, keyed by the hashes of the entity's base, ours
+//! and theirs content. The next merge that produces the same conflict
+//! replays the recorded resolution instead of emitting markers.
+//!
+//! Content is normalized before hashing (line endings, trailing whitespace,
+//! surrounding blank lines), so reformatting noise doesn't defeat a match.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::WeaveConfig;
+use crate::conflict::{block_spans, EntityConflict};
+use crate::merge::MergeResult;
+use crate::protect::is_protected_conflict;
+use crate::validate::{SemanticWarning, WarningKind};
+
+/// Location of the resolution cache relative to the repo root.
+pub const RERERE_DIR: &str = ".weave/rerere";
+
+/// Conflicts awaiting commit, inside [`RERERE_DIR`]. Ignored by git.
+const PENDING_DIR: &str = "pending";
+
+#[derive(Debug, thiserror::Error)]
+pub enum RerereError {
+    #[error("rerere cache: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid rerere record: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("no recorded resolution matches '{0}'")]
+    NotFound(String),
+    #[error("'{0}' matches more than one recorded resolution")]
+    Ambiguous(String),
+}
+
+/// A recorded resolution of one entity conflict.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Resolution {
+    /// Hash of the normalized base, ours and theirs content.
+    pub id: String,
+    /// File the conflict was first resolved in.
+    pub file_path: String,
+    pub entity_name: String,
+    pub entity_type: String,
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+    /// What replaced the conflict markers in the committed file.
+    pub resolution: String,
+}
+
+/// A conflicted merge result awaiting commit.
+#[derive(Debug, Serialize, Deserialize)]
+struct Pending {
+    file_path: String,
+    /// The merged content around the conflict blocks, one more than `conflicts`.
+    segments: Vec<String>,
+    conflicts: Vec<PendingConflict>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingConflict {
+    entity_name: String,
+    entity_type: String,
+    base: Option<String>,
+    ours: Option<String>,
+    theirs: Option<String>,
+}
+
+/// The id a conflict is recorded under: a hash of the hashes of its
+/// normalized base, ours and theirs content.
+pub fn conflict_id(base: Option<&str>, ours: Option<&str>, theirs: Option<&str>) -> String {
+    let side = |content: Option<&str>| match content {
+        Some(content) => hex(&Sha256::digest(normalize(content).as_bytes())),
+        // Distinct from empty content
+        None => "-".to_string(),
+    };
+    let key = format!("{}\n{}\n{}", side(base), side(ours), side(theirs));
+    hex(&Sha256::digest(key.as_bytes()))
+}
+
+fn entity_conflict_id(conflict: &EntityConflict) -> String {
+    conflict_id(
+        conflict.base_content.as_deref(),
+        conflict.ours_content.as_deref(),
+        conflict.theirs_content.as_deref(),
+    )
+}
+
+/// Unify line endings, drop trailing whitespace and surrounding blank lines.
+fn normalize(content: &str) -> String {
+    let lines: Vec<&str> = content.lines().map(str::trim_end).collect();
+    let start = lines.iter().position(|l| !l.is_empty()).unwrap_or(lines.len());
+    let end = lines.iter().rposition(|l| !l.is_empty()).map_or(start, |i| i + 1);
+    lines[start..end].join("\n")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The resolution cache of one repository.
+pub struct RerereCache {
+    dir: PathBuf,
+}
+
+impl RerereCache {
+    /// Open the cache of the repo at `repo_root`. Nothing is created until
+    /// something is recorded.
+    pub fn open(repo_root: &Path) -> Self {
+        Self {
+            dir: repo_root.join(RERERE_DIR),
+        }
+    }
+
+    /// The recorded resolution with this exact id.
+    pub fn get(&self, id: &str) -> Option<Resolution> {
+        let content = fs::read_to_string(self.dir.join(format!("{}.json", id))).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// All recorded resolutions, ordered by file and entity.
+    pub fn list(&self) -> Result<Vec<Resolution>, RerereError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let mut resolutions = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                resolutions.push(serde_json::from_str::<Resolution>(&fs::read_to_string(&path)?)?);
+            }
+        }
+        resolutions.sort_by(|a, b| (&a.file_path, &a.entity_name).cmp(&(&b.file_path, &b.entity_name)));
+        Ok(resolutions)
+    }
+
+    /// The recorded resolution whose id starts with `prefix`.
+    pub fn find(&self, prefix: &str) -> Result<Resolution, RerereError> {
+        let mut matches = self.list()?.into_iter().filter(|r| r.id.starts_with(prefix));
+        match (matches.next(), matches.next()) {
+            (Some(resolution), None) => Ok(resolution),
+            (Some(_), _) => Err(RerereError::Ambiguous(prefix.to_string())),
+            (None, _) => Err(RerereError::NotFound(prefix.to_string())),
+        }
+    }
+
+    /// Delete the recorded resolution whose id starts with `prefix`.
+    pub fn forget(&self, prefix: &str) -> Result<Resolution, RerereError> {
+        let resolution = self.find(prefix)?;
+        fs::remove_file(self.dir.join(format!("{}.json", resolution.id)))?;
+        Ok(resolution)
+    }
+
+    /// Store a resolution, replacing any with the same id.
+    pub fn save(&self, resolution: &Resolution) -> Result<(), RerereError> {
+        fs::create_dir_all(&self.dir)?;
+        let json = serde_json::to_string_pretty(resolution)?;
+        fs::write(self.dir.join(format!("{}.json", resolution.id)), json + "\n")?;
+        Ok(())
+    }
+
+    /// Replace the block of every conflict with a recorded resolution, each
+    /// reported as a [`WarningKind::ReplayedResolution`] warning. Conflicts on
+    /// protected entities are left alone. Returns how many conflicts were
+    /// resolved.
+    pub fn replay(&self, result: &mut MergeResult, file_path: &str, config: &WeaveConfig) -> usize {
+        let mut resolutions = Vec::new();
+        let mut warnings = Vec::new();
+        for (index, conflict) in result.conflicts.iter().enumerate() {
+            if result.conflict_block(index).is_none() || is_protected_conflict(conflict, file_path, config) {
+                continue;
+            }
+            let Some(resolution) = self.get(&entity_conflict_id(conflict)) else {
+                continue;
+            };
+            resolutions.push((index, resolution.resolution));
+            warnings.push(SemanticWarning {
+                entity_name: conflict.entity_name.clone(),
+                entity_type: conflict.entity_type.clone(),
+                file_path: file_path.to_string(),
+                kind: WarningKind::ReplayedResolution { id: resolution.id },
+                related: vec![],
+            });
+        }
+
+        let indices: Vec<usize> = resolutions.iter().map(|(index, _)| *index).collect();
+        let resolved = result.resolve_conflicts(resolutions);
+        result.warnings.extend(
+            indices
+                .into_iter()
+                .zip(warnings)
+                .filter(|(index, _)| resolved.contains(index))
+                .map(|(_, warning)| warning),
+        );
+        result.stats.entities_conflicted = result.stats.entities_conflicted.saturating_sub(resolved.len());
+        result.stats.resolved_via_rerere += resolved.len();
+        resolved.len()
+    }
+
+    /// Save the conflicts of a merge result as pending, to be recorded once
+    /// the file is committed (see [`RerereCache::record`]). Only conflicts
+    /// rendered as a block of their own are saved.
+    pub fn remember(&self, file_path: &str, result: &MergeResult) -> Result<(), RerereError> {
+        let spans = block_spans(&result.content);
+        let mut blocks: Vec<(std::ops::Range<usize>, &EntityConflict)> = result
+            .conflicts
+            .iter()
+            .enumerate()
+            .filter_map(|(index, conflict)| {
+                let span = spans.get(result.conflict_block(index)?)?.clone()?;
+                Some((span, conflict))
+            })
+            .collect();
+        if blocks.is_empty() {
+            return Ok(());
+        }
+        blocks.sort_by_key(|(span, _)| span.start);
+
+        let mut segments = Vec::new();
+        let mut conflicts = Vec::new();
+        let mut pos = 0;
+        for (span, conflict) in blocks {
+            segments.push(result.content[pos..span.start].to_string());
+            pos = span.end;
+            conflicts.push(PendingConflict {
+                entity_name: conflict.entity_name.clone(),
+                entity_type: conflict.entity_type.clone(),
+                base: conflict.base_content.clone(),
+                ours: conflict.ours_content.clone(),
+                theirs: conflict.theirs_content.clone(),
+            });
+        }
+        segments.push(result.content[pos..].to_string());
+
+        let pending_dir = self.dir.join(PENDING_DIR);
+        fs::create_dir_all(&pending_dir)?;
+        let ignore = self.dir.join(".gitignore");
+        if !ignore.exists() {
+            fs::write(ignore, format!("{}/\n", PENDING_DIR))?;
+        }
+        let pending = Pending {
+            file_path: file_path.to_string(),
+            segments,
+            conflicts,
+        };
+        fs::write(pending_path(&pending_dir, file_path), serde_json::to_string(&pending)?)?;
+        Ok(())
+    }
+
+    /// Files with conflicts awaiting commit.
+    pub fn pending_files(&self) -> Result<Vec<String>, RerereError> {
+        let entries = match fs::read_dir(self.dir.join(PENDING_DIR)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let mut files = Vec::new();
+        for entry in entries {
+            let pending: Pending = serde_json::from_str(&fs::read_to_string(entry?.path())?)?;
+            files.push(pending.file_path);
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Drop a file from pending without recording anything.
+    pub fn discard(&self, file_path: &str) -> Result<(), RerereError> {
+        match fs::remove_file(pending_path(&self.dir.join(PENDING_DIR), file_path)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record the resolutions of a pending file from its committed content,
+    /// and drop it from pending.
+    ///
+    /// A resolution is only recorded if the content around every conflict is
+    /// still there to locate it; a file still holding markers, or edited
+    /// beyond the conflict blocks, records nothing.
+    pub fn record(&self, file_path: &str, committed: &str) -> Result<Vec<Resolution>, RerereError> {
+        let path = pending_path(&self.dir.join(PENDING_DIR), file_path);
+        let pending: Pending = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        self.discard(file_path)?;
+
+        if committed.lines().any(|l| l.starts_with("<<<<<<<")) {
+            return Ok(vec![]);
+        }
+        let Some(resolved) = locate_resolutions(&pending.segments, committed) else {
+            return Ok(vec![]);
+        };
+
+        let mut recorded = Vec::new();
+        for (conflict, resolution) in pending.conflicts.into_iter().zip(resolved) {
+            let resolution = Resolution {
+                id: conflict_id(conflict.base.as_deref(), conflict.ours.as_deref(), conflict.theirs.as_deref()),
+                file_path: pending.file_path.clone(),
+                entity_name: conflict.entity_name,
+                entity_type: conflict.entity_type,
+                base: conflict.base,
+                ours: conflict.ours,
+                theirs: conflict.theirs,
+                resolution: resolution.to_string(),
+            };
+            self.save(&resolution)?;
+            recorded.push(resolution);
+        }
+        Ok(recorded)
+    }
+}
+
+fn pending_path(pending_dir: &Path, file_path: &str) -> PathBuf {
+    pending_dir.join(format!("{}.json", &hex(&Sha256::digest(file_path.as_bytes()))[..16]))
+}
+
+/// Split `committed` around the unchanged `segments`, returning what stands
+/// in place of each conflict block. None if a segment can't be found, or
+/// a whitespace-only one between two blocks makes the split ambiguous.
+fn locate_resolutions<'a>(segments: &[String], committed: &'a str) -> Option<Vec<&'a str>> {
+    let (first, rest) = segments.split_first()?;
+    let (last, middle) = rest.split_last()?;
+    if !committed.starts_with(first.as_str()) || !committed.ends_with(last.as_str()) {
+        return None;
+    }
+    let mut pos = first.len();
+    let end = committed.len().checked_sub(last.len()).filter(|&end| end >= pos)?;
+
+    let mut resolutions = Vec::new();
+    for segment in middle {
+        if segment.trim().is_empty() {
+            return None;
+        }
+        let start = pos + committed[pos..end].find(segment.as_str())?;
+        resolutions.push(&committed[pos..start]);
+        pos = start + segment.len();
+    }
+    resolutions.push(&committed[pos..end]);
+    Some(resolutions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conflict::{ConflictComplexity, ConflictKind, MergeStats};
+
+    fn conflict(name: &str, ours: &str, theirs: &str) -> EntityConflict {
+        EntityConflict {
+            entity_name: name.to_string(),
+            entity_type: "function".to_string(),
+            kind: ConflictKind::BothModified,
+            complexity: ConflictComplexity::Functional,
+            ours_content: Some(ours.to_string()),
+            theirs_content: Some(theirs.to_string()),
+            base_content: Some(format!("fn {}() {{ 0 }}\n", name)),
+        }
+    }
+
+    fn conflicted(conflicts: Vec<EntityConflict>) -> MergeResult {
+        let mut content = "use std::fmt;\n\n".to_string();
+        for c in &conflicts {
+            content.push_str(&c.to_conflict_markers());
+            content.push_str("\n// between\n\n");
+        }
+        MergeResult {
+            content,
+            stats: MergeStats {
+                entities_conflicted: conflicts.len(),
+                ..Default::default()
+            },
+            conflict_blocks: (0..conflicts.len()).map(Some).collect(),
+            conflicts,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_id_ignores_whitespace_noise() {
+        let id = conflict_id(Some("fn a() {\n  1\n}\n"), Some("x\n"), None);
+        assert_eq!(id, conflict_id(Some("\nfn a() {  \r\n  1\r\n}"), Some("x"), None));
+        assert_ne!(id, conflict_id(Some("fn a() {\n  1\n}\n"), Some("x\n"), Some("")));
+        assert_ne!(id, conflict_id(Some("fn a() {\n    1\n}\n"), Some("x\n"), None));
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = RerereCache::open(dir.path());
+        let result = conflicted(vec![
+            conflict("a", "fn a() { 1 }\n", "fn a() { 2 }\n"),
+            conflict("b", "fn b() { 1 }\n", "fn b() { 2 }\n"),
+        ]);
+        cache.remember("src/lib.rs", &result).unwrap();
+        assert_eq!(cache.pending_files().unwrap(), vec!["src/lib.rs"]);
+
+        let committed = "use std::fmt;\n\nfn a() { 3 }\n\n// between\n\nfn b() { 2 }\n\n// between\n\n";
+        let recorded = cache.record("src/lib.rs", committed).unwrap();
+        assert_eq!(recorded.len(), 2);
+        assert_eq!(recorded[0].resolution, "fn a() { 3 }\n");
+        assert!(cache.pending_files().unwrap().is_empty());
+
+        // The same conflicts again, in a different file
+        let mut again = conflicted(vec![conflict("b", "fn b() { 1 }\n", "fn b() { 2 }\n")]);
+        assert_eq!(cache.replay(&mut again, "src/main.rs", &WeaveConfig::default()), 1);
+        assert!(again.is_clean());
+        assert!(matches!(&again.warnings[..], [w] if matches!(w.kind, WarningKind::ReplayedResolution { .. })));
+        assert_eq!(again.stats.entities_conflicted, 0);
+        assert_eq!(again.stats.resolved_via_rerere, 1);
+        assert_eq!(again.content, "use std::fmt;\n\nfn b() { 2 }\n\n// between\n\n");
+    }
+
+    #[test]
+    fn test_unlocatable_resolution_is_not_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = RerereCache::open(dir.path());
+        let result = conflicted(vec![conflict("a", "fn a() { 1 }\n", "fn a() { 2 }\n")]);
+
+        cache.remember("src/lib.rs", &result).unwrap();
+        let edited_elsewhere = "use std::io;\n\nfn a() { 3 }\n\n// between\n\n";
+        assert!(cache.record("src/lib.rs", edited_elsewhere).unwrap().is_empty());
+
+        cache.remember("src/lib.rs", &result).unwrap();
+        assert!(cache.record("src/lib.rs", &result.content).unwrap().is_empty());
+        assert!(cache.list().unwrap().is_empty());
+        assert!(cache.pending_files().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_and_forget_by_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = RerereCache::open(dir.path());
+        let result = conflicted(vec![conflict("a", "fn a() { 1 }\n", "fn a() { 2 }\n")]);
+        cache.remember("src/lib.rs", &result).unwrap();
+        let recorded = cache
+            .record("src/lib.rs", "use std::fmt;\n\nfn a() { 3 }\n\n// between\n\n")
+            .unwrap();
+
+        let id = &recorded[0].id;
+        assert_eq!(cache.find(&id[..8]).unwrap().entity_name, "a");
+        // The only record: any prefix is unique
+        assert_eq!(cache.find("").unwrap().id, *id);
+        cache.forget(&id[..8]).unwrap();
+        assert!(matches!(cache.find(&id[..8]), Err(RerereError::NotFound(_))));
+    }
+}
@@ -0,0 +1,500 @@
+//! Section-level merge for single-file components (`.vue`, `.svelte`).
+//!
+//! sem-core has no parser for SFCs, so they used to go straight to the line
+//! fallback. Instead, the file is split into its top-level blocks and each
+//! one is merged with the right tool:
+//!
+//! - `<script>`: entity merge as TypeScript or JavaScript (per `lang`)
+//! - `<style>`: stylesheet merge as CSS, SCSS or LESS (per `lang`); other
+//!   languages, and blocks the stylesheet merger can't split, are merged
+//!   line by line as one opaque block
+//! - `<template>` and Svelte markup: line-level merge
+//!
+//! Top-level blocks are recognized only when their tag starts a line, which
+//! is how SFCs are written and avoids matching nested `<template>` elements.
+
+use std::collections::HashMap;
+
+use sem_core::parser::registry::ParserRegistry;
+
+use crate::config::WeaveConfig;
+use crate::conflict::{classify_conflict, BlockCounter, EntityConflict, MergeStats};
+use crate::keyed::{merge_keyed, pick_side, KeyedMerge};
+use crate::merge::{entity_merge_in, line_level_fallback, MergeContext, MergeResult};
+use crate::stylesheet::merge_stylesheet;
+
+/// Check if a file is a Vue or Svelte single-file component.
+pub fn is_single_file_component(file_path: &str) -> bool {
+    file_path.ends_with(".vue") || file_path.ends_with(".svelte")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SectionKind {
+    Script,
+    Style,
+    Template,
+    /// Anything outside the blocks: Svelte markup, or leading text.
+    Markup,
+}
+
+impl SectionKind {
+    fn tag(self) -> &'static str {
+        match self {
+            SectionKind::Script => "script",
+            SectionKind::Style => "style",
+            SectionKind::Template => "template",
+            SectionKind::Markup => "markup",
+        }
+    }
+}
+
+/// One top-level section. Whitespace following it is kept as `trailing`
+/// so blank lines between blocks don't become sections of their own.
+#[derive(Debug, Clone, PartialEq)]
+struct Section {
+    kind: SectionKind,
+    open_tag: String,
+    body: String,
+    close_tag: String,
+    trailing: String,
+}
+
+impl Section {
+    fn render(&self) -> String {
+        format!("{}{}{}{}", self.open_tag, self.body, self.close_tag, self.trailing)
+    }
+}
+
+/// Merge three versions of an SFC section by section.
+///
+/// Returns None if no version contains a recognizable block, so the caller
+/// can fall back to the regular merge.
+pub fn merge_single_file_component(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    registry: &ParserRegistry,
+    config: &WeaveConfig,
+    context: &MergeContext,
+) -> Option<MergeResult> {
+    let allow_template = file_path.ends_with(".vue");
+    let base_sections = split_sections(base, allow_template)?;
+    let ours_sections = split_sections(ours, allow_template)?;
+    let theirs_sections = split_sections(theirs, allow_template)?;
+
+    let has_blocks = |sections: &[(String, Section)]| sections.iter().any(|(_, s)| s.kind != SectionKind::Markup);
+    if !has_blocks(&base_sections) && !has_blocks(&ours_sections) && !has_blocks(&theirs_sections) {
+        return None;
+    }
+
+    let mut stats = MergeStats::default();
+    // Section key → the body's merge, conflicted
+    let mut inner_results = HashMap::new();
+
+    let merged = merge_keyed(&base_sections, &ours_sections, &theirs_sections, &mut stats, |key, b, o, t| {
+        // Opening tag changed on both sides (e.g. different `lang`): the whole block conflicts
+        let open_tag = pick_side(b.map(|s| &s.open_tag), Some(&o.open_tag), Some(&t.open_tag))
+            .flatten()
+            .ok_or(None)?
+            .clone();
+        let trailing = pick_side(b.map(|s| &s.trailing), Some(&o.trailing), Some(&t.trailing))
+            .flatten()
+            .unwrap_or(&o.trailing)
+            .clone();
+
+        let base_body = b.map(|s| s.body.as_str()).unwrap_or("");
+        let mut result = merge_body(o.kind, &open_tag, base_body, &o.body, &t.body, file_path, registry, config, context);
+        let section = Section {
+            kind: o.kind,
+            open_tag,
+            body: std::mem::take(&mut result.content),
+            close_tag: o.close_tag.clone(),
+            trailing,
+        };
+        if result.conflicts.is_empty() {
+            Ok(section)
+        } else {
+            inner_results.insert(key.to_string(), result);
+            Err(Some(section))
+        }
+    });
+
+    let mut content = String::new();
+    let mut conflicts = Vec::new();
+    let mut conflict_blocks = Vec::new();
+    let mut counter = BlockCounter::default();
+    for item in merged {
+        match item {
+            KeyedMerge::Clean(section) => content.push_str(&section.render()),
+            KeyedMerge::Conflict(c) => {
+                if let Some(section) = c.rendered {
+                    // Conflicts are scoped inside the block body
+                    let offset = counter.next(&content);
+                    if let Some(inner) = inner_results.remove(&c.key) {
+                        conflict_blocks.extend((0..inner.conflicts.len()).map(|i| inner.conflict_block(i).map(|b| b + offset)));
+                        conflicts.extend(inner.conflicts);
+                    }
+                    content.push_str(&section.render());
+                    continue;
+                }
+                let kind = c.ours.as_ref().or(c.theirs.as_ref()).map(|s| s.kind).unwrap_or(SectionKind::Markup);
+                let base_text = c.base.as_ref().map(Section::render);
+                let ours_text = c.ours.as_ref().map(Section::render);
+                let theirs_text = c.theirs.as_ref().map(Section::render);
+                let conflict = EntityConflict {
+                    entity_name: c.key.clone(),
+                    entity_type: kind.tag().to_string(),
+                    kind: c.kind,
+                    complexity: classify_conflict(base_text.as_deref(), ours_text.as_deref(), theirs_text.as_deref()),
+                    ours_content: ours_text,
+                    theirs_content: theirs_text,
+                    base_content: base_text,
+                };
+                conflict_blocks.push(Some(counter.next(&content)));
+                content.push_str(&conflict.to_conflict_markers());
+                conflicts.push(conflict);
+            }
+        }
+    }
+
+    Some(MergeResult {
+        content,
+        conflicts,
+        conflict_blocks,
+        stats,
+        ..Default::default()
+    })
+}
+
+/// Merge the body of a block changed on both sides.
+#[allow(clippy::too_many_arguments)]
+fn merge_body(
+    kind: SectionKind,
+    open_tag: &str,
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    file_path: &str,
+    registry: &ParserRegistry,
+    config: &WeaveConfig,
+    context: &MergeContext,
+) -> MergeResult {
+    let lang = tag_attribute(open_tag, "lang");
+    match kind {
+        SectionKind::Script => {
+            let ext = match lang.as_deref() {
+                Some("ts") | Some("typescript") => "ts",
+                Some("tsx") => "tsx",
+                Some("jsx") => "jsx",
+                _ => "js",
+            };
+            let path = format!("{}.{}", file_path, ext);
+            entity_merge_in(base, ours, theirs, &path, registry, config, &context.part())
+        }
+        SectionKind::Style => {
+            let ext = match lang.as_deref() {
+                Some("scss") => "scss",
+                Some("less") => "less",
+                Some("css") | None => "css",
+                // Stylus, PostCSS dialects: merged opaquely, line by line as
+                // plain text (not split at `{`, `}` and `;` like code)
+                Some(_) => {
+                    let path = format!("{}.txt", file_path);
+                    return line_level_fallback(base, ours, theirs, &path, context);
+                }
+            };
+            let path = format!("{}.{}", file_path, ext);
+            merge_stylesheet(base, ours, theirs, &path)
+                .unwrap_or_else(|| line_level_fallback(base, ours, theirs, &path, context))
+        }
+        SectionKind::Template | SectionKind::Markup => {
+            // Treat as HTML for the line merge (no separator expansion)
+            let path = format!("{}.html", file_path);
+            line_level_fallback(base, ours, theirs, &path, context)
+        }
+    }
+}
+
+/// Split an SFC into keyed top-level sections.
+///
+/// Blocks are keyed by tag plus the attribute that distinguishes a second
+/// script (`setup`, `context="module"`/`module`), then by occurrence.
+/// Markup between blocks is keyed by occurrence. Returns None if a block
+/// is never closed.
+fn split_sections(content: &str, allow_template: bool) -> Option<Vec<(String, Section)>> {
+    let mut sections: Vec<(String, Section)> = Vec::new();
+    let mut counts: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    let mut key_for = |base_key: String| {
+        let n = counts.entry(base_key.clone()).or_insert(0);
+        *n += 1;
+        format!("{}#{}", base_key, n)
+    };
+
+    let mut pos = 0;
+    let mut text_start = 0;
+    while pos < content.len() {
+        let line_end = content[pos..].find('\n').map(|i| pos + i + 1).unwrap_or(content.len());
+        let Some(kind) = block_kind_at(&content[pos..], allow_template) else {
+            pos = line_end;
+            continue;
+        };
+
+        // Markup before this block
+        push_text(&mut sections, &content[text_start..pos], &mut key_for);
+
+        let open_end = pos + content[pos..].find('>')? + 1;
+        let open_tag = &content[pos..open_end];
+        let close_tag = format!("</{}>", kind.tag());
+        let (close_start, close_end) = if open_tag.ends_with("/>") {
+            (open_end, open_end)
+        } else if kind == SectionKind::Template {
+            let start = find_template_close(content, open_end)?;
+            (start, start + close_tag.len())
+        } else {
+            let start = open_end + content[open_end..].find(&close_tag)?;
+            (start, start + close_tag.len())
+        };
+
+        let variant = if kind == SectionKind::Script {
+            script_variant(open_tag)
+        } else {
+            ""
+        };
+        let key = key_for(format!("{}{}", kind.tag(), variant));
+        sections.push((
+            key,
+            Section {
+                kind,
+                open_tag: open_tag.to_string(),
+                body: content[open_end..close_start].to_string(),
+                close_tag: content[close_start..close_end].to_string(),
+                trailing: String::new(),
+            },
+        ));
+
+        pos = close_end;
+        text_start = close_end;
+    }
+    push_text(&mut sections, &content[text_start..], &mut key_for);
+
+    Some(sections)
+}
+
+/// Attach whitespace to the previous section; anything else becomes a markup section.
+fn push_text(sections: &mut Vec<(String, Section)>, text: &str, key_for: &mut impl FnMut(String) -> String) {
+    if text.is_empty() {
+        return;
+    }
+    if text.trim().is_empty() {
+        if let Some((_, last)) = sections.last_mut() {
+            last.trailing.push_str(text);
+            return;
+        }
+    }
+    sections.push((
+        key_for("markup".to_string()),
+        Section {
+            kind: SectionKind::Markup,
+            open_tag: String::new(),
+            body: text.to_string(),
+            close_tag: String::new(),
+            trailing: String::new(),
+        },
+    ));
+}
+
+/// The block kind if `rest` starts with a top-level opening tag.
+fn block_kind_at(rest: &str, allow_template: bool) -> Option<SectionKind> {
+    let starts_tag = |tag: &str| {
+        rest.strip_prefix('<')
+            .and_then(|r| r.strip_prefix(tag))
+            .and_then(|r| r.chars().next())
+            .is_some_and(|c| c == '>' || c == '/' || c.is_whitespace())
+    };
+    if starts_tag("script") {
+        Some(SectionKind::Script)
+    } else if starts_tag("style") {
+        Some(SectionKind::Style)
+    } else if allow_template && starts_tag("template") {
+        Some(SectionKind::Template)
+    } else {
+        None
+    }
+}
+
+/// Find the `</template>` matching a top-level `<template>`, skipping nested ones.
+fn find_template_close(content: &str, from: usize) -> Option<usize> {
+    let mut depth = 1;
+    let mut pos = from;
+    loop {
+        let next_open = content[pos..].find("<template").map(|i| pos + i);
+        let next_close = content[pos..].find("</template>").map(|i| pos + i)?;
+        match next_open {
+            Some(open) if open < next_close => {
+                let is_tag = content[open + "<template".len()..]
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c == '>' || c.is_whitespace());
+                if is_tag {
+                    depth += 1;
+                }
+                pos = open + "<template".len();
+            }
+            _ => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(next_close);
+                }
+                pos = next_close + "</template>".len();
+            }
+        }
+    }
+}
+
+/// Distinguish Vue's `<script setup>` and Svelte's module script from the instance script.
+fn script_variant(open_tag: &str) -> &'static str {
+    if tag_has_attribute(open_tag, "setup") {
+        ":setup"
+    } else if tag_has_attribute(open_tag, "module")
+        || tag_attribute(open_tag, "context").as_deref() == Some("module")
+    {
+        ":module"
+    } else {
+        ""
+    }
+}
+
+fn tag_has_attribute(open_tag: &str, name: &str) -> bool {
+    attributes(open_tag).any(|(n, _)| n == name)
+}
+
+fn tag_attribute(open_tag: &str, name: &str) -> Option<String> {
+    attributes(open_tag).find(|(n, _)| *n == name).and_then(|(_, v)| v)
+}
+
+/// Iterate over `name` / `name="value"` attributes of an opening tag.
+fn attributes(open_tag: &str) -> impl Iterator<Item = (&str, Option<String>)> {
+    let inner = open_tag
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .trim_end_matches('/');
+    let mut rest = inner.split_once(char::is_whitespace).map(|(_, r)| r).unwrap_or("");
+    std::iter::from_fn(move || {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return None;
+        }
+        let name_end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+        let name = &rest[..name_end];
+        rest = &rest[name_end..];
+        let value = if let Some(after_eq) = rest.strip_prefix('=') {
+            let quote = after_eq.chars().next().filter(|c| *c == '"' || *c == '\'');
+            match quote {
+                Some(q) => {
+                    let end = after_eq[1..].find(q).map(|i| i + 1).unwrap_or(after_eq.len());
+                    let value = after_eq[1..end].to_string();
+                    rest = after_eq.get(end + 1..).unwrap_or("");
+                    Some(value)
+                }
+                None => {
+                    let end = after_eq.find(char::is_whitespace).unwrap_or(after_eq.len());
+                    let value = after_eq[..end].to_string();
+                    rest = &after_eq[end..];
+                    Some(value)
+                }
+            }
+        } else {
+            None
+        };
+        Some((name, value))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(base: &str, ours: &str, theirs: &str, path: &str) -> MergeResult {
+        let registry = sem_core::parser::plugins::create_default_registry();
+        merge_single_file_component(base, ours, theirs, path, &registry, &WeaveConfig::default(), &MergeContext::default()).unwrap()
+    }
+
+    #[test]
+    fn test_split_vue_sections() {
+        let vue = "<template>\n  <div>\n    <template v-if=\"ok\">x</template>\n  </div>\n</template>\n\n<script setup lang=\"ts\">\nconst a = 1\n</script>\n\n<style scoped>\n.a { color: red; }\n</style>\n";
+        let sections = split_sections(vue, true).unwrap();
+        let keys: Vec<&str> = sections.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["template#1", "script:setup#1", "style#1"]);
+        assert!(sections[0].1.body.contains("v-if"));
+        assert_eq!(sections[1].1.trailing, "\n\n");
+        let rebuilt: String = sections.iter().map(|(_, s)| s.render()).collect();
+        assert_eq!(rebuilt, vue);
+    }
+
+    #[test]
+    fn test_split_svelte_markup() {
+        let svelte = "<script context=\"module\">\nexport const x = 1;\n</script>\n<script>\nlet y = 2;\n</script>\n\n<h1>{y}</h1>\n\n<style>\nh1 { color: red; }\n</style>\n";
+        let sections = split_sections(svelte, false).unwrap();
+        let keys: Vec<&str> = sections.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["script:module#1", "script#1", "markup#1", "style#1"]);
+    }
+
+    #[test]
+    fn test_different_sections_edited() {
+        let base = "<template>\n  <p>{{ msg }}</p>\n</template>\n\n<script>\nexport default { data: () => ({ msg: 'hi' }) }\n</script>\n\n<style>\np { color: red; }\n</style>\n";
+        let ours = "<template>\n  <p class=\"big\">{{ msg }}</p>\n</template>\n\n<script>\nexport default { data: () => ({ msg: 'hi' }) }\n</script>\n\n<style>\np { color: red; }\n</style>\n";
+        let theirs = "<template>\n  <p>{{ msg }}</p>\n</template>\n\n<script>\nexport default { data: () => ({ msg: 'hi' }) }\n</script>\n\n<style>\np { color: blue; }\n</style>\n";
+
+        let result = merge(base, ours, theirs, "App.vue");
+        assert!(result.is_clean());
+        assert!(result.content.contains("class=\"big\""));
+        assert!(result.content.contains("color: blue"));
+    }
+
+    #[test]
+    fn test_block_added_on_one_side() {
+        let base = "<script>\nlet a = 1;\n</script>\n\n<p>{a}</p>\n";
+        let ours = "<script>\nlet a = 1;\n</script>\n\n<p>{a}</p>\n\n<style>\np { margin: 0; }\n</style>\n";
+        let theirs = "<script>\nlet a = 2;\n</script>\n\n<p>{a}</p>\n";
+
+        let result = merge(base, ours, theirs, "Counter.svelte");
+        assert!(result.is_clean());
+        assert!(result.content.contains("let a = 2;"));
+        assert!(result.content.contains("margin: 0"));
+    }
+
+    #[test]
+    fn test_unknown_style_lang_merged_opaquely() {
+        let base = "<template>\n  <p>x</p>\n</template>\n\n<style lang=\"stylus\">\np\n  color red\n\nh1\n  margin 0\n</style>\n";
+        let ours = base.replace("color red", "color blue");
+        let theirs = base.replace("margin 0", "margin 1em");
+
+        let result = merge(base, &ours, &theirs, "App.vue");
+        assert!(result.is_clean());
+        assert_eq!(result.content, ours.replace("margin 0", "margin 1em"));
+
+        // Plain text: one line changed on both sides conflicts, rather than
+        // being split at `;` as if it were code
+        let base = "<template>\n  <p>x</p>\n</template>\n\n<style lang=\"stylus\">\np\n  color red; margin 0\n</style>\n";
+        let ours = base.replace("color red", "color blue");
+        let theirs = base.replace("margin 0", "margin 1em");
+        let result = merge(base, &ours, &theirs, "App.vue");
+        assert!(!result.is_clean());
+        assert!(result.content.contains("<<<<<<< ours\n  color blue; margin 0\n"));
+    }
+
+    #[test]
+    fn test_no_blocks_returns_none() {
+        let registry = sem_core::parser::plugins::create_default_registry();
+        assert!(merge_single_file_component("a\n", "b\n", "c\n", "x.svelte", &registry, &WeaveConfig::default(), &MergeContext::default()).is_none());
+    }
+
+    #[test]
+    fn test_tag_attributes() {
+        assert_eq!(tag_attribute("<script lang=\"ts\" setup>", "lang").as_deref(), Some("ts"));
+        assert_eq!(script_variant("<script lang=\"ts\" setup>"), ":setup");
+        assert_eq!(script_variant("<script context=\"module\">"), ":module");
+        assert_eq!(script_variant("<script>"), "");
+    }
+}
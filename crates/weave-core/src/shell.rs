@@ -0,0 +1,21 @@
+//! Running configured commands (`[generated] command`, `[[verify]]`).
+
+use std::process::Command;
+
+/// `command` run by the platform's shell: `sh -c` on Unix, `cmd /C` on
+/// Windows, so configs can use pipes, redirections and variables.
+pub(crate) fn shell(command: &str) -> Command {
+    let (program, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let mut shell = Command::new(program);
+    shell.arg(flag).arg(command);
+    shell
+}
+
+/// `arg` quoted for [`shell`].
+pub(crate) fn quote(arg: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", arg.replace('"', "\"\""))
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
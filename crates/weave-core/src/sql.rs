@@ -0,0 +1,500 @@
+//! Statement-level merge for SQL schema and migration files (`.sql`).
+//!
+//! `CREATE TABLE`, `CREATE VIEW`, `CREATE FUNCTION` (and indexes, types,
+//! sequences, triggers, ...) are the entities, keyed by kind and name. Other
+//! statements (`ALTER TABLE`, `INSERT`, ...) are keyed by their text, so two
+//! branches appending different statements both keep theirs.
+//!
+//! Columns and constraints inside a `CREATE TABLE` merge as keyed members:
+//! one branch adding a column while the other adds an index merges cleanly,
+//! and so do two branches adding different columns.
+
+use std::collections::HashMap;
+
+use crate::conflict::{classify_conflict, BlockCounter, EntityConflict, MergeStats};
+use crate::keyed::{merge_keyed, pick_side, KeyedMerge};
+use crate::merge::{line_level_fallback, MergeContext, MergeResult};
+
+/// Check if a file is SQL.
+pub fn is_sql(file_path: &str) -> bool {
+    file_path.to_lowercase().ends_with(".sql")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Statement {
+    /// Whitespace and comments before the statement.
+    leading: String,
+    /// The statement including its `;`.
+    text: String,
+}
+
+/// A file split into keyed statements.
+struct Script {
+    statements: Vec<(String, Statement)>,
+    tail: String,
+}
+
+/// Merge three versions of a SQL file statement by statement.
+///
+/// Returns None if any version can't be split (unterminated string, comment
+/// or dollar quote).
+pub fn merge_sql(base: &str, ours: &str, theirs: &str, file_path: &str, context: &MergeContext) -> Option<MergeResult> {
+    let base_script = split_statements(base)?;
+    let ours_script = split_statements(ours)?;
+    let theirs_script = split_statements(theirs)?;
+
+    let mut stats = MergeStats::default();
+    let merged = merge_keyed(
+        &base_script.statements,
+        &ours_script.statements,
+        &theirs_script.statements,
+        &mut stats,
+        |key, b, o, t| merge_statement(key, b, o, t, file_path, context),
+    );
+
+    let mut content = String::new();
+    let mut conflicts = Vec::new();
+    let mut conflict_blocks = Vec::new();
+    let mut counter = BlockCounter::default();
+    for entry in merged {
+        match entry {
+            KeyedMerge::Clean(statement) => {
+                content.push_str(&statement.leading);
+                content.push_str(&statement.text);
+            }
+            KeyedMerge::Conflict(c) => {
+                let (entity_type, name) = describe_key(&c.key);
+                let base_text = c.base.as_ref().map(|s| s.text.clone());
+                let ours_text = c.ours.as_ref().map(|s| s.text.clone());
+                let theirs_text = c.theirs.as_ref().map(|s| s.text.clone());
+                let conflict = EntityConflict {
+                    entity_name: name.to_string(),
+                    entity_type: entity_type.to_string(),
+                    kind: c.kind,
+                    complexity: classify_conflict(base_text.as_deref(), ours_text.as_deref(), theirs_text.as_deref()),
+                    ours_content: ours_text,
+                    theirs_content: theirs_text,
+                    base_content: base_text,
+                };
+                let leading = c.ours.as_ref().or(c.theirs.as_ref()).map(|s| s.leading.as_str()).unwrap_or("");
+                content.push_str(leading);
+                if !content.is_empty() && !content.ends_with('\n') {
+                    content.push('\n');
+                }
+                conflict_blocks.push(Some(counter.next(&content)));
+                content.push_str(&conflict.to_conflict_markers());
+                conflicts.push(conflict);
+            }
+        }
+    }
+
+    let tail = pick_side(Some(&base_script.tail), Some(&ours_script.tail), Some(&theirs_script.tail))
+        .flatten()
+        .unwrap_or(&ours_script.tail);
+    content.push_str(tail);
+
+    Some(MergeResult {
+        content,
+        conflicts,
+        conflict_blocks,
+        stats,
+        ..Default::default()
+    })
+}
+
+/// Merge a statement changed on both sides: tables column by column,
+/// everything else line by line.
+fn merge_statement(
+    key: &str,
+    base: Option<&Statement>,
+    ours: &Statement,
+    theirs: &Statement,
+    file_path: &str,
+    context: &MergeContext,
+) -> Result<Statement, Option<Statement>> {
+    let leading = pick_side(base.map(|s| &s.leading), Some(&ours.leading), Some(&theirs.leading))
+        .flatten()
+        .unwrap_or(&ours.leading)
+        .clone();
+    let base_text = base.map(|s| s.text.as_str());
+
+    let text = if key.starts_with("table:") {
+        merge_table(base_text, &ours.text, &theirs.text).ok_or(None)?
+    } else {
+        let base_text = base_text.ok_or(None)?;
+        let result = line_level_fallback(base_text, &ours.text, &theirs.text, file_path, context);
+        if !result.is_clean() {
+            return Err(None);
+        }
+        result.content
+    };
+    Ok(Statement { leading, text })
+}
+
+/// A `CREATE TABLE` statement split around its column list.
+struct Table {
+    /// Up to and including the opening `(`.
+    prefix: String,
+    /// Column and constraint definitions, each with its leading whitespace.
+    members: Vec<(String, String)>,
+    /// Whitespace before the closing `)`.
+    tail: String,
+    /// From the closing `)` to the end of the statement.
+    suffix: String,
+}
+
+/// Merge two versions of a `CREATE TABLE` column by column. Returns None if
+/// the tables can't be split or any member conflicts.
+fn merge_table(base: Option<&str>, ours: &str, theirs: &str) -> Option<String> {
+    let base_table = match base {
+        Some(b) => split_table(b)?,
+        None => Table {
+            prefix: String::new(),
+            members: vec![],
+            tail: String::new(),
+            suffix: String::new(),
+        },
+    };
+    let ours_table = split_table(ours)?;
+    let theirs_table = split_table(theirs)?;
+
+    let base_prefix = base.map(|_| &base_table.prefix);
+    let base_suffix = base.map(|_| &base_table.suffix);
+    let prefix = pick_side(base_prefix, Some(&ours_table.prefix), Some(&theirs_table.prefix)).flatten()?;
+    let suffix = pick_side(base_suffix, Some(&ours_table.suffix), Some(&theirs_table.suffix)).flatten()?;
+    let tail = pick_side(Some(&base_table.tail), Some(&ours_table.tail), Some(&theirs_table.tail))
+        .flatten()
+        .unwrap_or(&ours_table.tail);
+
+    // Member changes don't count as top-level entities in the stats
+    let merged = merge_keyed(
+        &base_table.members,
+        &ours_table.members,
+        &theirs_table.members,
+        &mut MergeStats::default(),
+        |_, _, _, _| Err(None),
+    );
+    let mut members = Vec::new();
+    for entry in merged {
+        match entry {
+            KeyedMerge::Clean(member) => members.push(member),
+            KeyedMerge::Conflict(_) => return None,
+        }
+    }
+
+    Some(format!("{}{}{}{}", prefix, members.join(","), tail, suffix))
+}
+
+fn split_table(text: &str) -> Option<Table> {
+    let open = find_top_level(text, 0, |b| b == b'(')??;
+    let close = find_matching_paren(text, open)?;
+    let inner = &text[open + 1..close];
+
+    let mut members = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut start = 0;
+    loop {
+        let end = find_top_level(inner, start, |b| b == b',')?.unwrap_or(inner.len());
+        let member = &inner[start..end];
+        if !member.trim().is_empty() {
+            let key = member_key(member);
+            let count = seen.entry(key.clone()).or_insert(0);
+            *count += 1;
+            members.push((format!("{}#{}", key, count), member.to_string()));
+        }
+        if end == inner.len() {
+            break;
+        }
+        start = end + 1;
+    }
+
+    // Whitespace after the last member belongs to the table, so members
+    // appended by either side land before it
+    let mut tail = String::new();
+    if let Some((_, last)) = members.last_mut() {
+        let trimmed = last.trim_end().len();
+        tail = last[trimmed..].to_string();
+        last.truncate(trimmed);
+    }
+
+    Some(Table {
+        prefix: text[..=open].to_string(),
+        members,
+        tail,
+        suffix: text[close..].to_string(),
+    })
+}
+
+/// Key of a column or table constraint.
+fn member_key(member: &str) -> String {
+    let code = strip_comments(member);
+    let normalized = code.split_whitespace().collect::<Vec<_>>().join(" ");
+    let first = normalized.split([' ', '(']).next().unwrap_or("").to_uppercase();
+    match first.as_str() {
+        "CONSTRAINT" => {
+            let name = normalized.split_whitespace().nth(1).unwrap_or("");
+            format!("constraint:{}", unquote(name))
+        }
+        "PRIMARY" | "UNIQUE" | "FOREIGN" | "CHECK" | "EXCLUDE" | "INDEX" | "KEY" => {
+            format!("constraint:{}", normalized)
+        }
+        _ => {
+            let name = normalized.split_whitespace().next().unwrap_or("");
+            format!("column:{}", unquote(name))
+        }
+    }
+}
+
+/// Split a script into statements at top-level `;`.
+fn split_statements(content: &str) -> Option<Script> {
+    let mut statements = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut pos = 0;
+
+    loop {
+        let start = skip_trivia(content, pos)?;
+        if start >= content.len() {
+            return Some(Script {
+                statements,
+                tail: content[pos..].to_string(),
+            });
+        }
+        let end = match find_top_level(content, start, |b| b == b';')? {
+            Some(semicolon) => semicolon + 1,
+            // Last statement without `;`
+            None => start + content[start..].trim_end().len(),
+        };
+
+        let statement = Statement {
+            leading: content[pos..start].to_string(),
+            text: content[start..end].to_string(),
+        };
+        let key = statement_key(&statement.text);
+        let count = seen.entry(key.clone()).or_insert(0);
+        *count += 1;
+        statements.push((format!("{}#{}", key, count), statement));
+        pos = end;
+    }
+}
+
+/// Key of a statement: `kind:name` for `CREATE` statements, the normalized
+/// text otherwise.
+fn statement_key(text: &str) -> String {
+    let code = strip_comments(text);
+    let normalized = code.trim_end_matches(';').split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut words = normalized.split(' ');
+
+    if words.next().is_some_and(|w| w.eq_ignore_ascii_case("CREATE")) {
+        let mut kind = String::new();
+        for word in words {
+            let upper = word.to_uppercase();
+            match upper.as_str() {
+                "OR" | "REPLACE" | "GLOBAL" | "LOCAL" | "TEMP" | "TEMPORARY" | "UNLOGGED" | "UNIQUE"
+                | "RECURSIVE" | "MATERIALIZED" | "CONCURRENTLY" | "IF" | "NOT" | "EXISTS" | "DEFINER" => {}
+                "TABLE" | "VIEW" | "FUNCTION" | "PROCEDURE" | "INDEX" | "TYPE" | "SEQUENCE" | "TRIGGER"
+                | "SCHEMA" | "DOMAIN" | "EXTENSION" | "POLICY" | "ROLE"
+                    if kind.is_empty() =>
+                {
+                    kind = upper.to_lowercase();
+                }
+                _ if !kind.is_empty() => {
+                    let name = word.split('(').next().unwrap_or(word);
+                    return format!("{}:{}", kind, unquote(name));
+                }
+                _ => break,
+            }
+        }
+    }
+    format!("statement:{}", normalized)
+}
+
+/// Split a key into (entity type, name) for conflict reports.
+fn describe_key(key: &str) -> (&str, &str) {
+    let (kind, rest) = key.split_once(':').unwrap_or(("statement", key));
+    let name = rest.rsplit_once('#').map(|(n, _)| n).unwrap_or(rest);
+    (kind, name)
+}
+
+/// Lowercase unquoted identifiers (SQL folds them); keep quoted ones as written.
+fn unquote(name: &str) -> String {
+    if name.contains(['"', '`', '[']) {
+        name.replace(['"', '`', '[', ']'], "")
+    } else {
+        name.to_lowercase()
+    }
+}
+
+/// Skip whitespace and comments starting at `pos`. None if a block comment
+/// is unterminated.
+fn skip_trivia(content: &str, mut pos: usize) -> Option<usize> {
+    loop {
+        let rest = &content[pos..];
+        pos += rest.len() - rest.trim_start().len();
+        let next = skip_comment(content, pos)?;
+        if next == pos {
+            return Some(pos);
+        }
+        pos = next;
+    }
+}
+
+/// Text with `--` and `/* */` comments replaced by a space (strings left intact).
+fn strip_comments(text: &str) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < text.len() {
+        let after_comment = skip_comment(text, i).unwrap_or(text.len());
+        if after_comment > i {
+            out.push(' ');
+            i = after_comment;
+            continue;
+        }
+        let after_quoted = skip_quoted(text, i).unwrap_or(text.len());
+        let end = if after_quoted > i { after_quoted } else { i + text[i..].chars().next().map_or(1, char::len_utf8) };
+        out.push_str(&text[i..end]);
+        i = end;
+    }
+    out
+}
+
+/// Find the first byte at or after `from` matching `want`, outside strings,
+/// quoted identifiers, dollar quotes, comments and parentheses.
+///
+/// Returns `Some(None)` if there is none, and None if a string or comment
+/// is unterminated.
+fn find_top_level(text: &str, from: usize, want: impl Fn(u8) -> bool) -> Option<Option<usize>> {
+    let bytes = text.as_bytes();
+    let mut i = from;
+    let mut depth = 0usize;
+    while i < bytes.len() {
+        let next = skip_comment(text, skip_quoted(text, i)?)?;
+        if next > i {
+            i = next;
+            continue;
+        }
+        match bytes[i] {
+            b if depth == 0 && want(b) => return Some(Some(i)),
+            b'(' => depth += 1,
+            b')' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        i += 1;
+    }
+    Some(None)
+}
+
+/// Index of the `)` matching the `(` at `open`.
+fn find_matching_paren(text: &str, open: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut depth = 0usize;
+    let mut i = open;
+    while i < bytes.len() {
+        let next = skip_comment(text, skip_quoted(text, i)?)?;
+        if next > i {
+            i = next;
+            continue;
+        }
+        match bytes[i] {
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Index past the comment starting at `i`, or `i` if there is none.
+fn skip_comment(text: &str, i: usize) -> Option<usize> {
+    let rest = &text[i..];
+    if rest.starts_with("--") {
+        Some(i + rest.find('\n').map(|n| n + 1).unwrap_or(rest.len()))
+    } else if rest.starts_with("/*") {
+        Some(i + rest.find("*/")? + 2)
+    } else {
+        Some(i)
+    }
+}
+
+/// Index past the string, quoted identifier or dollar-quoted body starting
+/// at `i`, or `i` if there is none.
+fn skip_quoted(text: &str, i: usize) -> Option<usize> {
+    let rest = &text[i..];
+    match rest.as_bytes().first() {
+        // `'it''s'` is two adjacent strings as far as splitting is concerned
+        Some(&quote @ (b'\'' | b'"' | b'`')) => Some(i + 1 + rest[1..].find(quote as char)? + 1),
+        Some(b'$') => match dollar_tag(rest) {
+            Some(tag) => Some(i + tag.len() + rest[tag.len()..].find(tag)? + tag.len()),
+            None => Some(i),
+        },
+        _ => Some(i),
+    }
+}
+
+/// The `$tag$` opening a dollar-quoted body at the start of `s` (`$$` included).
+fn dollar_tag(s: &str) -> Option<&str> {
+    let end = s[1..].find('$')? + 1;
+    let tag = &s[1..end];
+    let valid = tag.chars().all(|c| c.is_alphanumeric() || c == '_') && !tag.starts_with(|c: char| c.is_ascii_digit());
+    valid.then(|| &s[..=end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "-- schema\nCREATE TABLE users (\n  id serial PRIMARY KEY,\n  name text NOT NULL\n);\n\nCREATE FUNCTION touch() RETURNS trigger AS $$\nBEGIN\n  NEW.updated_at = now();\n  RETURN NEW;\nEND;\n$$ LANGUAGE plpgsql;\n";
+
+    #[test]
+    fn test_split_and_keys() {
+        let script = split_statements(BASE).unwrap();
+        let keys: Vec<&str> = script.statements.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["table:users#1", "function:touch#1"]);
+        let rendered: String = script.statements.iter().map(|(_, s)| format!("{}{}", s.leading, s.text)).collect();
+        assert_eq!(format!("{}{}", rendered, script.tail), BASE);
+    }
+
+    #[test]
+    fn test_both_add_columns() {
+        let ours = BASE.replace("  name text NOT NULL\n", "  name text NOT NULL,\n  email text\n");
+        let theirs = BASE.replace("  name text NOT NULL\n", "  name text NOT NULL,\n  created_at timestamptz DEFAULT now()\n");
+        let result = merge_sql(BASE, &ours, &theirs, "schema.sql", &MergeContext::default()).unwrap();
+        assert!(result.is_clean());
+        assert!(result.content.contains(
+            "  name text NOT NULL,\n  email text,\n  created_at timestamptz DEFAULT now()\n);"
+        ));
+        assert_eq!(result.stats.entities_both_changed_merged, 1);
+    }
+
+    #[test]
+    fn test_both_append_statements() {
+        let ours = format!("{}\nCREATE VIEW active_users AS SELECT * FROM users;\n", BASE);
+        let theirs = format!("{}\nALTER TABLE users ADD COLUMN bio text;\n", BASE);
+        let result = merge_sql(BASE, &ours, &theirs, "schema.sql", &MergeContext::default()).unwrap();
+        assert!(result.is_clean());
+        assert!(result.content.contains("CREATE VIEW active_users"));
+        assert!(result.content.ends_with("ALTER TABLE users ADD COLUMN bio text;\n"));
+    }
+
+    #[test]
+    fn test_same_column_changed_differently_conflicts() {
+        let ours = BASE.replace("name text NOT NULL", "name varchar(100) NOT NULL");
+        let theirs = BASE.replace("name text NOT NULL", "name text");
+        let result = merge_sql(BASE, &ours, &theirs, "schema.sql", &MergeContext::default()).unwrap();
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].entity_type, "table");
+        assert_eq!(result.conflicts[0].entity_name, "users");
+        assert!(result.content.contains("<<<<<<< ours"));
+        assert!(result.content.contains("CREATE FUNCTION touch()"));
+    }
+
+    #[test]
+    fn test_unterminated_dollar_quote_returns_none() {
+        assert!(merge_sql("SELECT $$ x;", "", "", "x.sql", &MergeContext::default()).is_none());
+    }
+}
@@ -0,0 +1,175 @@
+//! Rule-level merge for stylesheets (`.css`, `.scss`, `.less`).
+//!
+//! Rules, at-rules and nested blocks are the entities: two branches adding
+//! different rules to `styles.css` merge cleanly. Inside a block, items are
+//! keyed again — declarations by property name, nested rules by selector —
+//! so edits to different properties of the same rule also merge.
+//!
+//! Comments and whitespace before an item travel with it, so the file
+//! round-trips byte for byte when nothing changes.
+
+use crate::braces::{self, Item, Syntax};
+use crate::conflict::MergeStats;
+use crate::merge::MergeResult;
+
+/// Check if a file is a stylesheet weave can merge by rule.
+pub fn is_stylesheet(file_path: &str) -> bool {
+    let lower = file_path.to_lowercase();
+    lower.ends_with(".css") || lower.ends_with(".scss") || lower.ends_with(".less")
+}
+
+/// Merge three versions of a stylesheet rule by rule.
+///
+/// Returns None if any version can't be parsed (unbalanced braces), so the
+/// caller can fall back to the regular merge.
+pub fn merge_stylesheet(base: &str, ours: &str, theirs: &str, file_path: &str) -> Option<MergeResult> {
+    let syntax = if file_path.to_lowercase().ends_with(".css") { &CSS } else { &SCSS };
+    let base_sheet = braces::parse(base, syntax)?;
+    let ours_sheet = braces::parse(ours, syntax)?;
+    let theirs_sheet = braces::parse(theirs, syntax)?;
+
+    let mut stats = MergeStats::default();
+    let mut conflicts = Vec::new();
+    let merged = braces::merge_block(&base_sheet, &ours_sheet, &theirs_sheet, syntax, &mut stats, &mut conflicts);
+
+    let (content, conflict_blocks) = merged.render_merged(conflicts.len());
+    Some(MergeResult {
+        content,
+        conflicts,
+        conflict_blocks,
+        stats,
+        ..Default::default()
+    })
+}
+
+const CSS: Syntax = Syntax {
+    line_comments: &[],
+    newline_terminates: false,
+    descriptions: false,
+    key: item_key,
+    entity_type,
+};
+
+const SCSS: Syntax = Syntax {
+    line_comments: &["//"],
+    ..CSS
+};
+
+/// Entity type for conflict reports.
+fn entity_type(kind: &str) -> &str {
+    match kind {
+        "at" => "at-rule",
+        "decl" => "declaration",
+        "stmt" => "statement",
+        _ => "rule",
+    }
+}
+
+/// Key for matching an item across versions.
+fn item_key(item: &Item) -> String {
+    let normalized = item.normalized_header();
+
+    if item.body.is_some() {
+        return if normalized.starts_with('@') {
+            format!("at:{}", normalized)
+        } else {
+            format!("rule:{}", normalized)
+        };
+    }
+
+    // `@import`, `@use`, `@include mixin(...)` and friends: the statement is its own key
+    if normalized.starts_with("@import")
+        || normalized.starts_with("@use")
+        || normalized.starts_with("@forward")
+        || normalized.starts_with("@include")
+        || normalized.starts_with("@extend")
+        || normalized.starts_with("@charset")
+    {
+        return format!("stmt:{}", normalized);
+    }
+
+    // Declarations and variables (`color:`, `--gap:`, `$size:`, `@size:`) are keyed by name
+    match normalized.split_once(':') {
+        Some((name, _)) if !name.trim().contains(' ') => {
+            let name = name.trim();
+            let name = if name.starts_with("--") || name.starts_with('$') || name.starts_with('@') {
+                name.to_string()
+            } else {
+                name.to_lowercase()
+            };
+            format!("decl:{}", name)
+        }
+        _ => format!("stmt:{}", normalized),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(base: &str, ours: &str, theirs: &str) -> MergeResult {
+        merge_stylesheet(base, ours, theirs, "styles.css").unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let css = "/* header */\n@import url(\"a.css\");\n\n.a, .b {\n  color: red;\n  background: url(data:image/png;base64,xx);\n}\n\n@media (max-width: 600px) {\n  .a { color: blue }\n}\n";
+        let sheet = braces::parse(css, &CSS).unwrap();
+        assert_eq!(sheet.render(), css);
+        let keys: Vec<&str> = sheet.items.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["stmt:@import url(\"a.css\")#1", "rule:.a, .b#1", "at:@media (max-width: 600px)#1"]);
+    }
+
+    #[test]
+    fn test_both_add_rules() {
+        let base = ".a {\n  color: red;\n}\n";
+        let ours = ".a {\n  color: red;\n}\n\n.b {\n  margin: 0;\n}\n";
+        let theirs = ".a {\n  color: red;\n}\n\n.c {\n  padding: 0;\n}\n";
+        let result = merge(base, ours, theirs);
+        assert!(result.is_clean());
+        assert_eq!(result.content, ".a {\n  color: red;\n}\n\n.b {\n  margin: 0;\n}\n\n.c {\n  padding: 0;\n}\n");
+        assert_eq!(result.stats.entities_added_ours, 1);
+        assert_eq!(result.stats.entities_added_theirs, 1);
+    }
+
+    #[test]
+    fn test_different_properties_of_same_rule() {
+        let base = ".btn {\n  color: red;\n  padding: 4px;\n}\n";
+        let ours = ".btn {\n  color: blue;\n  padding: 4px;\n}\n";
+        let theirs = ".btn {\n  color: red;\n  padding: 8px;\n  border: 0;\n}\n";
+        let result = merge(base, ours, theirs);
+        assert!(result.is_clean());
+        assert_eq!(result.content, ".btn {\n  color: blue;\n  padding: 8px;\n  border: 0;\n}\n");
+    }
+
+    #[test]
+    fn test_same_property_conflicts_in_place() {
+        let base = ".btn {\n  color: red;\n}\n.other { margin: 0; }\n";
+        let ours = ".btn {\n  color: blue;\n}\n.other { margin: 0; }\n";
+        let theirs = ".btn {\n  color: green;\n}\n.other { margin: 1px; }\n";
+        let result = merge(base, ours, theirs);
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].entity_type, "declaration");
+        assert_eq!(result.conflicts[0].entity_name, "color");
+        assert_eq!(result.conflict_blocks, vec![Some(0)]);
+        assert!(result.content.starts_with(".btn {\n<<<<<<< ours"));
+        assert!(result.content.contains(".other { margin: 1px; }"));
+    }
+
+    #[test]
+    fn test_scss_nesting_and_variables() {
+        let base = "$gap: 4px;\n.card {\n  // spacing\n  padding: $gap;\n  &:hover { color: red; }\n}\n";
+        let ours = "$gap: 8px;\n.card {\n  // spacing\n  padding: $gap;\n  &:hover { color: red; }\n}\n";
+        let theirs = "$gap: 4px;\n.card {\n  // spacing\n  padding: $gap;\n  &:hover { color: red; text-decoration: underline; }\n}\n";
+        let result = merge_stylesheet(base, ours, theirs, "card.scss").unwrap();
+        assert!(result.is_clean());
+        assert!(result.content.starts_with("$gap: 8px;"));
+        assert!(result.content.contains("&:hover { color: red; text-decoration: underline; }"));
+    }
+
+    #[test]
+    fn test_unbalanced_returns_none() {
+        assert!(merge_stylesheet(".a {", ".a {}", ".a {}", "x.css").is_none());
+        assert!(merge_stylesheet("}", "", "", "x.css").is_none());
+    }
+}
@@ -0,0 +1,265 @@
+//! Merge decision trace, for explaining why weave merged a file the way it did.
+//!
+//! Recorded when [`WeaveConfig::trace`](crate::config::WeaveConfig::trace) is
+//! set: every file-level decision (fast path, format-specific merger,
+//! fallback to line-level merge) and the resolution path of every entity,
+//! in the order they were taken.
+//!
+//! Entities are traced for the entity merge only. Format-specific mergers
+//! (notebooks, stylesheets, build files, the markdown retry...) record the
+//! merger they used, not how each of their cells, rules or blocks was
+//! resolved; their conflicts show in the result as usual.
+
+use std::fmt;
+
+/// The decisions taken while merging one file.
+#[derive(Debug, Clone, Default)]
+pub struct MergeTrace {
+    pub steps: Vec<TraceStep>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TraceStep {
+    /// A decision about the whole file.
+    File(FileDecision),
+    /// How one entity was resolved.
+    Entity {
+        name: String,
+        entity_type: String,
+        path: EntityPath,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileDecision {
+    /// Both sides are identical.
+    Identical,
+    /// Only theirs changed the file.
+    OnlyTheirsChanged,
+    /// Only ours changed the file.
+    OnlyOursChanged,
+    /// Merged by a format-specific merger (stylesheet, protobuf, notebook...).
+    FormatMerger(&'static str),
+    /// A format-specific merger couldn't parse one of the versions.
+    FormatUnparsed(&'static str),
+    /// Merged line by line instead of by entity.
+    Fallback(FallbackReason),
+    /// Entities matched through the parser plugin.
+    EntityMerge { plugin: String },
+    /// An entity renamed on one side was matched to its base by structure.
+    Renamed { in_ours: bool, from: String, to: String },
+    /// Both sides changed the imports between two entities: merged as a set.
+    ImportUnion,
+    /// Both sides changed the text between two entities: merged line by line.
+    InterstitialMerged { conflicted: bool },
+    /// Dependencies bumped on both sides were reconciled to the higher version.
+    VersionBumpsReconciled(usize),
+    /// Markdown retried with fences merged per language and lists unioned.
+    MarkdownRetry { kept: bool },
+    /// Conflicts resolved by replaying recorded resolutions.
+    RerereReplayed(usize),
+    /// Conflicts resolved by configured policies.
+    PoliciesApplied(usize),
+    /// Protected entities both sides changed, merged by a resolver and
+    /// conflicted again.
+    ProtectionEnforced(usize),
+}
+
+/// Why a file was merged line by line instead of by entity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FallbackReason {
+    /// One of the versions contains null bytes.
+    Binary,
+    /// One of the versions exceeds the size limit.
+    LargeFile,
+    /// No parser supports the file type.
+    NoParser,
+    /// The parser found no entities in non-empty content.
+    NoEntities,
+    /// Too many entities share a name for matching to stay fast.
+    ExcessiveDuplicates,
+    /// The entity merge left more conflict markers than `git merge-file`.
+    MoreMarkersThanGit { entity: usize, git: usize },
+    /// The entity merge didn't finish in time.
+    Timeout,
+    /// The entity merge was cancelled through its budget.
+    Cancelled,
+    /// The merged output has ERROR or MISSING nodes where neither side did.
+    SyntaxError { line: usize },
+    /// A `[[verify]]` check failed on the merged output.
+    VerificationFailed { command: String },
+}
+
+/// How an entity was resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityPath {
+    Unchanged,
+    OursOnly,
+    TheirsOnly,
+    /// Both sides made the same change.
+    SameChange,
+    /// Ours only reformatted it: took theirs.
+    OursReformatted,
+    /// Theirs only reformatted it: took ours.
+    TheirsReformatted,
+    /// Both changes merged line by line.
+    Diffy,
+    /// Decorators merged as a set, bodies line by line.
+    DecoratorMerge,
+    /// Members merged one by one.
+    InnerMerge,
+    /// Members merged one by one, with some conflicting.
+    InnerMergeConflict,
+    Conflict,
+    ModifyDelete,
+    /// Deleted on one side, unchanged on the other.
+    Deleted,
+    DeletedBoth,
+    AddedOurs,
+    AddedTheirs,
+    /// Both sides added it with the same content.
+    BothAddedSame,
+    BothAddedConflict,
+    RenameRename,
+}
+
+impl MergeTrace {
+    pub(crate) fn file(&mut self, decision: FileDecision) {
+        self.steps.push(TraceStep::File(decision));
+    }
+
+    pub(crate) fn entity(&mut self, name: &str, entity_type: &str, path: EntityPath) {
+        self.steps.push(TraceStep::Entity {
+            name: name.to_string(),
+            entity_type: entity_type.to_string(),
+            path,
+        });
+    }
+}
+
+/// Recording into a trace that may be off. The merge passes its trace down
+/// as `Option<&mut MergeTrace>`, `None` unless requested, so a merge without
+/// one records and allocates nothing.
+pub(crate) trait Record {
+    fn file(&mut self, decision: FileDecision);
+    fn entity(&mut self, name: &str, entity_type: &str, path: EntityPath);
+}
+
+impl Record for Option<&mut MergeTrace> {
+    fn file(&mut self, decision: FileDecision) {
+        if let Some(trace) = self {
+            trace.file(decision);
+        }
+    }
+
+    fn entity(&mut self, name: &str, entity_type: &str, path: EntityPath) {
+        if let Some(trace) = self {
+            trace.entity(name, entity_type, path);
+        }
+    }
+}
+
+impl fmt::Display for MergeTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceStep::File(decision) => write!(f, "{}", decision),
+            TraceStep::Entity { name, entity_type, path } => write!(f, "{} `{}`: {}", entity_type, name, path),
+        }
+    }
+}
+
+impl fmt::Display for FileDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileDecision::Identical => write!(f, "both sides identical: took ours"),
+            FileDecision::OnlyTheirsChanged => write!(f, "only theirs changed the file: took theirs"),
+            FileDecision::OnlyOursChanged => write!(f, "only ours changed the file: took ours"),
+            FileDecision::FormatMerger(format) => write!(f, "merged as {}", format),
+            FileDecision::FormatUnparsed(format) => write!(f, "could not parse as {}", format),
+            FileDecision::Fallback(reason) => write!(f, "line-level merge: {}", reason),
+            FileDecision::EntityMerge { plugin } => write!(f, "entity merge with the {} parser", plugin),
+            FileDecision::Renamed { in_ours, from, to } => {
+                let side = if *in_ours { "ours" } else { "theirs" };
+                write!(f, "rename detected in {}: `{}` → `{}`", side, from, to)
+            }
+            FileDecision::ImportUnion => write!(f, "imports changed on both sides: merged as a set"),
+            FileDecision::InterstitialMerged { conflicted: false } => {
+                write!(f, "text between entities changed on both sides: merged line by line")
+            }
+            FileDecision::InterstitialMerged { conflicted: true } => {
+                write!(f, "text between entities changed on both sides: conflicted")
+            }
+            FileDecision::VersionBumpsReconciled(n) => write!(f, "reconciled {} dependency bump(s)", n),
+            FileDecision::MarkdownRetry { kept: true } => {
+                write!(f, "retried as markdown blocks: fewer conflicts, kept")
+            }
+            FileDecision::MarkdownRetry { kept: false } => {
+                write!(f, "retried as markdown blocks: no fewer conflicts, discarded")
+            }
+            FileDecision::RerereReplayed(n) => write!(f, "replayed {} recorded resolution(s)", n),
+            FileDecision::PoliciesApplied(n) => write!(f, "resolved {} conflict(s) by policy", n),
+            FileDecision::ProtectionEnforced(n) => write!(f, "conflicted {} protected entit(ies) changed on both sides", n),
+        }
+    }
+}
+
+impl fmt::Display for FallbackReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FallbackReason::Binary => write!(f, "binary content"),
+            FallbackReason::LargeFile => write!(f, "file larger than 1MB"),
+            FallbackReason::NoParser => write!(f, "no parser for this file type"),
+            FallbackReason::NoEntities => write!(f, "parser found no entities"),
+            FallbackReason::ExcessiveDuplicates => write!(f, "too many entities with the same name"),
+            FallbackReason::MoreMarkersThanGit { entity, git } => write!(
+                f,
+                "entity merge left {} conflict marker(s), git merge-file {}",
+                entity, git
+            ),
+            FallbackReason::Timeout => write!(f, "entity merge timed out"),
+            FallbackReason::Cancelled => write!(f, "entity merge was cancelled"),
+            FallbackReason::SyntaxError { line } => {
+                write!(f, "merged output has a syntax error at line {}", line)
+            }
+            FallbackReason::VerificationFailed { command } => {
+                write!(f, "`{}` failed on the merged output", command)
+            }
+        }
+    }
+}
+
+impl fmt::Display for EntityPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            EntityPath::Unchanged => "unchanged",
+            EntityPath::OursOnly => "changed in ours only",
+            EntityPath::TheirsOnly => "changed in theirs only",
+            EntityPath::SameChange => "same change on both sides",
+            EntityPath::OursReformatted => "ours only reformatted: took theirs",
+            EntityPath::TheirsReformatted => "theirs only reformatted: took ours",
+            EntityPath::Diffy => "both changed: merged line by line",
+            EntityPath::DecoratorMerge => "both changed: decorators merged as a set",
+            EntityPath::InnerMerge => "both changed: merged member by member",
+            EntityPath::InnerMergeConflict => "both changed: merged member by member, with conflicts",
+            EntityPath::Conflict => "both changed: conflict",
+            EntityPath::ModifyDelete => "modified on one side, deleted on the other: conflict",
+            EntityPath::Deleted => "deleted on one side, unchanged on the other",
+            EntityPath::DeletedBoth => "deleted on both sides",
+            EntityPath::AddedOurs => "added in ours",
+            EntityPath::AddedTheirs => "added in theirs",
+            EntityPath::BothAddedSame => "added on both sides with the same content",
+            EntityPath::BothAddedConflict => "added on both sides with different content: conflict",
+            EntityPath::RenameRename => "renamed differently on both sides: conflict",
+        };
+        f.write_str(text)
+    }
+}
@@ -9,11 +9,16 @@
 //! This module flags such "semantic risk" cases as warnings, not errors.
 //! The merge still succeeds — this is advisory.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
+use sem_core::model::entity::SemanticEntity;
 use sem_core::parser::graph::EntityGraph;
 use sem_core::parser::registry::ParserRegistry;
 
+use crate::ast::{call_sites, function_signatures, language_for, CallSite, FunctionSignature};
+use crate::conflict::ConflictComplexity;
+
 /// A warning about a potentially unsafe merge.
 #[derive(Debug, Clone)]
 pub struct SemanticWarning {
@@ -37,6 +42,58 @@ pub enum WarningKind {
     DependentAlsoModified,
     /// The merged output failed to parse — syntactically broken merge result.
     ParseFailedAfterMerge,
+    /// A `[[verify]]` check failed on the auto-merged file, which was merged
+    /// line by line instead.
+    VerificationFailed { command: String, output: String },
+    /// The auto-merged file had a syntax error at `line` that neither side
+    /// had, so it was merged line by line instead.
+    SyntaxErrorFallback { line: usize },
+    /// Both branches bumped the same dependency; the higher compatible
+    /// version was picked automatically (`[manifest] semver = true`).
+    VersionBumpResolved {
+        ours: String,
+        theirs: String,
+        chosen: String,
+    },
+    /// Both branches added a protobuf field with the same number to the
+    /// same message; the schema merged cleanly but the fields collide.
+    FieldNumberCollision { number: u64 },
+    /// A conflict was resolved by a configured `[[policy]]` instead of
+    /// being left to the user.
+    ResolvedByPolicy {
+        complexity: ConflictComplexity,
+        took_ours: bool,
+    },
+    /// Both branches changed the same field of a notebook or cell
+    /// differently (e.g. its metadata); JSON can't hold conflict markers, so
+    /// ours was kept.
+    FieldKeptOurs { field: String },
+    /// Both branches changed a generated file and the `[generated]` policy
+    /// kept one side's version, dropping the other's changes.
+    GeneratedSideDropped { kept_ours: bool },
+    /// A conflict was resolved by replaying the resolution recorded under
+    /// `id` (see [`crate::rerere`]).
+    ReplayedResolution { id: String },
+    /// The merged file defines the name twice in the same scope, which
+    /// neither side did. The other definition is in `related`.
+    DuplicateDefinition {
+        line: usize,
+        origin: DefinitionSide,
+        other_line: usize,
+        other_origin: DefinitionSide,
+    },
+    /// Entity added or changed on one side references an entity the other
+    /// side deleted (the one in `related`).
+    ReferencesDeletedEntity { deleted_in_ours: bool },
+    /// A call added or changed on one side no longer matches the parameters
+    /// the other side gave the called function (the one in `related`).
+    CallSignatureMismatch {
+        call: String,
+        call_line: usize,
+        parameters: String,
+        definition_line: usize,
+        changed_in_ours: bool,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -52,7 +109,7 @@ pub struct RelatedEntity {
 /// branches) and uses the entity dependency graph to check for cross-references
 /// between modified entities.
 pub fn validate_merge(
-    repo_root: &std::path::Path,
+    repo_root: &Path,
     file_paths: &[String],
     modified_entities: &[ModifiedEntity],
     registry: &ParserRegistry,
@@ -132,6 +189,461 @@ pub fn validate_merge(
     warnings
 }
 
+/// Two definitions of the same name in the same scope of a merged file.
+#[derive(Debug, Clone)]
+pub struct DuplicateDefinition {
+    pub first: DefinitionSite,
+    pub second: DefinitionSite,
+}
+
+/// One of the clashing definitions, located in the merged file.
+#[derive(Debug, Clone)]
+pub struct DefinitionSite {
+    pub name: String,
+    pub entity_type: String,
+    /// 1-based, inclusive.
+    pub start_line: usize,
+    pub end_line: usize,
+    pub content: String,
+    pub origin: DefinitionSide,
+}
+
+/// Where a clashing definition comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionSide {
+    /// Only ours has it.
+    Ours,
+    /// Only theirs has it.
+    Theirs,
+    /// Both sides have it as it is.
+    Both,
+    /// Merged from both sides' changes.
+    Merged,
+}
+
+impl DefinitionSide {
+    pub fn tag(&self) -> &'static str {
+        match self {
+            DefinitionSide::Ours => "ours",
+            DefinitionSide::Theirs => "theirs",
+            DefinitionSide::Both => "both",
+            DefinitionSide::Merged => "the merge",
+        }
+    }
+}
+
+impl DuplicateDefinition {
+    pub fn to_warning(&self, file_path: &str) -> SemanticWarning {
+        SemanticWarning {
+            entity_name: self.first.name.clone(),
+            entity_type: self.first.entity_type.clone(),
+            file_path: file_path.to_string(),
+            kind: WarningKind::DuplicateDefinition {
+                line: self.first.start_line,
+                origin: self.first.origin,
+                other_line: self.second.start_line,
+                other_origin: self.second.origin,
+            },
+            related: vec![RelatedEntity {
+                name: self.second.name.clone(),
+                entity_type: self.second.entity_type.clone(),
+                file_path: file_path.to_string(),
+            }],
+        }
+    }
+}
+
+type Scopes<'a> = HashMap<(Option<&'a str>, &'a str), Vec<&'a SemanticEntity>>;
+
+/// Named definitions by scope (parent, name). Impl blocks are named after
+/// their type and never clash.
+fn scopes<'a>(entities: impl IntoIterator<Item = &'a SemanticEntity>) -> Scopes<'a> {
+    let mut scopes: Scopes = HashMap::new();
+    for entity in entities {
+        if entity.name.is_empty() || entity.entity_type.contains("impl") {
+            continue;
+        }
+        scopes
+            .entry((entity.parent_id.as_deref(), entity.name.as_str()))
+            .or_default()
+            .push(entity);
+    }
+    scopes
+}
+
+fn count(scopes: &Scopes, key: (Option<&str>, &str)) -> usize {
+    scopes.get(&key).map_or(0, |defs| defs.len())
+}
+
+/// Whether merging `ours` and `theirs` can define a name in one scope more
+/// often than either side does, judged from the sides' entities alone: a
+/// definition of the merged file comes from ours, theirs or both (same
+/// id). When it can't, [`find_duplicate_definitions`] has nothing to find
+/// and the merged file needn't be parsed.
+pub fn may_have_duplicate_definitions(ours: &[SemanticEntity], theirs: &[SemanticEntity]) -> bool {
+    let ours_ids: HashSet<&str> = ours.iter().map(|e| e.id.as_str()).collect();
+    let ours_scopes = scopes(ours);
+    let theirs_scopes = scopes(theirs);
+    let union = scopes(ours.iter().chain(theirs.iter().filter(|e| !ours_ids.contains(e.id.as_str()))));
+    union
+        .iter()
+        .any(|(key, defs)| defs.len() > count(&ours_scopes, *key).max(count(&theirs_scopes, *key)))
+}
+
+/// Find names defined more than once in the same scope of a merged file
+/// where neither side had as many: both branches added a definition with
+/// the same name, or a method and a field that clash.
+///
+/// Only duplicates the merge introduced are reported, so overloads,
+/// `#[cfg]` variants or getter/setter pairs already on one side are left
+/// alone.
+pub fn find_duplicate_definitions(
+    merged: &[SemanticEntity],
+    ours: &[SemanticEntity],
+    theirs: &[SemanticEntity],
+) -> Vec<DuplicateDefinition> {
+    let ours_scopes = scopes(ours);
+    let theirs_scopes = scopes(theirs);
+    let origin = |entity: &SemanticEntity| {
+        let same = |e: &&SemanticEntity| e.entity_type == entity.entity_type && e.content.trim() == entity.content.trim();
+        let key = (entity.parent_id.as_deref(), entity.name.as_str());
+        let in_ours = ours_scopes.get(&key).is_some_and(|defs| defs.iter().any(same));
+        let in_theirs = theirs_scopes.get(&key).is_some_and(|defs| defs.iter().any(same));
+        match (in_ours, in_theirs) {
+            (true, true) => DefinitionSide::Both,
+            (true, false) => DefinitionSide::Ours,
+            (false, true) => DefinitionSide::Theirs,
+            (false, false) => DefinitionSide::Merged,
+        }
+    };
+    let site = |entity: &SemanticEntity| DefinitionSite {
+        name: entity.name.clone(),
+        entity_type: entity.entity_type.clone(),
+        start_line: entity.start_line,
+        end_line: entity.end_line,
+        content: entity.content.clone(),
+        origin: origin(entity),
+    };
+
+    let mut duplicates: Vec<DuplicateDefinition> = scopes(merged)
+        .into_iter()
+        .filter(|(key, defs)| defs.len() > count(&ours_scopes, *key).max(count(&theirs_scopes, *key)))
+        .flat_map(|(_, mut defs)| {
+            defs.sort_by_key(|e| e.start_line);
+            let first = site(defs[0]);
+            defs[1..]
+                .iter()
+                .map(|other| DuplicateDefinition {
+                    first: first.clone(),
+                    second: site(other),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    duplicates.sort_by_key(|d| (d.first.start_line, d.second.start_line));
+    duplicates
+}
+
+/// The three versions of a file changed in a merge. A version is empty
+/// where the file doesn't exist.
+#[derive(Debug, Clone)]
+pub struct FileVersions {
+    pub file_path: String,
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+    /// Whether the file exists in the base, ours and theirs, telling a
+    /// missing file from an empty one.
+    pub exists: [bool; 3],
+}
+
+/// Find entities added or changed on one side that reference an entity the
+/// other side deleted: both merge cleanly, and the build breaks.
+///
+/// Builds an [`EntityGraph`] of each side over the changed files, so a
+/// reference in one file to an entity deleted from another is found too.
+/// An entity moved to another changed file isn't deleted.
+pub fn find_dangling_references(files: &[FileVersions], registry: &ParserRegistry) -> Vec<SemanticWarning> {
+    let mut base_hashes: HashMap<(&str, String), String> = HashMap::new();
+    let mut ours_names: HashSet<String> = HashSet::new();
+    let mut theirs_names: HashSet<String> = HashSet::new();
+    let mut ours_changed: HashSet<(&str, String)> = HashSet::new();
+    let mut theirs_changed: HashSet<(&str, String)> = HashSet::new();
+    for file in files {
+        let Some(plugin) = registry.get_plugin(&file.file_path) else {
+            continue;
+        };
+        let path = file.file_path.as_str();
+        for entity in plugin.extract_entities(&file.base, path) {
+            base_hashes.insert((path, entity.name), entity.content_hash);
+        }
+        for (content, names, changed) in [
+            (&file.ours, &mut ours_names, &mut ours_changed),
+            (&file.theirs, &mut theirs_names, &mut theirs_changed),
+        ] {
+            for entity in plugin.extract_entities(content, path) {
+                let key = (path, entity.name.clone());
+                if base_hashes.get(&key) != Some(&entity.content_hash) {
+                    changed.insert(key);
+                }
+                names.insert(entity.name);
+            }
+        }
+    }
+    let in_base = |name: &str| base_hashes.keys().any(|(_, n)| n == name);
+
+    let mut warnings = Vec::new();
+    for (referencing_ours, changed, deleted_from) in [(true, &ours_changed, &theirs_names), (false, &theirs_changed, &ours_names)] {
+        let Some(graph) = build_side_graph(files, referencing_ours, registry) else {
+            continue;
+        };
+        for entity in graph.entities.values() {
+            if !changed.contains(&(entity.file_path.as_str(), entity.name.clone())) {
+                continue;
+            }
+            for dep in graph.get_dependencies(&entity.id) {
+                if in_base(&dep.name) && !deleted_from.contains(&dep.name) {
+                    warnings.push(SemanticWarning {
+                        entity_name: entity.name.clone(),
+                        entity_type: entity.entity_type.clone(),
+                        file_path: entity.file_path.clone(),
+                        kind: WarningKind::ReferencesDeletedEntity {
+                            deleted_in_ours: !referencing_ours,
+                        },
+                        related: vec![RelatedEntity {
+                            name: dep.name.clone(),
+                            entity_type: dep.entity_type.clone(),
+                            file_path: dep.file_path.clone(),
+                        }],
+                    });
+                }
+            }
+        }
+    }
+    warnings.sort_by(|a, b| (&a.file_path, &a.entity_name).cmp(&(&b.file_path, &b.entity_name)));
+    warnings
+}
+
+/// The entity graph of one side's version of the changed files, written
+/// out to a temporary directory for [`EntityGraph::build`].
+fn build_side_graph(files: &[FileVersions], ours: bool, registry: &ParserRegistry) -> Option<EntityGraph> {
+    let dir = tempfile::tempdir().ok()?;
+    let mut paths = Vec::new();
+    for file in files {
+        let content = if ours { &file.ours } else { &file.theirs };
+        if content.is_empty() {
+            continue;
+        }
+        let path = dir.path().join(&file.file_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).ok()?;
+        }
+        std::fs::write(&path, content).ok()?;
+        paths.push(file.file_path.clone());
+    }
+    Some(EntityGraph::build(dir.path(), &paths, registry))
+}
+
+/// Find calls added or changed on one side that don't match the parameters
+/// the other side gave the called function: too many or too few arguments,
+/// or a keyword argument the function no longer takes.
+///
+/// A function's parameters are compared between base and the side that
+/// changed them, which is what a clean merge keeps. Calls are only checked
+/// against names declared once (or identically) across the changed files,
+/// when they matched the base parameters, and when the side making the
+/// call left the parameters alone.
+pub fn find_signature_mismatches(files: &[FileVersions], registry: &ParserRegistry) -> Vec<SemanticWarning> {
+    let ours_changed = changed_signatures(files, true);
+    let theirs_changed = changed_signatures(files, false);
+
+    let mut warnings = Vec::new();
+    for (changed_in_ours, changed, other_changed) in [(true, &ours_changed, &theirs_changed), (false, &theirs_changed, &ours_changed)] {
+        for file in files {
+            let Some(language) = language_for(&file.file_path) else {
+                continue;
+            };
+            let content = if changed_in_ours { &file.theirs } else { &file.ours };
+            let entities = registry
+                .get_plugin(&file.file_path)
+                .map(|plugin| plugin.extract_entities(content, &file.file_path))
+                .unwrap_or_default();
+            for call in new_calls(&language, &file.base, content) {
+                let Some((def_path, base_sig, sig)) = changed.get(&call.callee) else {
+                    continue;
+                };
+                if other_changed.contains_key(&call.callee) || !base_sig.accepts(&call) || sig.accepts(&call) {
+                    continue;
+                }
+                // The innermost entity making the call
+                let caller = entities
+                    .iter()
+                    .filter(|e| e.start_line <= call.line && call.line <= e.end_line)
+                    .min_by_key(|e| e.end_line - e.start_line);
+                warnings.push(SemanticWarning {
+                    entity_name: caller.map_or_else(|| file.file_path.clone(), |e| e.name.clone()),
+                    entity_type: caller.map_or_else(|| "module".to_string(), |e| e.entity_type.clone()),
+                    file_path: file.file_path.clone(),
+                    kind: WarningKind::CallSignatureMismatch {
+                        call: call.text.clone(),
+                        call_line: call.line,
+                        parameters: sig.text.clone(),
+                        definition_line: sig.line,
+                        changed_in_ours,
+                    },
+                    related: vec![RelatedEntity {
+                        name: sig.name.clone(),
+                        entity_type: "function".to_string(),
+                        file_path: def_path.clone(),
+                    }],
+                });
+            }
+        }
+    }
+    warnings.sort_by(|a, b| (&a.file_path, &a.entity_name).cmp(&(&b.file_path, &b.entity_name)));
+    warnings
+}
+
+/// Functions whose parameters one side changed from base, by name, with
+/// the file declaring them and their base and changed signatures. Names
+/// declared with different parameters in the same version are skipped.
+fn changed_signatures(files: &[FileVersions], ours: bool) -> HashMap<String, (String, FunctionSignature, FunctionSignature)> {
+    let mut base: HashMap<String, Option<FunctionSignature>> = HashMap::new();
+    let mut side: HashMap<String, Option<(String, FunctionSignature)>> = HashMap::new();
+    for file in files {
+        let Some(language) = language_for(&file.file_path) else {
+            continue;
+        };
+        for sig in function_signatures(&language, &file.base) {
+            base.entry(sig.name.clone())
+                .and_modify(|seen| {
+                    if seen.as_ref().is_some_and(|s| s.parameters != sig.parameters) {
+                        *seen = None;
+                    }
+                })
+                .or_insert(Some(sig));
+        }
+        let content = if ours { &file.ours } else { &file.theirs };
+        for sig in function_signatures(&language, content) {
+            side.entry(sig.name.clone())
+                .and_modify(|seen| {
+                    if seen.as_ref().is_some_and(|(_, s)| s.parameters != sig.parameters) {
+                        *seen = None;
+                    }
+                })
+                .or_insert(Some((file.file_path.clone(), sig)));
+        }
+    }
+    side.into_iter()
+        .filter_map(|(name, sig)| {
+            let (path, sig) = sig?;
+            let base_sig = base.get(&name)?.clone()?;
+            (base_sig.parameters != sig.parameters).then_some((name, (path, base_sig, sig)))
+        })
+        .collect()
+}
+
+/// Calls in `content` that aren't in `base`, compared by callee and arguments.
+fn new_calls(language: &tree_sitter::Language, base: &str, content: &str) -> Vec<CallSite> {
+    let key = |c: &CallSite| (c.callee.clone(), c.positional, c.keywords.clone(), c.spread);
+    let mut base_calls: HashMap<_, usize> = HashMap::new();
+    for call in call_sites(language, base) {
+        *base_calls.entry(key(&call)).or_default() += 1;
+    }
+    call_sites(language, content)
+        .into_iter()
+        .filter(|call| match base_calls.get_mut(&key(call)) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .collect()
+}
+
+/// Run every cross-entity check on the changed files of a merge:
+/// [`validate_merge`] over the repo's graph for the entities either side
+/// modified, then [`find_dangling_references`] and
+/// [`find_signature_mismatches`] across the versions.
+pub fn validate_versions(
+    repo_root: &Path,
+    file_paths: &[String],
+    files: &[FileVersions],
+    registry: &ParserRegistry,
+) -> Vec<SemanticWarning> {
+    let modified = modified_entities(files, registry);
+    let mut warnings = validate_merge(repo_root, file_paths, &modified, registry);
+    warnings.extend(find_dangling_references(files, registry));
+    warnings.extend(find_signature_mismatches(files, registry));
+    warnings
+}
+
+/// Entities added or changed on either side, deduplicated.
+pub fn modified_entities(files: &[FileVersions], registry: &ParserRegistry) -> Vec<ModifiedEntity> {
+    let mut modified = Vec::new();
+    for file in files {
+        let Some(plugin) = registry.get_plugin(&file.file_path) else {
+            continue;
+        };
+        let base_entities = plugin.extract_entities(&file.base, &file.file_path);
+        let ours_entities = plugin.extract_entities(&file.ours, &file.file_path);
+        let theirs_entities = plugin.extract_entities(&file.theirs, &file.file_path);
+        for entity in ours_entities.iter().chain(theirs_entities.iter()) {
+            let is_modified = match base_entities.iter().find(|b| b.name == entity.name) {
+                Some(b) => b.content_hash != entity.content_hash,
+                None => true, // new entity
+            };
+            if is_modified {
+                modified.push(ModifiedEntity {
+                    name: entity.name.clone(),
+                    file_path: file.file_path.clone(),
+                });
+            }
+        }
+    }
+    modified.sort_by(|a, b| (&a.file_path, &a.name).cmp(&(&b.file_path, &b.name)));
+    modified.dedup_by(|a, b| a.file_path == b.file_path && a.name == b.name);
+    modified
+}
+
+/// Every file under `root` a parser supports, relative and sorted. Hidden
+/// and dependency directories are skipped.
+pub fn supported_files(root: &Path, registry: &ParserRegistry) -> Vec<String> {
+    let mut files = Vec::new();
+    walk_dir(root, root, registry, &mut files);
+    files.sort();
+    files
+}
+
+fn walk_dir(dir: &Path, root: &Path, registry: &ParserRegistry, files: &mut Vec<String>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+            if name.starts_with('.')
+                || name == "node_modules"
+                || name == "target"
+                || name == "__pycache__"
+                || name == "venv"
+            {
+                continue;
+            }
+        }
+        if path.is_dir() {
+            walk_dir(&path, root, registry, files);
+        } else if let Ok(rel) = path.strip_prefix(root) {
+            let rel_str = rel.to_string_lossy().to_string();
+            if registry.get_plugin(&rel_str).is_some() {
+                files.push(rel_str);
+            }
+        }
+    }
+}
+
 /// A modified entity descriptor, used as input to validation.
 #[derive(Debug, Clone)]
 pub struct ModifiedEntity {
@@ -139,27 +651,67 @@ pub struct ModifiedEntity {
     pub file_path: String,
 }
 
+impl SemanticWarning {
+    /// The first related entity as "type `name`", for messages. A warning
+    /// built without one still renders.
+    fn related_entity(&self) -> String {
+        match self.related.first() {
+            Some(related) => format!("{} `{}`", related.entity_type, related.name),
+            None => "another entity".to_string(),
+        }
+    }
+}
+
 impl std::fmt::Display for SemanticWarning {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.kind {
             WarningKind::DependencyAlsoModified => {
                 write!(
                     f,
-                    "warning: {} `{}` was modified and references {} `{}` which was also modified",
+                    "warning: {} `{}` was modified and references {} which was also modified",
                     self.entity_type,
                     self.entity_name,
-                    self.related[0].entity_type,
-                    self.related[0].name,
+                    self.related_entity(),
                 )
             }
             WarningKind::DependentAlsoModified => {
                 write!(
                     f,
-                    "warning: {} `{}` was modified and is used by {} `{}` which was also modified",
+                    "warning: {} `{}` was modified and is used by {} which was also modified",
                     self.entity_type,
                     self.entity_name,
-                    self.related[0].entity_type,
-                    self.related[0].name,
+                    self.related_entity(),
+                )
+            }
+            WarningKind::VerificationFailed { command, output } => {
+                write!(
+                    f,
+                    "warning: `{}` failed on the merged `{}`, falling back to a line-level merge:\n{}",
+                    command,
+                    self.file_path,
+                    output.trim_end(),
+                )
+            }
+            WarningKind::SyntaxErrorFallback { line } => {
+                write!(
+                    f,
+                    "warning: merged `{}` had a syntax error at line {}, falling back to a line-level merge",
+                    self.file_path, line,
+                )
+            }
+            WarningKind::FieldKeptOurs { field } => {
+                write!(
+                    f,
+                    "warning: `{}` of {} `{}` changed on both sides — kept ours, dropped theirs",
+                    field, self.entity_type, self.entity_name,
+                )
+            }
+            WarningKind::GeneratedSideDropped { kept_ours } => {
+                let (kept, dropped) = if *kept_ours { ("ours", "theirs") } else { ("theirs", "ours") };
+                write!(
+                    f,
+                    "warning: generated file `{}` changed on both sides — kept {}, dropped {}'s changes",
+                    self.file_path, kept, dropped,
                 )
             }
             WarningKind::ParseFailedAfterMerge => {
@@ -169,6 +721,95 @@ impl std::fmt::Display for SemanticWarning {
                     self.file_path,
                 )
             }
+            WarningKind::VersionBumpResolved { ours, theirs, chosen } => {
+                write!(
+                    f,
+                    "warning: dependency `{}` bumped on both sides ({} vs {}) — picked {}",
+                    self.entity_name, ours, theirs, chosen,
+                )
+            }
+            WarningKind::FieldNumberCollision { number } => {
+                write!(
+                    f,
+                    "warning: {} `{}` uses field number {} which is also used by {}",
+                    self.entity_type,
+                    self.entity_name,
+                    number,
+                    self.related_entity(),
+                )
+            }
+            WarningKind::DuplicateDefinition {
+                line,
+                origin,
+                other_line,
+                other_origin,
+            } => {
+                write!(
+                    f,
+                    "warning: {} `{}` from {} (line {}) and {} from {} (line {}) are defined in the same scope",
+                    self.entity_type,
+                    self.entity_name,
+                    origin.tag(),
+                    line,
+                    self.related_entity(),
+                    other_origin.tag(),
+                    other_line,
+                )
+            }
+            WarningKind::ReferencesDeletedEntity { deleted_in_ours } => {
+                write!(
+                    f,
+                    "warning: {} `{}` references {} which was deleted in {}",
+                    self.entity_type,
+                    self.entity_name,
+                    self.related_entity(),
+                    if *deleted_in_ours { "ours" } else { "theirs" },
+                )
+            }
+            WarningKind::CallSignatureMismatch {
+                call,
+                call_line,
+                parameters,
+                definition_line,
+                changed_in_ours,
+            } => {
+                let (callee, path) = match self.related.first() {
+                    Some(related) => (format!("`{}`", related.name), related.file_path.as_str()),
+                    None => ("the function".to_string(), self.file_path.as_str()),
+                };
+                write!(
+                    f,
+                    "warning: {} `{}` calls `{}` (line {}), but {} changed {} to take `{}` ({}:{})",
+                    self.entity_type,
+                    self.entity_name,
+                    call,
+                    call_line,
+                    if *changed_in_ours { "ours" } else { "theirs" },
+                    callee,
+                    parameters,
+                    path,
+                    definition_line,
+                )
+            }
+            WarningKind::ReplayedResolution { id } => {
+                write!(
+                    f,
+                    "warning: {} `{}` conflicted as before — replayed recorded resolution {}",
+                    self.entity_type,
+                    self.entity_name,
+                    id.get(..12).unwrap_or(id),
+                )
+            }
+            WarningKind::ResolvedByPolicy { complexity, took_ours } => {
+                write!(
+                    f,
+                    "warning: {} `{}` had a {} conflict — resolved by policy, took {}",
+                    self.entity_type,
+                    self.entity_name,
+                    complexity,
+                    if *took_ours { "ours" } else { "theirs" },
+                )
+            }
         }
     }
 }
@@ -205,6 +846,124 @@ export function unrelated(): number {
         dir
     }
 
+    #[test]
+    fn test_warnings_without_related_entity_render() {
+        let kinds = [
+            WarningKind::DependencyAlsoModified,
+            WarningKind::DependentAlsoModified,
+            WarningKind::FieldNumberCollision { number: 3 },
+            WarningKind::DuplicateDefinition {
+                line: 1,
+                origin: DefinitionSide::Ours,
+                other_line: 2,
+                other_origin: DefinitionSide::Theirs,
+            },
+            WarningKind::ReferencesDeletedEntity { deleted_in_ours: true },
+            WarningKind::CallSignatureMismatch {
+                call: "f(1)".to_string(),
+                call_line: 4,
+                parameters: "(a, b)".to_string(),
+                definition_line: 1,
+                changed_in_ours: false,
+            },
+        ];
+        for kind in kinds {
+            let warning = SemanticWarning {
+                entity_name: "a".to_string(),
+                entity_type: "function".to_string(),
+                file_path: "src/lib.rs".to_string(),
+                kind,
+                related: vec![],
+            };
+            assert!(warning.to_string().starts_with("warning: function `a`"));
+        }
+    }
+
+    #[test]
+    fn test_duplicate_definitions_introduced_by_merge() {
+        let registry = sem_core::parser::plugins::create_default_registry();
+        let plugin = registry.get_plugin("util.ts").unwrap();
+        let extract = |content: &str| plugin.extract_entities(content, "util.ts");
+        let ours = extract("function a() {}\nfunction format() {}\n");
+        let theirs = extract("function a() {}\nconst format = 1;\n");
+        let merged = extract("function a() {}\nfunction format() {}\nconst format = 1;\n");
+
+        let duplicates = find_duplicate_definitions(&merged, &ours, &theirs);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].first.start_line, 2);
+        assert_eq!(duplicates[0].first.origin, DefinitionSide::Ours);
+        assert_eq!(duplicates[0].second.start_line, 3);
+        assert_eq!(duplicates[0].second.origin, DefinitionSide::Theirs);
+        assert!(duplicates[0].to_warning("util.ts").to_string().contains("from ours (line 2)"));
+
+        // Already duplicated on one side: not the merge's doing
+        assert!(find_duplicate_definitions(&merged, &merged, &theirs).is_empty());
+
+        assert!(may_have_duplicate_definitions(&ours, &theirs));
+        // Both sides changing the same function can't duplicate it
+        let theirs = extract("function a() { return 1; }\nfunction format() {}\n");
+        assert!(!may_have_duplicate_definitions(&ours, &theirs));
+    }
+
+    #[test]
+    fn test_reference_to_entity_deleted_on_other_side() {
+        let registry = sem_core::parser::plugins::create_default_registry();
+        let utils_base = "export function formatDate(d: Date): string {\n    return d.toISOString();\n}\n\nexport function keep(): number {\n    return 1;\n}\n";
+        let utils_ours = "export function keep(): number {\n    return 1;\n}\n";
+        let view_base = "import { keep } from './utils';\n\nexport function render(): number {\n    return keep();\n}\n";
+        let view_theirs = "import { formatDate, keep } from './utils';\n\nexport function render(): number {\n    return keep();\n}\n\nexport function stamp(): string {\n    return formatDate(new Date());\n}\n";
+        let files = vec![
+            FileVersions {
+                file_path: "src/utils.ts".to_string(),
+                base: utils_base.to_string(),
+                ours: utils_ours.to_string(),
+                theirs: utils_base.to_string(),
+                exists: [true; 3],
+            },
+            FileVersions {
+                file_path: "src/view.ts".to_string(),
+                base: view_base.to_string(),
+                ours: view_base.to_string(),
+                theirs: view_theirs.to_string(),
+                exists: [true; 3],
+            },
+        ];
+
+        let warnings = find_dangling_references(&files, &registry);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].entity_name, "stamp");
+        assert_eq!(warnings[0].file_path, "src/view.ts");
+        assert_eq!(warnings[0].related[0].name, "formatDate");
+        assert!(matches!(
+            warnings[0].kind,
+            WarningKind::ReferencesDeletedEntity { deleted_in_ours: true }
+        ));
+    }
+
+    #[test]
+    fn test_call_with_old_arity_after_signature_change() {
+        let registry = sem_core::parser::plugins::create_default_registry();
+        let base = "def process(item):\n    return item\n\n\ndef run(jobs):\n    for job in jobs:\n        process(job)\n";
+        let ours = "def process(item, retries):\n    return item\n\n\ndef run(jobs):\n    for job in jobs:\n        process(job, 3)\n";
+        let theirs = "def process(item):\n    return item\n\n\ndef run(jobs):\n    for job in jobs:\n        process(job)\n\n\ndef rerun(job):\n    return process(job)\n";
+        let files = vec![FileVersions {
+            file_path: "jobs.py".to_string(),
+            base: base.to_string(),
+            ours: ours.to_string(),
+            theirs: theirs.to_string(),
+            exists: [true; 3],
+        }];
+
+        let warnings = find_signature_mismatches(&files, &registry);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].entity_name, "rerun");
+        assert_eq!(warnings[0].related[0].name, "process");
+        assert!(matches!(
+            warnings[0].kind,
+            WarningKind::CallSignatureMismatch { call_line: 11, definition_line: 1, changed_in_ours: true, .. }
+        ));
+    }
+
     #[test]
     fn test_no_warnings_single_entity() {
         let dir = setup_test_repo();
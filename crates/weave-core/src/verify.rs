@@ -0,0 +1,417 @@
+//! Post-merge verification (`[[verify]]` in `.weave/config.toml`).
+//!
+//! A clean entity merge can still fail to build. Once every file of a merge
+//! is merged, the merged tree is checked out into a temporary worktree, and
+//! each `[[verify]]` check covering an auto-merged file runs there, once. If
+//! a check fails, the auto-resolution of each file it covers is vetoed: the
+//! file is merged with `git merge-file` instead, or, when that merges
+//! cleanly too, turned into one whole-file conflict. The check's output is
+//! attached to the result as a [`WarningKind::VerificationFailed`] warning.
+//!
+//! The repository's own working tree is never written to.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::config::{VerifyCommand, VerifyTemplate, WeaveConfig};
+use crate::conflict::{classify_conflict, ConflictKind, EntityConflict};
+use crate::glob::glob_match;
+use crate::merge::{git_merge_file, MergeResult};
+use crate::pipeline::MergedFile;
+use crate::shell::{quote, shell};
+use crate::trace::{FallbackReason, FileDecision};
+use crate::validate::{SemanticWarning, WarningKind};
+use crate::xdiff::ConflictFormat;
+
+/// The result of running a `[[verify]]` check.
+#[derive(Debug, Clone)]
+pub struct Verification {
+    pub command: String,
+    /// The auto-merged files the check covered.
+    pub files: Vec<String>,
+    pub success: bool,
+    /// Combined stdout and stderr, or the reason the command couldn't run.
+    pub output: String,
+}
+
+impl std::fmt::Display for Verification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.success {
+            write!(f, "verified with `{}`", self.command)
+        } else {
+            write!(f, "`{}` failed: {}", self.command, self.output.trim())
+        }
+    }
+}
+
+impl VerifyTemplate {
+    fn files(self) -> &'static [&'static str] {
+        match self {
+            VerifyTemplate::Rust => &["**/*.rs"],
+            VerifyTemplate::TypeScript => &["**/*.ts", "**/*.tsx"],
+            VerifyTemplate::Python => &["**/*.py"],
+            VerifyTemplate::Go => &["**/*.go"],
+        }
+    }
+
+    fn command(self) -> &'static str {
+        match self {
+            VerifyTemplate::Rust => "cargo check --quiet",
+            VerifyTemplate::TypeScript => "npx tsc --noEmit",
+            VerifyTemplate::Python => "python -m compileall -q .",
+            VerifyTemplate::Go => "go build ./...",
+        }
+    }
+}
+
+impl VerifyCommand {
+    /// Whether the check covers `file_path`: its `files` glob, or else its
+    /// template's.
+    pub fn matches(&self, file_path: &str) -> bool {
+        match (&self.files, self.template) {
+            (Some(files), _) => glob_match(files, file_path),
+            (None, Some(template)) => template.files().iter().any(|g| glob_match(g, file_path)),
+            (None, None) => false,
+        }
+    }
+
+    /// The command to run: its own, or else its template's.
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_deref().or(self.template.map(VerifyTemplate::command))
+    }
+}
+
+/// Run the `[[verify]]` checks covering the clean results of `merged` over
+/// the merged tree, and veto each covered file when a check fails.
+///
+/// The tree is `rev` (the ours side) with the merge applied: clean results
+/// as merged, conflicted files as on our side, and files only one side
+/// changed as on that side. Vetoed files are merged line by line in
+/// `format`.
+pub fn verify_merged(
+    repo_root: &Path,
+    rev: &str,
+    merged: &mut [MergedFile],
+    config: &WeaveConfig,
+    format: &ConflictFormat,
+) -> Vec<Verification> {
+    let mut tree = Vec::new();
+    let mut checked = Vec::new();
+    for merged_file in merged.iter() {
+        let versions = &merged_file.versions;
+        let content = match &merged_file.result {
+            Some(result) if !result.is_clean() => versions.exists[1].then_some(versions.ours.as_str()),
+            Some(_) => {
+                checked.push(versions.file_path.clone());
+                merged_file.content()
+            }
+            None => merged_file.content(),
+        };
+        tree.push((versions.file_path.clone(), content.map(str::to_string)));
+    }
+
+    let verifications = verify_tree(repo_root, rev, &tree, &checked, config);
+    for verification in verifications.iter().filter(|v| !v.success) {
+        for MergedFile { versions, result } in merged.iter_mut() {
+            if let Some(result) = result.as_mut().filter(|_| verification.files.contains(&versions.file_path)) {
+                veto(result, [&versions.base, &versions.ours, &versions.theirs], &versions.file_path, verification, format);
+            }
+        }
+    }
+    verifications
+}
+
+/// Run each `[[verify]]` check covering one of the auto-merged `checked`
+/// files, once, in a temporary worktree of `rev` with `tree` written over it
+/// (`None` removes the file). In `command`, `{files}` is replaced with the
+/// checked files the check covers.
+///
+/// Nothing is checked out when no check covers a file.
+pub fn verify_tree(
+    repo_root: &Path,
+    rev: &str,
+    tree: &[(String, Option<String>)],
+    checked: &[String],
+    config: &WeaveConfig,
+) -> Vec<Verification> {
+    let checks: Vec<(&str, Vec<String>)> = config
+        .verify
+        .iter()
+        .filter_map(|check| {
+            let files: Vec<String> = checked.iter().filter(|f| check.matches(f)).cloned().collect();
+            Some((check.command()?, files)).filter(|(_, files)| !files.is_empty())
+        })
+        .collect();
+    if checks.is_empty() {
+        return Vec::new();
+    }
+
+    let worktree = Worktree::add(repo_root, rev).and_then(|worktree| {
+        for (file, content) in tree {
+            let path = worktree.path.join(file);
+            match content {
+                Some(content) => {
+                    if let Some(parent) = path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(&path, content)?;
+                }
+                None if path.exists() => std::fs::remove_file(&path)?,
+                None => {}
+            }
+        }
+        Ok(worktree)
+    });
+
+    checks
+        .into_iter()
+        .map(|(command, files)| {
+            let quoted: Vec<String> = files.iter().map(|f| quote(f)).collect();
+            let command = command.replace("{files}", &quoted.join(" "));
+            let (success, output) = match &worktree {
+                Ok(worktree) => run(&command, &worktree.path),
+                Err(e) => (false, format!("failed to check out the merged tree: {}", e)),
+            };
+            Verification { command, files, success, output }
+        })
+        .collect()
+}
+
+fn run(command: &str, dir: &Path) -> (bool, String) {
+    match shell(command).current_dir(dir).stdin(Stdio::null()).output() {
+        Ok(output) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            if !output.status.success() && text.trim().is_empty() {
+                text = output.status.to_string();
+            }
+            (output.status.success(), text)
+        }
+        Err(e) => (false, e.to_string()),
+    }
+}
+
+/// A detached worktree in a temporary directory, removed on drop.
+struct Worktree {
+    repo_root: PathBuf,
+    path: PathBuf,
+    _dir: tempfile::TempDir,
+}
+
+impl Worktree {
+    fn add(repo_root: &Path, rev: &str) -> std::io::Result<Worktree> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("tree");
+        let output = Command::new("git")
+            .args(["worktree", "add", "--detach", "--quiet"])
+            .arg(&path)
+            .arg(rev)
+            .current_dir(repo_root)
+            .output()?;
+        if !output.status.success() {
+            return Err(std::io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+        }
+        Ok(Worktree {
+            repo_root: repo_root.to_path_buf(),
+            path,
+            _dir: dir,
+        })
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        let _ = Command::new("git")
+            .args(["worktree", "remove", "--force"])
+            .arg(&self.path)
+            .current_dir(&self.repo_root)
+            .stderr(Stdio::null())
+            .status();
+        let _ = Command::new("git")
+            .args(["worktree", "prune"])
+            .current_dir(&self.repo_root)
+            .status();
+    }
+}
+
+/// Replace the result with the line-level merge, conflicted as a whole if
+/// that merged cleanly too.
+fn veto(
+    result: &mut MergeResult,
+    [base, ours, theirs]: [&str; 3],
+    file_path: &str,
+    verification: &Verification,
+    format: &ConflictFormat,
+) {
+    let mut stats = result.stats.clone();
+    let mut fallback = git_merge_file(base, ours, theirs, &mut stats, format);
+    if fallback.is_clean() {
+        let conflict = EntityConflict {
+            entity_name: "(file)".to_string(),
+            entity_type: "file".to_string(),
+            kind: ConflictKind::BothModified,
+            complexity: classify_conflict(Some(base), Some(ours), Some(theirs)),
+            ours_content: Some(ours.to_string()),
+            theirs_content: Some(theirs.to_string()),
+            base_content: Some(base.to_string()),
+        };
+        fallback.content = conflict.to_conflict_markers();
+        fallback.conflicts = vec![conflict];
+        fallback.conflict_blocks = vec![Some(0)];
+        fallback.stats.entities_conflicted = 1;
+    }
+
+    result.content = fallback.content;
+    result.conflicts = fallback.conflicts;
+    result.conflict_blocks = fallback.conflict_blocks;
+    result.stats = fallback.stats;
+    result.warnings.push(SemanticWarning {
+        entity_name: "(file)".to_string(),
+        entity_type: "file".to_string(),
+        file_path: file_path.to_string(),
+        kind: WarningKind::VerificationFailed {
+            command: verification.command.clone(),
+            output: verification.output.clone(),
+        },
+        related: vec![],
+    });
+    if let Some(trace) = result.trace.as_mut() {
+        trace.file(FileDecision::Fallback(FallbackReason::VerificationFailed {
+            command: verification.command.clone(),
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::FileVersions;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=t", "-c", "user.email=t@t", "-c", "commit.gpgsign=false"])
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?}", args);
+    }
+
+    fn repo(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        git(dir.path(), &["init", "-q", "-b", "main"]);
+        for (file, content) in files {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        git(dir.path(), &["add", "-A"]);
+        git(dir.path(), &["commit", "-q", "-m", "init"]);
+        dir
+    }
+
+    fn merged(file: &str, base: &str, ours: &str, theirs: &str, content: &str) -> MergedFile {
+        MergedFile {
+            versions: FileVersions {
+                file_path: file.to_string(),
+                base: base.to_string(),
+                ours: ours.to_string(),
+                theirs: theirs.to_string(),
+                exists: [true; 3],
+            },
+            result: Some(MergeResult {
+                content: content.to_string(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn config(toml: &str) -> WeaveConfig {
+        WeaveConfig::from_toml(toml).unwrap()
+    }
+
+    #[test]
+    fn test_templates_and_overrides() {
+        let config = config("[[verify]]\ntemplate = \"typescript\"\n\n[[verify]]\ntemplate = \"rust\"\ncommand = \"cargo test\"\n\n[[verify]]\nfiles = \"*.sh\"\n");
+        assert!(config.verify[0].matches("web/app.tsx"));
+        assert!(!config.verify[0].matches("src/lib.rs"));
+        assert_eq!(config.verify[0].command(), Some("npx tsc --noEmit"));
+        assert!(config.verify[1].matches("src/lib.rs"));
+        assert_eq!(config.verify[1].command(), Some("cargo test"));
+        assert_eq!(config.verify[2].command(), None);
+    }
+
+    #[test]
+    fn test_checks_run_once_over_the_merged_tree() {
+        let dir = repo(&[("a.py", "x = 1\n"), ("b.py", "y = 1\n")]);
+        // Both files are merged when the check runs, and the repo's working
+        // tree is left alone
+        let config = config("[[verify]]\nfiles = \"*.py\"\ncommand = \"grep -q merged a.py && grep -q merged b.py && echo {files}\"\n");
+        let mut merged = vec![
+            merged("a.py", "x = 0\n", "x = 1\n", "x = 2\n", "x = 'merged'\n"),
+            merged("b.py", "y = 0\n", "y = 1\n", "y = 2\n", "y = 'merged'\n"),
+        ];
+
+        let verifications = verify_merged(dir.path(), "HEAD", &mut merged, &config, &ConflictFormat::default());
+        assert_eq!(verifications.len(), 1);
+        assert!(verifications[0].success, "{}", verifications[0].output);
+        assert_eq!(verifications[0].output.trim(), "a.py b.py");
+        assert!(merged.iter().all(|m| m.result.as_ref().unwrap().is_clean()));
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.py")).unwrap(), "x = 1\n");
+
+        git(dir.path(), &["worktree", "prune"]);
+        let output = Command::new("git").args(["worktree", "list"]).current_dir(dir.path()).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 1);
+    }
+
+    #[test]
+    fn test_failing_check_vetoes_the_files_it_covers() {
+        let dir = repo(&[("src/lib.rs", "fn a() { 1 }\n\nfn b() {}\n"), ("c.py", "z = 1\n")]);
+        let config = config("[[verify]]\nfiles = \"**/*.rs\"\ncommand = \"echo 'error[E0061]: wrong arity' >&2; exit 1\"\n");
+        let base = "fn a() {}\n\nfn b() {}\n";
+        let ours = "fn a() { 1 }\n\nfn b() {}\n";
+        let theirs = "fn a() {}\n\nfn b() { 2 }\n";
+        let mut merged = vec![
+            merged("src/lib.rs", base, ours, theirs, "fn a() { 1 }\n\nfn b() { 2 }\n"),
+            merged("c.py", "z = 0\n", "z = 1\n", "z = 2\n", "z = 3\n"),
+        ];
+
+        let verifications = verify_merged(dir.path(), "HEAD", &mut merged, &config, &ConflictFormat::default());
+        assert_eq!(verifications.len(), 1);
+        assert!(!verifications[0].success);
+        assert_eq!(verifications[0].files, vec!["src/lib.rs"]);
+
+        let result = merged[0].result.as_ref().unwrap();
+        assert!(!result.is_clean());
+        assert!(result.content.contains("<<<<<<<"));
+        assert!(matches!(
+            &result.warnings[0].kind,
+            WarningKind::VerificationFailed { output, .. } if output.contains("E0061")
+        ));
+        assert!(merged[1].result.as_ref().unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_empty_files_stay_in_the_merged_tree() {
+        let dir = repo(&[("a.py", "x = 1\n")]);
+        let config = config("[[verify]]\nfiles = \"*.py\"\ncommand = \"test -f a.py && test -f b.py\"\n");
+        // A merge that empties a.py, and an empty b.py only theirs added
+        let mut merged = vec![
+            merged("a.py", "x = 0\n", "x = 1\n", "", ""),
+            MergedFile {
+                versions: FileVersions {
+                    file_path: "b.py".to_string(),
+                    base: String::new(),
+                    ours: String::new(),
+                    theirs: String::new(),
+                    exists: [false, false, true],
+                },
+                result: None,
+            },
+        ];
+
+        let verifications = verify_merged(dir.path(), "HEAD", &mut merged, &config, &ConflictFormat::default());
+        assert_eq!(verifications.len(), 1);
+        assert!(verifications[0].success, "{}", verifications[0].output);
+    }
+}
@@ -0,0 +1,1091 @@
+//! In-process 3-way line merge, compatible with `git merge-file`.
+//!
+//! A port of git's xdiff merge (`xdl_merge` at the `zealous_alnum` level
+//! `git merge-file` uses): each side is diffed against base with xdiff's
+//! Myers variant, changes are slid to their canonical position, overlapping
+//! changes become conflicts, conflicts are narrowed to the lines that really
+//! differ, and conflicts separated by at most three lines (or by lines
+//! without letters or digits) are joined. git's `diff3` and `zdiff3`
+//! conflict styles (`merge.conflictStyle`) are ported too.
+
+use std::collections::HashMap;
+
+/// Length of the `<<<<<<<`, `=======` and `>>>>>>>` markers.
+const MARKER_SIZE: usize = 7;
+/// Limits of xdiff's heuristics, see `xdiff/xdiffi.c`.
+const MAX_EQLIMIT: usize = 1024;
+const SIMSCAN_WINDOW: usize = 100;
+const KPDIS_RUN: usize = 4;
+const MAX_COST_MIN: isize = 256;
+const HEUR_MIN_COST: isize = 256;
+const SNAKE_CNT: isize = 20;
+const K_HEUR: isize = 4;
+
+/// The result of a 3-way line merge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMerge {
+    pub content: String,
+    /// Conflict blocks in `content`; 0 for a clean merge.
+    pub conflicts: usize,
+}
+
+/// How conflicts are written, git's `merge.conflictStyle`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictStyle {
+    /// Ours and theirs.
+    #[default]
+    Merge,
+    /// Ours, base and theirs; conflicts aren't narrowed or joined.
+    Diff3,
+    /// Ours, base and theirs, with the lines both sides share at either end
+    /// of a conflict moved out of it.
+    ZealousDiff3,
+}
+
+impl ConflictStyle {
+    /// The style named by a `merge.conflictStyle` value.
+    pub fn from_git(value: &str) -> Option<Self> {
+        match value {
+            "merge" => Some(ConflictStyle::Merge),
+            "diff3" => Some(ConflictStyle::Diff3),
+            "zdiff3" => Some(ConflictStyle::ZealousDiff3),
+            _ => None,
+        }
+    }
+}
+
+/// The style and marker labels of the conflicts in a line merge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictFormat {
+    pub style: ConflictStyle,
+    pub ours: String,
+    pub base: String,
+    pub theirs: String,
+}
+
+impl Default for ConflictFormat {
+    fn default() -> Self {
+        Self {
+            style: ConflictStyle::Merge,
+            ours: "ours".to_string(),
+            base: "base".to_string(),
+            theirs: "theirs".to_string(),
+        }
+    }
+}
+
+/// Merge `ours` and `theirs` line by line, as `git merge-file -p -L <ours>
+/// -L <base> -L <theirs>` with `format`'s style would. Any three texts
+/// merge: there's no input it rejects.
+pub fn merge3(base: &str, ours: &str, theirs: &str, format: &ConflictFormat) -> LineMerge {
+    let base_lines = split_lines(base);
+    let ours_lines = split_lines(ours);
+    let theirs_lines = split_lines(theirs);
+    let mut interner = Interner::default();
+    let base_ids = interner.ids(&base_lines);
+    let ours_ids = interner.ids(&ours_lines);
+    let theirs_ids = interner.ids(&theirs_lines);
+
+    let ours_changes = diff_ids(&base_ids, &ours_ids);
+    let theirs_changes = diff_ids(&base_ids, &theirs_ids);
+    if ours_changes.is_empty() {
+        return LineMerge {
+            content: theirs.to_string(),
+            conflicts: 0,
+        };
+    }
+    if theirs_changes.is_empty() {
+        return LineMerge {
+            content: ours.to_string(),
+            conflicts: 0,
+        };
+    }
+
+    let lens = [base_ids.len(), ours_ids.len(), theirs_ids.len()];
+    let mut merges = combine(&ours_changes, &theirs_changes, lens, &ours_ids, &theirs_ids);
+    match format.style {
+        ConflictStyle::Merge => {
+            refine_conflicts(&mut merges, &ours_ids, &theirs_ids);
+            simplify_non_conflicts(&mut merges, &ours_lines);
+        }
+        // `diff3 -m` output doesn't make sense narrowed: git merges at the
+        // `eager` level instead
+        ConflictStyle::Diff3 => {}
+        ConflictStyle::ZealousDiff3 => trim_conflicts(&mut merges, &ours_ids, &theirs_ids),
+    }
+
+    let sides = Sides {
+        base: &base_lines,
+        ours: &ours_lines,
+        theirs: &theirs_lines,
+    };
+    let content = fill(&merges, &sides, format);
+    LineMerge {
+        content,
+        conflicts: merges.iter().filter(|m| m.mode == Mode::Conflict).count(),
+    }
+}
+
+/// Lines with their terminators, like xdiff records.
+fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Equal lines get equal ids, so comparing lines is comparing integers.
+#[derive(Default)]
+struct Interner<'a> {
+    ids: HashMap<&'a str, usize>,
+}
+
+impl<'a> Interner<'a> {
+    fn ids(&mut self, lines: &[&'a str]) -> Vec<usize> {
+        lines
+            .iter()
+            .map(|line| {
+                let next = self.ids.len();
+                *self.ids.entry(line).or_insert(next)
+            })
+            .collect()
+    }
+}
+
+/// A change from `a` to `b`: `a[a_start..a_start + a_len]` replaced by
+/// `b[b_start..b_start + b_len]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Change {
+    a_start: usize,
+    a_len: usize,
+    b_start: usize,
+    b_len: usize,
+}
+
+/// xdiff's diff: trim common ends, drop lines that can't match, split with
+/// Myers' middle snake, then compact the changes in both directions.
+fn diff_ids(a: &[usize], b: &[usize]) -> Vec<Change> {
+    let mut a_file = DiffFile::new(a);
+    let mut b_file = DiffFile::new(b);
+    prepare(&mut a_file, &mut b_file);
+
+    let mut kv = Diagonals::new(a_file.reduced.len(), b_file.reduced.len());
+    let mut split = Splitter {
+        a: &mut a_file,
+        b: &mut b_file,
+        kv: &mut kv,
+    };
+    let (a_len, b_len) = (split.a.reduced.len(), split.b.reduced.len());
+    split.compare(0, a_len, 0, b_len, false);
+
+    compact(&mut a_file, &b_file);
+    compact(&mut b_file, &a_file);
+    build_script(&a_file, &b_file)
+}
+
+struct DiffFile<'a> {
+    lines: &'a [usize],
+    /// Changed flags, with an unchanged sentinel on each side: line `i` is
+    /// at `changed[i + 1]`.
+    changed: Vec<bool>,
+    /// Line ids considered by the Myers pass, and their line numbers.
+    reduced: Vec<usize>,
+    index: Vec<usize>,
+}
+
+impl<'a> DiffFile<'a> {
+    fn new(lines: &'a [usize]) -> Self {
+        Self {
+            lines,
+            changed: vec![false; lines.len() + 2],
+            reduced: Vec::new(),
+            index: Vec::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn is_changed(&self, i: isize) -> bool {
+        self.changed[(i + 1) as usize]
+    }
+
+    fn set_changed(&mut self, i: usize, changed: bool) {
+        self.changed[i + 1] = changed;
+    }
+}
+
+/// `xdl_trim_ends` and `xdl_cleanup_records`: lines of the common prefix
+/// and suffix are unchanged, lines missing from the other file are changed,
+/// and lines matching too often are changed when surrounded by changes.
+fn prepare<'a>(a: &mut DiffFile<'a>, b: &mut DiffFile<'a>) {
+    let common = a.len().min(b.len());
+    let prefix = (0..common).take_while(|&i| a.lines[i] == b.lines[i]).count();
+    let suffix = (0..common - prefix)
+        .take_while(|&i| a.lines[a.len() - 1 - i] == b.lines[b.len() - 1 - i])
+        .count();
+
+    let mut counts_a: HashMap<usize, usize> = HashMap::new();
+    let mut counts_b: HashMap<usize, usize> = HashMap::new();
+    for &id in a.lines {
+        *counts_a.entry(id).or_default() += 1;
+    }
+    for &id in b.lines {
+        *counts_b.entry(id).or_default() += 1;
+    }
+
+    for (file, other_counts) in [(&mut *a, &counts_b), (&mut *b, &counts_a)] {
+        let (start, end) = (prefix, file.len() - suffix);
+        let limit = bogosqrt(file.len()).min(MAX_EQLIMIT);
+        let discard: Vec<u8> = (start..end)
+            .map(|i| match other_counts.get(&file.lines[i]).copied().unwrap_or(0) {
+                0 => 0,
+                n if n >= limit => 2,
+                _ => 1,
+            })
+            .collect();
+        for i in start..end {
+            let d = discard[i - start];
+            if d == 1 || (d == 2 && !clean_mmatch(&discard, i - start)) {
+                file.index.push(i);
+                file.reduced.push(file.lines[i]);
+            } else {
+                file.set_changed(i, true);
+            }
+        }
+    }
+}
+
+fn bogosqrt(mut n: usize) -> usize {
+    let mut i = 1;
+    while n > 0 {
+        i <<= 1;
+        n >>= 2;
+    }
+    i
+}
+
+/// `xdl_clean_mmatch`: whether a line matching too often sits in a run of
+/// lines without matches, and can be treated as changed.
+fn clean_mmatch(discard: &[u8], i: usize) -> bool {
+    let start = i.saturating_sub(SIMSCAN_WINDOW);
+    let end = (i + SIMSCAN_WINDOW).min(discard.len() - 1);
+
+    let (mut rdis0, mut rpdis0) = (0, 1);
+    for j in (start..i).rev() {
+        match discard[j] {
+            0 => rdis0 += 1,
+            2 => rpdis0 += 1,
+            _ => break,
+        }
+    }
+    if rdis0 == 0 {
+        return false;
+    }
+    let (mut rdis1, mut rpdis1) = (0, 1);
+    for &d in &discard[i + 1..=end] {
+        match d {
+            0 => rdis1 += 1,
+            2 => rpdis1 += 1,
+            _ => break,
+        }
+    }
+    if rdis1 == 0 {
+        return false;
+    }
+    let (rdis, rpdis) = (rdis0 + rdis1, rpdis0 + rpdis1);
+    rpdis * KPDIS_RUN < rpdis + rdis
+}
+
+/// The forward and backward furthest-reaching paths, by diagonal.
+struct Diagonals {
+    forward: Vec<isize>,
+    backward: Vec<isize>,
+    offset: isize,
+    max_cost: isize,
+}
+
+impl Diagonals {
+    fn new(a_len: usize, b_len: usize) -> Self {
+        let diagonals = a_len + b_len + 3;
+        Self {
+            forward: vec![0; diagonals + 1],
+            backward: vec![0; diagonals + 1],
+            offset: b_len as isize + 1,
+            max_cost: (bogosqrt(diagonals) as isize).max(MAX_COST_MIN),
+        }
+    }
+
+    fn f(&self, d: isize) -> isize {
+        self.forward[(d + self.offset) as usize]
+    }
+
+    fn set_f(&mut self, d: isize, v: isize) {
+        self.forward[(d + self.offset) as usize] = v;
+    }
+
+    fn b(&self, d: isize) -> isize {
+        self.backward[(d + self.offset) as usize]
+    }
+
+    fn set_b(&mut self, d: isize, v: isize) {
+        self.backward[(d + self.offset) as usize] = v;
+    }
+}
+
+struct Split {
+    a: isize,
+    b: isize,
+    min_lo: bool,
+    min_hi: bool,
+}
+
+struct Splitter<'s, 'a> {
+    a: &'s mut DiffFile<'a>,
+    b: &'s mut DiffFile<'a>,
+    kv: &'s mut Diagonals,
+}
+
+impl Splitter<'_, '_> {
+    /// `xdl_recs_cmp`: mark the changed lines of `a[off1..lim1]` against
+    /// `b[off2..lim2]` (in reduced coordinates).
+    fn compare(&mut self, mut off1: usize, mut lim1: usize, mut off2: usize, mut lim2: usize, need_min: bool) {
+        let (ha1, ha2) = (&self.a.reduced, &self.b.reduced);
+        while off1 < lim1 && off2 < lim2 && ha1[off1] == ha2[off2] {
+            off1 += 1;
+            off2 += 1;
+        }
+        while off1 < lim1 && off2 < lim2 && ha1[lim1 - 1] == ha2[lim2 - 1] {
+            lim1 -= 1;
+            lim2 -= 1;
+        }
+
+        if off1 == lim1 {
+            for i in off2..lim2 {
+                let line = self.b.index[i];
+                self.b.set_changed(line, true);
+            }
+        } else if off2 == lim2 {
+            for i in off1..lim1 {
+                let line = self.a.index[i];
+                self.a.set_changed(line, true);
+            }
+        } else {
+            let split = self.split(off1 as isize, lim1 as isize, off2 as isize, lim2 as isize, need_min);
+            let (s1, s2) = (split.a as usize, split.b as usize);
+            self.compare(off1, s1, off2, s2, split.min_lo);
+            self.compare(s1, lim1, s2, lim2, split.min_hi);
+        }
+    }
+
+    /// `xdl_split`: find the middle snake of the box, or a good enough
+    /// split once the cost gets too high.
+    fn split(&mut self, off1: isize, lim1: isize, off2: isize, lim2: isize, need_min: bool) -> Split {
+        let ha1 = &self.a.reduced;
+        let ha2 = &self.b.reduced;
+        let kv = &mut *self.kv;
+        let at1 = |i: isize| ha1[i as usize];
+        let at2 = |i: isize| ha2[i as usize];
+
+        let (dmin, dmax) = (off1 - lim2, lim1 - off2);
+        let (fmid, bmid) = (off1 - off2, lim1 - lim2);
+        let odd = (fmid - bmid) & 1 != 0;
+        let (mut fmin, mut fmax) = (fmid, fmid);
+        let (mut bmin, mut bmax) = (bmid, bmid);
+        kv.set_f(fmid, off1);
+        kv.set_b(bmid, lim1);
+
+        let mut ec: isize = 1;
+        loop {
+            let mut got_snake = false;
+
+            if fmin > dmin {
+                fmin -= 1;
+                kv.set_f(fmin - 1, -1);
+            } else {
+                fmin += 1;
+            }
+            if fmax < dmax {
+                fmax += 1;
+                kv.set_f(fmax + 1, -1);
+            } else {
+                fmax -= 1;
+            }
+            let mut d = fmax;
+            while d >= fmin {
+                let mut i1 = if kv.f(d - 1) >= kv.f(d + 1) { kv.f(d - 1) + 1 } else { kv.f(d + 1) };
+                let prev1 = i1;
+                let mut i2 = i1 - d;
+                while i1 < lim1 && i2 < lim2 && at1(i1) == at2(i2) {
+                    i1 += 1;
+                    i2 += 1;
+                }
+                if i1 - prev1 > SNAKE_CNT {
+                    got_snake = true;
+                }
+                kv.set_f(d, i1);
+                if odd && bmin <= d && d <= bmax && kv.b(d) <= i1 {
+                    return Split { a: i1, b: i2, min_lo: true, min_hi: true };
+                }
+                d -= 2;
+            }
+
+            if bmin > dmin {
+                bmin -= 1;
+                kv.set_b(bmin - 1, isize::MAX);
+            } else {
+                bmin += 1;
+            }
+            if bmax < dmax {
+                bmax += 1;
+                kv.set_b(bmax + 1, isize::MAX);
+            } else {
+                bmax -= 1;
+            }
+            let mut d = bmax;
+            while d >= bmin {
+                let mut i1 = if kv.b(d - 1) < kv.b(d + 1) { kv.b(d - 1) } else { kv.b(d + 1) - 1 };
+                let prev1 = i1;
+                let mut i2 = i1 - d;
+                while i1 > off1 && i2 > off2 && at1(i1 - 1) == at2(i2 - 1) {
+                    i1 -= 1;
+                    i2 -= 1;
+                }
+                if prev1 - i1 > SNAKE_CNT {
+                    got_snake = true;
+                }
+                kv.set_b(d, i1);
+                if !odd && fmin <= d && d <= fmax && i1 <= kv.f(d) {
+                    return Split { a: i1, b: i2, min_lo: true, min_hi: true };
+                }
+                d -= 2;
+            }
+
+            if need_min {
+                ec += 1;
+                continue;
+            }
+
+            // Past the trigger cost, take a split on a long enough snake
+            if got_snake && ec > HEUR_MIN_COST {
+                let mut best = 0;
+                let mut found = None;
+                let mut d = fmax;
+                while d >= fmin {
+                    let dd = (d - fmid).abs();
+                    let i1 = kv.f(d);
+                    let i2 = i1 - d;
+                    let v = (i1 - off1) + (i2 - off2) - dd;
+                    if v > K_HEUR * ec
+                        && v > best
+                        && off1 + SNAKE_CNT <= i1
+                        && i1 < lim1
+                        && off2 + SNAKE_CNT <= i2
+                        && i2 < lim2
+                        && (1..=SNAKE_CNT).all(|k| at1(i1 - k) == at2(i2 - k))
+                    {
+                        best = v;
+                        found = Some((i1, i2));
+                    }
+                    d -= 2;
+                }
+                if let Some((i1, i2)) = found {
+                    return Split { a: i1, b: i2, min_lo: true, min_hi: false };
+                }
+
+                let mut best = 0;
+                let mut found = None;
+                let mut d = bmax;
+                while d >= bmin {
+                    let dd = (d - bmid).abs();
+                    let i1 = kv.b(d);
+                    let i2 = i1 - d;
+                    let v = (lim1 - i1) + (lim2 - i2) - dd;
+                    if v > K_HEUR * ec
+                        && v > best
+                        && off1 < i1
+                        && i1 <= lim1 - SNAKE_CNT
+                        && off2 < i2
+                        && i2 <= lim2 - SNAKE_CNT
+                        && (0..SNAKE_CNT).all(|k| at1(i1 + k) == at2(i2 + k))
+                    {
+                        best = v;
+                        found = Some((i1, i2));
+                    }
+                    d -= 2;
+                }
+                if let Some((i1, i2)) = found {
+                    return Split { a: i1, b: i2, min_lo: false, min_hi: true };
+                }
+            }
+
+            // Enough: take the furthest reaching path
+            if ec >= kv.max_cost {
+                let (mut fbest, mut fbest1) = (-1, -1);
+                let mut d = fmax;
+                while d >= fmin {
+                    let mut i1 = kv.f(d).min(lim1);
+                    let mut i2 = i1 - d;
+                    if lim2 < i2 {
+                        i1 = lim2 + d;
+                        i2 = lim2;
+                    }
+                    if fbest < i1 + i2 {
+                        fbest = i1 + i2;
+                        fbest1 = i1;
+                    }
+                    d -= 2;
+                }
+                let (mut bbest, mut bbest1) = (isize::MAX, isize::MAX);
+                let mut d = bmax;
+                while d >= bmin {
+                    let mut i1 = off1.max(kv.b(d));
+                    let mut i2 = i1 - d;
+                    if i2 < off2 {
+                        i1 = off2 + d;
+                        i2 = off2;
+                    }
+                    if i1 + i2 < bbest {
+                        bbest = i1 + i2;
+                        bbest1 = i1;
+                    }
+                    d -= 2;
+                }
+                return if (lim1 + lim2) - bbest < fbest - (off1 + off2) {
+                    Split { a: fbest1, b: fbest - fbest1, min_lo: true, min_hi: false }
+                } else {
+                    Split { a: bbest1, b: bbest - bbest1, min_lo: false, min_hi: true }
+                };
+            }
+            ec += 1;
+        }
+    }
+}
+
+/// A run of changed lines, `[start, end)`; empty between two unchanged lines.
+struct Group {
+    start: usize,
+    end: usize,
+}
+
+impl Group {
+    fn first(file: &DiffFile) -> Self {
+        let mut end = 0;
+        while file.is_changed(end as isize) {
+            end += 1;
+        }
+        Group { start: 0, end }
+    }
+
+    fn next(&mut self, file: &DiffFile) -> bool {
+        if self.end == file.len() {
+            return false;
+        }
+        self.start = self.end + 1;
+        self.end = self.start;
+        while file.is_changed(self.end as isize) {
+            self.end += 1;
+        }
+        true
+    }
+
+    fn previous(&mut self, file: &DiffFile) -> bool {
+        if self.start == 0 {
+            return false;
+        }
+        self.end = self.start - 1;
+        self.start = self.end;
+        while file.is_changed(self.start as isize - 1) {
+            self.start -= 1;
+        }
+        true
+    }
+
+    fn slide_down(&mut self, file: &mut DiffFile) -> bool {
+        if self.end < file.len() && file.lines[self.start] == file.lines[self.end] {
+            file.set_changed(self.start, false);
+            file.set_changed(self.end, true);
+            self.start += 1;
+            self.end += 1;
+            while file.is_changed(self.end as isize) {
+                self.end += 1;
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    fn slide_up(&mut self, file: &mut DiffFile) -> bool {
+        if self.start > 0 && file.lines[self.start - 1] == file.lines[self.end - 1] {
+            self.start -= 1;
+            self.end -= 1;
+            file.set_changed(self.start, true);
+            file.set_changed(self.end, false);
+            while file.is_changed(self.start as isize - 1) {
+                self.start -= 1;
+            }
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// `xdl_change_compact`: slide each group of changes as far down as it
+/// goes, or back up to line up with a change in the other file.
+fn compact(file: &mut DiffFile, other: &DiffFile) {
+    let mut g = Group::first(file);
+    let mut go = Group::first(other);
+    loop {
+        if g.end != g.start {
+            let mut earliest_end;
+            let mut end_matching_other;
+            loop {
+                let size = g.end - g.start;
+                end_matching_other = None;
+                while g.slide_up(file) {
+                    go.previous(other);
+                }
+                earliest_end = g.end;
+                if go.end > go.start {
+                    end_matching_other = Some(g.end);
+                }
+                while g.slide_down(file) {
+                    go.next(other);
+                    if go.end > go.start {
+                        end_matching_other = Some(g.end);
+                    }
+                }
+                if size == g.end - g.start {
+                    break;
+                }
+            }
+            if g.end != earliest_end && end_matching_other.is_some() {
+                while go.end == go.start {
+                    g.slide_up(file);
+                    go.previous(other);
+                }
+            }
+        }
+        if !g.next(file) {
+            break;
+        }
+        go.next(other);
+    }
+}
+
+/// `xdl_build_script`: pair up runs of changed lines into changes.
+fn build_script(a: &DiffFile, b: &DiffFile) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if !a.is_changed(i as isize) && !b.is_changed(j as isize) {
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let (a_start, b_start) = (i, j);
+        while a.is_changed(i as isize) {
+            i += 1;
+        }
+        while b.is_changed(j as isize) {
+            j += 1;
+        }
+        changes.push(Change {
+            a_start,
+            a_len: i - a_start,
+            b_start,
+            b_len: j - b_start,
+        });
+    }
+    changes
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Conflict,
+    Ours,
+    Theirs,
+    /// Both sides made the same change: ours is kept as is.
+    Identical,
+}
+
+/// A merged region, `i1`/`chg1` in ours and `i2`/`chg2` in theirs. Signed
+/// like xdiff's: a conflict may start before the region it then joins.
+#[derive(Debug, Clone)]
+struct Merge {
+    mode: Mode,
+    i0: isize,
+    chg0: isize,
+    i1: isize,
+    chg1: isize,
+    i2: isize,
+    chg2: isize,
+}
+
+impl Merge {
+    fn base(&self) -> std::ops::Range<usize> {
+        self.i0 as usize..(self.i0 + self.chg0) as usize
+    }
+
+    fn ours(&self) -> std::ops::Range<usize> {
+        self.i1 as usize..(self.i1 + self.chg1) as usize
+    }
+
+    fn theirs(&self) -> std::ops::Range<usize> {
+        self.i2 as usize..(self.i2 + self.chg2) as usize
+    }
+}
+
+/// `xdl_append_merge`: a region touching the previous one joins it, as a
+/// conflict unless both came from the same side.
+fn append(merges: &mut Vec<Merge>, m: Merge) {
+    if let Some(last) = merges.last_mut() {
+        if m.i1 <= last.i1 + last.chg1 || m.i2 <= last.i2 + last.chg2 {
+            if m.mode != last.mode {
+                last.mode = Mode::Conflict;
+            }
+            last.chg0 = m.i0 + m.chg0 - last.i0;
+            last.chg1 = m.i1 + m.chg1 - last.i1;
+            last.chg2 = m.i2 + m.chg2 - last.i2;
+            return;
+        }
+    }
+    merges.push(m);
+}
+
+/// `xdl_do_merge`: walk both change lists in base order.
+fn combine(ours: &[Change], theirs: &[Change], lens: [usize; 3], ours_ids: &[usize], theirs_ids: &[usize]) -> Vec<Merge> {
+    let [base_len, ours_len, theirs_len] = lens.map(|n| n as isize);
+    // (base start, base len, side start, side len)
+    let signed = |c: &Change| (c.a_start as isize, c.a_len as isize, c.b_start as isize, c.b_len as isize);
+    let mut merges = Vec::new();
+    let (mut x1, mut x2) = (0, 0);
+    while x1 < ours.len() && x2 < theirs.len() {
+        let (a1, l1, b1, n1) = signed(&ours[x1]);
+        let (a2, l2, b2, n2) = signed(&theirs[x2]);
+        if a1 + l1 < a2 {
+            append(&mut merges, Merge { mode: Mode::Ours, i0: a1, chg0: l1, i1: b1, chg1: n1, i2: b2 - a2 + a1, chg2: l1 });
+            x1 += 1;
+            continue;
+        }
+        if a2 + l2 < a1 {
+            append(&mut merges, Merge { mode: Mode::Theirs, i0: a2, chg0: l2, i1: b1 - a1 + a2, chg1: l2, i2: b2, chg2: n2 });
+            x2 += 1;
+            continue;
+        }
+        let same = a1 == a2
+            && l1 == l2
+            && n1 == n2
+            && ours_ids[b1 as usize..(b1 + n1) as usize] == theirs_ids[b2 as usize..(b2 + n2) as usize];
+        if !same {
+            let off = a1 - a2;
+            let ffo = off + l1 - l2;
+            let (mut i0, mut i1, mut i2) = (a1, b1, b2);
+            if off > 0 {
+                i0 -= off;
+                i1 -= off;
+            } else {
+                i2 += off;
+            }
+            let (mut chg0, mut chg1, mut chg2) = (a1 + l1 - i0, b1 + n1 - i1, b2 + n2 - i2);
+            if ffo < 0 {
+                chg0 -= ffo;
+                chg1 -= ffo;
+            } else {
+                chg2 += ffo;
+            }
+            append(&mut merges, Merge { mode: Mode::Conflict, i0, chg0, i1, chg1, i2, chg2 });
+        }
+        let (end1, end2) = (a1 + l1, a2 + l2);
+        if end1 >= end2 {
+            x2 += 1;
+        }
+        if end2 >= end1 {
+            x1 += 1;
+        }
+    }
+    for c in &ours[x1..] {
+        let (a1, l1, b1, n1) = signed(c);
+        let i2 = a1 + theirs_len - base_len;
+        append(&mut merges, Merge { mode: Mode::Ours, i0: a1, chg0: l1, i1: b1, chg1: n1, i2, chg2: l1 });
+    }
+    for c in &theirs[x2..] {
+        let (a2, l2, b2, n2) = signed(c);
+        let i1 = a2 + ours_len - base_len;
+        append(&mut merges, Merge { mode: Mode::Theirs, i0: a2, chg0: l2, i1, chg1: l2, i2: b2, chg2: n2 });
+    }
+    merges
+}
+
+/// `xdl_refine_conflicts`: diff the two sides of each conflict, and keep
+/// only the lines that differ as conflicts.
+fn refine_conflicts(merges: &mut Vec<Merge>, ours: &[usize], theirs: &[usize]) {
+    let mut refined = Vec::with_capacity(merges.len());
+    for m in merges.drain(..) {
+        if m.mode != Mode::Conflict || m.chg1 == 0 || m.chg2 == 0 {
+            refined.push(m);
+            continue;
+        }
+        let changes = diff_ids(&ours[m.ours()], &theirs[m.theirs()]);
+        if changes.is_empty() {
+            refined.push(Merge { mode: Mode::Identical, ..m });
+            continue;
+        }
+        for (k, c) in changes.iter().enumerate() {
+            refined.push(Merge {
+                mode: Mode::Conflict,
+                i0: if k == 0 { m.i0 } else { 0 },
+                chg0: if k == 0 { m.chg0 } else { 0 },
+                i1: m.i1 + c.a_start as isize,
+                chg1: c.a_len as isize,
+                i2: m.i2 + c.b_start as isize,
+                chg2: c.b_len as isize,
+            });
+        }
+    }
+    *merges = refined;
+}
+
+/// `xdl_refine_zdiff3_conflicts`: move the lines both sides share at the
+/// start and end of each conflict out of it; its base stays whole.
+fn trim_conflicts(merges: &mut [Merge], ours: &[usize], theirs: &[usize]) {
+    for m in merges.iter_mut().filter(|m| m.mode == Mode::Conflict) {
+        while m.chg1 > 0 && m.chg2 > 0 && ours[m.i1 as usize] == theirs[m.i2 as usize] {
+            m.i1 += 1;
+            m.i2 += 1;
+            m.chg1 -= 1;
+            m.chg2 -= 1;
+        }
+        while m.chg1 > 0 && m.chg2 > 0 && ours[(m.i1 + m.chg1 - 1) as usize] == theirs[(m.i2 + m.chg2 - 1) as usize] {
+            m.chg1 -= 1;
+            m.chg2 -= 1;
+        }
+    }
+}
+
+/// `xdl_simplify_non_conflicts`: join conflicts separated by at most three
+/// lines, or by lines without letters or digits.
+fn simplify_non_conflicts(merges: &mut Vec<Merge>, ours: &[&str]) {
+    let mut k = 0;
+    while k + 1 < merges.len() {
+        let (m, next) = (&merges[k], &merges[k + 1]);
+        let (begin, end) = ((m.i1 + m.chg1) as usize, next.i1 as usize);
+        let joinable = m.mode == Mode::Conflict
+            && next.mode == Mode::Conflict
+            && (end - begin <= 3 || !ours[begin..end].iter().any(|l| l.chars().any(char::is_alphanumeric)));
+        if joinable {
+            let next = merges.remove(k + 1);
+            let m = &mut merges[k];
+            m.chg1 = next.i1 + next.chg1 - m.i1;
+            m.chg2 = next.i2 + next.chg2 - m.i2;
+        } else {
+            k += 1;
+        }
+    }
+}
+
+struct Sides<'a> {
+    base: &'a [&'a str],
+    ours: &'a [&'a str],
+    theirs: &'a [&'a str],
+}
+
+/// `xdl_fill_merge_buffer`: ours between the regions, each region from its
+/// side, conflicts between markers.
+fn fill(merges: &[Merge], sides: &Sides, format: &ConflictFormat) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    // Ours up to `end`, from where the previous region stopped
+    let before = |out: &mut String, i: usize, end: isize| out.extend(sides.ours[i..(end as usize).max(i)].iter().copied());
+    for m in merges {
+        match m.mode {
+            Mode::Identical => continue,
+            Mode::Conflict => {
+                let eol = if needs_cr(sides, m) { "\r\n" } else { "\n" };
+                before(&mut out, i, m.i1);
+                out.push_str(&"<".repeat(MARKER_SIZE));
+                push_label(&mut out, &format.ours, eol);
+                push_lines(&mut out, &sides.ours[m.ours()], eol);
+                if format.style != ConflictStyle::Merge {
+                    out.push_str(&"|".repeat(MARKER_SIZE));
+                    push_label(&mut out, &format.base, eol);
+                    push_lines(&mut out, &sides.base[m.base()], eol);
+                }
+                out.push_str(&"=".repeat(MARKER_SIZE));
+                out.push_str(eol);
+                push_lines(&mut out, &sides.theirs[m.theirs()], eol);
+                out.push_str(&">".repeat(MARKER_SIZE));
+                push_label(&mut out, &format.theirs, eol);
+            }
+            Mode::Ours => before(&mut out, i, m.i1 + m.chg1),
+            Mode::Theirs => {
+                before(&mut out, i, m.i1);
+                out.extend(sides.theirs[m.theirs()].iter().copied());
+            }
+        }
+        i = (m.i1 + m.chg1) as usize;
+    }
+    out.extend(sides.ours[i..].iter().copied());
+    out
+}
+
+fn push_label(out: &mut String, label: &str, eol: &str) {
+    if !label.is_empty() {
+        out.push(' ');
+        out.push_str(label);
+    }
+    out.push_str(eol);
+}
+
+/// Conflict sides always end with a line break.
+fn push_lines(out: &mut String, lines: &[&str], eol: &str) {
+    out.extend(lines.iter().copied());
+    if lines.last().is_some_and(|l| !l.ends_with('\n')) {
+        out.push_str(eol);
+    }
+}
+
+/// `is_cr_needed`: markers follow the line endings around the conflict.
+fn needs_cr(sides: &Sides, m: &Merge) -> bool {
+    let crlf = |lines: &[&str], i: usize| -> Option<bool> {
+        let line = lines.get(i)?;
+        if i + 1 == lines.len() && !line.ends_with('\n') {
+            return None;
+        }
+        Some(line.ends_with("\r\n"))
+    };
+    let mut needs = crlf(sides.ours, (m.i1 - 1).max(0) as usize);
+    if needs != Some(false) {
+        needs = crlf(sides.theirs, (m.i2 - 1).max(0) as usize);
+    }
+    if needs != Some(false) {
+        needs = crlf(sides.base, 0);
+    }
+    needs == Some(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    const STYLES: [(ConflictStyle, &str); 3] = [
+        (ConflictStyle::Merge, "--no-diff3"),
+        (ConflictStyle::Diff3, "--diff3"),
+        (ConflictStyle::ZealousDiff3, "--zdiff3"),
+    ];
+
+    /// What `git merge-file` makes of the same inputs.
+    fn git_merge_file(base: &str, ours: &str, theirs: &str, style: &str) -> LineMerge {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in [("base", base), ("ours", ours), ("theirs", theirs)] {
+            std::fs::write(dir.path().join(name), content).unwrap();
+        }
+        let output = Command::new("git")
+            .args(["merge-file", "-p", style, "-L", "ours", "-L", "base", "-L", "theirs", "ours", "base", "theirs"])
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+        LineMerge {
+            content: String::from_utf8(output.stdout).unwrap(),
+            conflicts: output.status.code().unwrap() as usize,
+        }
+    }
+
+    fn assert_matches_git(base: &str, ours: &str, theirs: &str) {
+        for (style, flag) in STYLES {
+            let format = ConflictFormat {
+                style,
+                ..Default::default()
+            };
+            assert_eq!(
+                merge3(base, ours, theirs, &format),
+                git_merge_file(base, ours, theirs, flag),
+                "{}\nbase:\n{}\nours:\n{}\ntheirs:\n{}",
+                flag,
+                base,
+                ours,
+                theirs
+            );
+        }
+    }
+
+    #[test]
+    fn test_clean_and_conflicting_merges() {
+        let base = "fn a() {\n    1\n}\n\nfn b() {\n    2\n}\n\nfn c() {\n    3\n}\n";
+        // Apart: clean
+        assert_matches_git(base, &base.replace("    1", "    10"), &base.replace("    3", "    30"));
+        // Same lines: conflict
+        assert_matches_git(base, &base.replace("    2", "    20"), &base.replace("    2", "    21"));
+        // Adjacent lines conflict in git
+        assert_matches_git("a\nb\nc\nd\n", "a\nB\nc\nd\n", "a\nb\nC\nd\n");
+        // Same change on both sides
+        assert_matches_git(base, &base.replace("    2", "    20"), &base.replace("    2", "    20"));
+        // Conflict narrowed to the lines that differ, and joined across a brace
+        assert_matches_git(
+            "x\n{\ny\n}\nz\n",
+            "x1\n{\ny1\n}\nz1\n",
+            "x2\n{\ny1\n}\nz2\n",
+        );
+        // Missing final newlines and CRLF
+        assert_matches_git("a\nb", "a\nc", "a\nd");
+        assert_matches_git("a\r\nb\r\n", "a\r\nc\r\n", "a\r\nd\r\n");
+        // One side unchanged
+        assert_matches_git(base, base, &base.replace("fn b", "fn bb"));
+    }
+
+    #[test]
+    fn test_inserted_functions_slide_like_git() {
+        let base = "fn a() {\n}\n\nfn z() {\n}\n";
+        let ours = "fn a() {\n}\n\nfn b() {\n}\n\nfn z() {\n}\n";
+        let theirs = "fn a() {\n}\n\nfn c() {\n}\n\nfn z() {\n}\n";
+        assert_matches_git(base, ours, theirs);
+        assert_matches_git(base, ours, "fn a() {\n}\n\nfn z() {\n}\n\nfn c() {\n}\n");
+    }
+
+    #[test]
+    fn test_random_merges_match_git() {
+        // Few distinct lines, so the diffs are ambiguous and the conflicts
+        // overlap, with CRLF and a line break missing here and there
+        let alphabet = ["a\n", "b\n", "}\n", "\n", "x = 1\n", "c\r\n", "d"];
+        let mut seed: u64 = 0x5eed;
+        let mut random = move |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % n
+        };
+        for _ in 0..300 {
+            let base: Vec<&str> = (0..random(24)).map(|_| alphabet[random(7) as usize]).collect();
+            let mut edit = |lines: &[&str]| -> String {
+                let mut out = String::new();
+                for line in lines {
+                    match random(6) {
+                        0 => {}
+                        1 => {
+                            out.push_str(line);
+                            out.push_str(alphabet[random(7) as usize]);
+                        }
+                        2 => out.push_str(alphabet[random(7) as usize]),
+                        _ => out.push_str(line),
+                    }
+                }
+                out
+            };
+            let (ours, theirs) = (edit(&base), edit(&base));
+            assert_matches_git(&base.concat(), &ours, &theirs);
+        }
+    }
+
+    #[test]
+    fn test_labels_and_conflict_styles() {
+        let format = ConflictFormat {
+            style: ConflictStyle::ZealousDiff3,
+            ours: "HEAD".to_string(),
+            base: "1a2b3c4".to_string(),
+            theirs: "feature".to_string(),
+        };
+        let merged = merge3("a\nb\nc\n", "a\nx\ny\nc\n", "a\nx\nz\nc\n", &format);
+        assert_eq!(merged.content, "a\nx\n<<<<<<< HEAD\ny\n||||||| 1a2b3c4\nb\n=======\nz\n>>>>>>> feature\nc\n");
+        assert_eq!(ConflictStyle::from_git("zdiff3"), Some(ConflictStyle::ZealousDiff3));
+        assert_eq!(ConflictStyle::from_git("merge3"), None);
+    }
+}
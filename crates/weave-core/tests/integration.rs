@@ -1,4 +1,4 @@
-use weave_core::entity_merge;
+use weave_core::{entity_merge, entity_merge_with_config, WeaveConfig};
 
 // =============================================================================
 // Core value prop: independent entity changes auto-resolve
@@ -1568,6 +1568,45 @@ fn rust_impl_scoped_conflict() {
     }
 }
 
+// =============================================================================
+// Manifests: opt-in semver resolution of concurrent dependency bumps
+// =============================================================================
+
+#[test]
+fn cargo_toml_both_bump_dependency_picks_higher_with_semver_config() {
+    let base = "[package]\nname = \"app\"\n\n[dependencies]\nserde = \"1.0.190\"\nanyhow = \"1.0\"\n";
+    let ours = "[package]\nname = \"app\"\n\n[dependencies]\nserde = \"1.0.195\"\nanyhow = \"1.0\"\n";
+    let theirs = "[package]\nname = \"app\"\n\n[dependencies]\nserde = \"1.0.193\"\nanyhow = \"1.0\"\nlog = \"0.4\"\n";
+
+    let config = WeaveConfig::from_toml("[manifest]\nsemver = true\n").unwrap();
+    let result = entity_merge_with_config(base, ours, theirs, "Cargo.toml", &config);
+
+    assert!(result.is_clean(), "Compatible bumps should resolve: {:?}", result.conflicts);
+    assert!(result.content.contains("serde = \"1.0.195\""));
+    assert!(result.content.contains("log = \"0.4\""));
+    assert!(
+        result.warnings.iter().any(|w| w.entity_name == "serde"),
+        "Auto-picked bump should be reported as a warning"
+    );
+}
+
+// =============================================================================
+// Markdown: changelog bullets added on both sides are unioned
+// =============================================================================
+
+#[test]
+fn changelog_both_add_bullets_under_unreleased() {
+    let base = "# Changelog\n\n## [Unreleased]\n\n- Existing entry\n\n## [1.0.0]\n\n- Initial release\n";
+    let ours = "# Changelog\n\n## [Unreleased]\n\n- Add feature A\n- Existing entry\n\n## [1.0.0]\n\n- Initial release\n";
+    let theirs = "# Changelog\n\n## [Unreleased]\n\n- Fix bug B\n- Existing entry\n\n## [1.0.0]\n\n- Initial release\n";
+
+    let result = entity_merge(base, ours, theirs, "CHANGELOG.md");
+    assert!(result.is_clean(), "Bullets added on both sides should union: {}", result.content);
+    assert!(result.content.contains("- Add feature A\n"));
+    assert!(result.content.contains("- Fix bug B\n"));
+    assert!(result.content.contains("- Existing entry\n"));
+}
+
 /// Check if a needle appears only inside conflict marker blocks
 fn is_inside_conflict_markers(content: &str, needle: &str) -> bool {
     let mut in_conflict = false;
@@ -1,6 +1,7 @@
 use std::path::Path;
 
 use sem_core::parser::registry::ParserRegistry;
+use weave_core::cache::{cached_entities, EntityCache};
 
 use crate::error::Result;
 use crate::ops::upsert_entity;
@@ -9,12 +10,14 @@ use crate::state::EntityStateDoc;
 /// Sync entities from working tree files into CRDT state.
 ///
 /// Extracts entities from each file using sem-core's parser registry,
-/// then upserts them into the automerge document.
+/// through the repo's entity cache when there is one, then upserts them
+/// into the automerge document.
 pub fn sync_from_files(
     state: &mut EntityStateDoc,
     repo_root: &Path,
     file_paths: &[String],
     registry: &ParserRegistry,
+    cache: Option<&EntityCache>,
 ) -> Result<usize> {
     let mut count = 0;
 
@@ -30,7 +33,9 @@ pub fn sync_from_files(
             None => continue, // No parser for this file type
         };
 
-        let entities = plugin.extract_entities(&content, file_path);
+        let entities = cached_entities(cache, plugin.id(), file_path, &content, || {
+            plugin.extract_entities(&content, file_path)
+        });
         for entity in &entities {
             upsert_entity(
                 state,
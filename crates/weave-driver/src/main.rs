@@ -1,7 +1,9 @@
 use std::fs;
+use std::path::Path;
 use std::process;
 
-use weave_core::entity_merge;
+use weave_core::rerere::RerereCache;
+use weave_core::{entity_merge_in, file_budget, shared_registry, MergeContext, WeaveConfig};
 
 fn main() {
     env_logger::init();
@@ -9,8 +11,9 @@ fn main() {
     let raw_args: Vec<String> = std::env::args().collect();
 
     // Parse optional flags before positional args
-    // Supported flags: -o <path> / --output <path>
+    // Supported flags: -o <path> / --output <path>, --explain
     let mut output_override: Option<String> = None;
+    let mut explain = false;
     let mut positional: Vec<String> = Vec::new();
     let mut i = 1;
     while i < raw_args.len() {
@@ -24,6 +27,10 @@ fn main() {
                     process::exit(2);
                 }
             }
+            "--explain" => {
+                explain = true;
+                i += 1;
+            }
             "-l" | "--marker-length" => {
                 // Accept and skip (we use our own markers)
                 i += 2;
@@ -44,14 +51,16 @@ fn main() {
         }
     }
 
-    // Git calls: weave-driver %O %A %B %L %P
+    // Git calls: weave-driver %O %A %B %L %P %S %X %Y
     // jj calls:  weave-driver $base $left $right -o $output -l $marker_length -p $path
     // %O = ancestor (base), %A = current (ours), %B = other (theirs)
     // %L = conflict marker size, %P = file path
+    // %S, %X, %Y = the base, ours and theirs conflict marker labels
     if positional.len() < 3 {
-        eprintln!("Usage: weave-driver <base> <ours> <theirs> [marker-size] [file-path]");
+        eprintln!("Usage: weave-driver <base> <ours> <theirs> [marker-size] [file-path] [base-label ours-label theirs-label]");
         eprintln!("       weave-driver <base> <ours> <theirs> -o <output> [-l <marker-length>] [-p <path>]");
         eprintln!("  Invoked by git as a merge driver, or by jj as a merge tool.");
+        eprintln!("  --explain  print why each file and entity was merged the way it was");
         process::exit(2);
     }
 
@@ -66,6 +75,11 @@ fn main() {
     } else {
         ours_path.clone()
     };
+    // Labels git didn't expand (before 2.44) are left as `%S`: keep ours
+    let label = |i: usize, default: &str| {
+        positional.get(i).filter(|l| !l.starts_with('%')).cloned().unwrap_or_else(|| default.to_string())
+    };
+    let (base_label, ours_label, theirs_label) = (label(5, "base"), label(6, "ours"), label(7, "theirs"));
 
     // Read input files
     let base = match fs::read_to_string(base_path) {
@@ -97,7 +111,24 @@ fn main() {
     }
 
     // Run entity merge
-    let result = entity_merge(&base, &ours, &theirs, &file_path);
+    let repo_root = weave_core::git::find_repo_root().ok();
+    let config = load_config(repo_root.as_deref());
+    let entity_cache = repo_root.as_deref().and_then(|root| config.entity_cache(root));
+    let context = MergeContext {
+        budget: file_budget(&config),
+        repo_root: repo_root.as_deref(),
+        entity_cache: entity_cache.as_ref(),
+        trace: explain,
+        // `merge.conflictStyle` applies to the conflicts merged line by line
+        conflict_format: weave_core::git::conflict_format(
+            repo_root.as_deref().unwrap_or(Path::new(".")),
+            &ours_label,
+            &base_label,
+            &theirs_label,
+        ),
+        ..Default::default()
+    };
+    let result = entity_merge_in(&base, &ours, &theirs, &file_path, shared_registry(), &config, &context);
 
     // Write result: to -o path if specified (jj), else to ours path (git convention: %A)
     let write_path = output_override.as_deref().unwrap_or(ours_path);
@@ -108,10 +139,28 @@ fn main() {
 
     // Print stats to stderr
     eprintln!("weave [{}]: {}", file_path, result.stats);
+    for warning in &result.warnings {
+        eprintln!("weave [{}]: {}", file_path, warning);
+    }
+    if let Some(generated) = &result.generated {
+        eprintln!("weave [{}]: {}", file_path, generated);
+    }
+    if let Some(trace) = &result.trace {
+        for step in &trace.steps {
+            eprintln!("weave [{}]: explain: {}", file_path, step);
+        }
+    }
+
+    // Remember the conflicts, so their resolution is recorded on commit
+    if let (Some(repo_root), false) = (&repo_root, result.is_clean()) {
+        if let Err(e) = RerereCache::open(repo_root).remember(&file_path, &result) {
+            eprintln!("weave: {}", e);
+        }
+    }
 
     // Optionally record merge in CRDT state
     #[cfg(feature = "crdt")]
-    record_merge_in_crdt(&file_path, &result.content);
+    record_merge_in_crdt(&file_path, &result.content, entity_cache.as_ref());
 
     if result.is_clean() {
         process::exit(0);
@@ -135,10 +184,26 @@ fn is_binary(content: &str) -> bool {
     content.as_bytes().iter().take(8192).any(|&b| b == 0)
 }
 
+/// Load `.weave/config.toml` from the enclosing repo.
+/// A missing or invalid config falls back to defaults — never breaks the merge.
+fn load_config(repo_root: Option<&Path>) -> WeaveConfig {
+    let Some(repo_root) = repo_root else {
+        return WeaveConfig::default();
+    };
+    WeaveConfig::load(repo_root).unwrap_or_else(|e| {
+        eprintln!("weave: ignoring config: {}", e);
+        WeaveConfig::default()
+    })
+}
+
 /// Record merge results in CRDT state if `.weave/state.automerge` exists.
 /// Fails silently — this is purely advisory and must never break the merge.
 #[cfg(feature = "crdt")]
-fn record_merge_in_crdt(file_path: &str, _merged_content: &str) {
+fn record_merge_in_crdt(
+    file_path: &str,
+    _merged_content: &str,
+    entity_cache: Option<&weave_core::cache::EntityCache>,
+) {
     let _ = (|| -> Result<(), Box<dyn std::error::Error>> {
         let repo_root = weave_core::git::find_repo_root()?;
         let state_path = repo_root.join(".weave").join("state.automerge");
@@ -147,7 +212,7 @@ fn record_merge_in_crdt(file_path: &str, _merged_content: &str) {
         }
         let mut state = weave_crdt::EntityStateDoc::open(&state_path)?;
         let registry = sem_core::parser::plugins::create_default_registry();
-        weave_crdt::sync_from_files(&mut state, &repo_root, &[file_path.to_string()], &registry)?;
+        weave_crdt::sync_from_files(&mut state, &repo_root, &[file_path.to_string()], &registry, entity_cache)?;
         state.save()?;
         Ok(())
     })();
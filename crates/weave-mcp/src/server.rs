@@ -5,12 +5,16 @@ use rmcp::handler::server::router::tool::ToolRouter;
 use rmcp::handler::server::wrapper::Parameters;
 use rmcp::model::{CallToolResult, Content, ServerCapabilities, ServerInfo};
 use rmcp::{tool, tool_handler, tool_router, ServerHandler};
+use sem_core::model::entity::SemanticEntity;
 use sem_core::parser::graph::EntityGraph;
 use sem_core::parser::plugins::create_default_registry;
 use sem_core::parser::registry::ParserRegistry;
 use tokio::sync::Mutex;
 
+use weave_core::MergeContext;
+use weave_core::cache::{cached_entities, EntityCache};
 use weave_core::git;
+use weave_core::pipeline::{load_versions, merge_files, MergedFile};
 use weave_crdt::{
     claim_entity, detect_potential_conflicts, get_entities_for_file, get_entity_status,
     register_agent, release_entity, resolve_entity_id, sync_from_files, upsert_entity,
@@ -23,6 +27,19 @@ use crate::tools::*;
 struct RepoContext {
     state: Mutex<EntityStateDoc>,
     repo_root: PathBuf,
+    /// The repo's entity cache, shared with the merge driver.
+    cache: Option<EntityCache>,
+}
+
+impl RepoContext {
+    /// Entities of a file, through the repo's entity cache. `None` when no
+    /// parser handles the file.
+    fn entities(&self, registry: &ParserRegistry, content: &str, file_path: &str) -> Option<Vec<SemanticEntity>> {
+        let plugin = registry.get_plugin(file_path)?;
+        Some(cached_entities(self.cache.as_ref(), plugin.id(), file_path, content, || {
+            plugin.extract_entities(content, file_path)
+        }))
+    }
 }
 
 #[derive(Clone)]
@@ -99,9 +116,13 @@ impl WeaveServer {
                 let state_path = repo_root.join(".weave").join("state.automerge");
                 let state = EntityStateDoc::open(&state_path)
                     .map_err(|e| format!("Failed to open CRDT state: {}", e))?;
+                let cache = weave_core::WeaveConfig::load(&repo_root)
+                    .map_err(|e| format!("Failed to load .weave/config.toml: {}", e))?
+                    .entity_cache(&repo_root);
                 *guard = Some(RepoContext {
                     state: Mutex::new(state),
                     repo_root,
+                    cache,
                 });
             }
         }
@@ -113,43 +134,7 @@ impl WeaveServer {
 
     /// Find all files in the repo that have a supported parser.
     fn find_supported_files(root: &Path, registry: &ParserRegistry) -> Vec<String> {
-        let mut files = Vec::new();
-        Self::walk_dir(root, root, registry, &mut files);
-        files.sort();
-        files
-    }
-
-    fn walk_dir(
-        dir: &Path,
-        root: &Path,
-        registry: &ParserRegistry,
-        files: &mut Vec<String>,
-    ) {
-        let entries = match std::fs::read_dir(dir) {
-            Ok(e) => e,
-            Err(_) => return,
-        };
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                if name.starts_with('.')
-                    || name == "node_modules"
-                    || name == "target"
-                    || name == "__pycache__"
-                    || name == "venv"
-                {
-                    continue;
-                }
-            }
-            if path.is_dir() {
-                Self::walk_dir(&path, root, registry, files);
-            } else if let Ok(rel) = path.strip_prefix(root) {
-                let rel_str = rel.to_string_lossy().to_string();
-                if registry.get_plugin(&rel_str).is_some() {
-                    files.push(rel_str);
-                }
-            }
-        }
+        weave_core::supported_files(root, registry)
     }
 
     fn read_file_at(abs_path: &Path, display_path: &str) -> Result<String, String> {
@@ -191,12 +176,9 @@ impl WeaveServer {
             Self::resolve_file_path(&ctx.repo_root, &params.file_path);
         let content = Self::read_file_at(&abs_path, &rel_path).map_err(internal_err)?;
 
-        let plugin = self
-            .registry
-            .get_plugin(&rel_path)
+        let entities = ctx
+            .entities(&self.registry, &content, &rel_path)
             .ok_or_else(|| internal_err(format!("No parser for file: {}", rel_path)))?;
-
-        let entities = plugin.extract_entities(&content, &rel_path);
         let result: Vec<serde_json::Value> = entities
             .iter()
             .map(|e| {
@@ -232,11 +214,9 @@ impl WeaveServer {
                 .map_err(internal_err)?;
 
         let mut state = ctx.state.lock().await;
-        let plugin = self
-            .registry
-            .get_plugin(&rel_path)
+        let entities = ctx
+            .entities(&self.registry, &content, &rel_path)
             .ok_or_else(|| internal_err("No parser for file"))?;
-        let entities = plugin.extract_entities(&content, &rel_path);
         if let Some(e) = entities.iter().find(|e| e.id == entity_id) {
             let _ = upsert_entity(
                 &mut state,
@@ -365,14 +345,14 @@ impl WeaveServer {
             &ctx.repo_root,
             &[rel_path.clone()],
             &self.registry,
+            ctx.cache.as_ref(),
         );
 
         let entities = get_entities_for_file(&state, &rel_path)
             .map_err(|e| internal_err(e.to_string()))?;
 
-        let plugin = self.registry.get_plugin(&rel_path);
-        let file_entities = plugin
-            .map(|p| p.extract_entities(&content, &rel_path))
+        let file_entities = ctx
+            .entities(&self.registry, &content, &rel_path)
             .unwrap_or_default();
 
         let result: Vec<serde_json::Value> = file_entities
@@ -490,33 +470,50 @@ impl WeaveServer {
                 .map_err(|e| internal_err(e.to_string()))?
         };
 
-        let mut results = Vec::new();
-        for file in &files {
-            let base = git::git_show(&merge_base, file).unwrap_or_default();
-            let ours = git::git_show(&params.base_branch, file).unwrap_or_default();
-            let theirs = git::git_show(&params.target_branch, file).unwrap_or_default();
+        let config = weave_core::WeaveConfig::load(&ctx.repo_root)
+            .map_err(|e| internal_err(e.to_string()))?;
+        let context = MergeContext {
+            repo_root: Some(&ctx.repo_root),
+            entity_cache: ctx.cache.as_ref(),
+            trace: params.explain.unwrap_or(false),
+            conflict_format: git::conflict_format(
+                &ctx.repo_root,
+                &params.base_branch,
+                git::short_sha(&merge_base),
+                &params.target_branch,
+            ),
+            ..Default::default()
+        };
 
-            if ours == theirs || base == ours || base == theirs {
-                continue;
-            }
+        let versions = load_versions(
+            &ctx.repo_root,
+            &merge_base,
+            &params.base_branch,
+            &params.target_branch,
+            &files,
+        )
+        .map_err(|e| internal_err(e.to_string()))?;
+        let merged = merge_files(versions, &self.registry, &config, &context, config.merge.timeout());
 
-            let merge_result = weave_core::entity_merge_with_registry(
-                &base,
-                &ours,
-                &theirs,
-                file,
-                &self.registry,
-            );
+        let mut results = Vec::new();
+        for MergedFile { versions, result } in &merged {
+            let Some(merge_result) = result else {
+                continue;
+            };
+            let file = &versions.file_path;
 
             let conflicts: Vec<serde_json::Value> = merge_result
                 .conflicts
                 .iter()
                 .map(|c| {
+                    let (ours_complexity, theirs_complexity) = c.side_complexities(file);
                     serde_json::json!({
                         "entity_type": c.entity_type,
                         "entity_name": c.entity_name,
                         "kind": format!("{}", c.kind),
                         "complexity": format!("{}", c.complexity),
+                        "ours_complexity": format!("{}", ours_complexity),
+                        "theirs_complexity": format!("{}", theirs_complexity),
                     })
                 })
                 .collect();
@@ -527,6 +524,17 @@ impl WeaveServer {
                 .map(|w| format!("{}", w))
                 .collect();
 
+            let protected: Vec<serde_json::Value> =
+                weave_core::protect::protected_entities(&[&versions.ours, &versions.theirs], file, &self.registry, &config)
+                    .iter()
+                    .map(|p| {
+                        serde_json::json!({
+                            "entity_type": p.entity_type,
+                            "entity_name": p.name,
+                        })
+                    })
+                    .collect();
+
             results.push(serde_json::json!({
                 "file": file,
                 "clean": merge_result.is_clean(),
@@ -542,9 +550,17 @@ impl WeaveServer {
                     "conflicted": merge_result.stats.entities_conflicted,
                     "resolved_via_diffy": merge_result.stats.resolved_via_diffy,
                     "resolved_via_inner_merge": merge_result.stats.resolved_via_inner_merge,
+                    "resolved_via_rerere": merge_result.stats.resolved_via_rerere,
+                    "resolved_via_policy": merge_result.stats.resolved_via_policy,
                 },
                 "conflicts": conflicts,
                 "warnings": warnings,
+                "protected": protected,
+                "generated": merge_result.generated.as_ref().map(|g| g.to_string()),
+                "trace": merge_result
+                    .trace
+                    .as_ref()
+                    .map(|t| t.steps.iter().map(|s| s.to_string()).collect::<Vec<_>>()),
             }));
         }
 
@@ -755,7 +771,7 @@ impl WeaveServer {
         &self,
         Parameters(params): Parameters<DiffParams>,
     ) -> Result<CallToolResult, rmcp::ErrorData> {
-        let _ctx = self
+        let ctx = self
             .get_context(params.file_path.as_deref())
             .await
             .map_err(internal_err)?;
@@ -782,16 +798,15 @@ impl WeaveServer {
         let mut all_changes = Vec::new();
 
         for file in &files {
-            let plugin = match self.registry.get_plugin(file) {
-                Some(p) => p,
-                None => continue, // skip unsupported files
-            };
+            if self.registry.get_plugin(file).is_none() {
+                continue; // skip unsupported files
+            }
 
             let base_content = git::git_show(&params.base_ref, file).unwrap_or_default();
             let target_content = git::git_show(target_ref, file).unwrap_or_default();
 
-            let base_entities = plugin.extract_entities(&base_content, file);
-            let target_entities = plugin.extract_entities(&target_content, file);
+            let base_entities = ctx.entities(&self.registry, &base_content, file).unwrap_or_default();
+            let target_entities = ctx.entities(&self.registry, &target_content, file).unwrap_or_default();
 
             let match_result = sem_core::model::identity::match_entities(
                 &base_entities,
@@ -863,7 +878,7 @@ impl WeaveServer {
         )]))
     }
 
-    #[tool(description = "Validate a merge for semantic risks: detect when auto-merged entities reference other entities that were also modified")]
+    #[tool(description = "Validate a merge for semantic risks: detect when auto-merged entities reference other entities that were also modified, that the other branch deleted, or whose parameters the other branch changed")]
     async fn weave_validate_merge(
         &self,
         Parameters(params): Parameters<ValidateMergeParams>,
@@ -884,44 +899,21 @@ impl WeaveServer {
                 .map_err(|e| internal_err(e.to_string()))?
         };
 
-        // Collect modified entities from both branches
-        let mut modified_entities = Vec::new();
-        for file in &files {
-            let base_content = git::git_show(&merge_base, file).unwrap_or_default();
-            let ours_content = git::git_show(&params.base_branch, file).unwrap_or_default();
-            let theirs_content = git::git_show(&params.target_branch, file).unwrap_or_default();
-
-            if let Some(plugin) = self.registry.get_plugin(file) {
-                let base_entities = plugin.extract_entities(&base_content, file);
-                let ours_entities = plugin.extract_entities(&ours_content, file);
-                let theirs_entities = plugin.extract_entities(&theirs_content, file);
-
-                // Find entities modified in ours or theirs vs base
-                for entity in ours_entities.iter().chain(theirs_entities.iter()) {
-                    let base_match = base_entities.iter().find(|b| b.name == entity.name);
-                    let is_modified = match base_match {
-                        Some(b) => b.content_hash != entity.content_hash,
-                        None => true, // new entity
-                    };
-                    if is_modified {
-                        modified_entities.push(weave_core::ModifiedEntity {
-                            name: entity.name.clone(),
-                            file_path: file.clone(),
-                        });
-                    }
-                }
-            }
-        }
-
-        // Deduplicate
-        modified_entities.sort_by(|a, b| (&a.file_path, &a.name).cmp(&(&b.file_path, &b.name)));
-        modified_entities.dedup_by(|a, b| a.file_path == b.file_path && a.name == b.name);
+        let versions = load_versions(
+            &ctx.repo_root,
+            &merge_base,
+            &params.base_branch,
+            &params.target_branch,
+            &files,
+        )
+        .map_err(|e| internal_err(e.to_string()))?;
+        let modified_entities = weave_core::modified_entities(&versions, &self.registry);
 
         let all_files = Self::find_supported_files(&ctx.repo_root, &self.registry);
-        let warnings = weave_core::validate_merge(
+        let warnings = weave_core::validate_versions(
             &ctx.repo_root,
             &all_files,
-            &modified_entities,
+            &versions,
             &self.registry,
         );
 
@@ -56,6 +56,8 @@ pub struct PreviewMergeParams {
     pub target_branch: String,
     #[schemars(description = "Optional: preview only this file")]
     pub file_path: Option<String>,
+    #[schemars(description = "Optional: include the decision trace explaining how each file and entity was merged")]
+    pub explain: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]